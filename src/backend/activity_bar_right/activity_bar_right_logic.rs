@@ -1,12 +1,13 @@
 use std::collections::HashMap;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum CodeAgentActivityItem {
     Assistant,
     CodeAnalysis,
     Suggestions,
     CodeMetrics,
     CodeSearch,
+    Memory,
 }
 
 pub struct ActivityBarRightLogic {
@@ -29,6 +30,7 @@ impl ActivityBarRightLogic {
         item_states.insert(CodeAgentActivityItem::Suggestions, false);
         item_states.insert(CodeAgentActivityItem::CodeMetrics, false);
         item_states.insert(CodeAgentActivityItem::CodeSearch, false);
+        item_states.insert(CodeAgentActivityItem::Memory, false);
         
         Self {
             active_item: Some(CodeAgentActivityItem::Assistant),
@@ -3,23 +3,45 @@
 // TODO: 
 // FIXME: 
 
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use super::agent_loop::{self, AgentEvent, AgentRun, LoopStop};
+use super::chat::{ChatMessage, MessageRole};
+use super::completion::{FimCompleter, FimContext};
+use super::model_loader::{ModelConfig, ModelParameters, ModelProvider};
+use super::providers::{self, CompletionRequest, Provider, ProviderError};
+use super::tools::registry::ToolRegistry;
+use crate::backend::settings_manager::{AIProvider, AISettings};
+
+pub const DEFAULT_EMBEDDING_MODEL: &str = "nomic-embed-text";
 
 #[derive(Debug, Clone)]
 pub struct AgentConfig {
+    pub provider: ModelProvider,
     pub model: String,
     pub api_key: String,
+    /// Overrides the provider's default endpoint, e.g. for a proxy or a mock server.
+    pub api_endpoint: Option<String>,
     pub temperature: f32,
     pub max_tokens: u32,
     pub system_prompt: String,
+    /// Sampling settings not covered above (`top_p`, stop sequences).
+    pub parameters: ModelParameters,
+    /// Model calls allowed per message while the agent is using tools.
+    pub max_tool_steps: usize,
+    /// Embedding model on the same server, for semantic code search.
+    pub embedding_model: String,
 }
 
-#[derive(Debug)]
+/// Cloning is cheap: clones share the provider, tools, context and memory.
+#[derive(Debug, Clone)]
 pub struct CodeAgent {
     config: AgentConfig,
     context: Arc<Mutex<AgentContext>>,
-    memory: Arc<Mutex<AgentMemory>>,
+    memory: Arc<Mutex<SessionMemory>>,
+    provider: Arc<dyn Provider>,
+    tools: Arc<ToolRegistry>,
 }
 
 #[derive(Debug, Default)]
@@ -30,11 +52,11 @@ pub struct AgentContext {
     pub recent_edits: Vec<EditRecord>,
 }
 
+/// What the agent said and was told since it started; long-term notes live in
+/// [`super::memory::agent_memory_logic::AgentMemory`].
 #[derive(Debug, Default)]
-pub struct AgentMemory {
+pub struct SessionMemory {
     pub conversation_history: Vec<Message>,
-    pub code_snippets: Vec<CodeSnippet>,
-    pub learned_patterns: Vec<Pattern>,
 }
 
 #[derive(Debug, Clone)]
@@ -52,29 +74,67 @@ pub struct EditRecord {
     pub timestamp: chrono::DateTime<chrono::Utc>,
 }
 
-#[derive(Debug, Clone)]
-pub struct CodeSnippet {
-    pub language: String,
-    pub code: String,
-    pub description: String,
-    pub tags: Vec<String>,
-}
-
-#[derive(Debug, Clone)]
-pub struct Pattern {
-    pub name: String,
-    pub description: String,
-    pub examples: Vec<String>,
-}
-
 impl Default for AgentConfig {
     fn default() -> Self {
         Self {
+            provider: ModelProvider::Anthropic,
             model: "claude-3-sonnet-20240229".to_string(),
             api_key: String::new(),
+            api_endpoint: None,
             temperature: 0.7,
             max_tokens: 4096,
             system_prompt: "You are an AI coding assistant integrated into JadioAI IDE.".to_string(),
+            parameters: ModelParameters::default(),
+            max_tool_steps: agent_loop::DEFAULT_MAX_STEPS,
+            embedding_model: DEFAULT_EMBEDDING_MODEL.to_string(),
+        }
+    }
+}
+
+impl From<&AISettings> for AgentConfig {
+    fn from(settings: &AISettings) -> Self {
+        let provider = match &settings.provider {
+            AIProvider::Anthropic => ModelProvider::Anthropic,
+            AIProvider::OpenAI => ModelProvider::OpenAI,
+            AIProvider::Local => ModelProvider::Local,
+            AIProvider::Custom(name) => ModelProvider::Custom(name.clone()),
+        };
+        Self {
+            provider,
+            model: settings.model.clone(),
+            api_key: settings.api_key.clone(),
+            temperature: settings.temperature,
+            max_tokens: settings.max_tokens,
+            embedding_model: settings.embedding_model.clone(),
+            ..Self::default()
+        }
+    }
+}
+
+impl AgentConfig {
+    /// Switch to a registered model, keeping the system prompt.
+    pub fn with_model(self, model: &ModelConfig) -> Self {
+        Self {
+            provider: model.provider.clone(),
+            model: model.name.clone(),
+            api_key: model.api_key.clone().unwrap_or_default(),
+            api_endpoint: model.api_endpoint.clone(),
+            temperature: model.parameters.temperature,
+            max_tokens: model.parameters.max_tokens,
+            parameters: model.parameters.clone(),
+            ..self
+        }
+    }
+    
+    /// The model this config talks to, as a loader entry.
+    pub fn model_config(&self) -> ModelConfig {
+        ModelConfig {
+            name: self.model.clone(),
+            provider: self.provider.clone(),
+            api_endpoint: self.api_endpoint.clone(),
+            api_key: Some(self.api_key.clone()).filter(|key| !key.is_empty()),
+            model_path: None,
+            parameters: self.parameters.clone(),
         }
     }
 }
@@ -82,30 +142,71 @@ impl Default for AgentConfig {
 impl CodeAgent {
     pub fn new(config: AgentConfig) -> Self {
         Self {
+            provider: providers::create_provider(&config.provider, &config.api_key, config.api_endpoint.as_deref()),
             config,
             context: Arc::new(Mutex::new(AgentContext::default())),
-            memory: Arc::new(Mutex::new(AgentMemory::default())),
+            memory: Arc::new(Mutex::new(SessionMemory::default())),
+            tools: Arc::new(ToolRegistry::new(PathBuf::from("."))),
         }
     }
     
-    pub async fn process_message(&self, message: String) -> Result<String, Box<dyn std::error::Error>> {
-        // TODO: Implement actual AI API call
-        let response = format!("I received your message: '{}'. AI integration coming soon!", message);
+    pub fn config(&self) -> &AgentConfig {
+        &self.config
+    }
+    
+    pub fn set_config(&mut self, config: AgentConfig) {
+        let provider = providers::create_provider(&config.provider, &config.api_key, config.api_endpoint.as_deref());
+        self.set_provider(config, provider);
+    }
+    
+    /// Use an already-created client, e.g. one cached by the model loader.
+    pub fn set_provider(&mut self, config: AgentConfig, provider: Arc<dyn Provider>) {
+        self.config = config;
+        self.provider = provider;
+    }
+    
+    /// Set the tools the model may call. An empty registry turns tool use off.
+    pub fn set_tools(&mut self, tools: ToolRegistry) {
+        self.tools = Arc::new(tools);
+    }
+    
+    pub fn get_tools(&self) -> &ToolRegistry {
+        &self.tools
+    }
+    
+    /// Send the chat history to the configured model, running any tools it calls, and return its reply.
+    ///
+    /// `on_event` receives the reply as it streams in, along with each tool call and result.
+    pub async fn process_message<F: FnMut(AgentEvent) + Send>(&self, history: &[ChatMessage], on_event: F) -> Result<AgentRun, ProviderError> {
+        self.process_message_with_tools(history, &self.tools, on_event).await
+    }
+    
+    /// Like [`process_message`](Self::process_message), with `tools` in place of the agent's own.
+    pub async fn process_message_with_tools<F: FnMut(AgentEvent) + Send>(&self, history: &[ChatMessage], tools: &Arc<ToolRegistry>, mut on_event: F) -> Result<AgentRun, ProviderError> {
+        let request = CompletionRequest::from_chat(&self.config, &self.config.parameters, history);
+        let mut run = agent_loop::run_agent_loop(self.provider.as_ref(), tools, request, self.config.max_tool_steps, &mut on_event).await?;
+        if run.stop == LoopStop::StepBudget {
+            let notice = format!("\n\n(Stopped after {} steps without a final answer.)", run.steps);
+            on_event(AgentEvent::Text(notice.clone()));
+            run.text.push_str(&notice);
+        }
         
         // Add to memory
         let mut memory = self.memory.lock().await;
-        memory.conversation_history.push(Message {
-            role: "user".to_string(),
-            content: message,
-            timestamp: chrono::Utc::now(),
-        });
+        if let Some(message) = history.iter().rev().find(|m| m.role == MessageRole::User) {
+            memory.conversation_history.push(Message {
+                role: "user".to_string(),
+                content: message.content.clone(),
+                timestamp: chrono::Utc::now(),
+            });
+        }
         memory.conversation_history.push(Message {
             role: "assistant".to_string(),
-            content: response.clone(),
+            content: run.text.clone(),
             timestamp: chrono::Utc::now(),
         });
         
-        Ok(response)
+        Ok(run)
     }
     
     pub async fn analyze_code(&self, code: String, language: String) -> Result<String, Box<dyn std::error::Error>> {
@@ -113,9 +214,12 @@ impl CodeAgent {
         Ok(format!("Code analysis for {} code coming soon!", language))
     }
     
+    /// Fill in the code at `cursor_pos` with the configured FIM model.
     pub async fn suggest_completion(&self, code: String, cursor_pos: usize) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-        // TODO: Implement code completion
-        Ok(vec!["// Code completion coming soon!".to_string()])
+        let completer = FimCompleter::for_config(&self.config)
+            .ok_or_else(|| format!("{} does not support fill-in-the-middle completion", self.config.model))?;
+        let text = completer.complete(&FimContext::new(&code, cursor_pos, None)).await?;
+        Ok(if text.is_empty() { Vec::new() } else { vec![text] })
     }
     
     pub async fn update_context(&self, file: Option<String>, project: Option<String>) {
@@ -0,0 +1,198 @@
+use std::sync::Arc;
+use super::chat::MessageRole;
+use super::edit_proposal::EditProposal;
+use super::providers::{CompletionRequest, Provider, ProviderError, RequestMessage, ToolCall, ToolResult, Usage};
+use super::tools::registry::ToolRegistry;
+
+/// Model calls allowed per user message before the loop gives up.
+pub const DEFAULT_MAX_STEPS: usize = 10;
+
+/// Progress of an agent run, in order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AgentEvent {
+    /// A piece of the model's reply.
+    Text(String),
+    /// The model asked for a tool; it runs next.
+    ToolCall(ToolCall),
+    ToolResult(ToolResult),
+    /// The run ended with file writes waiting for review.
+    EditProposal(EditProposal),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopStop {
+    /// The model replied without calling any tools.
+    FinalAnswer,
+    /// The step budget ran out while the model was still calling tools.
+    StepBudget,
+}
+
+/// The outcome of [`run_agent_loop`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentRun {
+    /// Everything the model said across all steps, separated by blank lines.
+    pub text: String,
+    /// Assistant turns and tool results added to the conversation by the run.
+    pub transcript: Vec<RequestMessage>,
+    /// Number of model calls made.
+    pub steps: usize,
+    pub stop: LoopStop,
+    /// Token usage summed over all steps.
+    pub usage: Usage,
+    /// Files the model wrote during the run, not yet applied.
+    pub proposal: Option<EditProposal>,
+}
+
+/// Run the model with the registry's tools until it answers without calling one, or `max_steps` model calls are used.
+///
+/// Tool calls are executed in order and their results fed back as the next user turn.
+/// Tools run on the blocking thread pool, since a command may wait for the user's approval.
+/// File writes are staged rather than written and come back as [`AgentRun::proposal`].
+pub async fn run_agent_loop(
+    provider: &dyn Provider,
+    tools: &Arc<ToolRegistry>,
+    mut request: CompletionRequest,
+    max_steps: usize,
+    on_event: &mut (dyn FnMut(AgentEvent) + Send),
+) -> Result<AgentRun, ProviderError> {
+    request.tools = tools.definitions();
+    tools.clear_staged();
+    let description = proposal_description(&request);
+    let mut run = AgentRun {
+        text: String::new(),
+        transcript: Vec::new(),
+        steps: 0,
+        stop: LoopStop::StepBudget,
+        usage: Usage::default(),
+        proposal: None,
+    };
+
+    while run.steps < max_steps {
+        if run.steps > 0 && !run.text.is_empty() && !run.text.ends_with("\n\n") {
+            run.text.push_str("\n\n");
+            on_event(AgentEvent::Text("\n\n".to_string()));
+        }
+        let mut on_text = |text: &str| on_event(AgentEvent::Text(text.to_string()));
+        let response = provider.stream(&request, &mut on_text).await?;
+        run.steps += 1;
+        run.text.push_str(&response.text);
+        run.usage.input_tokens += response.usage.input_tokens;
+        run.usage.output_tokens += response.usage.output_tokens;
+
+        let assistant = RequestMessage {
+            tool_calls: response.tool_calls.clone(),
+            ..RequestMessage::text(MessageRole::Assistant, &response.text)
+        };
+        request.messages.push(assistant.clone());
+        run.transcript.push(assistant);
+        if response.tool_calls.is_empty() {
+            run.stop = LoopStop::FinalAnswer;
+            break;
+        }
+
+        let mut results = Vec::new();
+        for call in &response.tool_calls {
+            on_event(AgentEvent::ToolCall(call.clone()));
+            let (registry, owned) = (Arc::clone(tools), call.clone());
+            let result = tokio::task::spawn_blocking(move || registry.execute(&owned)).await.unwrap_or_else(|e| ToolResult {
+                call_id: call.id.clone(),
+                name: call.name.clone(),
+                content: format!("Tool failed: {}", e),
+                is_error: true,
+            });
+            on_event(AgentEvent::ToolResult(result.clone()));
+            results.push(result);
+        }
+        let user = RequestMessage { tool_results: results, ..RequestMessage::text(MessageRole::User, "") };
+        request.messages.push(user.clone());
+        run.transcript.push(user);
+    }
+
+    run.proposal = tools.take_proposal(&description);
+    if let Some(proposal) = &run.proposal {
+        on_event(AgentEvent::EditProposal(proposal.clone()));
+    }
+    Ok(run)
+}
+
+/// The first line of the user's request, used to label the edits it leads to.
+fn proposal_description(request: &CompletionRequest) -> String {
+    let asked = request.messages.iter().rev().find(|message| message.role == MessageRole::User).map_or("", |message| message.content.as_str());
+    let line = asked.lines().map(str::trim).find(|line| !line.is_empty()).unwrap_or("Agent edit");
+    match line.char_indices().nth(80) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::code_agent::providers::scripted::ScriptedProvider;
+    use crate::backend::code_agent::test_support::TempDir;
+    use serde_json::json;
+
+    fn request() -> CompletionRequest {
+        CompletionRequest {
+            model: "scripted".to_string(),
+            system: None,
+            messages: vec![RequestMessage::text(MessageRole::User, "What's in notes.txt?")],
+            max_tokens: 256,
+            temperature: 0.0,
+            top_p: 1.0,
+            stop_sequences: Vec::new(),
+            tools: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_loop_runs_tools_until_final_answer_or_budget() {
+        let temp = TempDir::new("agent_loop");
+        let workspace = temp.path().to_path_buf();
+        std::fs::write(workspace.join("notes.txt"), "buy milk\n").unwrap();
+        let tools = Arc::new(ToolRegistry::with_default_tools(workspace.clone()));
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+        let model = ScriptedProvider::new(vec![
+            ScriptedProvider::tool_call("Let me look.", "call_1", "read_file", json!({"path": "notes.txt"})),
+            ScriptedProvider::reply("It says to buy milk."),
+        ]);
+        let mut events = Vec::new();
+        let run = runtime
+            .block_on(run_agent_loop(&model, &tools, request(), 5, &mut |event| events.push(event)))
+            .unwrap();
+
+        assert_eq!(run.stop, LoopStop::FinalAnswer);
+        assert_eq!(run.steps, 2);
+        assert_eq!(run.text, "Let me look.\n\nIt says to buy milk.");
+        assert!(matches!(&events[1], AgentEvent::ToolCall(call) if call.name == "read_file"));
+        assert!(matches!(&events[2], AgentEvent::ToolResult(result) if result.content == "buy milk\n" && !result.is_error));
+
+        // The second request carries the tool definitions, the call and its result.
+        let requests = model.requests();
+        assert_eq!(requests[1].tools.len(), tools.definitions().len());
+        assert_eq!(requests[1].messages[1].tool_calls[0].id, "call_1");
+        assert_eq!(requests[1].messages[2].tool_results[0].call_id, "call_1");
+
+        // A model that never stops calling tools is cut off at the budget.
+        let looping = ScriptedProvider::new(
+            (0..3).map(|i| ScriptedProvider::tool_call("", &format!("call_{}", i), "search", json!({"query": "milk"}))).collect(),
+        );
+        let run = runtime.block_on(run_agent_loop(&looping, &tools, request(), 2, &mut |_| {})).unwrap();
+        assert_eq!((run.stop, run.steps, looping.remaining()), (LoopStop::StepBudget, 2, 1));
+        assert_eq!(run.proposal, None);
+
+        // Writes come back as a proposal labelled with the request instead of reaching disk.
+        let editing = ScriptedProvider::new(vec![
+            ScriptedProvider::tool_call("", "call_1", "write_file", json!({"path": "notes.txt", "content": "buy eggs\n"})),
+            ScriptedProvider::reply("Done."),
+        ]);
+        let mut events = Vec::new();
+        let run = runtime.block_on(run_agent_loop(&editing, &tools, request(), 5, &mut |event| events.push(event))).unwrap();
+        let proposal = run.proposal.unwrap();
+        assert_eq!(proposal.description, "What's in notes.txt?");
+        assert_eq!(proposal.files[0].hunks[0].removed(), 1);
+        assert!(matches!(events.last(), Some(AgentEvent::EditProposal(_))));
+        assert_eq!(std::fs::read_to_string(workspace.join("notes.txt")).unwrap(), "buy milk\n");
+    }
+}
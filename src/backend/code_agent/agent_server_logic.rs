@@ -1,22 +1,75 @@
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, watch, Mutex};
 use std::net::SocketAddr;
+use serde_json::Value;
+use super::agent::CodeAgent;
+use super::agent_loop::AgentEvent;
+use super::chat::{ChatMessage, MessageMetadata, MessageRole};
+use super::completion::{FimCompleter, FimContext};
+use super::server_auth::{ApiToken, AuditEntry, AuditLog, TokenStore};
+use super::tools::registry::{Capability, ToolRegistry};
+use super::websocket::{self, Message, WebSocketReader};
+
+/// JSON-RPC 2.0 error codes.
+pub const PARSE_ERROR: i32 = -32700;
+pub const INVALID_REQUEST: i32 = -32600;
+pub const METHOD_NOT_FOUND: i32 = -32601;
+pub const INVALID_PARAMS: i32 = -32602;
+pub const INTERNAL_ERROR: i32 = -32603;
+/// The IDE isn't set up for the method, e.g. no model is configured.
+pub const NOT_CONFIGURED: i32 = -32000;
+/// Refused because the server is at `max_connections`.
+pub const SERVER_BUSY: i32 = -32001;
+/// No valid token was presented.
+pub const UNAUTHORIZED: i32 = -32002;
+/// The token lacks the capability the method needs.
+pub const FORBIDDEN: i32 = -32003;
+/// Notification carrying a partial result of a running request: `{"id", "event"}`.
+pub const PROGRESS_METHOD: &str = "$/progress";
 
 #[derive(Debug, Clone)]
 pub struct AgentServerConfig {
     pub host: String,
     pub port: u16,
     pub max_connections: usize,
+    /// Idle time after which a connection is closed.
     pub timeout_seconds: u64,
+    /// Accept WebSocket connections from browser pages at all.
     pub enable_cors: bool,
+    /// Origins browser pages may connect from; `"*"` allows any.
     pub allowed_origins: Vec<String>,
+    /// Require an API token before handling requests.
+    pub require_auth: bool,
 }
 
+/// JSON-RPC 2.0 server for the agent, on one port for both transports: plain TCP
+/// with one message per line, and WebSocket with one message per text frame.
 #[derive(Debug)]
 pub struct AgentServer {
     config: AgentServerConfig,
     running: Arc<Mutex<bool>>,
     connections: Arc<Mutex<Vec<ClientConnection>>>,
+    /// Model answering `complete` requests; `None` until a FIM model is configured.
+    completer: Option<Arc<FimCompleter>>,
+    /// Agent answering `chat` requests.
+    agent: Option<Arc<Mutex<CodeAgent>>>,
+    tokens: TokenStore,
+    audit: AuditLog,
+    /// Set to `true` to stop the listener and close every connection.
+    shutdown: watch::Sender<bool>,
+}
+
+/// Who a request comes from, for scoping and the audit log.
+#[derive(Debug, Clone, Default)]
+pub struct Caller {
+    /// `None` for requests made in-process.
+    pub address: Option<SocketAddr>,
+    /// The token the caller authenticated with.
+    pub token: Option<ApiToken>,
 }
 
 #[derive(Debug, Clone)]
@@ -27,17 +80,24 @@ pub struct ClientConnection {
     pub last_activity: chrono::DateTime<chrono::Utc>,
 }
 
+/// A JSON-RPC request; without an `id` it is a notification and gets no response.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentRequest {
-    pub id: String,
+    pub jsonrpc: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
     pub method: String,
-    pub params: serde_json::Value,
+    #[serde(default)]
+    pub params: Value,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentResponse {
-    pub id: String,
-    pub result: Option<serde_json::Value>,
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<AgentError>,
 }
 
@@ -45,7 +105,106 @@ pub struct AgentResponse {
 pub struct AgentError {
     pub code: i32,
     pub message: String,
-    pub data: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl AgentResponse {
+    pub fn result(id: Option<Value>, result: Value) -> Self {
+        Self { jsonrpc: "2.0".to_string(), id: id.unwrap_or_default(), result: Some(result), error: None }
+    }
+
+    pub fn error(id: Option<Value>, code: i32, message: impl Into<String>) -> Self {
+        Self {
+            jsonrpc: "2.0".to_string(),
+            id: id.unwrap_or_default(),
+            result: None,
+            error: Some(AgentError { code, message: message.into(), data: None }),
+        }
+    }
+}
+
+/// Sends `$/progress` notifications for one request back over its connection.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    id: Value,
+    outgoing: Option<mpsc::UnboundedSender<Outgoing>>,
+}
+
+impl Progress {
+    /// Progress that goes nowhere, for requests handled in-process.
+    pub fn none() -> Self {
+        Self { id: Value::Null, outgoing: None }
+    }
+
+    pub fn send(&self, event: Value) {
+        if let Some(outgoing) = &self.outgoing {
+            let notification = serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": PROGRESS_METHOD,
+                "params": { "id": self.id, "event": event },
+            });
+            let _ = outgoing.send(Outgoing::Text(notification.to_string()));
+        }
+    }
+}
+
+/// Something to write to a client.
+#[derive(Debug)]
+enum Outgoing {
+    Text(String),
+    Pong(Vec<u8>),
+    Close,
+}
+
+/// How a connection frames its messages, decided by its first bytes.
+enum Transport {
+    Lines(BufReader<OwnedReadHalf>),
+    WebSocket(WebSocketReader<BufReader<OwnedReadHalf>>),
+}
+
+impl Transport {
+    /// The next JSON-RPC message, or `None` once the client closes the connection.
+    async fn next(&mut self, outgoing: &mpsc::UnboundedSender<Outgoing>) -> std::io::Result<Option<String>> {
+        match self {
+            Self::Lines(reader) => loop {
+                let Some(line) = read_limited_line(reader, websocket::MAX_MESSAGE_BYTES).await? else {
+                    return Ok(None);
+                };
+                if !line.trim().is_empty() {
+                    return Ok(Some(line));
+                }
+            },
+            Self::WebSocket(reader) => loop {
+                match reader.next().await? {
+                    Some(Message::Text(text)) => return Ok(Some(text)),
+                    Some(Message::Binary(data)) => return Ok(Some(String::from_utf8_lossy(&data).into_owned())),
+                    Some(Message::Ping(data)) => {
+                        let _ = outgoing.send(Outgoing::Pong(data));
+                    }
+                    Some(Message::Pong(_)) => {}
+                    Some(Message::Close) => {
+                        let _ = outgoing.send(Outgoing::Close);
+                        return Ok(None);
+                    }
+                    None => return Ok(None),
+                }
+            },
+        }
+    }
+}
+
+/// The next line, or `None` at the end of the stream. Fails rather than buffer
+/// more than `limit` bytes of a line that doesn't end.
+async fn read_limited_line<R: AsyncBufRead + Unpin>(reader: &mut R, limit: usize) -> std::io::Result<Option<String>> {
+    let mut line = Vec::new();
+    if reader.take(limit as u64 + 1).read_until(b'\n', &mut line).await? == 0 {
+        return Ok(None);
+    }
+    if line.len() > limit {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Request line too long"));
+    }
+    Ok(Some(String::from_utf8_lossy(&line).into_owned()))
 }
 
 impl Default for AgentServerConfig {
@@ -55,8 +214,9 @@ impl Default for AgentServerConfig {
             port: 8765,
             max_connections: 100,
             timeout_seconds: 300,
-            enable_cors: true,
-            allowed_origins: vec!["*".to_string()],
+            enable_cors: false,
+            allowed_origins: Vec::new(),
+            require_auth: true,
         }
     }
 }
@@ -67,23 +227,72 @@ impl AgentServer {
             config,
             running: Arc::new(Mutex::new(false)),
             connections: Arc::new(Mutex::new(Vec::new())),
+            completer: None,
+            agent: None,
+            tokens: TokenStore::default(),
+            audit: AuditLog::default(),
+            shutdown: watch::channel(false).0,
         }
     }
+
+    /// Answer `complete` requests with a fill-in-the-middle model.
+    pub fn with_completer(mut self, completer: FimCompleter) -> Self {
+        self.completer = Some(Arc::new(completer));
+        self
+    }
+
+    /// Answer `chat` requests with this agent, streaming its reply as progress notifications.
+    pub fn with_agent(mut self, agent: Arc<Mutex<CodeAgent>>) -> Self {
+        self.agent = Some(agent);
+        self
+    }
+    
+    /// Accept the tokens in `store`; with none, every request needing auth is refused.
+    pub fn with_tokens(mut self, store: TokenStore) -> Self {
+        self.tokens = store;
+        self
+    }
+    
+    /// Record handled requests in `log` instead of only in memory.
+    pub fn with_audit_log(mut self, log: AuditLog) -> Self {
+        self.audit = log;
+        self
+    }
+    
+    pub fn audit_log(&self) -> &AuditLog {
+        &self.audit
+    }
     
-    pub async fn start(&self) -> Result<(), Box<dyn std::error::Error>> {
+    /// Listen on the configured address; returns the bound address (useful with port 0).
+    pub async fn start(self: &Arc<Self>) -> Result<SocketAddr, Box<dyn std::error::Error>> {
         let mut running = self.running.lock().await;
         if *running {
             return Err("Server already running".into());
         }
         
+        let listener = TcpListener::bind((self.config.host.as_str(), self.config.port)).await?;
+        let address = listener.local_addr()?;
+        self.shutdown.send_replace(false);
+        let mut shutdown = self.shutdown.subscribe();
+        let server = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = shutdown.wait_for(|stop| *stop) => break,
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, address)) => {
+                            tokio::spawn(server.clone().serve(stream, address));
+                        }
+                        Err(e) => eprintln!("Agent server failed to accept a connection: {}", e),
+                    },
+                }
+            }
+        });
         *running = true;
         
-        // In a real implementation, this would start an actual HTTP/WebSocket server
-        // For now, we'll just set the flag
-        
-        println!("Agent server started on {}:{}", self.config.host, self.config.port);
+        println!("Agent server started on {}", address);
         
-        Ok(())
+        Ok(address)
     }
     
     pub async fn stop(&self) -> Result<(), Box<dyn std::error::Error>> {
@@ -93,6 +302,7 @@ impl AgentServer {
         }
         
         *running = false;
+        self.shutdown.send_replace(true);
         
         // Clean up connections
         let mut connections = self.connections.lock().await;
@@ -107,21 +317,114 @@ impl AgentServer {
         *self.running.lock().await
     }
     
-    pub async fn handle_request(&self, request: AgentRequest) -> AgentResponse {
+    /// Check the caller may make `request`, handle it and record it in the audit log.
+    ///
+    /// `authenticate` requests set the caller's token.
+    pub async fn handle_request(&self, request: AgentRequest, caller: &mut Caller, progress: Progress) -> AgentResponse {
+        let started = Instant::now();
+        let (method, id) = (request.method.clone(), request.id.clone());
+        let response = if method == "authenticate" {
+            self.handle_authenticate(request, caller)
+        } else {
+            match self.authorize(caller) {
+                Ok(capabilities) => self.dispatch(request, &capabilities, progress).await,
+                Err((code, message)) => AgentResponse::error(request.id, code, message),
+            }
+        };
+        self.audit.record(AuditEntry {
+            timestamp: chrono::Utc::now(),
+            client: caller.address.map(|address| address.to_string()),
+            token: caller.token.as_ref().map(|token| token.name.clone()),
+            method,
+            id: id.unwrap_or_default(),
+            outcome: match &response.error {
+                Some(error) => format!("{} {}", error.code, error.message),
+                None => "ok".to_string(),
+            },
+            duration_ms: started.elapsed().as_millis() as u64,
+        });
+        response
+    }
+    
+    /// The caller's capabilities, if it may call the method.
+    ///
+    /// Every method needs `read`; `write` and `execute` decide which tools `chat` may use.
+    fn authorize(&self, caller: &Caller) -> Result<Vec<Capability>, (i32, String)> {
+        if !self.config.require_auth {
+            return Ok(Capability::ALL.to_vec());
+        }
+        let Some(token) = &caller.token else {
+            return Err((UNAUTHORIZED, "Authentication required".to_string()));
+        };
+        if !token.allows(Capability::Read) {
+            return Err((FORBIDDEN, format!("Token '{}' lacks the read capability", token.name)));
+        }
+        Ok(token.capabilities.clone())
+    }
+    
+    fn handle_authenticate(&self, request: AgentRequest, caller: &mut Caller) -> AgentResponse {
+        let secret = request.params.get("token").and_then(|v| v.as_str()).unwrap_or("");
+        match self.tokens.authenticate(secret) {
+            Some(token) => {
+                caller.token = Some(token.clone());
+                AgentResponse::result(request.id, serde_json::json!({
+                    "name": token.name,
+                    "capabilities": token.capabilities
+                }))
+            }
+            None => AgentResponse::error(request.id, UNAUTHORIZED, "Invalid token"),
+        }
+    }
+    
+    /// Handle one raw JSON-RPC message, a single call or a batch.
+    ///
+    /// Returns the serialized reply, or `None` if the message held only notifications.
+    async fn handle_message(&self, text: &str, caller: &StdMutex<Caller>, progress: Option<mpsc::UnboundedSender<Outgoing>>) -> Option<String> {
+        let value: Value = match serde_json::from_str(text) {
+            Ok(value) => value,
+            Err(e) => return Some(to_json(&AgentResponse::error(None, PARSE_ERROR, format!("Parse error: {}", e)))),
+        };
+        match value {
+            Value::Array(calls) if calls.is_empty() => Some(to_json(&AgentResponse::error(None, INVALID_REQUEST, "Empty batch"))),
+            Value::Array(calls) => {
+                let mut responses = Vec::new();
+                for call in calls {
+                    responses.extend(self.handle_call(call, caller, progress.clone()).await);
+                }
+                (!responses.is_empty()).then(|| to_json(&responses))
+            }
+            call => self.handle_call(call, caller, progress).await.map(|response| to_json(&response)),
+        }
+    }
+    
+    async fn handle_call(&self, call: Value, caller: &StdMutex<Caller>, outgoing: Option<mpsc::UnboundedSender<Outgoing>>) -> Option<AgentResponse> {
+        let id = call.get("id").cloned();
+        let request: AgentRequest = match serde_json::from_value(call) {
+            Ok(request) => request,
+            Err(e) => return Some(AgentResponse::error(id, INVALID_REQUEST, format!("Invalid request: {}", e))),
+        };
+        if request.jsonrpc != "2.0" {
+            return Some(AgentResponse::error(request.id, INVALID_REQUEST, "Only JSON-RPC 2.0 is supported"));
+        }
+        let notification = request.id.is_none();
+        let progress = Progress { id: request.id.clone().unwrap_or_default(), outgoing: outgoing.filter(|_| !notification) };
+        let authenticating = request.method == "authenticate";
+        let mut snapshot = caller.lock().unwrap().clone();
+        let response = self.handle_request(request, &mut snapshot, progress).await;
+        if authenticating {
+            *caller.lock().unwrap() = snapshot;
+        }
+        (!notification).then_some(response)
+    }
+    
+    async fn dispatch(&self, request: AgentRequest, capabilities: &[Capability], progress: Progress) -> AgentResponse {
         match request.method.as_str() {
             "complete" => self.handle_completion(request).await,
             "analyze" => self.handle_analysis(request).await,
             "suggest" => self.handle_suggestion(request).await,
             "refactor" => self.handle_refactor(request).await,
-            _ => AgentResponse {
-                id: request.id,
-                result: None,
-                error: Some(AgentError {
-                    code: -32601,
-                    message: "Method not found".to_string(),
-                    data: None,
-                }),
-            },
+            "chat" => self.handle_chat(request, capabilities, progress).await,
+            _ => AgentResponse::error(request.id, METHOD_NOT_FOUND, "Method not found"),
         }
     }
     
@@ -135,20 +438,67 @@ impl AgentServer {
             .and_then(|v| v.as_u64())
             .unwrap_or(0) as usize;
         
-        // Simulate completion
-        let completions = vec![
-            "// TODO: Implement this function",
-            "return result;",
-            "console.log('Debug:', ",
-        ];
+        let Some(completer) = &self.completer else {
+            return AgentResponse::error(request.id, NOT_CONFIGURED, "No fill-in-the-middle model configured");
+        };
+        
+        match completer.complete(&FimContext::new(code, cursor_pos, None)).await {
+            Ok(text) => {
+                let completions: Vec<String> = if text.is_empty() { Vec::new() } else { vec![text] };
+                AgentResponse::result(request.id, serde_json::json!({
+                    "completions": completions,
+                    "cursor": cursor_pos
+                }))
+            }
+            Err(e) => AgentResponse::error(request.id, INTERNAL_ERROR, e.to_string()),
+        }
+    }
+    
+    /// Run the agent on `message` after an optional `history` of `{role, content}` turns.
+    async fn handle_chat(&self, request: AgentRequest, capabilities: &[Capability], progress: Progress) -> AgentResponse {
+        let Some(agent) = &self.agent else {
+            return AgentResponse::error(request.id, NOT_CONFIGURED, "No agent attached to the server");
+        };
+        let Some(message) = request.params.get("message").and_then(|v| v.as_str()) else {
+            return AgentResponse::error(request.id, INVALID_PARAMS, "Missing string parameter 'message'");
+        };
+        
+        let mut history = Vec::new();
+        for turn in request.params.get("history").and_then(|v| v.as_array()).into_iter().flatten() {
+            let role = turn.get("role").cloned().and_then(|role| serde_json::from_value::<MessageRole>(role).ok());
+            let (Some(role), Some(content)) = (role, turn.get("content").and_then(|v| v.as_str())) else {
+                return AgentResponse::error(request.id, INVALID_PARAMS, "History turns need a 'role' and 'content'");
+            };
+            history.push(chat_message(role, content));
+        }
+        history.push(chat_message(MessageRole::User, message));
         
-        AgentResponse {
-            id: request.id,
-            result: Some(serde_json::json!({
-                "completions": completions,
-                "cursor": cursor_pos
+        // Run on a clone so the editor can use the agent while this reply streams.
+        let agent = agent.lock().await.clone();
+        // The agent's own registry can't be narrowed per caller, so remote chats get a
+        // fresh set of the built-in tools limited to the caller's capabilities.
+        let workspace = agent.get_tools().workspace().to_path_buf();
+        let mut tools = if agent.get_tools().is_empty() { ToolRegistry::new(workspace) } else { ToolRegistry::with_default_tools(workspace) };
+        tools.retain_capabilities(capabilities);
+        let run = agent.process_message_with_tools(&history, &Arc::new(tools), |event| {
+            progress.send(match event {
+                AgentEvent::Text(text) => serde_json::json!({"type": "text", "text": text}),
+                AgentEvent::ToolCall(call) => serde_json::json!({"type": "tool_call", "name": call.name, "input": call.input}),
+                AgentEvent::ToolResult(result) => serde_json::json!({"type": "tool_result", "name": result.name, "is_error": result.is_error}),
+                AgentEvent::EditProposal(proposal) => serde_json::json!({
+                    "type": "edit_proposal",
+                    "description": proposal.description,
+                    "files": proposal.files.iter().map(|file| file.path.to_string_lossy().to_string()).collect::<Vec<_>>(),
+                }),
+            });
+        }).await;
+        match run {
+            Ok(run) => AgentResponse::result(request.id, serde_json::json!({
+                "text": run.text,
+                "steps": run.steps,
+                "usage": {"input_tokens": run.usage.input_tokens, "output_tokens": run.usage.output_tokens},
             })),
-            error: None,
+            Err(e) => AgentResponse::error(request.id, INTERNAL_ERROR, e.to_string()),
         }
     }
     
@@ -182,11 +532,7 @@ impl AgentServer {
             ]
         });
         
-        AgentResponse {
-            id: request.id,
-            result: Some(analysis),
-            error: None,
-        }
+        AgentResponse::result(request.id, analysis)
     }
     
     async fn handle_suggestion(&self, request: AgentRequest) -> AgentResponse {
@@ -210,11 +556,7 @@ impl AgentServer {
             ]
         });
         
-        AgentResponse {
-            id: request.id,
-            result: Some(suggestions),
-            error: None,
-        }
+        AgentResponse::result(request.id, suggestions)
     }
     
     async fn handle_refactor(&self, request: AgentRequest) -> AgentResponse {
@@ -237,18 +579,155 @@ impl AgentServer {
             _ => code.to_string(),
         };
         
-        AgentResponse {
-            id: request.id,
-            result: Some(serde_json::json!({
-                "refactored_code": refactored,
-                "changes": 1
-            })),
-            error: None,
+        AgentResponse::result(request.id, serde_json::json!({
+            "refactored_code": refactored,
+            "changes": 1
+        }))
+    }
+    
+    /// Serve one client until it disconnects, goes idle or the server stops.
+    async fn serve(self: Arc<Self>, stream: TcpStream, address: SocketAddr) {
+        if let Err(e) = self.serve_connection(stream, address).await {
+            eprintln!("Agent server connection from {} failed: {}", address, e);
+        }
+    }
+    
+    async fn serve_connection(self: &Arc<Self>, stream: TcpStream, address: SocketAddr) -> std::io::Result<()> {
+        let idle = Duration::from_secs(self.config.timeout_seconds.max(1));
+        let (read, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(read);
+        let Ok(first) = tokio::time::timeout(idle, reader.fill_buf()).await else {
+            return Ok(());
+        };
+        
+        // WebSocket clients open with an HTTP upgrade request; anything else speaks lines.
+        let is_websocket = first?.starts_with(b"GET ");
+        // Take a slot before reading the handshake, so half-open clients count against the limit.
+        let Some(id) = self.add_connection(address).await else {
+            let refusal = match is_websocket {
+                true => websocket::http_error("503 Service Unavailable", "Too many connections"),
+                false => format!("{}\n", to_json(&AgentResponse::error(None, SERVER_BUSY, "Too many connections"))),
+            };
+            return writer.write_all(refusal.as_bytes()).await;
+        };
+        let result = self.open_connection(&id, address, is_websocket, reader, writer, idle).await;
+        self.remove_connection(&id).await;
+        result
+    }
+    
+    /// Finish the WebSocket handshake, if the client started one, and serve the connection.
+    async fn open_connection(self: &Arc<Self>, id: &str, address: SocketAddr, is_websocket: bool, mut reader: BufReader<OwnedReadHalf>, mut writer: OwnedWriteHalf, idle: Duration) -> std::io::Result<()> {
+        let mut caller = Caller { address: Some(address), token: None };
+        let transport = if is_websocket {
+            let Ok(accepted) = tokio::time::timeout(idle, self.accept_websocket(&mut reader, &mut caller)).await else {
+                return Ok(());
+            };
+            match accepted? {
+                Ok(response) => {
+                    writer.write_all(response.as_bytes()).await?;
+                    Transport::WebSocket(WebSocketReader::new(reader))
+                }
+                Err(reply) => {
+                    // Free the slot before replying, so the client can retry straight away.
+                    self.remove_connection(id).await;
+                    return writer.write_all(reply.as_bytes()).await;
+                }
+            }
+        } else {
+            Transport::Lines(reader)
+        };
+        self.run_connection(id, Arc::new(StdMutex::new(caller)), transport, writer, idle).await
+    }
+    
+    /// Read the opening handshake and check the client may connect. Returns the
+    /// `101` reply to send, or the HTTP error to close the connection with.
+    async fn accept_websocket(&self, reader: &mut BufReader<OwnedReadHalf>, caller: &mut Caller) -> std::io::Result<Result<String, String>> {
+        let handshake = websocket::read_handshake(reader).await?;
+        if let Err(reason) = self.check_origin(handshake.origin()) {
+            return Ok(Err(websocket::http_error("403 Forbidden", &reason)));
+        }
+        // Browsers can't set headers on a WebSocket, so the token may also come in the URL.
+        let secret = handshake.header("authorization").and_then(|value| value.strip_prefix("Bearer ")).or_else(|| handshake.query_param("token"));
+        if let Some(secret) = secret {
+            match self.tokens.authenticate(secret) {
+                Some(token) => caller.token = Some(token.clone()),
+                None => return Ok(Err(websocket::http_error("401 Unauthorized", "Invalid token"))),
+            }
+        }
+        Ok(handshake.accept().map_err(|reason| websocket::http_error("400 Bad Request", &reason)))
+    }
+    
+    /// Read requests and handle each in its own task, so a streaming `chat` doesn't
+    /// hold up the requests behind it. Replies may come back out of order.
+    async fn run_connection(self: &Arc<Self>, id: &str, caller: Arc<StdMutex<Caller>>, mut transport: Transport, mut writer: OwnedWriteHalf, idle: Duration) -> std::io::Result<()> {
+        let is_websocket = matches!(transport, Transport::WebSocket(_));
+        let (outgoing, mut queue) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(message) = queue.recv().await {
+                let bytes = match (is_websocket, message) {
+                    (true, Outgoing::Text(text)) => websocket::encode(&Message::Text(text), None),
+                    (true, Outgoing::Pong(data)) => websocket::encode(&Message::Pong(data), None),
+                    (true, Outgoing::Close) => {
+                        let _ = writer.write_all(&websocket::encode(&Message::Close, None)).await;
+                        break;
+                    }
+                    (false, Outgoing::Text(text)) => format!("{}\n", text).into_bytes(),
+                    (false, _) => continue,
+                };
+                if writer.write_all(&bytes).await.is_err() {
+                    break;
+                }
+            }
+            let _ = writer.shutdown().await;
+        });
+        
+        let mut shutdown = self.shutdown.subscribe();
+        let result = loop {
+            let next = tokio::select! {
+                _ = shutdown.changed() => break Ok(()),
+                next = tokio::time::timeout(idle, transport.next(&outgoing)) => next,
+            };
+            match next {
+                Ok(Ok(Some(text))) => {
+                    self.update_activity(id).await;
+                    let server = self.clone();
+                    let (caller, outgoing) = (caller.clone(), outgoing.clone());
+                    tokio::spawn(async move {
+                        if let Some(reply) = server.handle_message(&text, &caller, Some(outgoing.clone())).await {
+                            let _ = outgoing.send(Outgoing::Text(reply));
+                        }
+                    });
+                }
+                Ok(Ok(None)) | Err(_) => break Ok(()),
+                Ok(Err(e)) => break Err(e),
+            }
+        };
+        // Requests still running keep the writer open until they reply.
+        let _ = outgoing.send(Outgoing::Close);
+        result
+    }
+    
+    /// Browser pages must come from an allowed origin; clients without an `Origin` aren't browsers.
+    fn check_origin(&self, origin: Option<&str>) -> Result<(), String> {
+        let Some(origin) = origin else {
+            return Ok(());
+        };
+        if !self.config.enable_cors {
+            return Err("Browser connections are disabled".to_string());
+        }
+        if self.config.allowed_origins.iter().any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(origin)) {
+            Ok(())
+        } else {
+            Err(format!("Origin {} is not allowed", origin))
         }
     }
     
-    pub async fn add_connection(&self, address: SocketAddr) -> String {
+    /// Register a client, or `None` if the server is already at `max_connections`.
+    pub async fn add_connection(&self, address: SocketAddr) -> Option<String> {
         let mut connections = self.connections.lock().await;
+        if connections.len() >= self.config.max_connections {
+            return None;
+        }
         
         let connection = ClientConnection {
             id: uuid::Uuid::new_v4().to_string(),
@@ -260,12 +739,7 @@ impl AgentServer {
         let id = connection.id.clone();
         connections.push(connection);
         
-        // Enforce max connections
-        if connections.len() > self.config.max_connections {
-            connections.remove(0);
-        }
-        
-        id
+        Some(id)
     }
     
     pub async fn remove_connection(&self, id: &str) {
@@ -296,6 +770,20 @@ impl AgentServer {
 // Placeholder for serde traits
 use serde::{Serialize, Deserialize};
 
+fn to_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+fn chat_message(role: MessageRole, content: &str) -> ChatMessage {
+    ChatMessage {
+        id: String::new(),
+        role,
+        content: content.to_string(),
+        timestamp: chrono::Utc::now(),
+        metadata: MessageMetadata::default(),
+    }
+}
+
 // Mock UUID
 mod uuid {
     pub struct Uuid;
@@ -307,9 +795,9 @@ mod uuid {
         
         pub fn to_string(&self) -> String {
             format!("{:x}-{:x}-{:x}", 
-                rand::random::<u32>(),
-                rand::random::<u16>(),
-                rand::random::<u32>()
+                super::rand::random() as u32,
+                super::rand::random() as u16,
+                super::rand::random() as u32
             )
         }
     }
@@ -317,13 +805,154 @@ mod uuid {
 
 // Mock random
 mod rand {
+    use std::sync::atomic::{AtomicU64, Ordering};
     use std::time::{SystemTime, UNIX_EPOCH};
     
-    pub fn random<T: From<u64>>() -> T {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    
+    pub fn random() -> u64 {
         let nanos = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_nanos() as u64;
-        T::from(nanos)
+        // Mix in a counter so calls within the same nanosecond differ
+        let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+        (nanos ^ count.wrapping_mul(0x9E37_79B9_7F4A_7C15)).rotate_left((count % 64) as u32)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::backend::code_agent::agent::AgentConfig;
+    use crate::backend::code_agent::providers::scripted::ScriptedProvider;
+    use crate::backend::code_agent::test_support::TempDir;
+
+    async fn websocket_handshake(address: SocketAddr, path: &str, origin: &str) -> (String, TcpStream) {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\nOrigin: {}\r\n\r\n",
+            path, address, origin
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = Vec::new();
+        while !response.ends_with(b"\r\n\r\n") {
+            response.push(stream.read_u8().await.unwrap());
+        }
+        (String::from_utf8(response).unwrap(), stream)
     }
-}
\ No newline at end of file
+
+    async fn read_frame(stream: &mut TcpStream) -> Value {
+        let mut head = [0u8; 2];
+        stream.read_exact(&mut head).await.unwrap();
+        assert_eq!(head[0], 0x81, "expected a final text frame");
+        let len = match head[1] {
+            126 => stream.read_u16().await.unwrap() as usize,
+            len => len as usize,
+        };
+        let mut payload = vec![0u8; len];
+        stream.read_exact(&mut payload).await.unwrap();
+        serde_json::from_slice(&payload).unwrap()
+    }
+
+    async fn call(write: &mut OwnedWriteHalf, lines: &mut tokio::io::Lines<BufReader<OwnedReadHalf>>, request: Value) -> Value {
+        write.write_all(format!("{}\n", request).as_bytes()).await.unwrap();
+        serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_json_rpc_over_tcp_and_websocket() {
+        let temp = TempDir::new("agent_server");
+        let workspace = temp.path().to_path_buf();
+        let model = Arc::new(ScriptedProvider::new(vec![ScriptedProvider::reply("Hello from the agent."), ScriptedProvider::reply("Hello again.")]));
+        let mut agent = CodeAgent::new(AgentConfig::default());
+        agent.set_provider(AgentConfig::default(), model.clone());
+        agent.set_tools(ToolRegistry::with_default_tools(workspace));
+        let mut tokens = TokenStore::default();
+        let ide = tokens.create("ide", &Capability::ALL).unwrap();
+        let ci = tokens.create("ci", &[Capability::Read]).unwrap();
+        let blind = tokens.create("blind", &[Capability::Write]).unwrap();
+        let config = AgentServerConfig {
+            port: 0,
+            max_connections: 2,
+            enable_cors: true,
+            allowed_origins: vec!["http://localhost:3000".to_string()],
+            ..AgentServerConfig::default()
+        };
+        let server = Arc::new(AgentServer::new(config).with_agent(Arc::new(Mutex::new(agent))).with_tokens(tokens));
+        let address = server.start().await.unwrap();
+
+        // Line-delimited TCP: nothing is handled before the client authenticates.
+        let (read, mut write) = TcpStream::connect(address).await.unwrap().into_split();
+        let mut lines = BufReader::new(read).lines();
+        let reply = call(&mut write, &mut lines, json!({"jsonrpc": "2.0", "id": 1, "method": "analyze"})).await;
+        assert_eq!(reply["error"]["code"], UNAUTHORIZED);
+        let reply = call(&mut write, &mut lines, json!({"jsonrpc": "2.0", "id": 2, "method": "authenticate", "params": {"token": blind.secret}})).await;
+        assert_eq!(reply["result"]["name"], "blind");
+        let reply = call(&mut write, &mut lines, json!({"jsonrpc": "2.0", "id": 3, "method": "analyze"})).await;
+        assert_eq!(reply["error"]["code"], FORBIDDEN);
+        let reply = call(&mut write, &mut lines, json!({"jsonrpc": "2.0", "id": 4, "method": "authenticate", "params": {"token": ci.secret}})).await;
+        assert_eq!(reply["result"]["capabilities"], json!(["read"]));
+
+        // Notifications get no reply, batches get an array.
+        write.write_all(b"{\"jsonrpc\":\"2.0\",\"method\":\"analyze\",\"params\":{}}\n").await.unwrap();
+        write.write_all(b"[{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"complete\"},{\"jsonrpc\":\"2.0\",\"id\":\"b\",\"method\":\"nope\"}]\n").await.unwrap();
+        let reply: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!((reply[0]["id"].clone(), reply[0]["error"]["code"].clone()), (json!(1), json!(NOT_CONFIGURED)));
+        assert_eq!((reply[1]["id"].clone(), reply[1]["error"]["code"].clone()), (json!("b"), json!(METHOD_NOT_FOUND)));
+        write.write_all(b"not json\n").await.unwrap();
+        let reply: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!((reply["id"].clone(), reply["error"]["code"].clone()), (Value::Null, json!(PARSE_ERROR)));
+
+        // A read-only token chats without the tools that write or run commands.
+        let reply = call(&mut write, &mut lines, json!({"jsonrpc": "2.0", "id": 5, "method": "chat", "params": {"message": "Hi"}})).await;
+        assert_eq!(reply["params"]["event"]["text"], "Hello from the agent.");
+        let reply: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(reply["result"]["text"], "Hello from the agent.");
+
+        // WebSocket: the origin and the token in the URL are checked, and chat streams progress before its result.
+        let (response, _) = websocket_handshake(address, "/", "http://evil.example").await;
+        assert!(response.starts_with("HTTP/1.1 403"));
+        let (response, _) = websocket_handshake(address, "/?token=wrong", "http://localhost:3000").await;
+        assert!(response.starts_with("HTTP/1.1 401"));
+        let (response, mut socket) = websocket_handshake(address, &format!("/?token={}", ide.secret), "http://localhost:3000").await;
+        assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+        let chat = json!({"jsonrpc": "2.0", "id": 7, "method": "chat", "params": {"message": "Hi"}});
+        socket.write_all(&websocket::encode(&Message::Text(chat.to_string()), Some([9, 8, 7, 6]))).await.unwrap();
+        let progress = read_frame(&mut socket).await;
+        assert_eq!(progress["method"], PROGRESS_METHOD);
+        assert_eq!(progress["params"], json!({"id": 7, "event": {"type": "text", "text": "Hello again."}}));
+        let result = read_frame(&mut socket).await;
+        assert_eq!((result["id"].clone(), result["result"]["text"].clone()), (json!(7), json!("Hello again.")));
+
+        let tool_names = |request: usize| model.requests()[request].tools.iter().map(|tool| tool.name.clone()).collect::<Vec<_>>();
+        assert!(tool_names(0).contains(&"read_file".to_string()));
+        assert!(!tool_names(0).contains(&"write_file".to_string()) && !tool_names(0).contains(&"run_command".to_string()));
+        assert!(tool_names(1).contains(&"write_file".to_string()) && tool_names(1).contains(&"run_command".to_string()));
+
+        // Both connection slots are taken.
+        let mut third = TcpStream::connect(address).await.unwrap();
+        third.write_all(b"{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"analyze\"}\n").await.unwrap();
+        let mut refusal = String::new();
+        BufReader::new(third).read_line(&mut refusal).await.unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&refusal).unwrap()["error"]["code"], SERVER_BUSY);
+
+        let audit = server.audit_log().recent();
+        let first = &audit[0];
+        assert_eq!((first.method.as_str(), first.token.as_deref(), first.outcome.as_str()), ("analyze", None, "-32002 Authentication required"));
+        assert!(audit.iter().any(|entry| entry.method == "chat" && entry.token.as_deref() == Some("ide") && entry.outcome == "ok"));
+        assert!(audit.iter().all(|entry| entry.client.is_some()));
+
+        server.stop().await.unwrap();
+        assert!(lines.next_line().await.unwrap().is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_request_lines_are_bounded() {
+        let mut reader = "{}\nendless".as_bytes();
+        assert_eq!(read_limited_line(&mut reader, 4).await.unwrap().as_deref(), Some("{}\n"));
+        assert_eq!(read_limited_line(&mut reader, 4).await.unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(read_limited_line(&mut "".as_bytes(), 4).await.unwrap(), None);
+    }
+}
@@ -1,17 +1,281 @@
-// WHAT I WANT: 
-// WHAT IT DOES: 
-// TODO: 
-// FIXME: 
+//! Prompt templates, run from the agent chat as slash commands.
+//!
+//! Besides the built-in templates, a project can keep its own in
+//! `.jadio/prompts/<name>.md`, run as `/<name>`. The front matter declares the
+//! template's variables:
+//!
+//! ```text
+//! ---
+//! description: Write tests for the selected code
+//! category: testing
+//! variables:
+//!   framework: choice(unit|integration) = unit
+//!   focus: text?
+//! ---
+//! Write {{framework}} tests for this {{language}} code from {{file}}:
+//!
+//! {{selection}}
+//! ```
+//!
+//! A variable is `name: type`, with `?` if it may be left empty and `= value`
+//! for a default. Types are `text`, `number`, `bool` and `choice(a|b|...)`.
+//! The [`BUILTIN_VARIABLES`] are filled in from the editor and need no
+//! declaration. Project templates replace built-in ones of the same name.
 
 use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use super::context_builder::Selection;
+
+/// Variables every template can use, filled in by the caller.
+pub const BUILTIN_VARIABLES: &[&str] = &["selection", "file", "language", "diagnostics", "git_diff"];
+const PROMPTS_DIR: &str = ".jadio/prompts";
+const RECENT_LIMIT: usize = 50;
+
+/// The built-in templates, in the template file format.
+const DEFAULT_TEMPLATES: &[(&str, &str)] = &[
+    (
+        "generate_function",
+        "---\ndescription: Generate a function with given specifications\ncategory: code_generation\nvariables:\n  description: text\n  requirements: text?\n---\n\
+         Generate a {{language}} function that {{description}}. The function should {{requirements}}.\n",
+    ),
+    (
+        "fix_bug",
+        "---\ndescription: Fix a bug in the selected code\ncategory: bug_fix\nvariables:\n  error: text\n---\n\
+         Fix the following bug in this {{language}} code from {{file}}:\n\n{{selection}}\n\nError: {{error}}\n\n\
+         Diagnostics:\n{{diagnostics}}\n\nProvide the corrected code and explain the fix.\n",
+    ),
+    (
+        "refactor_code",
+        "---\ndescription: Refactor the selected code\ncategory: refactoring\nvariables:\n  aspect: text = readability\n---\n\
+         Refactor this {{language}} code to improve {{aspect}}:\n\n{{selection}}\n\nMaintain the same functionality.\n",
+    ),
+    (
+        "add_documentation",
+        "---\ndescription: Document the selected code\ncategory: documentation\nvariables:\n  doc_type: text = API\n---\n\
+         Add comprehensive documentation to this {{language}} code:\n\n{{selection}}\n\nInclude {{doc_type}} documentation.\n",
+    ),
+    (
+        "write_tests",
+        "---\ndescription: Write tests for the selected code\ncategory: testing\nvariables:\n  test_type: choice(unit|integration|property) = unit\n---\n\
+         Write {{test_type}} tests for this {{language}} code from {{file}}:\n\n{{selection}}\n\nCover edge cases and common scenarios.\n",
+    ),
+    (
+        "optimize_performance",
+        "---\ndescription: Optimize the selected code\ncategory: optimization\nvariables:\n  optimization_goal: text = speed\n---\n\
+         Optimize this {{language}} code for {{optimization_goal}}:\n\n{{selection}}\n\nProvide benchmarks if possible.\n",
+    ),
+    (
+        "security_review",
+        "---\ndescription: Review the selected code for security issues\ncategory: security\n---\n\
+         Review this {{language}} code for security vulnerabilities:\n\n{{selection}}\n\nSuggest fixes for any issues found.\n",
+    ),
+    (
+        "review_changes",
+        "---\ndescription: Review uncommitted changes\ncategory: code_review\n---\n\
+         Review these uncommitted changes for bugs, missing tests and unclear code:\n\n```diff\n{{git_diff}}\n```\n",
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariableType {
+    Text,
+    Number,
+    Bool,
+    /// One of the listed values.
+    Choice(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateVariable {
+    pub name: String,
+    pub kind: VariableType,
+    pub default: Option<String>,
+    /// May be left empty.
+    pub optional: bool,
+}
+
+impl TemplateVariable {
+    /// Parse a declaration such as `choice(unit|integration) = unit` or `text?`.
+    fn parse(name: &str, spec: &str) -> Result<Self, String> {
+        let (kind, default) = match spec.split_once('=') {
+            Some((kind, default)) => (kind.trim(), Some(unquote(default.trim()).to_string())),
+            None => (spec.trim(), None),
+        };
+        let (kind, optional) = match kind.strip_suffix('?') {
+            Some(kind) => (kind.trim_end(), true),
+            None => (kind, false),
+        };
+        let kind = match kind {
+            "text" | "" => VariableType::Text,
+            "number" => VariableType::Number,
+            "bool" => VariableType::Bool,
+            _ => match kind.strip_prefix("choice(").and_then(|rest| rest.strip_suffix(')')) {
+                Some(options) => VariableType::Choice(options.split('|').map(|option| option.trim().to_string()).filter(|option| !option.is_empty()).collect()),
+                None => return Err(format!("Variable '{}' has unknown type '{}'", name, kind)),
+            },
+        };
+        let variable = Self { name: name.to_string(), kind, default, optional };
+        if let Some(default) = &variable.default {
+            variable.check(default)?;
+        }
+        Ok(variable)
+    }
+
+    /// Check that `value` suits the variable's type.
+    pub fn check(&self, value: &str) -> Result<(), String> {
+        let valid = match &self.kind {
+            VariableType::Text => true,
+            VariableType::Number => value.trim().parse::<f64>().is_ok(),
+            VariableType::Bool => matches!(value.trim(), "true" | "false" | "yes" | "no"),
+            VariableType::Choice(options) => options.iter().any(|option| option == value.trim()),
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("'{}' must be {}, not '{}'", self.name, self.type_name(), value.trim()))
+        }
+    }
+
+    /// The type as written in a template file.
+    pub fn type_name(&self) -> String {
+        match &self.kind {
+            VariableType::Text => "text".to_string(),
+            VariableType::Number => "number".to_string(),
+            VariableType::Bool => "bool".to_string(),
+            VariableType::Choice(options) => format!("choice({})", options.join("|")),
+        }
+    }
+
+    fn spec(&self) -> String {
+        let mut spec = self.type_name();
+        if self.optional {
+            spec.push('?');
+        }
+        if let Some(default) = &self.default {
+            spec.push_str(&format!(" = {}", default));
+        }
+        spec
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct AutoPromptTemplate {
     pub name: String,
     pub description: String,
+    /// The prompt, with `{{variable}}` placeholders.
     pub template: String,
-    pub variables: Vec<String>,
+    /// Declared variables, in the order they are filled from a slash command.
+    pub variables: Vec<TemplateVariable>,
     pub category: PromptCategory,
+    /// The file the template was loaded from; `None` for built-in templates.
+    pub path: Option<PathBuf>,
+}
+
+impl AutoPromptTemplate {
+    /// Parse a template file, front matter and body.
+    pub fn parse(name: &str, text: &str) -> Result<Self, String> {
+        if !is_identifier(name) {
+            return Err(format!("Template name '{}' may only contain letters, digits, '_' and '-'", name));
+        }
+        let text = text.replace("\r\n", "\n");
+        let (front, body) = match text.strip_prefix("---\n").and_then(|rest| rest.split_once("\n---")) {
+            Some((front, body)) => (front, body.strip_prefix('\n').unwrap_or(body)),
+            None => ("", text.as_str()),
+        };
+
+        let mut template = Self {
+            name: name.to_string(),
+            description: String::new(),
+            template: body.trim().to_string(),
+            variables: Vec::new(),
+            category: PromptCategory::Custom,
+            path: None,
+        };
+        let mut in_variables = false;
+        for line in front.lines().filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#')) {
+            let (key, value) = line.split_once(':').ok_or_else(|| format!("Expected 'key: value' in front matter, got '{}'", line.trim()))?;
+            let (key, value) = (key.trim(), value.trim());
+            if line.starts_with([' ', '\t']) && in_variables {
+                if !is_identifier(key) {
+                    return Err(format!("Invalid variable name '{}'", key));
+                }
+                if BUILTIN_VARIABLES.contains(&key) {
+                    return Err(format!("'{}' is a built-in variable", key));
+                }
+                template.variables.push(TemplateVariable::parse(key, value)?);
+                continue;
+            }
+            in_variables = key == "variables";
+            match key {
+                "description" => template.description = unquote(value).to_string(),
+                "category" => template.category = PromptCategory::parse(value),
+                "variables" if value.is_empty() => {}
+                _ => return Err(format!("Unknown front matter key '{}'", key)),
+            }
+        }
+
+        if template.template.is_empty() {
+            return Err("Template has no prompt text".to_string());
+        }
+        let undeclared: Vec<String> = template
+            .placeholders()
+            .into_iter()
+            .filter(|name| !BUILTIN_VARIABLES.contains(&name.as_str()) && !template.variables.iter().any(|variable| &variable.name == name))
+            .collect();
+        if !undeclared.is_empty() {
+            return Err(format!("Undeclared variables: {}", undeclared.join(", ")));
+        }
+        Ok(template)
+    }
+
+    /// The template in the file format [`Self::parse`] reads.
+    pub fn to_markdown(&self) -> String {
+        let mut text = format!("---\ndescription: {}\ncategory: {}\n", self.description, self.category.name());
+        if !self.variables.is_empty() {
+            text.push_str("variables:\n");
+            for variable in &self.variables {
+                text.push_str(&format!("  {}: {}\n", variable.name, variable.spec()));
+            }
+        }
+        text.push_str(&format!("---\n{}\n", self.template));
+        text
+    }
+
+    /// Names of the placeholders in the prompt, in order of first use.
+    pub fn placeholders(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        let mut rest = self.template.as_str();
+        while let Some(start) = rest.find("{{") {
+            let Some(end) = rest[start..].find("}}") else { break };
+            let name = rest[start + 2..start + end].trim().to_string();
+            if !names.contains(&name) {
+                names.push(name);
+            }
+            rest = &rest[start + end + 2..];
+        }
+        names
+    }
+
+    /// The built-in variables the prompt uses, which the caller has to supply.
+    pub fn builtins_used(&self) -> Vec<&'static str> {
+        let placeholders = self.placeholders();
+        BUILTIN_VARIABLES.iter().copied().filter(|name| placeholders.iter().any(|used| used == name)).collect()
+    }
+
+    fn render(&self, values: &HashMap<String, String>) -> String {
+        let mut prompt = String::new();
+        let mut rest = self.template.as_str();
+        while let Some(start) = rest.find("{{") {
+            let Some(end) = rest[start..].find("}}") else { break };
+            prompt.push_str(&rest[..start]);
+            let name = rest[start + 2..start + end].trim();
+            prompt.push_str(values.get(name).map(String::as_str).unwrap_or_default());
+            rest = &rest[start + end + 2..];
+        }
+        prompt.push_str(rest);
+        prompt
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -23,13 +287,86 @@ pub enum PromptCategory {
     Testing,
     Optimization,
     Security,
+    CodeReview,
     Custom,
 }
 
+impl PromptCategory {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PromptCategory::CodeGeneration => "code_generation",
+            PromptCategory::BugFix => "bug_fix",
+            PromptCategory::Refactoring => "refactoring",
+            PromptCategory::Documentation => "documentation",
+            PromptCategory::Testing => "testing",
+            PromptCategory::Optimization => "optimization",
+            PromptCategory::Security => "security",
+            PromptCategory::CodeReview => "code_review",
+            PromptCategory::Custom => "custom",
+        }
+    }
+
+    /// The category named `name`, or `Custom` if there is none.
+    pub fn parse(name: &str) -> Self {
+        let name = name.trim().to_lowercase().replace(['-', ' '], "_");
+        [
+            PromptCategory::CodeGeneration,
+            PromptCategory::BugFix,
+            PromptCategory::Refactoring,
+            PromptCategory::Documentation,
+            PromptCategory::Testing,
+            PromptCategory::Optimization,
+            PromptCategory::Security,
+            PromptCategory::CodeReview,
+        ]
+        .into_iter()
+        .find(|category| category.name() == name)
+        .unwrap_or(PromptCategory::Custom)
+    }
+}
+
+/// Editor state that prompt suggestions are based on.
+#[derive(Debug, Clone, Default)]
+pub struct PromptContext {
+    pub has_selection: bool,
+    /// Diagnostics for the current file, e.g. `src/main.rs:12: error: ...`.
+    pub diagnostics: Vec<String>,
+    /// The function or method under the cursor, if it has no doc comment.
+    pub undocumented_function: Option<(String, Selection)>,
+    /// Latest terminal output, oldest first.
+    pub terminal_output: Vec<String>,
+    pub has_uncommitted_changes: bool,
+}
+
+/// A prompt offered to the user as a chip in the agent panel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptSuggestion {
+    pub label: String,
+    /// Why it's suggested.
+    pub reason: String,
+    /// What clicking it puts in the chat input: a slash command or a message.
+    pub input: String,
+    /// Code to select before running it, e.g. the function to document.
+    pub selection: Option<Selection>,
+    pub score: f32,
+}
+
+/// A slash command typed in the chat, before its template is filled in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlashCommand {
+    pub name: String,
+    pub variables: HashMap<String, String>,
+}
+
 pub struct AutoPromptEngine {
     templates: HashMap<String, AutoPromptTemplate>,
     recent_prompts: Vec<String>,
+    /// Project templates and ones added at runtime; these win over built-in templates.
     custom_templates: HashMap<String, AutoPromptTemplate>,
+    /// The project's `.jadio/prompts`, once a project is open.
+    prompts_dir: Option<PathBuf>,
+    /// Template files that failed to load, and why.
+    load_errors: Vec<String>,
 }
 
 impl Default for AutoPromptEngine {
@@ -44,134 +381,381 @@ impl AutoPromptEngine {
             templates: HashMap::new(),
             recent_prompts: Vec::new(),
             custom_templates: HashMap::new(),
+            prompts_dir: None,
+            load_errors: Vec::new(),
         };
-        
+
         engine.load_default_templates();
         engine
     }
-    
+
     fn load_default_templates(&mut self) {
-        // Code generation templates
-        self.add_template(AutoPromptTemplate {
-            name: "generate_function".to_string(),
-            description: "Generate a function with given specifications".to_string(),
-            template: "Generate a {language} function that {description}. The function should {requirements}.".to_string(),
-            variables: vec!["language".to_string(), "description".to_string(), "requirements".to_string()],
-            category: PromptCategory::CodeGeneration,
-        });
-        
-        // Bug fix templates
-        self.add_template(AutoPromptTemplate {
-            name: "fix_bug".to_string(),
-            description: "Fix a bug in the code".to_string(),
-            template: "Fix the following bug in this {language} code:\n{code}\nError: {error}\nProvide the corrected code and explain the fix.".to_string(),
-            variables: vec!["language".to_string(), "code".to_string(), "error".to_string()],
-            category: PromptCategory::BugFix,
-        });
-        
-        // Refactoring templates
-        self.add_template(AutoPromptTemplate {
-            name: "refactor_code".to_string(),
-            description: "Refactor code for better quality".to_string(),
-            template: "Refactor this {language} code to improve {aspect}:\n{code}\nMaintain the same functionality.".to_string(),
-            variables: vec!["language".to_string(), "aspect".to_string(), "code".to_string()],
-            category: PromptCategory::Refactoring,
-        });
-        
-        // Documentation templates
-        self.add_template(AutoPromptTemplate {
-            name: "add_documentation".to_string(),
-            description: "Add documentation to code".to_string(),
-            template: "Add comprehensive documentation to this {language} code:\n{code}\nInclude {doc_type} documentation.".to_string(),
-            variables: vec!["language".to_string(), "code".to_string(), "doc_type".to_string()],
-            category: PromptCategory::Documentation,
-        });
-        
-        // Testing templates
-        self.add_template(AutoPromptTemplate {
-            name: "write_tests".to_string(),
-            description: "Write tests for code".to_string(),
-            template: "Write {test_type} tests for this {language} code:\n{code}\nCover edge cases and common scenarios.".to_string(),
-            variables: vec!["test_type".to_string(), "language".to_string(), "code".to_string()],
-            category: PromptCategory::Testing,
-        });
-        
-        // Optimization templates
-        self.add_template(AutoPromptTemplate {
-            name: "optimize_performance".to_string(),
-            description: "Optimize code for performance".to_string(),
-            template: "Optimize this {language} code for {optimization_goal}:\n{code}\nProvide benchmarks if possible.".to_string(),
-            variables: vec!["language".to_string(), "optimization_goal".to_string(), "code".to_string()],
-            category: PromptCategory::Optimization,
-        });
-        
-        // Security templates
-        self.add_template(AutoPromptTemplate {
-            name: "security_review".to_string(),
-            description: "Review code for security issues".to_string(),
-            template: "Review this {language} code for security vulnerabilities:\n{code}\nSuggest fixes for any issues found.".to_string(),
-            variables: vec!["language".to_string(), "code".to_string()],
-            category: PromptCategory::Security,
-        });
-    }
-    
+        for (name, text) in DEFAULT_TEMPLATES {
+            match AutoPromptTemplate::parse(name, text) {
+                Ok(template) => self.add_template(template),
+                Err(e) => eprintln!("Invalid built-in prompt template '{}': {}", name, e),
+            }
+        }
+    }
+
     fn add_template(&mut self, template: AutoPromptTemplate) {
         self.templates.insert(template.name.clone(), template);
     }
-    
+
+    /// Use the templates in `root`'s `.jadio/prompts`.
+    pub fn load_project(&mut self, root: &Path) {
+        self.prompts_dir = Some(root.join(PROMPTS_DIR));
+        self.reload();
+    }
+
+    /// Read the project's template files again, so edits apply to the next command.
+    pub fn reload(&mut self) {
+        self.custom_templates.retain(|_, template| template.path.is_none());
+        self.load_errors.clear();
+        let Some(entries) = self.prompts_dir.as_ref().and_then(|dir| fs::read_dir(dir).ok()) else {
+            return;
+        };
+        let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).filter(|path| path.extension().is_some_and(|ext| ext == "md")).collect();
+        paths.sort();
+        for path in paths {
+            let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+            let loaded = fs::read_to_string(&path).map_err(|e| e.to_string()).and_then(|text| AutoPromptTemplate::parse(&name, &text));
+            match loaded {
+                Ok(template) => {
+                    self.custom_templates.insert(name, AutoPromptTemplate { path: Some(path), ..template });
+                }
+                Err(e) => self.load_errors.push(format!("{}: {}", path.display(), e)),
+            }
+        }
+    }
+
+    /// Template files that failed to load at the last reload.
+    pub fn load_errors(&self) -> &[String] {
+        &self.load_errors
+    }
+
     pub fn get_template(&self, name: &str) -> Option<&AutoPromptTemplate> {
-        self.templates.get(name).or_else(|| self.custom_templates.get(name))
+        self.custom_templates.get(name).or_else(|| self.templates.get(name))
     }
-    
+
+    /// Templates by name, project templates replacing built-in ones.
     pub fn list_templates(&self, category: Option<PromptCategory>) -> Vec<&AutoPromptTemplate> {
         let mut templates: Vec<_> = self.templates.values()
+            .filter(|t| !self.custom_templates.contains_key(&t.name))
             .chain(self.custom_templates.values())
             .collect();
-            
+
         if let Some(cat) = category {
             templates.retain(|t| t.category == cat);
         }
-        
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+
         templates
     }
-    
+
+    /// Fill in a template. Declared variables fall back to their defaults, and built-in
+    /// variables the template uses must be in `variables`; every problem is reported at once.
     pub fn generate_prompt(&mut self, template_name: &str, variables: HashMap<String, String>) -> Result<String, String> {
         let template = self.get_template(template_name)
             .ok_or_else(|| format!("Template '{}' not found", template_name))?;
-        
-        let mut prompt = template.template.clone();
-        
-        for var in &template.variables {
-            if let Some(value) = variables.get(var) {
-                prompt = prompt.replace(&format!("{{{}}}", var), value);
-            } else {
-                return Err(format!("Missing variable: {}", var));
+
+        let mut errors = Vec::new();
+        let mut unknown: Vec<&String> = variables
+            .keys()
+            .filter(|name| !BUILTIN_VARIABLES.contains(&name.as_str()) && !template.variables.iter().any(|variable| &variable.name == *name))
+            .collect();
+        unknown.sort();
+        errors.extend(unknown.into_iter().map(|name| format!("Unknown variable '{}'", name)));
+
+        let mut values = HashMap::new();
+        for variable in &template.variables {
+            let value = variables.get(&variable.name).map(|value| value.trim()).filter(|value| !value.is_empty()).or(variable.default.as_deref());
+            match value {
+                Some(value) => match variable.check(value) {
+                    Ok(()) => {
+                        values.insert(variable.name.clone(), value.to_string());
+                    }
+                    Err(e) => errors.push(e),
+                },
+                None if variable.optional => {}
+                None => errors.push(format!("Missing value for '{}'", variable.name)),
             }
         }
-        
+        for name in template.builtins_used() {
+            match variables.get(name) {
+                Some(value) => {
+                    values.insert(name.to_string(), value.clone());
+                }
+                None => errors.push(format!("{{{{{}}}}} is not available{}", name, builtin_hint(name))),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors.join("; "));
+        }
+
+        let prompt = template.render(&values);
         self.recent_prompts.push(prompt.clone());
-        if self.recent_prompts.len() > 50 {
+        if self.recent_prompts.len() > RECENT_LIMIT {
             self.recent_prompts.remove(0);
         }
-        
+
         Ok(prompt)
     }
-    
+
+    /// Add a template for this session only; see [`Self::save_custom_template`] to keep it.
     pub fn add_custom_template(&mut self, template: AutoPromptTemplate) {
         self.custom_templates.insert(template.name.clone(), template);
     }
-    
+
+    /// Write a template to the project's prompts directory and start using it.
+    pub fn save_custom_template(&mut self, template: AutoPromptTemplate) -> Result<PathBuf, String> {
+        let dir = self.prompts_dir.clone().ok_or("Open a project to save prompt templates")?;
+        let template = AutoPromptTemplate::parse(&template.name, &template.to_markdown())?;
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let path = dir.join(format!("{}.md", template.name));
+        fs::write(&path, template.to_markdown()).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        self.custom_templates.insert(template.name.clone(), AutoPromptTemplate { path: Some(path.clone()), ..template });
+        Ok(path)
+    }
+
+    /// Parse chat input such as `/write_tests test_type=integration focus on parsing`.
+    ///
+    /// Leading `name=value` pairs (values may be quoted) set variables, and the rest of
+    /// the input goes to the first declared variable not set that way. Returns `None`
+    /// if the input isn't a slash command.
+    pub fn parse_command(&self, input: &str) -> Option<Result<SlashCommand, String>> {
+        let input = input.trim_start().strip_prefix('/')?;
+        let name_end = input.find(char::is_whitespace).unwrap_or(input.len());
+        let name = &input[..name_end];
+        if name.is_empty() {
+            return None;
+        }
+        let Some(template) = self.get_template(name) else {
+            return Some(Err(format!("Unknown command '/{}'", name)));
+        };
+
+        let mut variables = HashMap::new();
+        let mut rest = input[name_end..].trim_start();
+        while let Some((key, after)) = rest.split_once('=').filter(|(key, _)| is_identifier(key)) {
+            let (value, remaining) = match after.strip_prefix('"') {
+                Some(quoted) => match quoted.find('"') {
+                    Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                    None => return Some(Err(format!("Unclosed quote in the value of '{}'", key))),
+                },
+                None => after.split_at(after.find(char::is_whitespace).unwrap_or(after.len())),
+            };
+            variables.insert(key.to_string(), value.to_string());
+            rest = remaining.trim_start();
+        }
+
+        let text = rest.trim();
+        if !text.is_empty() {
+            match template.variables.iter().find(|variable| !variables.contains_key(&variable.name)) {
+                Some(variable) => {
+                    variables.insert(variable.name.clone(), text.to_string());
+                }
+                None => return Some(Err(format!("/{} takes no further text", name))),
+            }
+        }
+        Some(Ok(SlashCommand { name: name.to_string(), variables }))
+    }
+
     pub fn get_recent_prompts(&self) -> &[String] {
         &self.recent_prompts
     }
-    
-    pub fn suggest_prompt(&self, context: &str) -> Vec<String> {
-        // TODO: Implement intelligent prompt suggestions based on context
-        vec![
-            "Explain this code".to_string(),
-            "Find potential bugs".to_string(),
-            "Suggest improvements".to_string(),
-            "Add documentation".to_string(),
-        ]
+
+    /// Templates and quick actions that fit the editor state, most relevant first.
+    ///
+    /// Failing tests and build errors rank highest, then diagnostics, an undocumented
+    /// function under the cursor, and actions on the selection. The generic quick
+    /// actions fill any remaining places.
+    pub fn suggest_prompt(&self, context: &PromptContext, limit: usize) -> Vec<PromptSuggestion> {
+        let mut suggestions = Vec::new();
+        let mut suggest = |label: String, reason: String, input: String, selection: Option<Selection>, score: f32| {
+            suggestions.push(PromptSuggestion { label, reason, input, selection, score });
+        };
+        let has_template = |name: &str| self.get_template(name).is_some();
+
+        let output: Vec<&str> = context.terminal_output.iter().flat_map(|output| output.lines()).collect();
+        let tests = failing_tests(&output);
+        if !tests.is_empty() {
+            let names = tests.iter().map(|name| format!("`{}`", name)).collect::<Vec<_>>().join(", ");
+            let mut input = format!("These tests fail: {}. Find out why and fix the cause.", names);
+            if let Some(panic) = output.iter().find(|line| line.contains("panicked at") || line.starts_with("E ")) {
+                input.push_str(&format!("\n\n{}", panic.trim()));
+            }
+            let label = match tests.as_slice() {
+                [name] => format!("🧪 Fix {}", name),
+                _ => format!("🧪 Fix {} failing tests", tests.len()),
+            };
+            suggest(label, "Tests failed in the terminal".to_string(), input, None, 4.0);
+        }
+        if let Some(error) = build_error(&output).filter(|_| has_template("fix_bug")) {
+            suggest("🛠 Fix build error".to_string(), error.to_string(), format!("/fix_bug {}", error), None, 3.5);
+        }
+
+        let errors = context.diagnostics.iter().filter(|diagnostic| diagnostic.contains("error")).count();
+        if let Some(first) = context.diagnostics.first().filter(|_| has_template("fix_bug")) {
+            let (label, score) = match errors {
+                0 => (format!("🐛 Fix {} warning(s)", context.diagnostics.len()), 1.5),
+                _ => (format!("🐛 Fix {} error(s)", errors), 3.0),
+            };
+            suggest(label, first.clone(), format!("/fix_bug {}", first), None, score);
+        }
+
+        if let Some((name, selection)) = context.undocumented_function.as_ref().filter(|_| has_template("add_documentation")) {
+            let reason = format!("`{}` has no doc comment", name);
+            suggest(format!("📚 Document {}", name), reason, "/add_documentation".to_string(), Some(selection.clone()), 2.5);
+        }
+
+        if context.has_selection {
+            for (name, label, score) in [("refactor_code", "♻ Refactor selection", 1.2), ("write_tests", "🧪 Write tests", 1.1)] {
+                if has_template(name) {
+                    suggest(label.to_string(), "Code is selected".to_string(), format!("/{}", name), None, score);
+                }
+            }
+        }
+        if context.has_uncommitted_changes && has_template("review_changes") {
+            suggest("📝 Review changes".to_string(), "There are uncommitted changes".to_string(), "/review_changes".to_string(), None, 1.0);
+        }
+
+        let quick_actions = [("📚 Explain code", "Explain what this code does"), ("🐛 Find bugs", "Find potential bugs in this code"), ("⚡ Suggest improvements", "Suggest improvements to this code")];
+        for (index, (label, input)) in quick_actions.into_iter().enumerate() {
+            suggest(label.to_string(), "Quick action".to_string(), input.to_string(), None, 0.5 - index as f32 * 0.1);
+        }
+
+        suggestions.sort_by(|a, b| b.score.total_cmp(&a.score));
+        suggestions.truncate(limit);
+        suggestions
+    }
+}
+
+/// Names of the failing tests in cargo or pytest output.
+fn failing_tests(output: &[&str]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for line in output {
+        let line = line.trim();
+        let name = match line.strip_prefix("test ").and_then(|rest| rest.strip_suffix(" ... FAILED")) {
+            Some(name) => name,
+            None => match line.strip_prefix("FAILED ") {
+                Some(rest) => rest.split(" - ").next().unwrap_or(rest),
+                None => continue,
+            },
+        };
+        if !names.iter().any(|known| known == name) {
+            names.push(name.to_string());
+        }
     }
-}
\ No newline at end of file
+    names
+}
+
+/// The first compiler error in the output, skipping cargo's closing summary.
+fn build_error<'a>(output: &[&'a str]) -> Option<&'a str> {
+    output.iter().map(|line| line.trim()).find(|line| {
+        (line.starts_with("error[") || line.starts_with("error:"))
+            && !["test failed", "could not compile", "aborting due to"].iter().any(|summary| line.contains(summary))
+    })
+}
+
+/// What to do when a built-in variable has no value.
+fn builtin_hint(name: &str) -> &'static str {
+    match name {
+        "selection" => "; select some code in the editor",
+        "file" | "language" => "; open a file in the editor",
+        "git_diff" => "; the project is not a git repository",
+        _ => "",
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+fn unquote(value: &str) -> &str {
+    value.strip_prefix('"').and_then(|value| value.strip_suffix('"')).unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::code_agent::test_support::TempDir;
+
+    #[test]
+    fn test_project_templates_validate_and_run_as_slash_commands() {
+        let temp = TempDir::new("prompts");
+        let root = temp.path().to_path_buf();
+        let dir = root.join(PROMPTS_DIR);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("explain.md"),
+            "---\ndescription: Explain code\ncategory: documentation\nvariables:\n  depth: choice(brief|detailed) = brief\n  audience: text?\n  lines: number\n---\nGive a {{depth}} explanation of {{ file }} for {{audience}}, in {{lines}} lines:\n{{selection}}\n",
+        )
+        .unwrap();
+        fs::write(dir.join("broken.md"), "---\nvariables:\n  name: text\n---\nHello {{nmae}}\n").unwrap();
+        fs::write(dir.join("fix_bug.md"), "---\ndescription: Our bug template\n---\nFix {{selection}}\n").unwrap();
+
+        let mut engine = AutoPromptEngine::new();
+        assert_eq!(engine.list_templates(None).len(), DEFAULT_TEMPLATES.len());
+        engine.load_project(&root);
+        assert_eq!(engine.load_errors().len(), 1);
+        assert!(engine.load_errors()[0].contains("Undeclared variables: nmae"));
+        // Project templates replace built-in ones of the same name.
+        assert_eq!(engine.get_template("fix_bug").unwrap().description, "Our bug template");
+        assert_eq!(engine.list_templates(None).len(), DEFAULT_TEMPLATES.len() + 1);
+
+        let command = engine.parse_command("/explain depth=detailed audience=\"new hires\" 12").unwrap().unwrap();
+        assert_eq!(command.variables["audience"], "new hires");
+        assert_eq!(command.variables["lines"], "12");
+        let mut variables = command.variables.clone();
+        variables.insert("file".to_string(), "src/main.rs".to_string());
+        assert_eq!(engine.generate_prompt("explain", variables.clone()), Err("{{selection}} is not available; select some code in the editor".to_string()));
+        variables.insert("selection".to_string(), "fn main() {}".to_string());
+        assert_eq!(engine.generate_prompt("explain", variables).unwrap(), "Give a detailed explanation of src/main.rs for new hires, in 12 lines:\nfn main() {}");
+
+        let command = engine.parse_command("/explain depth=long lines=lots").unwrap().unwrap();
+        let errors = engine.generate_prompt("explain", command.variables).unwrap_err();
+        assert_eq!(errors, "'depth' must be choice(brief|detailed), not 'long'; 'lines' must be number, not 'lots'; {{selection}} is not available; select some code in the editor; {{file}} is not available; open a file in the editor");
+        assert_eq!(engine.parse_command("/nope"), Some(Err("Unknown command '/nope'".to_string())));
+        assert_eq!(engine.parse_command("plain message"), None);
+
+        // Saved templates survive a restart.
+        let saved = AutoPromptTemplate::parse("todo", "---\ncategory: code review\nvariables:\n  strict: bool = yes\n---\nList TODOs in {{file}}").unwrap();
+        engine.save_custom_template(saved).unwrap();
+        let mut reopened = AutoPromptEngine::new();
+        reopened.load_project(&root);
+        let todo = reopened.get_template("todo").unwrap();
+        assert_eq!((todo.category.clone(), todo.variables[0].default.as_deref()), (PromptCategory::CodeReview, Some("yes")));
+    }
+
+    #[test]
+    fn test_suggestions_follow_editor_state() {
+        let engine = AutoPromptEngine::new();
+        let inputs = |context: &PromptContext| engine.suggest_prompt(context, 3).into_iter().map(|suggestion| suggestion.input).collect::<Vec<_>>();
+        assert_eq!(inputs(&PromptContext::default()), vec!["Explain what this code does", "Find potential bugs in this code", "Suggest improvements to this code"]);
+
+        let function = Selection { path: "src/lib.rs".to_string(), start_line: 3, end_line: 5, text: "fn parse() {}".to_string() };
+        let context = PromptContext {
+            has_selection: true,
+            diagnostics: vec!["src/lib.rs:4: warning: unused variable `x`".to_string()],
+            undocumented_function: Some(("parse".to_string(), function.clone())),
+            terminal_output: vec![
+                "error[E0425]: cannot find value `y` in this scope".to_string(),
+                "error: could not compile `demo` (lib) due to 1 previous error".to_string(),
+                "test parser::tests::test_empty ... FAILED\nthread 'parser::tests::test_empty' panicked at src/parser.rs:9:5:".to_string(),
+            ],
+            has_uncommitted_changes: true,
+        };
+        let suggestions = engine.suggest_prompt(&context, 4);
+        assert_eq!(
+            suggestions.iter().map(|suggestion| suggestion.input.as_str()).collect::<Vec<_>>(),
+            vec![
+                "These tests fail: `parser::tests::test_empty`. Find out why and fix the cause.\n\nthread 'parser::tests::test_empty' panicked at src/parser.rs:9:5:",
+                "/fix_bug error[E0425]: cannot find value `y` in this scope",
+                "/add_documentation",
+                "/fix_bug src/lib.rs:4: warning: unused variable `x`",
+            ]
+        );
+        assert_eq!(suggestions[2].selection, Some(function));
+        assert!(failing_tests(&["FAILED tests/test_io.py::test_read - AssertionError"]).contains(&"tests/test_io.py::test_read".to_string()));
+    }
+}
@@ -1,23 +1,42 @@
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::path::Path;
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use super::chat_store::{ChatStore, SessionHeader};
 
-#[derive(Debug, Clone)]
+const DEFAULT_TITLE: &str = "New chat";
+/// Longest title taken from a session's first message, in characters.
+const MAX_TITLE_LEN: usize = 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub id: String,
     pub role: MessageRole,
     pub content: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(default)]
     pub metadata: MessageMetadata,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum MessageRole {
     User,
     Assistant,
     System,
 }
 
-#[derive(Debug, Clone, Default)]
+impl MessageRole {
+    fn label(&self) -> &'static str {
+        match self {
+            MessageRole::User => "User",
+            MessageRole::Assistant => "Assistant",
+            MessageRole::System => "System",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MessageMetadata {
     pub file_context: Option<String>,
     pub line_range: Option<(usize, usize)>,
@@ -26,13 +45,136 @@ pub struct MessageMetadata {
     pub processing_time: Option<f64>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct ChatSession {
     pub id: String,
+    pub title: String,
     pub messages: VecDeque<ChatMessage>,
     pub created_at: DateTime<Utc>,
     pub last_activity: DateTime<Utc>,
+    #[serde(skip)]
     pub max_messages: usize,
+    pub forked_from: Option<String>,
+    /// False while the messages are still on disk.
+    #[serde(skip)]
+    loaded: bool,
+}
+
+impl ChatSession {
+    fn new(title: String, forked_from: Option<String>) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            title,
+            messages: VecDeque::new(),
+            created_at: Utc::now(),
+            last_activity: Utc::now(),
+            max_messages: 1000,
+            forked_from,
+            loaded: true,
+        }
+    }
+    
+    fn header(&self) -> SessionHeader {
+        SessionHeader {
+            id: self.id.clone(),
+            title: self.title.clone(),
+            created_at: self.created_at,
+            forked_from: self.forked_from.clone(),
+        }
+    }
+    
+    /// The session as Markdown, one section per message.
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!("# {}\n\n_Created {}_\n", self.title, self.created_at.format("%Y-%m-%d %H:%M"));
+        for message in &self.messages {
+            markdown.push_str(&format!(
+                "\n## {} · {}\n\n{}\n",
+                message.role.label(),
+                message.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                message.content.trim_end()
+            ));
+        }
+        markdown
+    }
+    
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Failed to serialize session: {}", e))
+    }
+}
+
+/// A session as listed in the session picker, without its messages.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionSummary {
+    pub id: String,
+    pub title: String,
+    pub last_activity: DateTime<Utc>,
+    pub forked_from: Option<String>,
+}
+
+/// A message found by [`ChatManager::search_messages`].
+#[derive(Debug, Clone)]
+pub struct MessageHit {
+    pub session_id: String,
+    pub session_title: String,
+    pub message: ChatMessage,
+}
+
+/// Inverted index from lowercase words to the messages containing them.
+#[derive(Debug, Default)]
+struct MessageIndex {
+    words: BTreeMap<String, HashSet<(String, String)>>,
+}
+
+impl MessageIndex {
+    fn add(&mut self, session_id: &str, message: &ChatMessage) {
+        for word in index_words(&message.content) {
+            self.words.entry(word).or_default().insert((session_id.to_string(), message.id.clone()));
+        }
+    }
+    
+    fn remove(&mut self, session_id: &str, message: &ChatMessage) {
+        let key = (session_id.to_string(), message.id.clone());
+        for word in index_words(&message.content) {
+            if let Some(postings) = self.words.get_mut(&word) {
+                postings.remove(&key);
+                if postings.is_empty() {
+                    self.words.remove(&word);
+                }
+            }
+        }
+    }
+    
+    fn remove_session(&mut self, session_id: &str) {
+        self.words.retain(|_, postings| {
+            postings.retain(|(session, _)| session != session_id);
+            !postings.is_empty()
+        });
+    }
+    
+    /// Messages containing a word starting with every word of `query`, as (session id, message id).
+    fn search(&self, query: &str) -> HashSet<(String, String)> {
+        let mut result: Option<HashSet<(String, String)>> = None;
+        for word in index_words(query) {
+            let matches: HashSet<(String, String)> = self
+                .words
+                .range(word.clone()..)
+                .take_while(|(indexed, _)| indexed.starts_with(&word))
+                .flat_map(|(_, postings)| postings.iter().cloned())
+                .collect();
+            result = Some(match result {
+                Some(previous) => previous.intersection(&matches).cloned().collect(),
+                None => matches,
+            });
+        }
+        result.unwrap_or_default()
+    }
+}
+
+fn index_words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
 }
 
 #[derive(Debug)]
@@ -40,6 +182,10 @@ pub struct ChatManager {
     sessions: Vec<ChatSession>,
     active_session: Option<usize>,
     system_prompts: Vec<String>,
+    /// Where sessions are saved; `None` keeps them in memory until a project is opened.
+    store: Option<ChatStore>,
+    /// Built on the first search, then kept up to date.
+    index: Option<MessageIndex>,
 }
 
 impl Default for ChatManager {
@@ -54,6 +200,8 @@ impl ChatManager {
             sessions: Vec::new(),
             active_session: None,
             system_prompts: Vec::new(),
+            store: None,
+            index: None,
         };
         
         // Create default session
@@ -61,16 +209,55 @@ impl ChatManager {
         manager
     }
     
-    pub fn create_session(&mut self) -> &mut ChatSession {
-        let session = ChatSession {
-            id: uuid::Uuid::new_v4().to_string(),
-            messages: VecDeque::new(),
-            created_at: Utc::now(),
-            last_activity: Utc::now(),
-            max_messages: 1000,
+    /// Save sessions under `root/.jadio/chats` and list the ones already there.
+    ///
+    /// Only session headers are read; messages load when a session is opened.
+    /// Unsaved sessions with messages in them move into the project.
+    pub fn open_project(&mut self, root: &Path) {
+        let store = ChatStore::for_project(root);
+        if self.store.as_ref() == Some(&store) {
+            return;
+        }
+        
+        let unsaved: Vec<ChatSession> = if self.store.is_none() {
+            self.sessions.drain(..).filter(|session| session.messages.iter().any(|m| m.role != MessageRole::System)).collect()
+        } else {
+            Vec::new()
         };
+        self.sessions = store
+            .list()
+            .into_iter()
+            .map(|(header, modified)| ChatSession {
+                id: header.id,
+                title: header.title,
+                messages: VecDeque::new(),
+                created_at: header.created_at,
+                last_activity: modified,
+                max_messages: 1000,
+                forked_from: header.forked_from,
+                loaded: false,
+            })
+            .collect();
+        self.store = Some(store);
+        self.index = None;
+        self.active_session = None;
         
-        self.sessions.push(session);
+        for session in unsaved {
+            self.save_session(&session);
+            self.sessions.push(session);
+        }
+        match self.sessions.len() {
+            0 => {
+                self.create_session();
+            }
+            count => {
+                self.activate(count - 1);
+            }
+        }
+    }
+    
+    pub fn create_session(&mut self) -> &mut ChatSession {
+        self.sessions.push(ChatSession::new(DEFAULT_TITLE.to_string(), None));
         self.active_session = Some(self.sessions.len() - 1);
         
         // Add system prompt if available
@@ -78,7 +265,8 @@ impl ChatManager {
             self.add_system_message(system_prompt.clone());
         }
         
-        &mut self.sessions[self.sessions.len() - 1]
+        let index = self.sessions.len() - 1;
+        &mut self.sessions[index]
     }
     
     pub fn get_active_session(&mut self) -> Option<&mut ChatSession> {
@@ -89,40 +277,153 @@ impl ChatManager {
         }
     }
     
+    pub fn active_session_id(&self) -> Option<&str> {
+        self.active_session.and_then(|index| self.sessions.get(index)).map(|session| session.id.as_str())
+    }
+    
+    /// All sessions, most recently active first.
+    pub fn list_sessions(&self) -> Vec<SessionSummary> {
+        let mut summaries: Vec<SessionSummary> = self
+            .sessions
+            .iter()
+            .map(|session| SessionSummary {
+                id: session.id.clone(),
+                title: session.title.clone(),
+                last_activity: session.last_activity,
+                forked_from: session.forked_from.clone(),
+            })
+            .collect();
+        summaries.sort_by_key(|summary| std::cmp::Reverse(summary.last_activity));
+        summaries
+    }
+    
+    /// Make a session active, loading its messages if needed.
+    pub fn switch_session(&mut self, id: &str) -> Result<(), String> {
+        let index = self.position(id)?;
+        self.load(index)?;
+        self.active_session = Some(index);
+        Ok(())
+    }
+    
+    pub fn rename_session(&mut self, id: &str, title: &str) -> Result<(), String> {
+        let index = self.position(id)?;
+        self.load(index)?;
+        self.sessions[index].title = title.trim().to_string();
+        self.persist(index)
+    }
+    
+    pub fn delete_session(&mut self, id: &str) -> Result<(), String> {
+        let index = self.position(id)?;
+        if let Some(store) = &self.store {
+            store.delete(id)?;
+        }
+        self.sessions.remove(index);
+        if let Some(search_index) = &mut self.index {
+            search_index.remove_session(id);
+        }
+        
+        self.active_session = match self.active_session {
+            Some(active) if active > index => Some(active - 1),
+            Some(active) if active == index => None,
+            active => active,
+        };
+        if self.active_session.is_none() {
+            match self.sessions.len() {
+                0 => {
+                    self.create_session();
+                }
+                count => {
+                    self.activate(count - 1);
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    /// Start a new session holding a copy of `id` up to and including `message_id`, and make it active.
+    pub fn fork_session(&mut self, id: &str, message_id: &str) -> Result<String, String> {
+        let index = self.position(id)?;
+        self.load(index)?;
+        let source = &self.sessions[index];
+        let end = source
+            .messages
+            .iter()
+            .position(|message| message.id == message_id)
+            .ok_or_else(|| format!("Message {} is not in session {}", message_id, id))?;
+        
+        let mut fork = ChatSession::new(format!("{} (fork)", source.title), Some(source.id.clone()));
+        fork.messages = source.messages.iter().take(end + 1).cloned().collect();
+        if let Some(search_index) = &mut self.index {
+            for message in &fork.messages {
+                search_index.add(&fork.id, message);
+            }
+        }
+        self.save_session(&fork);
+        let fork_id = fork.id.clone();
+        self.sessions.push(fork);
+        self.active_session = Some(self.sessions.len() - 1);
+        Ok(fork_id)
+    }
+    
     pub fn add_user_message(&mut self, content: String) -> Option<&ChatMessage> {
-        self.add_message(MessageRole::User, content)
+        self.add_message(MessageRole::User, content, MessageMetadata::default())
     }
     
     pub fn add_assistant_message(&mut self, content: String) -> Option<&ChatMessage> {
-        self.add_message(MessageRole::Assistant, content)
+        self.add_message(MessageRole::Assistant, content, MessageMetadata::default())
+    }
+    
+    /// Add a reply along with what it cost, e.g. `tokens_used`.
+    pub fn add_assistant_message_with_metadata(&mut self, content: String, metadata: MessageMetadata) -> Option<&ChatMessage> {
+        self.add_message(MessageRole::Assistant, content, metadata)
     }
     
     pub fn add_system_message(&mut self, content: String) -> Option<&ChatMessage> {
-        self.add_message(MessageRole::System, content)
+        self.add_message(MessageRole::System, content, MessageMetadata::default())
     }
     
-    fn add_message(&mut self, role: MessageRole, content: String) -> Option<&ChatMessage> {
-        if let Some(session) = self.get_active_session() {
-            let message = ChatMessage {
-                id: uuid::Uuid::new_v4().to_string(),
-                role,
-                content,
-                timestamp: Utc::now(),
-                metadata: MessageMetadata::default(),
+    fn add_message(&mut self, role: MessageRole, content: String, metadata: MessageMetadata) -> Option<&ChatMessage> {
+        let index = self.active_session?;
+        let session = self.sessions.get_mut(index)?;
+        let message = ChatMessage {
+            id: uuid::Uuid::new_v4().to_string(),
+            role,
+            content,
+            timestamp: Utc::now(),
+            metadata,
+        };
+        
+        // The first thing the user asks names the session.
+        let retitle = message.role == MessageRole::User && session.title == DEFAULT_TITLE;
+        if retitle {
+            session.title = title_from(&message.content);
+        }
+        if let Some(search_index) = &mut self.index {
+            search_index.add(&session.id, &message);
+        }
+        session.messages.push_back(message);
+        session.last_activity = Utc::now();
+        
+        // Trim old messages if exceeding limit
+        let mut trimmed = false;
+        while session.messages.len() > session.max_messages {
+            if let (Some(old), Some(search_index)) = (session.messages.pop_front(), &mut self.index) {
+                search_index.remove(&session.id, &old);
+            }
+            trimmed = true;
+        }
+        
+        if let Some(store) = &self.store {
+            let session = &self.sessions[index];
+            let saved = match (retitle || trimmed, session.messages.back()) {
+                (false, Some(message)) => store.append(&session.header(), message),
+                _ => store.save(&session.header(), &session.messages),
             };
-            
-            session.messages.push_back(message);
-            session.last_activity = Utc::now();
-            
-            // Trim old messages if exceeding limit
-            while session.messages.len() > session.max_messages {
-                session.messages.pop_front();
+            if let Err(e) = saved {
+                eprintln!("Failed to save chat: {}", e);
             }
-            
-            session.messages.back()
-        } else {
-            None
         }
+        self.sessions[index].messages.back()
     }
     
     pub fn get_conversation_context(&self, max_messages: usize) -> Vec<ChatMessage> {
@@ -139,14 +440,22 @@ impl ChatManager {
     }
     
     pub fn clear_current_session(&mut self) {
-        if let Some(session) = self.get_active_session() {
-            session.messages.clear();
-            session.last_activity = Utc::now();
-            
-            // Re-add system prompt
-            if let Some(system_prompt) = self.system_prompts.first() {
-                self.add_system_message(system_prompt.clone());
-            }
+        let Some(index) = self.active_session else {
+            return;
+        };
+        let session = &mut self.sessions[index];
+        session.messages.clear();
+        session.last_activity = Utc::now();
+        if let Some(search_index) = &mut self.index {
+            search_index.remove_session(&session.id);
+        }
+        if let Err(e) = self.persist(index) {
+            eprintln!("Failed to save chat: {}", e);
+        }
+        
+        // Re-add system prompt
+        if let Some(system_prompt) = self.system_prompts.first() {
+            self.add_system_message(system_prompt.clone());
         }
     }
     
@@ -155,7 +464,8 @@ impl ChatManager {
         self.system_prompts.push(prompt);
     }
     
-    pub fn export_session(&self, session_index: usize) -> Option<String> {
+    pub fn export_session(&mut self, session_index: usize) -> Option<String> {
+        self.load(session_index).ok()?;
         if let Some(session) = self.sessions.get(session_index) {
             let mut export = String::new();
             export.push_str(&format!("Chat Session: {}\n", session.id));
@@ -165,11 +475,7 @@ impl ChatManager {
             for message in &session.messages {
                 export.push_str(&format!("[{}] {}: {}\n\n", 
                     message.timestamp.format("%Y-%m-%d %H:%M:%S"),
-                    match message.role {
-                        MessageRole::User => "User",
-                        MessageRole::Assistant => "Assistant",
-                        MessageRole::System => "System",
-                    },
+                    message.role.label(),
                     message.content
                 ));
             }
@@ -180,18 +486,109 @@ impl ChatManager {
         }
     }
     
-    pub fn search_messages(&self, query: &str) -> Vec<&ChatMessage> {
-        let mut results = Vec::new();
+    pub fn export_markdown(&mut self, id: &str) -> Result<String, String> {
+        let index = self.position(id)?;
+        self.load(index)?;
+        Ok(self.sessions[index].to_markdown())
+    }
+    
+    pub fn export_json(&mut self, id: &str) -> Result<String, String> {
+        let index = self.position(id)?;
+        self.load(index)?;
+        self.sessions[index].to_json()
+    }
+    
+    /// Messages containing every word of `query` (word prefixes match), newest first.
+    ///
+    /// The first search indexes every session, reading unloaded ones from disk without
+    /// keeping them; later searches and new messages only touch the index.
+    pub fn search_messages(&mut self, query: &str) -> Vec<MessageHit> {
+        if self.index.is_none() {
+            self.index = Some(self.build_index());
+        }
+        let Some(search_index) = &self.index else {
+            return Vec::new();
+        };
         
+        let mut hits = Vec::new();
+        for (session_id, message_id) in search_index.search(query) {
+            let Some(index) = self.sessions.iter().position(|session| session.id == session_id) else {
+                continue;
+            };
+            if let Err(e) = self.load(index) {
+                eprintln!("Failed to load chat: {}", e);
+                continue;
+            }
+            let session = &self.sessions[index];
+            if let Some(message) = session.messages.iter().find(|message| message.id == message_id) {
+                hits.push(MessageHit { session_id, session_title: session.title.clone(), message: message.clone() });
+            }
+        }
+        hits.sort_by_key(|hit| std::cmp::Reverse(hit.message.timestamp));
+        hits
+    }
+    
+    fn build_index(&self) -> MessageIndex {
+        let mut search_index = MessageIndex::default();
         for session in &self.sessions {
-            for message in &session.messages {
-                if message.content.to_lowercase().contains(&query.to_lowercase()) {
-                    results.push(message);
-                }
+            if session.loaded {
+                session.messages.iter().for_each(|message| search_index.add(&session.id, message));
+            } else if let Some(Ok((_, messages))) = self.store.as_ref().map(|store| store.load(&session.id)) {
+                messages.iter().for_each(|message| search_index.add(&session.id, message));
             }
         }
-        
-        results
+        search_index
+    }
+    
+    fn position(&self, id: &str) -> Result<usize, String> {
+        self.sessions.iter().position(|session| session.id == id).ok_or_else(|| format!("No chat session {}", id))
+    }
+    
+    /// Read a session's messages from disk if they aren't in memory yet.
+    fn load(&mut self, index: usize) -> Result<(), String> {
+        let session = self.sessions.get_mut(index).ok_or("No such chat session")?;
+        if session.loaded {
+            return Ok(());
+        }
+        let store = self.store.as_ref().ok_or("No chat store")?;
+        let (_, messages) = store.load(&session.id)?;
+        session.messages = messages.into();
+        session.loaded = true;
+        Ok(())
+    }
+    
+    /// Switch to a session, falling back to a fresh one if it can't be read.
+    fn activate(&mut self, index: usize) {
+        match self.load(index) {
+            Ok(()) => self.active_session = Some(index),
+            Err(e) => {
+                eprintln!("Failed to load chat: {}", e);
+                self.create_session();
+            }
+        }
+    }
+    
+    /// Rewrite a session's file from memory.
+    fn persist(&self, index: usize) -> Result<(), String> {
+        match &self.store {
+            Some(store) => store.save(&self.sessions[index].header(), &self.sessions[index].messages),
+            None => Ok(()),
+        }
+    }
+    
+    fn save_session(&self, session: &ChatSession) {
+        if let Some(Err(e)) = self.store.as_ref().map(|store| store.save(&session.header(), &session.messages)) {
+            eprintln!("Failed to save chat: {}", e);
+        }
+    }
+}
+
+/// A session title from its first line, shortened to [`MAX_TITLE_LEN`] characters.
+fn title_from(content: &str) -> String {
+    let line = content.lines().map(str::trim).find(|line| !line.is_empty()).unwrap_or(DEFAULT_TITLE);
+    match line.char_indices().nth(MAX_TITLE_LEN) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.to_string(),
     }
 }
 
@@ -206,10 +603,10 @@ mod uuid {
         
         pub fn to_string(&self) -> String {
             format!("{:x}-{:x}-{:x}-{:x}", 
-                rand::random::<u32>(),
-                rand::random::<u16>(),
-                rand::random::<u16>(),
-                rand::random::<u32>()
+                super::rand::random() as u32,
+                super::rand::random() as u16,
+                super::rand::random() as u16,
+                super::rand::random() as u32
             )
         }
     }
@@ -217,13 +614,72 @@ mod uuid {
 
 // Mock random
 mod rand {
+    use std::sync::atomic::{AtomicU64, Ordering};
     use std::time::{SystemTime, UNIX_EPOCH};
     
-    pub fn random<T: From<u64>>() -> T {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    
+    pub fn random() -> u64 {
         let nanos = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_nanos() as u64;
-        T::from(nanos)
+        // Mix in a counter so calls within the same nanosecond differ
+        let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+        (nanos ^ count.wrapping_mul(0x9E37_79B9_7F4A_7C15)).rotate_left((count % 64) as u32)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::code_agent::test_support::TempDir;
+
+    #[test]
+    fn test_sessions_persist_fork_and_search() {
+        let temp = TempDir::new("chats");
+        let root = temp.path().to_path_buf();
+
+        let mut chat = ChatManager::new();
+        chat.add_user_message("How do I parse TOML config files?".to_string());
+        chat.add_assistant_message("Use the toml crate with serde.".to_string());
+        // The unsaved session moves into the project when it opens.
+        chat.open_project(&root);
+        let first = chat.active_session_id().unwrap().to_string();
+        chat.add_user_message("And for YAML?".to_string());
+
+        chat.create_session();
+        chat.add_user_message("Explain the borrow checker".to_string());
+        let second = chat.active_session_id().unwrap().to_string();
+        chat.rename_session(&second, "Borrowing").unwrap();
+
+        // A fresh manager lists both sessions without loading their messages.
+        let mut reopened = ChatManager::new();
+        reopened.open_project(&root);
+        let titles: Vec<String> = reopened.list_sessions().into_iter().map(|summary| summary.title).collect();
+        assert_eq!(titles, vec!["Borrowing".to_string(), "How do I parse TOML config files?".to_string()]);
+        assert!(!reopened.sessions.iter().find(|session| session.id == first).unwrap().loaded);
+
+        let hits = reopened.search_messages("pars conf");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].session_id, first);
+        assert!(reopened.search_messages("toml yaml").is_empty());
+        let crate_message = reopened.search_messages("crate")[0].message.id.clone();
+
+        let fork = reopened.fork_session(&first, &crate_message).unwrap();
+        reopened.add_user_message("What about JSON?".to_string());
+        assert_eq!(reopened.get_conversation_context(10).len(), 3);
+        assert_eq!(reopened.search_messages("json")[0].session_id, fork);
+        assert_eq!(reopened.search_messages("serde").len(), 2);
+
+        reopened.delete_session(&second).unwrap();
+        assert!(reopened.search_messages("borrow").is_empty());
+        assert_eq!(reopened.list_sessions().len(), 2);
+
+        let markdown = reopened.export_markdown(&fork).unwrap();
+        assert!(markdown.starts_with("# How do I parse TOML config files? (fork)\n"));
+        assert!(markdown.contains("\n## User · "));
+        let json: serde_json::Value = serde_json::from_str(&reopened.export_json(&fork).unwrap()).unwrap();
+        assert_eq!(json["forked_from"], first.as_str());
+        assert_eq!(json["messages"][2]["role"], "user");
     }
-}
\ No newline at end of file
+}
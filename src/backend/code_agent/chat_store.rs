@@ -0,0 +1,117 @@
+//! Chat sessions on disk: one JSON Lines file per session under `.jadio/chats`.
+//!
+//! The first line of a file is the [`SessionHeader`]; every later line is a
+//! [`ChatMessage`]. Messages are appended as they arrive, so a crash loses at most
+//! the line being written. Renaming or clearing a session rewrites its file.
+
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use super::chat::ChatMessage;
+
+const CHATS_DIR: &str = ".jadio/chats";
+const EXTENSION: &str = "jsonl";
+
+/// The first line of a session file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionHeader {
+    pub id: String,
+    pub title: String,
+    pub created_at: DateTime<Utc>,
+    /// The session this one was forked from.
+    #[serde(default)]
+    pub forked_from: Option<String>,
+}
+
+/// The chat directory of one project.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatStore {
+    dir: PathBuf,
+}
+
+impl ChatStore {
+    pub fn for_project(root: &Path) -> Self {
+        Self { dir: root.join(CHATS_DIR) }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Every stored session's header and last write time, reading only the first line of each file.
+    pub fn list(&self) -> Vec<(SessionHeader, DateTime<Utc>)> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut sessions: Vec<(SessionHeader, DateTime<Utc>)> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == EXTENSION))
+            .filter_map(|path| {
+                let mut line = String::new();
+                BufReader::new(fs::File::open(&path).ok()?).read_line(&mut line).ok()?;
+                let header: SessionHeader = serde_json::from_str(&line).ok()?;
+                let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok()?;
+                Some((header, DateTime::<Utc>::from(modified)))
+            })
+            .collect();
+        sessions.sort_by_key(|(header, modified)| (*modified, header.created_at));
+        sessions
+    }
+
+    /// Read a whole session. Lines that fail to parse, such as a half-written last line, are skipped.
+    pub fn load(&self, id: &str) -> Result<(SessionHeader, Vec<ChatMessage>), String> {
+        let path = self.path(id)?;
+        let file = fs::File::open(&path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let mut lines = BufReader::new(file).lines();
+        let first = lines.next().ok_or_else(|| format!("{} is empty", path.display()))?;
+        let first = first.map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let header = serde_json::from_str(&first).map_err(|e| format!("Bad session header in {}: {}", path.display(), e))?;
+        let messages = lines.map_while(Result::ok).filter_map(|line| serde_json::from_str(&line).ok()).collect();
+        Ok((header, messages))
+    }
+
+    /// Add one message to the end of a session file, creating it with `header` if needed.
+    pub fn append(&self, header: &SessionHeader, message: &ChatMessage) -> Result<(), String> {
+        let path = self.path(&header.id)?;
+        if !path.exists() {
+            return self.save(header, std::iter::once(message));
+        }
+        let line = serde_json::to_string(message).map_err(|e| format!("Failed to serialize message: {}", e))?;
+        let mut file = OpenOptions::new().append(true).open(&path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        writeln!(file, "{}", line).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Replace a session file with `header` and `messages`.
+    pub fn save<'a>(&self, header: &SessionHeader, messages: impl IntoIterator<Item = &'a ChatMessage>) -> Result<(), String> {
+        let path = self.path(&header.id)?;
+        fs::create_dir_all(&self.dir).map_err(|e| format!("Failed to create {}: {}", self.dir.display(), e))?;
+        let mut content = serde_json::to_string(header).map_err(|e| format!("Failed to serialize session: {}", e))?;
+        content.push('\n');
+        for message in messages {
+            content.push_str(&serde_json::to_string(message).map_err(|e| format!("Failed to serialize message: {}", e))?);
+            content.push('\n');
+        }
+        // Write then rename so a crash never leaves a truncated session.
+        let temp = path.with_extension("jsonl.tmp");
+        fs::write(&temp, content).map_err(|e| format!("Failed to write {}: {}", temp.display(), e))?;
+        fs::rename(&temp, &path).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), String> {
+        let path = self.path(id)?;
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(format!("Failed to delete {}: {}", path.display(), e)),
+            _ => Ok(()),
+        }
+    }
+
+    fn path(&self, id: &str) -> Result<PathBuf, String> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(format!("Invalid session id '{}'", id));
+        }
+        Ok(self.dir.join(format!("{}.{}", id, EXTENSION)))
+    }
+}
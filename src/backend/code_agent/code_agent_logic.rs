@@ -1,12 +1,154 @@
-use super::{agent::CodeAgent, chat::ChatManager, autoprompt::AutoPromptEngine, context::ContextManager};
-use std::sync::Arc;
+use super::{agent::{AgentConfig, CodeAgent}, chat::{ChatManager, ChatMessage, MessageMetadata}, autoprompt::{AutoPromptEngine, PromptContext, PromptSuggestion}, context::ContextManager};
+use super::agent_loop::AgentEvent;
+use super::agent_server_logic::{AgentServer, AgentServerConfig};
+use super::command_policy::{CommandGate, CommandPolicy};
+use super::completion::{Completion, CompletionEngine, FimCompleter, FimContext};
+use super::context_builder::{self, ContextReport, ContextSources, Selection};
+use super::edit_proposal::{AppliedEdit, EditProposal};
+use super::files_changed::{FileChange, FileChangeTracker};
+use super::instructions::command_approval::{ApprovalDecision, CommandApprovalQueue, PendingCommand};
+use super::instructions::project_instructions::ProjectInstructions;
+use super::memory::agent_memory_logic::AgentMemory;
+use super::model_loader::{ModelConfig, ModelLoader};
+use super::providers::{ToolCall, ToolResult, Usage};
+use super::semantic_index::{Embedder, IndexStatus, SemanticIndex, SemanticMatch};
+use super::server_auth::{AuditLog, TokenStore};
+use super::tokenizer::{self, TokenCounter, Tokenizer};
+use super::tools::base::docstring_audit::DocstringAuditTool;
+use super::tools::registry::ToolRegistry;
+use super::usage::{self, UsageBudget, UsageLedger, UsageRecord, UsageTotals};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 
+/// Number of chat messages shown by [`CodeAgentSystem::get_chat_history`].
+const HISTORY_LIMIT: usize = 50;
+/// Most recent file changes offered to the context builder.
+const RECENT_EDITS: usize = 5;
+/// Most symbols offered to the context builder.
+const SYMBOL_LIMIT: usize = 30;
+/// Chunks retrieved from the semantic index for each prompt.
+const RETRIEVED_CHUNKS: usize = 4;
+/// Most memory entries offered to the context builder.
+const MEMORY_ENTRIES: usize = 8;
+/// How often [`CodeAgentSystem::instructions`] looks for changed instruction files.
+const INSTRUCTIONS_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// Longest `{{git_diff}}`, or whole file standing in for `{{selection}}`, put in a prompt template, in characters.
+const MAX_TEMPLATE_VALUE_LEN: usize = 20_000;
+
+/// Progress of a reply started with [`CodeAgentSystem::send_message`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum AgentStreamEvent {
+    /// The next piece of the reply.
+    Text(String),
+    /// The agent is running a tool.
+    ToolCall(ToolCall),
+    ToolResult(ToolResult),
+    /// File edits the agent wants to make, for the user to review.
+    Proposal(EditProposal),
+    /// The prompt nears or passes a usage budget; sent before the reply starts.
+    BudgetWarning(String),
+    /// The reply finished; carries the full text.
+    Done(String),
+    Error(String),
+}
+
+/// A prompt whose sources are gathered, still to be given retrieved code and
+/// packed into the model's budget.
+struct PendingExchange {
+    /// Everything but the recent edits, which are owned alongside so the
+    /// exchange can leave the file tracker behind.
+    sources: ContextSources<'static>,
+    recent_edits: Vec<FileChange>,
+    max_tokens: usize,
+    model: ModelConfig,
+    tokenizer: Arc<dyn Tokenizer>,
+    session_id: Option<String>,
+    semantic_index: Option<Arc<SemanticIndex>>,
+    usage: Arc<Mutex<UsageLedger>>,
+    usage_budget: UsageBudget,
+    /// Where the context report and budget warnings are left for the UI.
+    context_report: Arc<std::sync::Mutex<Option<ContextReport>>>,
+    budget_warnings: Arc<std::sync::Mutex<Vec<String>>>,
+}
+
+impl PendingExchange {
+    /// Retrieve code related to the latest user message, pack the prompt and check
+    /// it against the usage budget, returning the budget warnings raised. Runs with
+    /// the reply rather than on the UI thread, as retrieval asks the embedding server.
+    async fn pack(self) -> (Exchange, Vec<String>) {
+        let mut sources = self.sources;
+        sources.recent_edits = self.recent_edits.iter().collect();
+        if let Some(index) = &self.semantic_index {
+            // The index may still be warming up, so failures only skip retrieval.
+            let query = sources.history.last().map(|message| message.content.clone()).unwrap_or_default();
+            match index.search(&query, RETRIEVED_CHUNKS).await {
+                Ok(matches) => sources.retrieved = matches,
+                Err(e) => eprintln!("Semantic search failed: {}", e),
+            }
+        }
+        
+        let built = context_builder::build_context(&sources, self.max_tokens, self.tokenizer.as_ref());
+        let prompt_tokens = tokenizer::count_messages(self.tokenizer.as_ref(), &built.messages);
+        if let Ok(mut report) = self.context_report.lock() {
+            *report = Some(built.report);
+        }
+        
+        let prompt_cost = usage::pricing_for(&self.model).map_or(0.0, |pricing| pricing.cost(prompt_tokens as u64, 0));
+        let warnings = self.usage.lock().await.budget_warnings(&self.usage_budget, self.session_id.as_deref(), prompt_tokens as u64, prompt_cost);
+        if let Ok(mut budget_warnings) = self.budget_warnings.lock() {
+            budget_warnings.clone_from(&warnings);
+        }
+        
+        let exchange = Exchange { messages: built.messages, model: self.model, tokenizer: self.tokenizer, prompt_tokens, session_id: self.session_id };
+        (exchange, warnings)
+    }
+}
+
+/// A prompt ready to send, with what's needed to account for it afterwards.
+struct Exchange {
+    messages: Vec<ChatMessage>,
+    model: ModelConfig,
+    tokenizer: Arc<dyn Tokenizer>,
+    prompt_tokens: usize,
+    session_id: Option<String>,
+}
+
 pub struct CodeAgentSystem {
     agent: Arc<Mutex<CodeAgent>>,
     chat_manager: Arc<Mutex<ChatManager>>,
     prompt_engine: Arc<Mutex<AutoPromptEngine>>,
     context_manager: Arc<Mutex<ContextManager>>,
+    model_loader: ModelLoader,
+    file_tracker: FileChangeTracker,
+    /// Applied agent edits, most recent last, for undoing turn by turn.
+    applied_edits: Vec<AppliedEdit>,
+    /// What went into the last prompt.
+    last_context_report: Arc<std::sync::Mutex<Option<ContextReport>>>,
+    token_counter: TokenCounter,
+    /// Tokenizer for the agent's current model, kept here so counting never waits on the agent's lock.
+    tokenizer: Arc<dyn Tokenizer>,
+    /// Requests made in the current project.
+    usage: Arc<Mutex<UsageLedger>>,
+    usage_budget: UsageBudget,
+    /// Budget warnings raised for the last prompt.
+    budget_warnings: Arc<std::sync::Mutex<Vec<String>>>,
+    /// Ghost text completion; `None` when disabled or the model can't fill in the middle.
+    completion: Option<CompletionEngine>,
+    auto_complete: bool,
+    /// JSON-RPC server letting external tools drive the agent, while running.
+    agent_server: Option<Arc<AgentServer>>,
+    /// Policy for the agent's shell commands and the queue of commands waiting for approval.
+    commands: Arc<CommandGate>,
+    /// Long-term notes about the current project, shared with the agent's `remember` tool.
+    memory: Arc<std::sync::Mutex<AgentMemory>>,
+    /// JADIO.md files from the user's directory and the project, added to every prompt.
+    instructions: ProjectInstructions,
+    instructions_checked: Option<Instant>,
+    /// Embeddings of the project's code; `None` without a project or a local embedding model.
+    semantic_index: Option<Arc<SemanticIndex>>,
     runtime: Option<tokio::runtime::Runtime>,
 }
 
@@ -19,41 +161,285 @@ impl Default for CodeAgentSystem {
 impl CodeAgentSystem {
     pub fn new() -> Self {
         let config = super::agent::AgentConfig::default();
+        let token_counter = TokenCounter::default();
+        let tokenizer = token_counter.for_model(&config.model_config());
         
         Self {
             agent: Arc::new(Mutex::new(CodeAgent::new(config))),
             chat_manager: Arc::new(Mutex::new(ChatManager::new())),
             prompt_engine: Arc::new(Mutex::new(AutoPromptEngine::new())),
             context_manager: Arc::new(Mutex::new(ContextManager::new())),
+            model_loader: ModelLoader::new(),
+            file_tracker: FileChangeTracker::new(),
+            applied_edits: Vec::new(),
+            last_context_report: Arc::default(),
+            token_counter,
+            tokenizer,
+            usage: Arc::new(Mutex::new(UsageLedger::default())),
+            usage_budget: UsageBudget::default(),
+            budget_warnings: Arc::default(),
+            completion: None,
+            auto_complete: false,
+            agent_server: None,
+            commands: Arc::new(CommandGate::new(CommandPolicy::default(), Some(CommandApprovalQueue::default()))),
+            memory: Arc::default(),
+            instructions: ProjectInstructions::new(ProjectInstructions::default_user_dir()),
+            instructions_checked: None,
+            semantic_index: None,
             runtime: tokio::runtime::Runtime::new().ok(),
         }
     }
     
-    pub fn process_user_message(&mut self, message: String) -> Result<String, Box<dyn std::error::Error>> {
+    pub fn set_config(&mut self, config: AgentConfig) {
+        self.tokenizer = self.token_counter.for_model(&config.model_config());
         if let Some(runtime) = &self.runtime {
             let agent = self.agent.clone();
-            let chat_manager = self.chat_manager.clone();
-            
             runtime.block_on(async move {
-                // Add user message to chat
+                agent.lock().await.set_config(config);
+            });
+        }
+        self.rebuild_completion();
+        self.rebuild_semantic_index();
+    }
+    
+    pub fn get_model_loader(&mut self) -> &mut ModelLoader {
+        &mut self.model_loader
+    }
+    
+    /// Load a registered model and switch the agent over to its client.
+    pub fn load_model(&mut self, name: &str) -> Result<(), String> {
+        self.model_loader.load_model(name)?;
+        let model = self.model_loader.get_active_model().cloned().ok_or("No active model")?;
+        let provider = self.model_loader.get_active_provider().ok_or("No client for the active model")?;
+        
+        let runtime = self.runtime.as_ref().ok_or("Tokio runtime not available")?;
+        let agent = self.agent.clone();
+        let model = runtime.block_on(async move {
+            let mut agent = agent.lock().await;
+            let config = agent.config().clone().with_model(&model);
+            let model = config.model_config();
+            agent.set_provider(config, provider);
+            model
+        });
+        self.tokenizer = self.token_counter.for_model(&model);
+        self.rebuild_completion();
+        self.rebuild_semantic_index();
+        Ok(())
+    }
+    
+    pub fn process_user_message(&mut self, message: String) -> Result<String, Box<dyn std::error::Error>> {
+        let pending = self.prepare_exchange(message)?;
+        let runtime = self.runtime.as_ref().ok_or("Tokio runtime not available")?;
+        let agent = self.agent.clone();
+        let chat_manager = self.chat_manager.clone();
+        let usage = self.usage.clone();
+        
+        runtime.block_on(async move {
+            let (exchange, _) = pending.pack().await;
+            Ok(Self::run_exchange(agent, chat_manager, usage, exchange, |_| {}).await?)
+        })
+    }
+    
+    /// Start a reply to `message` in the background.
+    ///
+    /// The reply streams back over the returned channel and ends with `Done` or `Error`.
+    pub fn send_message(&mut self, message: String) -> Result<mpsc::Receiver<AgentStreamEvent>, Box<dyn std::error::Error>> {
+        let pending = self.prepare_exchange(message)?;
+        let runtime = self.runtime.as_ref().ok_or("Tokio runtime not available")?;
+        let agent = self.agent.clone();
+        let chat_manager = self.chat_manager.clone();
+        let usage = self.usage.clone();
+        let (sender, receiver) = mpsc::channel();
+        
+        runtime.spawn(async move {
+            let (exchange, warnings) = pending.pack().await;
+            for warning in warnings {
+                let _ = sender.send(AgentStreamEvent::BudgetWarning(warning));
+            }
+            let text_sender = sender.clone();
+            let result = Self::run_exchange(agent, chat_manager, usage, exchange, |event| {
+                let _ = text_sender.send(match event {
+                    AgentEvent::Text(text) => AgentStreamEvent::Text(text),
+                    AgentEvent::ToolCall(call) => AgentStreamEvent::ToolCall(call),
+                    AgentEvent::ToolResult(result) => AgentStreamEvent::ToolResult(result),
+                    AgentEvent::EditProposal(proposal) => AgentStreamEvent::Proposal(proposal),
+                });
+            })
+            .await;
+            let _ = sender.send(match result {
+                Ok(response) => AgentStreamEvent::Done(response),
+                Err(e) => AgentStreamEvent::Error(e.to_string()),
+            });
+        });
+        
+        Ok(receiver)
+    }
+    
+    /// Record `message` in the chat and gather what its prompt is packed from.
+    fn prepare_exchange(&mut self, message: String) -> Result<PendingExchange, Box<dyn std::error::Error>> {
+        let runtime = self.runtime.as_ref().ok_or("Tokio runtime not available")?;
+        let agent = self.agent.clone();
+        let chat_manager = self.chat_manager.clone();
+        let context_manager = self.context_manager.clone();
+        
+        let (config, session_id, current_file, mut sources) = runtime.block_on(async move {
+            let config = agent.lock().await.config().clone();
+            let (session_id, history) = {
                 let mut chat = chat_manager.lock().await;
                 chat.add_user_message(message.clone());
-                drop(chat);
-                
-                // Process with agent
-                let agent = agent.lock().await;
-                let response = agent.process_message(message).await?;
-                drop(agent);
-                
-                // Add assistant response to chat
-                let mut chat = chat_manager.lock().await;
-                chat.add_assistant_message(response.clone());
-                
-                Ok(response)
-            })
+                (chat.active_session_id().map(str::to_string), chat.get_conversation_context(usize::MAX))
+            };
+            let context = context_manager.lock().await;
+            let sources = ContextSources {
+                system_prompt: config.system_prompt.clone(),
+                instructions: String::new(),
+                instruction_files: 0,
+                history,
+                selection: context.get_selection().cloned(),
+                pinned_files: context.pinned_file_contents(),
+                memories: Vec::new(),
+                retrieved: Vec::new(),
+                symbols: context.relevant_symbols(&message, SYMBOL_LIMIT),
+                recent_edits: Vec::new(),
+                workspace: context.get_current_project().map(Into::into).unwrap_or_default(),
+            };
+            (config, session_id, context.get_current_file().cloned(), sources)
+        });
+        self.instructions.refresh(current_file.as_deref().map(std::path::Path::new));
+        self.instructions_checked = Some(Instant::now());
+        sources.instructions = self.instructions.merged();
+        sources.instruction_files = self.instructions.files().len();
+        let recent_edits = self.file_tracker.get_recent_changes(RECENT_EDITS).into_iter().cloned().collect();
+        let query = sources.history.last().map(|message| message.content.clone()).unwrap_or_default();
+        if let Ok(memory) = self.memory.lock() {
+            sources.memories = memory.relevant(&query, MEMORY_ENTRIES);
+        }
+        
+        let model = config.model_config();
+        Ok(PendingExchange {
+            sources,
+            recent_edits,
+            max_tokens: config.parameters.max_tokens as usize,
+            tokenizer: self.token_counter.for_model(&model),
+            model,
+            session_id,
+            semantic_index: self.semantic_index.clone(),
+            usage: self.usage.clone(),
+            usage_budget: self.usage_budget,
+            context_report: self.last_context_report.clone(),
+            budget_warnings: self.budget_warnings.clone(),
+        })
+    }
+    
+    /// The instruction files added to prompts, re-read if they changed since the last check.
+    pub fn instructions(&mut self) -> &ProjectInstructions {
+        if self.instructions_checked.is_none_or(|checked| checked.elapsed() >= INSTRUCTIONS_CHECK_INTERVAL) {
+            let context_manager = self.context_manager.clone();
+            let current_file = self.runtime.as_ref().and_then(|runtime| runtime.block_on(async move { context_manager.lock().await.get_current_file().cloned() }));
+            self.instructions.refresh(current_file.as_deref().map(std::path::Path::new));
+            self.instructions_checked = Some(Instant::now());
+        }
+        &self.instructions
+    }
+    
+    /// What the last prompt included and dropped.
+    pub fn last_context_report(&self) -> Option<ContextReport> {
+        self.last_context_report.lock().ok()?.clone()
+    }
+    
+    /// Ask the agent to reply to the packed prompt, then record the reply in the chat and its usage in the ledger.
+    async fn run_exchange<F: FnMut(AgentEvent) + Send>(
+        agent: Arc<Mutex<CodeAgent>>,
+        chat_manager: Arc<Mutex<ChatManager>>,
+        usage: Arc<Mutex<UsageLedger>>,
+        exchange: Exchange,
+        on_event: F,
+    ) -> Result<String, super::providers::ProviderError> {
+        let started = Instant::now();
+        // Run on a clone so the lock is free while the reply streams in and tools run.
+        let agent = agent.lock().await.clone();
+        let run = agent.process_message(&exchange.messages, on_event).await?;
+        
+        // Count locally when the provider reports no usage.
+        let estimated = run.usage == Usage::default();
+        let (input_tokens, output_tokens) = if estimated {
+            (exchange.prompt_tokens as u64, exchange.tokenizer.count(&run.text) as u64)
         } else {
-            Err("Tokio runtime not available".into())
+            (run.usage.input_tokens as u64, run.usage.output_tokens as u64)
+        };
+        let metadata = MessageMetadata {
+            tokens_used: Some((input_tokens + output_tokens) as u32),
+            processing_time: Some(started.elapsed().as_secs_f64()),
+            ..MessageMetadata::default()
+        };
+        chat_manager.lock().await.add_assistant_message_with_metadata(run.text.clone(), metadata);
+        
+        let record = UsageRecord::new(&exchange.model, exchange.session_id, input_tokens, output_tokens, estimated);
+        if let Err(e) = usage.lock().await.record(record) {
+            eprintln!("Failed to record usage: {}", e);
+        }
+        Ok(run.text)
+    }
+    
+    /// Limits checked before each request.
+    pub fn set_usage_budget(&mut self, budget: UsageBudget) {
+        self.usage_budget = budget;
+    }
+    
+    /// Warnings raised when the last message was sent, if it neared or passed a budget.
+    pub fn budget_warnings(&self) -> Vec<String> {
+        self.budget_warnings.lock().map(|warnings| warnings.clone()).unwrap_or_default()
+    }
+    
+    /// Usage of the active chat session and of the whole project.
+    pub fn usage_totals(&self) -> (UsageTotals, UsageTotals) {
+        let Some(runtime) = &self.runtime else {
+            return Default::default();
+        };
+        let chat_manager = self.chat_manager.clone();
+        let usage = self.usage.clone();
+        runtime.block_on(async move {
+            let session_id = chat_manager.lock().await.active_session_id().map(str::to_string);
+            let ledger = usage.lock().await;
+            let session = session_id.map(|id| ledger.session_totals(&id)).unwrap_or_default();
+            (session, ledger.project_totals())
+        })
+    }
+    
+    /// Tokens in `text` for the current model, e.g. a message about to be sent.
+    pub fn count_tokens(&self, text: &str) -> usize {
+        self.tokenizer.count(text)
+    }
+    
+    pub fn get_file_tracker(&self) -> &FileChangeTracker {
+        &self.file_tracker
+    }
+    
+    /// Write the accepted hunks of an agent proposal, recording them as agent changes.
+    pub fn apply_proposal(&mut self, proposal: &EditProposal) -> Result<(), String> {
+        let applied = proposal.apply(&mut self.file_tracker)?;
+        if let Some(index) = &self.semantic_index {
+            applied.files.iter().for_each(|file| index.refresh_file(&file.path));
+        }
+        if !applied.is_empty() {
+            self.applied_edits.push(applied);
+        }
+        Ok(())
+    }
+    
+    /// Description of the agent turn [`Self::revert_last_agent_turn`] would undo.
+    pub fn last_agent_turn(&self) -> Option<&str> {
+        self.applied_edits.last().map(|applied| applied.description.as_str())
+    }
+    
+    /// Undo every file change from the most recently applied proposal.
+    pub fn revert_last_agent_turn(&mut self) -> Result<String, String> {
+        let applied = self.applied_edits.pop().ok_or("No agent edits to revert")?;
+        if let Err(e) = applied.revert(&mut self.file_tracker) {
+            self.applied_edits.push(applied);
+            return Err(e);
         }
+        Ok(applied.description)
     }
     
     pub fn analyze_current_code(&mut self, code: String, language: String) -> Result<String, Box<dyn std::error::Error>> {
@@ -69,6 +455,141 @@ impl CodeAgentSystem {
         }
     }
     
+    /// Turn ghost text completion on or off.
+    pub fn set_auto_complete(&mut self, enabled: bool) {
+        if enabled != self.auto_complete {
+            self.auto_complete = enabled;
+            self.rebuild_completion();
+        }
+    }
+    
+    /// Whether a completion is waiting to start or to come back from the model.
+    pub fn completion_busy(&self) -> bool {
+        self.completion.as_ref().is_some_and(CompletionEngine::is_busy)
+    }
+    
+    /// Ask for a completion at the editor's caret, replacing any earlier request.
+    pub fn request_completion(&mut self, context: FimContext) {
+        if let Some(engine) = &mut self.completion {
+            engine.request(context, Instant::now());
+        }
+    }
+    
+    /// A finished completion, if one arrived since the last call.
+    pub fn poll_completion(&mut self) -> Option<Completion> {
+        self.completion.as_mut()?.poll(Instant::now())
+    }
+    
+    pub fn cancel_completion(&mut self) {
+        if let Some(engine) = &mut self.completion {
+            engine.cancel();
+        }
+    }
+    
+    fn rebuild_completion(&mut self) {
+        self.completion = None;
+        let Some(runtime) = self.runtime.as_ref().filter(|_| self.auto_complete) else {
+            return;
+        };
+        let agent = self.agent.clone();
+        let config = runtime.block_on(async move { agent.lock().await.config().clone() });
+        self.completion = FimCompleter::for_config(&config).map(|completer| CompletionEngine::new(completer, runtime.handle().clone()));
+    }
+    
+    /// Reopen the semantic index for the current project and embedding model.
+    fn rebuild_semantic_index(&mut self) {
+        self.semantic_index = None;
+        let Some(runtime) = &self.runtime else {
+            return;
+        };
+        let agent = self.agent.clone();
+        let context_manager = self.context_manager.clone();
+        let (config, project) = runtime.block_on(async move {
+            let project = context_manager.lock().await.get_current_project().cloned();
+            (agent.lock().await.config().clone(), project)
+        });
+        let (Some(project), Some(embedder)) = (project, Embedder::for_config(&config)) else {
+            return;
+        };
+        self.semantic_index = Some(Arc::new(SemanticIndex::open(PathBuf::from(project), embedder, runtime.handle().clone())));
+    }
+    
+    /// Start looking for the code in the project closest in meaning to `query`.
+    ///
+    /// The matches, best first, come back over the returned channel.
+    pub fn semantic_search(&self, query: &str, limit: usize) -> Result<mpsc::Receiver<Result<Vec<SemanticMatch>, String>>, String> {
+        let index = self.semantic_index.clone().ok_or("Semantic search needs an open project and a local embedding model")?;
+        let runtime = self.runtime.as_ref().ok_or("Tokio runtime not available")?;
+        let (sender, receiver) = mpsc::channel();
+        let query = query.to_string();
+        runtime.spawn(async move {
+            let _ = sender.send(index.search(&query, limit).await);
+        });
+        Ok(receiver)
+    }
+    
+    /// Progress of the semantic index, if there is one.
+    pub fn semantic_index_status(&self) -> Option<IndexStatus> {
+        self.semantic_index.as_ref().map(|index| index.status())
+    }
+    
+    /// Re-embed a file that changed outside the agent, e.g. after the editor saved it.
+    pub fn refresh_semantic_index(&self, path: &std::path::Path) {
+        if let Some(index) = &self.semantic_index {
+            index.refresh_file(path);
+        }
+    }
+    
+    /// Serve the agent over JSON-RPC; returns the address it listens on.
+    ///
+    /// Clients authenticate with a token from the config dir's token file, which is
+    /// created with a full-access `default` token on first start.
+    pub fn start_agent_server(&mut self, config: AgentServerConfig) -> Result<SocketAddr, String> {
+        if self.agent_server.is_some() {
+            return Err("Agent server already running".to_string());
+        }
+        let tokens = match TokenStore::default_path() {
+            Some(path) => TokenStore::load_or_create(&path)?,
+            None if config.require_auth => return Err("No config directory for agent server tokens".to_string()),
+            None => TokenStore::default(),
+        };
+        let runtime = self.runtime.as_ref().ok_or("Tokio runtime not available")?;
+        let agent = self.agent.clone();
+        let server_agent = agent.clone();
+        let completer = runtime.block_on(async move { FimCompleter::for_config(agent.lock().await.config()) });
+        let mut server = AgentServer::new(config)
+            .with_agent(server_agent)
+            .with_tokens(tokens)
+            .with_audit_log(AuditLog::new(AuditLog::default_path()));
+        if let Some(completer) = completer {
+            server = server.with_completer(completer);
+        }
+        let server = Arc::new(server);
+        let address = runtime.block_on(server.start()).map_err(|e| format!("Failed to start agent server: {}", e))?;
+        self.agent_server = Some(server);
+        Ok(address)
+    }
+    
+    pub fn stop_agent_server(&mut self) -> Result<(), String> {
+        let server = self.agent_server.take().ok_or("Agent server not running")?;
+        let runtime = self.runtime.as_ref().ok_or("Tokio runtime not available")?;
+        runtime.block_on(server.stop()).map_err(|e| e.to_string())
+    }
+    
+    /// Limits on the commands the agent runs; applies to commands not yet checked.
+    pub fn set_command_policy(&mut self, policy: CommandPolicy) {
+        self.commands.set_policy(policy);
+    }
+    
+    /// Commands the agent is waiting to run until the user decides.
+    pub fn pending_commands(&self) -> Vec<PendingCommand> {
+        self.commands.approvals().map(CommandApprovalQueue::pending).unwrap_or_default()
+    }
+    
+    pub fn decide_command(&mut self, id: u64, decision: ApprovalDecision) -> Result<(), String> {
+        self.commands.approvals().ok_or("No approval queue")?.decide(id, decision)
+    }
+    
     pub fn get_code_suggestions(&mut self, code: String, cursor_pos: usize) -> Result<Vec<String>, Box<dyn std::error::Error>> {
         if let Some(runtime) = &self.runtime {
             let agent = self.agent.clone();
@@ -83,16 +604,48 @@ impl CodeAgentSystem {
     }
     
     pub fn update_context(&mut self, file: Option<String>, project: Option<String>) {
+        let project_root = project.as_ref().map(PathBuf::from);
+        let project_changed = project.as_ref().is_some_and(|p| self.semantic_index.as_ref().is_none_or(|index| index.root() != std::path::Path::new(p)));
         if let Some(runtime) = &self.runtime {
             let agent = self.agent.clone();
+            let chat_manager = self.chat_manager.clone();
             let context_manager = self.context_manager.clone();
+            let usage = self.usage.clone();
+            let commands = self.commands.clone();
+            let memory = self.memory.clone();
+            let prompt_engine = self.prompt_engine.clone();
             
             runtime.block_on(async move {
+                // Load the project's usage ledger, memory and prompt templates when switching projects
+                if let Some(p) = &project {
+                    if context_manager.lock().await.get_current_project() != Some(p) {
+                        *usage.lock().await = UsageLedger::for_project(std::path::Path::new(p));
+                        if let Ok(mut memory) = memory.lock() {
+                            *memory = AgentMemory::for_project(std::path::Path::new(p));
+                        }
+                        prompt_engine.lock().await.load_project(std::path::Path::new(p));
+                    }
+                }
+                
                 // Update agent context
-                let agent = agent.lock().await;
+                let mut agent = agent.lock().await;
                 agent.update_context(file.clone(), project.clone()).await;
+                // Scope the agent's file and command tools to the project
+                if let Some(p) = &project {
+                    if agent.get_tools().workspace() != std::path::Path::new(p) {
+                        let mut tools = ToolRegistry::with_default_tools(p.into());
+                        tools.set_command_gate(commands);
+                        tools.set_memory(memory);
+                        agent.set_tools(tools);
+                    }
+                }
                 drop(agent);
                 
+                // Keep chat sessions with the project
+                if let Some(p) = &project {
+                    chat_manager.lock().await.open_project(std::path::Path::new(p));
+                }
+                
                 // Update context manager
                 let mut ctx = context_manager.lock().await;
                 if let Some(f) = file {
@@ -103,6 +656,13 @@ impl CodeAgentSystem {
                 }
             });
         }
+        if let Some(p) = project_root {
+            self.instructions.set_root(Some(p));
+            self.instructions_checked = None;
+        }
+        if project_changed {
+            self.rebuild_semantic_index();
+        }
     }
     
     pub fn get_chat_history(&self) -> Vec<String> {
@@ -111,7 +671,7 @@ impl CodeAgentSystem {
             
             runtime.block_on(async move {
                 let chat = chat_manager.lock().await;
-                chat.get_conversation_context(50)
+                chat.get_conversation_context(HISTORY_LIMIT)
                     .iter()
                     .map(|msg| format!("{}: {}", 
                         match msg.role {
@@ -128,6 +688,25 @@ impl CodeAgentSystem {
         }
     }
     
+    /// Run `f` on the chat manager, e.g. to switch, rename or search sessions.
+    pub fn with_chat<R>(&self, f: impl FnOnce(&mut ChatManager) -> R) -> Option<R> {
+        let runtime = self.runtime.as_ref()?;
+        let chat_manager = self.chat_manager.clone();
+        Some(runtime.block_on(async move { f(&mut *chat_manager.lock().await) }))
+    }
+    
+    /// Run `f` on the project's memory, e.g. to review, edit or purge it.
+    pub fn with_memory<R>(&self, f: impl FnOnce(&mut AgentMemory) -> R) -> Option<R> {
+        self.memory.lock().ok().map(|mut memory| f(&mut memory))
+    }
+    
+    /// Run `f` on the context manager, e.g. to pin files or record the editor selection.
+    pub fn with_context<R>(&self, f: impl FnOnce(&mut ContextManager) -> R) -> Option<R> {
+        let runtime = self.runtime.as_ref()?;
+        let context_manager = self.context_manager.clone();
+        Some(runtime.block_on(async move { f(&mut *context_manager.lock().await) }))
+    }
+    
     pub fn clear_chat(&mut self) {
         if let Some(runtime) = &self.runtime {
             let chat_manager = self.chat_manager.clone();
@@ -139,17 +718,182 @@ impl CodeAgentSystem {
         }
     }
     
-    pub fn get_prompt_suggestions(&self, context: &str) -> Vec<String> {
-        if let Some(runtime) = &self.runtime {
-            let prompt_engine = self.prompt_engine.clone();
+    /// Prompts to offer for the current editor state, most relevant first, worked out
+    /// in the background as it parses the current file and asks git for its status.
+    /// `editor_cursor` is the editor's active file name and 1-based caret line.
+    pub fn prompt_suggestions(&self, editor_cursor: Option<(String, usize)>, terminal_output: Vec<String>, limit: usize) -> mpsc::Receiver<Vec<PromptSuggestion>> {
+        let (sender, receiver) = mpsc::channel();
+        let Some(runtime) = &self.runtime else {
+            return receiver;
+        };
+        let prompt_engine = self.prompt_engine.clone();
+        let context_manager = self.context_manager.clone();
+        runtime.spawn(async move {
+            let ctx = context_manager.lock().await;
+            let current_file = ctx.get_current_file().cloned();
+            let root = ctx.get_current_project().map(PathBuf::from);
+            let context = PromptContext {
+                has_selection: ctx.get_selection().is_some(),
+                diagnostics: ctx.get_diagnostics().to_vec(),
+                terminal_output,
+                ..PromptContext::default()
+            };
+            drop(ctx);
             
-            runtime.block_on(async move {
-                let engine = prompt_engine.lock().await;
-                engine.suggest_prompt(context)
-            })
-        } else {
-            Vec::new()
-        }
+            let context = tokio::task::spawn_blocking(move || {
+                let mut context = context;
+                // The editor knows files by name only, so the caret counts if it is in the agent's current file.
+                let cursor_line = editor_cursor.and_then(|(name, line)| {
+                    current_file.as_deref().map(std::path::Path::new).and_then(std::path::Path::file_name).is_some_and(|file| file.to_string_lossy() == name).then_some(line)
+                });
+                if let (Some(file), Some(line)) = (current_file.filter(|file| file.ends_with(".rs")), cursor_line) {
+                    let undocumented = DocstringAuditTool::audit_file(&file).ok().and_then(|audit| {
+                        audit.missing_docstrings.into_iter().rfind(|item| (item.span.start_line..=item.span.end_line).contains(&line))
+                    });
+                    if let Some(item) = undocumented {
+                        let text = std::fs::read_to_string(&file).unwrap_or_default();
+                        let text = text.lines().skip(item.span.start_line - 1).take(item.span.end_line + 1 - item.span.start_line).collect::<Vec<_>>().join("\n");
+                        let selection = Selection { path: file.clone(), start_line: item.span.start_line, end_line: item.span.end_line, text };
+                        context.undocumented_function = Some((item.name, selection));
+                    }
+                }
+                context.has_uncommitted_changes = root.as_deref().is_some_and(has_uncommitted_changes);
+                context
+            }).await;
+            
+            if let Ok(context) = context {
+                let _ = sender.send(prompt_engine.lock().await.suggest_prompt(&context, limit));
+            }
+        });
+        receiver
+    }
+    
+    /// Prompt templates that can be run as `/name`, with their descriptions. Template
+    /// files are read again, so edits show up straight away.
+    pub fn slash_commands(&self) -> Vec<(String, String)> {
+        let Some(runtime) = &self.runtime else {
+            return Vec::new();
+        };
+        let prompt_engine = self.prompt_engine.clone();
+        runtime.block_on(async move {
+            let mut engine = prompt_engine.lock().await;
+            engine.reload();
+            engine.list_templates(None).into_iter().map(|template| (template.name.clone(), template.description.clone())).collect()
+        })
+    }
+    
+    /// Template files in `.jadio/prompts` that failed to load.
+    pub fn prompt_template_errors(&self) -> Vec<String> {
+        let Some(runtime) = &self.runtime else {
+            return Vec::new();
+        };
+        let prompt_engine = self.prompt_engine.clone();
+        runtime.block_on(async move { prompt_engine.lock().await.load_errors().to_vec() })
+    }
+    
+    /// Turn chat input such as `/fix_bug error="index out of bounds"` into the prompt of
+    /// its template, filling the built-in variables from the editor state.
+    /// Returns `None` if the input isn't a slash command.
+    pub fn expand_slash_command(&self, input: &str) -> Option<Result<String, String>> {
+        let runtime = self.runtime.as_ref()?;
+        let prompt_engine = self.prompt_engine.clone();
+        let context_manager = self.context_manager.clone();
+        runtime.block_on(async move {
+            let mut engine = prompt_engine.lock().await;
+            let command = match engine.parse_command(input)? {
+                Ok(command) => command,
+                Err(e) => return Some(Err(e)),
+            };
+            let builtins = engine.get_template(&command.name).map(|template| template.builtins_used()).unwrap_or_default();
+            let mut variables = command.variables;
+            let ctx = context_manager.lock().await;
+            let root = ctx.get_current_project().map(PathBuf::from);
+            for name in builtins {
+                let value = match name {
+                    // Without a selection, templates work on the whole current file.
+                    "selection" => match ctx.get_selection() {
+                        Some(selection) => Some(selection.text.clone()),
+                        None => ctx.get_current_file().and_then(|file| match ctx.get_file_context(file) {
+                            Some(context) => Some(context.content.clone()),
+                            None => std::fs::read_to_string(file).ok(),
+                        }).map(|content| truncate(&content, "file")),
+                    },
+                    "file" => ctx.get_current_file().map(|file| {
+                        let path = std::path::Path::new(file);
+                        root.as_deref().and_then(|root| path.strip_prefix(root).ok()).unwrap_or(path).to_string_lossy().replace('\\', "/")
+                    }),
+                    "language" => ctx.get_current_file().map(|file| {
+                        ctx.get_file_context(file).map(|context| context.language.clone()).unwrap_or_else(|| language_for_path(std::path::Path::new(file)).to_string())
+                    }),
+                    "diagnostics" if ctx.get_diagnostics().is_empty() => Some("No diagnostics.".to_string()),
+                    "diagnostics" => Some(ctx.get_diagnostics().join("\n")),
+                    "git_diff" => root.as_deref().and_then(git_diff),
+                    _ => None,
+                };
+                if let Some(value) = value {
+                    variables.insert(name.to_string(), value);
+                }
+            }
+            drop(ctx);
+            Some(engine.generate_prompt(&command.name, variables))
+        })
     }
     
-    pub fn execute_quick_action(&
\ No newline at end of file
+    pub fn execute_quick_action(&mut self, action: &str, code: String, language: String) -> Result<String, Box<dyn std::error::Error>> {
+        let request = match action {
+            "review" => "Review this code for improvements",
+            "find_bugs" => "Find potential bugs in this code",
+            "explain" => "Explain what this code does",
+            "optimize" => "Suggest optimizations for this code",
+            _ => return Err(format!("Unknown quick action: {}", action).into()),
+        };
+        self.process_user_message(format!("{}:\n\n```{}\n{}\n```", request, language, code))
+    }
+}
+
+/// Language name of a file, from its extension.
+fn language_for_path(path: &std::path::Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()).unwrap_or("") {
+        "rs" => "rust",
+        "py" => "python",
+        "js" => "javascript",
+        "ts" => "typescript",
+        "html" => "html",
+        "css" => "css",
+        "json" => "json",
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        "md" => "markdown",
+        _ => "text",
+    }
+}
+
+/// Uncommitted changes in `root`, or `None` if it isn't a git repository.
+fn git_diff(root: &std::path::Path) -> Option<String> {
+    let output = std::process::Command::new("git").arg("diff").arg("HEAD").current_dir(root).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let diff = String::from_utf8_lossy(&output.stdout);
+    if diff.trim().is_empty() {
+        return Some("No uncommitted changes.".to_string());
+    }
+    Some(truncate(&diff, "diff"))
+}
+
+/// `text` cut to [`MAX_TEMPLATE_VALUE_LEN`].
+fn truncate(text: &str, what: &str) -> String {
+    match text.char_indices().nth(MAX_TEMPLATE_VALUE_LEN) {
+        Some((end, _)) => format!("{}\n... ({} truncated)", &text[..end], what),
+        None => text.to_string(),
+    }
+}
+
+/// Whether `root` is a git repository with uncommitted changes.
+fn has_uncommitted_changes(root: &std::path::Path) -> bool {
+    std::process::Command::new("git")
+        .args(["status", "--porcelain"])
+        .current_dir(root)
+        .output()
+        .is_ok_and(|output| output.status.success() && !output.stdout.is_empty())
+}
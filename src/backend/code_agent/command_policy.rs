@@ -0,0 +1,369 @@
+//! What shell commands the agent may run, and where.
+//!
+//! A command is checked as text before it runs. Each part of a pipeline or `&&`
+//! chain is matched against deny and allow patterns, and paths in its arguments,
+//! quoted or not, must stay inside the project root. Commands whose words the
+//! shell would expand (`$VAR`, `$(..)`, backticks) can't be checked as text and
+//! need approval, as does a `cd` whose target isn't a plain project path. This guards against mistakes
+//! rather than sandboxing the process: whatever the patterns don't cover goes to
+//! the user for approval, and denied commands never run.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use crate::backend::settings_manager::AISettings;
+use super::instructions::command_approval::{ApprovalDecision, CommandApprovalQueue};
+use super::tools::registry::resolve_path;
+
+/// Commands that only look at the project.
+pub const READ_ONLY_COMMANDS: &[&str] = &[
+    "ls", "ls *", "cd *", "cat *", "head *", "tail *", "wc *", "grep *", "rg *", "pwd", "echo *", "which *",
+    "git status*", "git diff*", "git log*", "git show*", "git branch", "git blame *",
+    "cargo check*", "cargo tree*", "cargo metadata*",
+];
+
+/// Build and test commands run without asking unless the policy is read-only.
+pub const BUILD_COMMANDS: &[&str] = &["cargo build*", "cargo test*", "cargo clippy*", "cargo fmt*", "cargo doc*"];
+
+/// Commands never run, even if the user would approve them.
+pub const DENIED_COMMANDS: &[&str] = &[
+    "rm -r*", "rm -R*", "rm -f*", "rm * -r*", "rm * -R*", "rm * -f*", "rm --recursive*",
+    "sudo *", "su *", "doas *", "mkfs*", "dd *", "shutdown*", "reboot*", "chmod -R*", "chown *",
+    "git push*", "git reset --hard*", "git clean*", "git checkout -- *", "curl *", "wget *",
+];
+
+/// What the policy says about a command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Allow,
+    /// Run only if the user approves; says why.
+    Ask(String),
+    /// Never run; says why.
+    Deny(String),
+}
+
+/// Limits on the commands the agent runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandPolicy {
+    /// Glob patterns (`*` matches anything) for commands that run without asking.
+    pub allow: Vec<String>,
+    /// Patterns for commands that never run. Checked before `allow`.
+    pub deny: Vec<String>,
+    /// Longest a command may run before it is killed.
+    pub max_timeout: Duration,
+    /// Bytes of stdout and of stderr kept from a command.
+    pub max_output: usize,
+    /// Only run [`READ_ONLY_COMMANDS`], and refuse everything else without asking.
+    pub read_only: bool,
+}
+
+impl Default for CommandPolicy {
+    fn default() -> Self {
+        Self {
+            allow: READ_ONLY_COMMANDS.iter().chain(BUILD_COMMANDS).map(|pattern| pattern.to_string()).collect(),
+            deny: DENIED_COMMANDS.iter().map(|pattern| pattern.to_string()).collect(),
+            max_timeout: Duration::from_secs(600),
+            max_output: 1024 * 1024,
+            read_only: false,
+        }
+    }
+}
+
+impl From<&AISettings> for CommandPolicy {
+    fn from(settings: &AISettings) -> Self {
+        let mut policy = Self { read_only: settings.agent_commands_read_only, ..Self::default() };
+        let patterns = |list: &[String]| list.iter().map(|pattern| pattern.trim().to_string()).filter(|pattern| !pattern.is_empty()).collect::<Vec<_>>();
+        policy.allow.extend(patterns(&settings.agent_command_allow));
+        policy.deny.extend(patterns(&settings.agent_command_deny));
+        policy
+    }
+}
+
+impl CommandPolicy {
+    /// Check `command`, to be run in `dir` inside the project `root`.
+    pub fn check(&self, command: &str, root: &Path, dir: &Path) -> Verdict {
+        if command.trim().is_empty() {
+            return Verdict::Deny("Empty command".to_string());
+        }
+        let Some(parsed) = ParsedCommand::parse(command) else {
+            return Verdict::Deny("Unbalanced quotes".to_string());
+        };
+
+        let mut verdict = Verdict::Allow;
+        let mut dir = dir.to_path_buf();
+        for segment in &parsed.segments {
+            if let Some(pattern) = self.deny.iter().find(|pattern| glob_match(pattern, &segment.text)) {
+                return Verdict::Deny(format!("`{}` matches the denied pattern `{}`", segment.text, pattern));
+            }
+            for path in &segment.paths {
+                if let Err(e) = jail(root, &dir, path) {
+                    return Verdict::Deny(e);
+                }
+            }
+            // Later paths are relative to where a `cd` goes.
+            if segment.words.first().map(String::as_str) == Some("cd") {
+                match segment.words.get(1).filter(|target| !target.starts_with('-')).map(|target| jail(root, &dir, target)) {
+                    Some(Ok(target)) => dir = target,
+                    _ if verdict == Verdict::Allow => {
+                        verdict = Verdict::Ask(format!("`{}` may leave the project", segment.text));
+                    }
+                    _ => {}
+                }
+            }
+            let allowed = if self.read_only {
+                READ_ONLY_COMMANDS.iter().any(|pattern| glob_match(pattern, &segment.text))
+            } else {
+                self.allow.iter().any(|pattern| glob_match(pattern, &segment.text))
+            };
+            if !allowed && verdict == Verdict::Allow {
+                verdict = Verdict::Ask(format!("`{}` is not on the allow list", segment.text));
+            }
+        }
+        if parsed.expansion && verdict == Verdict::Allow {
+            verdict = Verdict::Ask("The command expands variables or commands".to_string());
+        }
+        if parsed.redirects && verdict == Verdict::Allow {
+            verdict = Verdict::Ask("The command redirects output to a file".to_string());
+        }
+
+        match verdict {
+            Verdict::Ask(reason) if self.read_only => Verdict::Deny(format!("{} (commands are read-only)", reason)),
+            verdict => verdict,
+        }
+    }
+}
+
+/// The policy the agent's command tool checks against, and the queue where
+/// commands it doesn't allow wait for the user.
+///
+/// Shared between the tools and the UI, so the policy can change while a command waits.
+#[derive(Debug, Default)]
+pub struct CommandGate {
+    policy: Mutex<CommandPolicy>,
+    /// `None` refuses commands that need approval, e.g. for remote clients.
+    approvals: Option<CommandApprovalQueue>,
+}
+
+impl CommandGate {
+    pub fn new(policy: CommandPolicy, approvals: Option<CommandApprovalQueue>) -> Self {
+        Self { policy: Mutex::new(policy), approvals }
+    }
+
+    pub fn policy(&self) -> CommandPolicy {
+        self.policy.lock().unwrap().clone()
+    }
+
+    pub fn set_policy(&self, policy: CommandPolicy) {
+        *self.policy.lock().unwrap() = policy;
+    }
+
+    pub fn approvals(&self) -> Option<&CommandApprovalQueue> {
+        self.approvals.as_ref()
+    }
+
+    /// Check `command` and, if the policy asks, wait for the user.
+    ///
+    /// Returns the command line to run, which the user may have edited.
+    pub fn authorize(&self, command: &str, root: &Path, dir: &Path) -> Result<String, String> {
+        let reason = match self.policy().check(command, root, dir) {
+            Verdict::Allow => return Ok(command.to_string()),
+            Verdict::Deny(reason) => return Err(format!("Command refused: {}", reason)),
+            Verdict::Ask(reason) => reason,
+        };
+        let Some(approvals) = &self.approvals else {
+            return Err(format!("Command refused: {}, and no one is available to approve it", reason));
+        };
+        match approvals.request(command, dir.to_path_buf(), &reason) {
+            ApprovalDecision::Approve => Ok(command.to_string()),
+            ApprovalDecision::Edit(edited) => match self.policy().check(&edited, root, dir) {
+                // The user has approved the edited command, but not past the deny list or the root.
+                Verdict::Deny(reason) => Err(format!("Edited command refused: {}", reason)),
+                _ => Ok(edited),
+            },
+            ApprovalDecision::Reject(reason) => Err(format!("The user rejected the command: {}", reason)),
+        }
+    }
+}
+
+/// One simple command of a command line.
+#[derive(Debug, Clone, PartialEq)]
+struct Segment {
+    /// The command with whitespace collapsed, for matching patterns.
+    text: String,
+    /// The words of the command with quotes removed.
+    words: Vec<String>,
+    /// Arguments that look like paths.
+    paths: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct ParsedCommand {
+    segments: Vec<Segment>,
+    /// `$VAR`, `${..}`, `$(..)` or backticks outside single quotes.
+    expansion: bool,
+    redirects: bool,
+}
+
+impl ParsedCommand {
+    /// Split on `;`, `&`, `|` and newlines outside quotes. `None` if a quote is left open.
+    fn parse(command: &str) -> Option<Self> {
+        let mut parsed = Self::default();
+        let mut words: Vec<String> = Vec::new();
+        let mut word = String::new();
+        let mut quote = None;
+        let mut chars = command.chars().peekable();
+
+        let end_word = |words: &mut Vec<String>, paths: &mut Vec<String>, word: &mut String| {
+            if !word.is_empty() {
+                if looks_like_path(word) {
+                    paths.push(word.split_once('=').map_or(word.as_str(), |(_, value)| value).to_string());
+                }
+                words.push(std::mem::take(word));
+            }
+        };
+        let mut paths = Vec::new();
+        let end_segment = |parsed: &mut Self, words: &mut Vec<String>, paths: &mut Vec<String>| {
+            if !words.is_empty() {
+                parsed.segments.push(Segment { text: words.join(" "), words: std::mem::take(words), paths: std::mem::take(paths) });
+            }
+        };
+
+        while let Some(c) = chars.next() {
+            match (quote, c) {
+                (Some(open), c) if c == open => quote = None,
+                (Some('"'), '`') | (None, '`') => parsed.expansion = true,
+                (Some('"'), '$') | (None, '$') if chars.peek().is_some_and(|next| next.is_alphanumeric() || "_{(@*#?!$-".contains(*next)) => {
+                    parsed.expansion = true;
+                    word.push(c);
+                }
+                (Some(_), c) => word.push(c),
+                (None, '\'' | '"') => quote = Some(c),
+                (None, '\\') => word.extend(chars.next()),
+                (None, c) if c.is_whitespace() && c != '\n' => end_word(&mut words, &mut paths, &mut word),
+                (None, ';' | '&' | '|' | '\n' | '(' | ')') => {
+                    end_word(&mut words, &mut paths, &mut word);
+                    end_segment(&mut parsed, &mut words, &mut paths);
+                }
+                (None, '>') => {
+                    end_word(&mut words, &mut paths, &mut word);
+                    chars.next_if_eq(&'>');
+                    // `2>&1` only joins streams; anything else writes a file, which must stay in the project.
+                    if chars.next_if_eq(&'&').is_some() {
+                        while chars.next_if(char::is_ascii_digit).is_some() {}
+                        continue;
+                    }
+                    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                    let target: String = std::iter::from_fn(|| chars.next_if(|c| !c.is_whitespace() && !";&|".contains(*c))).collect();
+                    if target != "/dev/null" {
+                        parsed.redirects = true;
+                        paths.push(target);
+                    }
+                }
+                (None, c) => word.push(c),
+            }
+        }
+        if quote.is_some() {
+            return None;
+        }
+        end_word(&mut words, &mut paths, &mut word);
+        end_segment(&mut parsed, &mut words, &mut paths);
+        Some(parsed)
+    }
+}
+
+fn looks_like_path(word: &str) -> bool {
+    let value = word.split_once('=').map_or(word, |(_, value)| value);
+    value.starts_with('/') || value.starts_with('~') || value.split(['/', '\\']).any(|part| part == "..")
+}
+
+/// Resolve `path` from `dir`, refusing anything outside `root`.
+fn jail(root: &Path, dir: &Path, path: &str) -> Result<PathBuf, String> {
+    if path.starts_with('~') {
+        return Err(format!("Path '{}' is outside the project", path));
+    }
+    let relative_dir = dir.strip_prefix(root).map_err(|_| format!("Directory '{}' is outside the project", dir.display()))?;
+    let joined = if Path::new(path).is_absolute() { PathBuf::from(path) } else { relative_dir.join(path) };
+    resolve_path(root, &joined.to_string_lossy()).map_err(|_| format!("Path '{}' is outside the project", path))
+}
+
+/// Match `text` against a pattern where `*` stands for any run of characters.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_policy_checks_patterns_paths_and_waits_for_approval() {
+        let root = std::env::temp_dir().join("jadio_policy_project");
+        let policy = CommandPolicy::default();
+        let check = |command: &str| policy.check(command, &root, &root);
+
+        assert_eq!(check("cargo test -p core 2>&1 && git status"), Verdict::Allow);
+        assert_eq!(check("grep -rn \"fn main\" src | wc -l"), Verdict::Allow);
+        assert!(matches!(check("npm install"), Verdict::Ask(_)));
+        assert!(matches!(check("ls $(pwd)"), Verdict::Ask(_)));
+        assert!(matches!(check("cat $HOME/.ssh/id_rsa"), Verdict::Ask(_)));
+        assert!(matches!(check("cat \"${HOME}/.ssh/id_rsa\""), Verdict::Ask(_)));
+        assert!(matches!(check("ls `pwd`"), Verdict::Ask(_)));
+        assert_eq!(check("echo 'costs $5'"), Verdict::Allow);
+        assert!(matches!(check("echo hi > notes.txt"), Verdict::Ask(_)));
+        assert!(matches!(check("cargo build; rm -rf target"), Verdict::Deny(reason) if reason.contains("rm -r*")));
+        assert!(matches!(check("cat /etc/passwd"), Verdict::Deny(reason) if reason.contains("outside the project")));
+        assert!(matches!(check("cat \"/etc/passwd\""), Verdict::Deny(_)));
+        assert!(matches!(check("cat '/home/u/.ssh/id_rsa'"), Verdict::Deny(_)));
+        assert!(matches!(check("cd .. && ls"), Verdict::Deny(_)));
+        assert!(matches!(check("cd /; cat etc/passwd"), Verdict::Deny(_)));
+        assert!(matches!(check("cd; cat .ssh/id_rsa"), Verdict::Ask(_)));
+        assert!(matches!(check("cd - && ls"), Verdict::Ask(_)));
+        assert!(matches!(check("cd src && cat ../../secret"), Verdict::Deny(_)));
+        assert_eq!(check("cd src && cat ../README.md"), Verdict::Allow);
+        assert!(matches!(check("echo hi >> ~/.bashrc"), Verdict::Deny(_)));
+        assert_eq!(policy.check("cat ../README.md", &root, &root.join("src")), Verdict::Allow);
+        assert!(matches!(check("echo 'open"), Verdict::Deny(_)));
+
+        let read_only = CommandPolicy { read_only: true, ..CommandPolicy::default() };
+        assert_eq!(read_only.check("git diff HEAD", &root, &root), Verdict::Allow);
+        assert!(matches!(read_only.check("cargo build", &root, &root), Verdict::Deny(_)));
+
+        // Unlisted commands block until the user decides; edits are checked again.
+        let gate = Arc::new(CommandGate::new(policy.clone(), Some(CommandApprovalQueue::default())));
+        let decide = |decision: ApprovalDecision| {
+            let (waiting_gate, root) = (gate.clone(), root.clone());
+            let waiting = std::thread::spawn(move || waiting_gate.authorize("npm install", &root, &root));
+            let pending = loop {
+                match gate.approvals().unwrap().pending().pop() {
+                    Some(pending) => break pending,
+                    None => std::thread::sleep(Duration::from_millis(5)),
+                }
+            };
+            assert_eq!(pending.instruction.text, "npm install");
+            gate.approvals().unwrap().decide(pending.id, decision).unwrap();
+            waiting.join().unwrap()
+        };
+        assert_eq!(decide(ApprovalDecision::Approve).unwrap(), "npm install");
+        assert_eq!(decide(ApprovalDecision::Edit("npm ci".to_string())).unwrap(), "npm ci");
+        assert!(decide(ApprovalDecision::Edit("sudo npm ci".to_string())).is_err());
+        assert!(decide(ApprovalDecision::Reject("not now".to_string())).unwrap_err().contains("not now"));
+        assert!(gate.approvals().unwrap().is_empty());
+        assert!(CommandGate::default().authorize("npm install", &root, &root).is_err());
+    }
+}
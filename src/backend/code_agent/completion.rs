@@ -0,0 +1,430 @@
+//! Fill-in-the-middle code completion shown as ghost text in the editor.
+//!
+//! The text around the caret is wrapped in the model's FIM tokens and sent to a raw
+//! completion endpoint. [`CompletionEngine`] sits between the editor and the model:
+//! it waits for a pause in typing, drops requests the user has typed past, and
+//! remembers recent answers so moving back to the same spot needs no new request.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+use super::agent::AgentConfig;
+use super::providers::fim::{self, FimProvider, FimRequest};
+use super::providers::ProviderError;
+use crate::backend::code_editor::code_editor_logic::EditorTab;
+
+/// Bytes of text before and after the caret sent with each request.
+const MAX_PREFIX: usize = 4000;
+const MAX_SUFFIX: usize = 1000;
+const MAX_TOKENS: u32 = 128;
+const TEMPERATURE: f32 = 0.2;
+const CACHE_SIZE: usize = 64;
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Prompt format of a FIM-trained model family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FimTemplate {
+    StarCoder,
+    CodeLlama,
+    DeepSeek,
+    /// Qwen2.5-Coder and CodeGemma share these tokens.
+    Qwen,
+    Codestral,
+}
+
+impl FimTemplate {
+    /// The template for a model name, or `None` if the model wasn't trained for FIM.
+    pub fn for_model(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        let has = |needles: &[&str]| needles.iter().any(|needle| name.contains(needle));
+        if has(&["starcoder", "stable-code", "santacoder"]) {
+            Some(Self::StarCoder)
+        } else if has(&["codellama", "code-llama"]) {
+            Some(Self::CodeLlama)
+        } else if has(&["deepseek-coder"]) {
+            Some(Self::DeepSeek)
+        } else if has(&["codegemma"]) || (name.contains("qwen") && name.contains("coder")) {
+            Some(Self::Qwen)
+        } else if has(&["codestral"]) {
+            Some(Self::Codestral)
+        } else {
+            None
+        }
+    }
+
+    pub fn prompt(&self, prefix: &str, suffix: &str) -> String {
+        match self {
+            Self::StarCoder => format!("<fim_prefix>{}<fim_suffix>{}<fim_middle>", prefix, suffix),
+            Self::CodeLlama => format!("<PRE> {} <SUF>{} <MID>", prefix, suffix),
+            Self::DeepSeek => format!("<｜fim▁begin｜>{}<｜fim▁hole｜>{}<｜fim▁end｜>", prefix, suffix),
+            Self::Qwen => format!("<|fim_prefix|>{}<|fim_suffix|>{}<|fim_middle|>", prefix, suffix),
+            Self::Codestral => format!("[SUFFIX]{}[PREFIX]{}", suffix, prefix),
+        }
+    }
+
+    /// Tokens that end the middle section; some servers don't stop on them by themselves.
+    pub fn stop_tokens(&self) -> &'static [&'static str] {
+        match self {
+            Self::StarCoder => &["<|endoftext|>", "<fim_prefix>", "<file_sep>"],
+            Self::CodeLlama => &["<EOT>", "<PRE>", "<SUF>"],
+            Self::DeepSeek => &["<｜end▁of▁sentence｜>", "<｜fim▁begin｜>"],
+            Self::Qwen => &["<|endoftext|>", "<|fim_prefix|>", "<|file_separator|>", "<|fim_pad|>"],
+            Self::Codestral => &["</s>", "[PREFIX]", "[SUFFIX]"],
+        }
+    }
+}
+
+/// The text around the caret that a completion is requested for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FimContext {
+    pub prefix: String,
+    pub suffix: String,
+    pub path: Option<PathBuf>,
+    /// Byte offset of the caret in the whole document.
+    pub offset: usize,
+}
+
+impl FimContext {
+    /// Cut the context around `offset` out of `text`, keeping it near the caret.
+    pub fn new(text: &str, offset: usize, path: Option<PathBuf>) -> Self {
+        let offset = floor_char_boundary(text, offset);
+        let start = floor_char_boundary(text, offset.saturating_sub(MAX_PREFIX));
+        let end = floor_char_boundary(text, offset + MAX_SUFFIX);
+        Self { prefix: text[start..offset].to_string(), suffix: text[offset..end].to_string(), path, offset }
+    }
+
+    /// Context at the primary caret of an editor tab. Only the window around the
+    /// caret is copied out of the buffer.
+    pub fn from_tab(tab: &EditorTab) -> Self {
+        let buffer = &tab.buffer;
+        let offset = buffer.floor_char_boundary(tab.selections.last().map_or(0, |selection| selection.head));
+        let start = buffer.floor_char_boundary(offset.saturating_sub(MAX_PREFIX));
+        let end = buffer.floor_char_boundary(offset + MAX_SUFFIX);
+        Self {
+            prefix: buffer.slice(start..offset),
+            suffix: buffer.slice(offset..end),
+            path: tab.file_path.clone(),
+            offset,
+        }
+    }
+
+    /// Identifies the request for caching and for matching answers to the editor state.
+    pub fn key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (&self.prefix, &self.suffix, &self.path).hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+fn floor_char_boundary(text: &str, offset: usize) -> usize {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+/// A FIM-capable model and how to prompt it.
+#[derive(Debug, Clone)]
+pub struct FimCompleter {
+    provider: Arc<dyn FimProvider>,
+    model: String,
+    template: FimTemplate,
+    pub max_tokens: u32,
+    pub temperature: f32,
+}
+
+impl FimCompleter {
+    pub fn new(provider: Arc<dyn FimProvider>, model: &str, template: FimTemplate) -> Self {
+        Self { provider, model: model.to_string(), template, max_tokens: MAX_TOKENS, temperature: TEMPERATURE }
+    }
+
+    /// A completer for the agent's model, if it is a FIM model behind a raw completion API.
+    pub fn for_config(config: &AgentConfig) -> Option<Self> {
+        let template = FimTemplate::for_model(&config.model)?;
+        let provider = fim::create_fim_provider(&config.provider, &config.api_key, config.api_endpoint.as_deref())?;
+        Some(Self::new(provider, &config.model, template))
+    }
+
+    /// The text to insert at the caret, possibly empty.
+    pub async fn complete(&self, context: &FimContext) -> Result<String, ProviderError> {
+        let stop = self.template.stop_tokens();
+        let request = FimRequest {
+            model: self.model.clone(),
+            prompt: self.template.prompt(&context.prefix, &context.suffix),
+            max_tokens: self.max_tokens,
+            temperature: self.temperature,
+            stop: stop.iter().map(|token| token.to_string()).collect(),
+        };
+        let text = self.provider.complete(&request).await?;
+        Ok(clean_completion(&text, &context.suffix, stop))
+    }
+}
+
+/// Cut the answer at the first stop token and drop any repeat of the text after the caret.
+fn clean_completion(text: &str, suffix: &str, stop: &[&str]) -> String {
+    let end = stop.iter().filter_map(|token| text.find(token)).min().unwrap_or(text.len());
+    let mut text = &text[..end];
+    if let Some(next_line) = suffix.lines().map(str::trim).find(|line| !line.is_empty()) {
+        if let Some(stripped) = text.trim_end().strip_suffix(next_line) {
+            text = stripped;
+        }
+    }
+    if suffix.is_empty() || suffix.starts_with('\n') {
+        text = text.trim_end();
+    }
+    text.to_string()
+}
+
+/// An answer from the model for the context with `key`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    pub key: u64,
+    pub offset: usize,
+    pub text: String,
+}
+
+struct InFlight {
+    context: FimContext,
+    task: JoinHandle<()>,
+    result: mpsc::Receiver<Result<String, ProviderError>>,
+}
+
+/// Debounces, cancels and caches completion requests for one editor.
+///
+/// Driven from the UI thread: [`request`](Self::request) on every edit or caret
+/// move and [`poll`](Self::poll) once per frame.
+pub struct CompletionEngine {
+    completer: Arc<FimCompleter>,
+    runtime: Handle,
+    pub debounce: Duration,
+    cache: HashMap<u64, String>,
+    cache_order: VecDeque<u64>,
+    pending: Option<(FimContext, Instant)>,
+    in_flight: Option<InFlight>,
+    ready: Option<Completion>,
+}
+
+impl std::fmt::Debug for CompletionEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("CompletionEngine")
+            .field("completer", &self.completer)
+            .field("debounce", &self.debounce)
+            .field("cached", &self.cache.len())
+            .field("busy", &self.is_busy())
+            .finish()
+    }
+}
+
+impl CompletionEngine {
+    pub fn new(completer: FimCompleter, runtime: Handle) -> Self {
+        Self {
+            completer: Arc::new(completer),
+            runtime,
+            debounce: DEFAULT_DEBOUNCE,
+            cache: HashMap::new(),
+            cache_order: VecDeque::new(),
+            pending: None,
+            in_flight: None,
+            ready: None,
+        }
+    }
+
+    /// Ask for a completion at `context`, replacing any earlier request.
+    ///
+    /// A cached answer is ready on the next poll; otherwise the model is asked once
+    /// the debounce delay has passed without another request.
+    pub fn request(&mut self, context: FimContext, now: Instant) {
+        self.cancel();
+        let key = context.key();
+        match self.cache.get(&key) {
+            Some(text) if !text.is_empty() => self.ready = Some(Completion { key, offset: context.offset, text: text.clone() }),
+            Some(_) => {}
+            None => self.pending = Some((context, now + self.debounce)),
+        }
+    }
+
+    /// Drop the pending request and abort the one in flight.
+    pub fn cancel(&mut self) {
+        self.pending = None;
+        self.ready = None;
+        if let Some(in_flight) = self.in_flight.take() {
+            in_flight.task.abort();
+        }
+    }
+
+    /// Start a due request and return a finished, non-empty completion.
+    pub fn poll(&mut self, now: Instant) -> Option<Completion> {
+        if let Some(completion) = self.ready.take() {
+            return Some(completion);
+        }
+        if self.pending.as_ref().is_some_and(|(_, due)| *due <= now) {
+            let (context, _) = self.pending.take()?;
+            self.start(context);
+        }
+        let in_flight = self.in_flight.as_ref()?;
+        let result = match in_flight.result.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return None,
+            Err(mpsc::TryRecvError::Disconnected) => Err(ProviderError::Network("Completion task stopped".to_string())),
+        };
+        let context = self.in_flight.take()?.context;
+        match result {
+            Ok(text) => {
+                let key = context.key();
+                self.remember(key, text.clone());
+                (!text.is_empty()).then_some(Completion { key, offset: context.offset, text })
+            }
+            Err(e) => {
+                eprintln!("Code completion failed: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Whether a request is waiting for its debounce or for the model.
+    pub fn is_busy(&self) -> bool {
+        self.pending.is_some() || self.in_flight.is_some()
+    }
+
+    fn start(&mut self, context: FimContext) {
+        let (sender, result) = mpsc::channel();
+        let completer = self.completer.clone();
+        let request = context.clone();
+        let task = self.runtime.spawn(async move {
+            let _ = sender.send(completer.complete(&request).await);
+        });
+        self.in_flight = Some(InFlight { context, task, result });
+    }
+
+    fn remember(&mut self, key: u64, text: String) {
+        if self.cache.insert(key, text).is_none() {
+            self.cache_order.push_back(key);
+        }
+        while self.cache_order.len() > CACHE_SIZE {
+            if let Some(oldest) = self.cache_order.pop_front() {
+                self.cache.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// A suggestion shown after the caret until it is accepted, typed through or dismissed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GhostText {
+    /// Byte offset the suggestion is anchored at.
+    pub offset: usize,
+    pub text: String,
+}
+
+impl GhostText {
+    /// Consume `typed` if it matches the start of the suggestion.
+    ///
+    /// Returns `false` once the typing diverges or the suggestion is used up.
+    pub fn type_through(&mut self, typed: &str) -> bool {
+        match self.text.strip_prefix(typed) {
+            Some(rest) => {
+                self.text = rest.to_string();
+                self.offset += typed.len();
+                !self.text.is_empty()
+            }
+            None => false,
+        }
+    }
+
+    /// The next word of the suggestion with the whitespace before it.
+    pub fn next_word(&self) -> &str {
+        let start = self.text.len() - self.text.trim_start().len();
+        let rest = &self.text[start..];
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let word = match rest.chars().next() {
+            Some(c) if is_word(c) => rest.find(|c: char| !is_word(c)).unwrap_or(rest.len()),
+            Some(c) => c.len_utf8(),
+            None => 0,
+        };
+        &self.text[..start + word]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::backend::code_agent::providers::fim::FimFuture;
+
+    /// Answers every prompt after a delay, recording the prompts it was asked.
+    #[derive(Debug, Default)]
+    struct SlowModel {
+        prompts: Mutex<Vec<String>>,
+    }
+
+    impl FimProvider for SlowModel {
+        fn complete<'a>(&'a self, request: &'a FimRequest) -> FimFuture<'a> {
+            self.prompts.lock().unwrap().push(request.prompt.clone());
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok("a + b\n}<|endoftext|>ignored".to_string())
+            })
+        }
+    }
+
+    fn wait_for(engine: &mut CompletionEngine) -> Completion {
+        let started = Instant::now();
+        loop {
+            if let Some(completion) = engine.poll(Instant::now()) {
+                return completion;
+            }
+            assert!(started.elapsed() < Duration::from_secs(5), "no completion");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_engine_debounces_cancels_and_caches() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let model = Arc::new(SlowModel::default());
+        let completer = FimCompleter::new(model.clone(), "qwen2.5-coder:1.5b", FimTemplate::for_model("qwen2.5-coder:1.5b").unwrap());
+        let mut engine = CompletionEngine::new(completer, runtime.handle().clone());
+        engine.debounce = Duration::from_millis(20);
+
+        let text = "fn add(a: i32, b: i32) -> i32 {\n    \n}\n";
+        let caret = text.find("    \n").unwrap() + 4;
+        let now = Instant::now();
+        engine.request(FimContext::new(&text[..caret - 1], caret - 1, None), now);
+        engine.request(FimContext::new(text, caret, None), now + Duration::from_millis(10));
+        assert!(engine.poll(now + Duration::from_millis(25)).is_none());
+        assert!(model.prompts.lock().unwrap().is_empty());
+
+        // Started, then abandoned because the user kept typing.
+        assert!(engine.poll(now + Duration::from_millis(30)).is_none() && engine.is_busy());
+        while model.prompts.lock().unwrap().is_empty() {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let typed = format!("{}a{}", &text[..caret], &text[caret..]);
+        engine.request(FimContext::new(&typed, caret + 1, None), Instant::now());
+        let completion = wait_for(&mut engine);
+        assert_eq!(completion.key, FimContext::new(&typed, caret + 1, None).key());
+        assert_eq!((completion.offset, completion.text.as_str()), (caret + 1, "a + b"));
+        assert!(!engine.is_busy());
+
+        let prompts = model.prompts.lock().unwrap().clone();
+        assert_eq!(prompts.len(), 2);
+        assert_eq!(prompts[1], format!("<|fim_prefix|>{}<|fim_suffix|>{}<|fim_middle|>", &typed[..caret + 1], &typed[caret + 1..]));
+
+        // Served from the cache without asking the model again.
+        engine.request(FimContext::new(&typed, caret + 1, None), Instant::now());
+        assert_eq!(engine.poll(Instant::now()), Some(completion));
+        assert_eq!(model.prompts.lock().unwrap().len(), 2);
+
+        let mut ghost = GhostText { offset: 10, text: "  total_len(x)".to_string() };
+        assert_eq!(ghost.next_word(), "  total_len");
+        assert!(ghost.type_through("  total_len"));
+        assert_eq!((ghost.offset, ghost.next_word()), (21, "("));
+        assert!(!ghost.type_through("[") && ghost.type_through("(x") && !ghost.type_through(")"));
+    }
+}
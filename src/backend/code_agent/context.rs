@@ -5,6 +5,10 @@
 
 use std::collections::{HashMap, VecDeque};
 use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use super::context_builder::{PinnedFile, Selection};
+use super::symbol_index::{self, SymbolIndex, SymbolMatch};
+use super::tools::base::parse::{ItemKind, ItemVisibility, ParseTool};
 
 #[derive(Debug, Clone)]
 pub struct FileContext {
@@ -15,16 +19,20 @@ pub struct FileContext {
     pub symbols: Vec<Symbol>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Symbol {
     pub name: String,
     pub kind: SymbolKind,
     pub line: usize,
     pub column: usize,
+    /// Last line of the symbol's definition.
+    pub end_line: usize,
     pub scope: String,
+    pub visibility: ItemVisibility,
+    pub docs: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum SymbolKind {
     Function,
     Class,
@@ -34,6 +42,7 @@ pub enum SymbolKind {
     Interface,
     Enum,
     Module,
+    Macro,
 }
 
 #[derive(Debug)]
@@ -42,8 +51,14 @@ pub struct ContextManager {
     current_project: Option<String>,
     open_files: HashMap<String, FileContext>,
     recent_files: VecDeque<String>,
-    project_symbols: HashMap<String, Vec<Symbol>>,
+    /// Workspace-wide index for the current project.
+    symbol_index: Option<SymbolIndex>,
     max_recent_files: usize,
+    /// Files included in every prompt.
+    pinned_files: Vec<String>,
+    selection: Option<Selection>,
+    /// Compiler and language server messages for the current file, one per line.
+    diagnostics: Vec<String>,
 }
 
 impl Default for ContextManager {
@@ -59,8 +74,11 @@ impl ContextManager {
             current_project: None,
             open_files: HashMap::new(),
             recent_files: VecDeque::new(),
-            project_symbols: HashMap::new(),
+            symbol_index: None,
             max_recent_files: 20,
+            pinned_files: Vec::new(),
+            selection: None,
+            diagnostics: Vec::new(),
         }
     }
     
@@ -80,10 +98,19 @@ impl ContextManager {
         self.current_file.as_ref()
     }
     
+    /// Set the current project and start indexing its symbols in the background.
     pub fn set_current_project(&mut self, project_path: String) {
+        let root = PathBuf::from(&project_path);
+        if self.symbol_index.as_ref().is_none_or(|index| index.root() != root) {
+            self.symbol_index = Some(SymbolIndex::open(root));
+        }
         self.current_project = Some(project_path);
     }
     
+    pub fn get_symbol_index(&self) -> Option<&SymbolIndex> {
+        self.symbol_index.as_ref()
+    }
+    
     pub fn get_current_project(&self) -> Option<&String> {
         self.current_project.as_ref()
     }
@@ -94,7 +121,7 @@ impl ContextManager {
             content: content.clone(),
             language: language.clone(),
             last_modified: chrono::Utc::now(),
-            symbols: self.extract_symbols(&content, &language),
+            symbols: Self::extract_symbols(&content, &language).unwrap_or_default(),
         };
         
         self.open_files.insert(path.clone(), context);
@@ -102,10 +129,17 @@ impl ContextManager {
     }
     
     pub fn update_file_content(&mut self, path: &str, content: String) {
+        let Some(language) = self.open_files.get(path).map(|context| context.language.clone()) else {
+            return;
+        };
+        let symbols = Self::extract_symbols(&content, &language);
         if let Some(context) = self.open_files.get_mut(path) {
-            context.content = content.clone();
+            context.content = content;
             context.last_modified = chrono::Utc::now();
-            context.symbols = self.extract_symbols(&content, &context.language);
+            // Keep the previous symbols while nothing in the file parses, e.g. mid-edit.
+            if let Some(symbols) = symbols {
+                context.symbols = symbols;
+            }
         }
     }
     
@@ -128,46 +162,42 @@ impl ContextManager {
         self.recent_files.iter().cloned().collect()
     }
     
-    fn extract_symbols(&self, content: &str, language: &str) -> Vec<Symbol> {
-        // Basic symbol extraction - would be replaced with proper parsing
+    /// Extract symbols from a file. A Rust file with syntax errors yields the items that
+    /// still parse, or `None` if none do.
+    pub fn extract_symbols(content: &str, language: &str) -> Option<Vec<Symbol>> {
         let mut symbols = Vec::new();
         
         match language {
             "rust" => {
-                for (line_num, line) in content.lines().enumerate() {
-                    let trimmed = line.trim();
-                    
-                    if trimmed.starts_with("fn ") {
-                        if let Some(name) = self.extract_rust_function_name(trimmed) {
-                            symbols.push(Symbol {
-                                name,
-                                kind: SymbolKind::Function,
-                                line: line_num + 1,
-                                column: line.find("fn").unwrap_or(0) + 1,
-                                scope: "global".to_string(),
-                            });
-                        }
-                    } else if trimmed.starts_with("struct ") {
-                        if let Some(name) = self.extract_rust_type_name(trimmed, "struct") {
-                            symbols.push(Symbol {
-                                name,
-                                kind: SymbolKind::Class,
-                                line: line_num + 1,
-                                column: line.find("struct").unwrap_or(0) + 1,
-                                scope: "global".to_string(),
-                            });
-                        }
-                    } else if trimmed.starts_with("impl ") {
-                        if let Some(name) = self.extract_rust_impl_name(trimmed) {
-                            symbols.push(Symbol {
-                                name: format!("impl {}", name),
-                                kind: SymbolKind::Class,
-                                line: line_num + 1,
-                                column: line.find("impl").unwrap_or(0) + 1,
-                                scope: "global".to_string(),
-                            });
-                        }
-                    }
+                let (items, error) = ParseTool::parse_source_partial(content);
+                if items.is_empty() && error.is_some() {
+                    return None;
+                }
+                for item in items {
+                    let kind = match item.kind {
+                        ItemKind::Function => SymbolKind::Function,
+                        ItemKind::Method => SymbolKind::Method,
+                        ItemKind::Struct | ItemKind::Union | ItemKind::TypeAlias | ItemKind::Impl => SymbolKind::Class,
+                        ItemKind::Enum => SymbolKind::Enum,
+                        ItemKind::Trait => SymbolKind::Interface,
+                        ItemKind::Const | ItemKind::Static => SymbolKind::Constant,
+                        ItemKind::Module => SymbolKind::Module,
+                        ItemKind::Macro => SymbolKind::Macro,
+                    };
+                    let name = match item.kind {
+                        ItemKind::Impl => format!("impl {}", item.name),
+                        _ => item.name,
+                    };
+                    symbols.push(Symbol {
+                        name,
+                        kind,
+                        line: item.name_line,
+                        column: item.name_column + 1,
+                        end_line: item.span.end_line,
+                        scope: item.parent.unwrap_or_else(|| "global".to_string()),
+                        visibility: item.visibility,
+                        docs: item.docs,
+                    });
                 }
             }
             "python" => {
@@ -175,23 +205,29 @@ impl ContextManager {
                     let trimmed = line.trim();
                     
                     if trimmed.starts_with("def ") {
-                        if let Some(name) = self.extract_python_function_name(trimmed) {
+                        if let Some(name) = Self::extract_python_function_name(trimmed) {
                             symbols.push(Symbol {
                                 name,
                                 kind: SymbolKind::Function,
                                 line: line_num + 1,
                                 column: line.find("def").unwrap_or(0) + 1,
+                                end_line: line_num + 1,
                                 scope: "global".to_string(),
+                                visibility: ItemVisibility::Public,
+                                docs: None,
                             });
                         }
                     } else if trimmed.starts_with("class ") {
-                        if let Some(name) = self.extract_python_class_name(trimmed) {
+                        if let Some(name) = Self::extract_python_class_name(trimmed) {
                             symbols.push(Symbol {
                                 name,
                                 kind: SymbolKind::Class,
                                 line: line_num + 1,
                                 column: line.find("class").unwrap_or(0) + 1,
+                                end_line: line_num + 1,
                                 scope: "global".to_string(),
+                                visibility: ItemVisibility::Public,
+                                docs: None,
                             });
                         }
                     }
@@ -200,60 +236,16 @@ impl ContextManager {
             _ => {}
         }
         
-        symbols
-    }
-    
-    fn extract_rust_function_name(&self, line: &str) -> Option<String> {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() >= 2 && parts[0] == "fn" {
-            let name_part = parts[1];
-            if let Some(paren_pos) = name_part.find('(') {
-                Some(name_part[..paren_pos].to_string())
-            } else if let Some(angle_pos) = name_part.find('<') {
-                Some(name_part[..angle_pos].to_string())
-            } else {
-                Some(name_part.to_string())
-            }
-        } else {
-            None
-        }
-    }
-    
-    fn extract_rust_type_name(&self, line: &str, keyword: &str) -> Option<String> {
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() >= 2 && parts[0] == keyword {
-            let name_part = parts[1];
-            if let Some(angle_pos) = name_part.find('<') {
-                Some(name_part[..angle_pos].to_string())
-            } else if let Some(brace_pos) = name_part.find('{') {
-                Some(name_part[..brace_pos].to_string())
-            } else {
-                Some(name_part.to_string())
-            }
-        } else {
-            None
-        }
-    }
-    
-    fn extract_rust_impl_name(&self, line: &str) -> Option<String> {
-        let after_impl = line.strip_prefix("impl")?.trim();
-        let name = if after_impl.contains(" for ") {
-            after_impl.split(" for ").nth(1)?
-        } else {
-            after_impl
-        };
-        
-        let name = name.split_whitespace().next()?;
-        Some(name.split('{').next()?.trim().to_string())
+        Some(symbols)
     }
     
-    fn extract_python_function_name(&self, line: &str) -> Option<String> {
+    fn extract_python_function_name(line: &str) -> Option<String> {
         let after_def = line.strip_prefix("def")?.trim();
         let name = after_def.split('(').next()?;
         Some(name.trim().to_string())
     }
     
-    fn extract_python_class_name(&self, line: &str) -> Option<String> {
+    fn extract_python_class_name(line: &str) -> Option<String> {
         let after_class = line.strip_prefix("class")?.trim();
         let name = after_class.split(&['(', ':'][..]).next()?;
         Some(name.trim().to_string())
@@ -267,18 +259,118 @@ impl ContextManager {
         }
     }
     
-    pub fn search_symbols(&self, query: &str) -> Vec<(&String, &Symbol)> {
-        let mut results = Vec::new();
-        let query_lower = query.to_lowercase();
+    pub fn pin_file(&mut self, path: String) {
+        if !self.pinned_files.contains(&path) {
+            self.pinned_files.push(path);
+        }
+    }
+    
+    pub fn unpin_file(&mut self, path: &str) {
+        self.pinned_files.retain(|pinned| pinned != path);
+    }
+    
+    pub fn get_pinned_files(&self) -> &[String] {
+        &self.pinned_files
+    }
+    
+    /// Pinned files with their content: the editor's copy if open, otherwise the file on disk.
+    ///
+    /// Relative paths are read from the current project. Unreadable files are skipped.
+    pub fn pinned_file_contents(&self) -> Vec<PinnedFile> {
+        self.pinned_files
+            .iter()
+            .filter_map(|path| {
+                let content = match self.open_files.get(path) {
+                    Some(context) => context.content.clone(),
+                    None => {
+                        let project = self.current_project.as_deref().unwrap_or(".");
+                        std::fs::read_to_string(PathBuf::from(project).join(path)).ok()?
+                    }
+                };
+                Some(PinnedFile { path: path.clone(), content })
+            })
+            .collect()
+    }
+    
+    /// Record the editor selection, or `None` when nothing is selected.
+    pub fn set_selection(&mut self, selection: Option<Selection>) {
+        self.selection = selection;
+    }
+    
+    pub fn get_selection(&self) -> Option<&Selection> {
+        self.selection.as_ref()
+    }
+    
+    /// Record the diagnostics shown for the current file, e.g. `src/main.rs:12: error: ...`.
+    pub fn set_diagnostics(&mut self, diagnostics: Vec<String>) {
+        self.diagnostics = diagnostics;
+    }
+    
+    pub fn get_diagnostics(&self) -> &[String] {
+        &self.diagnostics
+    }
+    
+    /// Symbols that `text` seems to be about: names containing one of its identifiers,
+    /// then the current file's symbols.
+    pub fn relevant_symbols(&self, text: &str, limit: usize) -> Vec<SymbolMatch> {
+        let mut words: Vec<String> = text
+            .split(|c: char| !c.is_alphanumeric() && c != '_')
+            .filter(|word| word.chars().count() >= 4)
+            .map(str::to_lowercase)
+            .collect();
+        words.sort();
+        words.dedup();
+        
+        let mut results: Vec<SymbolMatch> = Vec::new();
+        for word in &words {
+            for found in self.search_symbols(word, limit) {
+                let duplicate = results.iter().any(|m| m.path == found.path && m.symbol.line == found.symbol.line);
+                if found.symbol.name.to_lowercase().contains(word.as_str()) && !duplicate {
+                    results.push(found);
+                }
+            }
+        }
+        results.sort_by_key(|m| std::cmp::Reverse(m.score));
         
-        for (path, context) in &self.open_files {
-            for symbol in &context.symbols {
-                if symbol.name.to_lowercase().contains(&query_lower) {
-                    results.push((path, symbol));
+        if let Some(current) = self.current_file.as_ref().and_then(|path| self.open_files.get(path)) {
+            for symbol in &current.symbols {
+                if !results.iter().any(|m| m.path == current.path && m.symbol.line == symbol.line) {
+                    results.push(SymbolMatch { path: current.path.clone(), symbol: symbol.clone(), score: 0 });
                 }
             }
         }
+        results.truncate(limit);
+        results
+    }
+    
+    /// Fuzzy-search symbols across the project index and open files, best matches first.
+    ///
+    /// Open files are searched from their in-editor content, which may be newer than the index.
+    pub fn search_symbols(&self, query: &str, limit: usize) -> Vec<SymbolMatch> {
+        let query_chars: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_lowercase).collect();
+        let mut results: Vec<SymbolMatch> = self
+            .open_files
+            .values()
+            .flat_map(|context| context.symbols.iter().map(move |symbol| (context, symbol)))
+            .filter_map(|(context, symbol)| {
+                symbol_index::fuzzy_score(&query_chars, &symbol.name).map(|score| SymbolMatch {
+                    path: context.path.clone(),
+                    symbol: symbol.clone(),
+                    score,
+                })
+            })
+            .collect();
+        
+        if let Some(index) = &self.symbol_index {
+            // Over-fetch so matches from open files can be dropped without coming up short.
+            let indexed = index
+                .search(query, limit + self.open_files.len() * 8)
+                .into_iter()
+                .filter(|m| !self.open_files.values().any(|context| context.path == m.path));
+            results.extend(indexed);
+        }
         
+        symbol_index::rank_matches(&mut results, limit, |m| (m.score, &m.symbol.name));
         results
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,452 @@
+//! Packs the prompt context for an agent request into a token budget.
+//!
+//! Candidates are ranked: the system prompt, instruction files and the latest
+//! user message always go in, then the editor selection, pinned files, recent conversation turns
+//! (newest first), notes from the project's memory, code retrieved from the
+//! semantic index, relevant symbols and recent edits. Large sections are cut to
+//! whatever room is left; conversation turns that don't fit are replaced by
+//! one-line summaries. The [`ContextReport`] lists what went in and what didn't.
+
+use std::path::{Path, PathBuf};
+use super::chat::{ChatMessage, MessageMetadata, MessageRole};
+use super::files_changed::FileChange;
+use super::memory::agent_memory_logic::MemoryEntry;
+use super::semantic_index::SemanticMatch;
+use super::symbol_index::SymbolMatch;
+use super::tokenizer::Tokenizer;
+
+/// Smallest remainder worth filling with a truncated section.
+const MIN_TRUNCATED_TOKENS: usize = 48;
+/// Longest line of an older message kept in its summary, in characters.
+const SUMMARY_LINE_LEN: usize = 80;
+
+/// Rough token count: about four characters per token for code and English.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Lines selected in the editor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection {
+    pub path: String,
+    /// 1-based, inclusive.
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+}
+
+/// A file the user pinned to every prompt.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PinnedFile {
+    pub path: String,
+    pub content: String,
+}
+
+/// Everything that could go into a prompt.
+#[derive(Debug, Default)]
+pub struct ContextSources<'a> {
+    pub system_prompt: String,
+    /// Merged instruction files, added to the system prompt.
+    pub instructions: String,
+    /// How many files `instructions` came from, for the report.
+    pub instruction_files: usize,
+    /// The conversation so far, oldest first, ending with the message being answered.
+    pub history: Vec<ChatMessage>,
+    pub selection: Option<Selection>,
+    pub pinned_files: Vec<PinnedFile>,
+    /// Memory entries related to the latest message, best first.
+    pub memories: Vec<MemoryEntry>,
+    /// Code related to the latest message, best matches first.
+    pub retrieved: Vec<SemanticMatch>,
+    /// Best matches first.
+    pub symbols: Vec<SymbolMatch>,
+    /// Newest first.
+    pub recent_edits: Vec<&'a FileChange>,
+    /// Paths in symbols and edits are shown relative to this.
+    pub workspace: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextKind {
+    SystemPrompt,
+    Instructions,
+    LatestMessage,
+    Selection,
+    PinnedFile,
+    History,
+    Memory,
+    Retrieved,
+    Symbols,
+    RecentEdit,
+    HistorySummary,
+}
+
+/// One candidate and what happened to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextEntry {
+    pub kind: ContextKind,
+    /// File path, message role and time, or similar.
+    pub label: String,
+    /// Tokens used if included, or needed if dropped.
+    pub tokens: usize,
+    /// Included, but cut short to fit.
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContextReport {
+    pub budget: usize,
+    pub used: usize,
+    pub included: Vec<ContextEntry>,
+    pub dropped: Vec<ContextEntry>,
+}
+
+/// The packed context, ready for [`super::providers::CompletionRequest::from_chat`].
+#[derive(Debug, Clone)]
+pub struct BuiltContext {
+    /// The system prompt, a system message with the gathered context, then the kept conversation.
+    pub messages: Vec<ChatMessage>,
+    pub report: ContextReport,
+}
+
+/// A candidate section of the context block.
+struct Section {
+    kind: ContextKind,
+    label: String,
+    heading: String,
+    body: String,
+    /// Whether the body may be cut to fit.
+    truncatable: bool,
+}
+
+/// Pack `sources` into at most `budget` tokens, or just the required items if those alone exceed it.
+pub fn build_context(sources: &ContextSources, budget: usize, tokenizer: &dyn Tokenizer) -> BuiltContext {
+    let mut report = ContextReport { budget, ..Default::default() };
+    let mut messages = Vec::new();
+
+    // Always included.
+    let mut system = Vec::new();
+    if !sources.system_prompt.trim().is_empty() {
+        let tokens = tokenizer.count(&sources.system_prompt);
+        report.used += tokens;
+        report.included.push(entry(ContextKind::SystemPrompt, "system prompt", tokens, false));
+        system.push(sources.system_prompt.trim_end());
+    }
+    if !sources.instructions.trim().is_empty() {
+        let tokens = tokenizer.count(&sources.instructions);
+        report.used += tokens;
+        report.included.push(entry(ContextKind::Instructions, &format!("{} instruction files", sources.instruction_files), tokens, false));
+        system.push(sources.instructions.trim_end());
+    }
+    if !system.is_empty() {
+        messages.push(system_message(&system.join("\n\n")));
+    }
+    let (earlier, latest) = match sources.history.iter().rposition(|message| message.role == MessageRole::User) {
+        Some(index) => (&sources.history[..index], &sources.history[index..]),
+        None => (&sources.history[..], &[][..]),
+    };
+    for message in latest {
+        let tokens = tokenizer.count(&message.content);
+        report.used += tokens;
+        report.included.push(entry(ContextKind::LatestMessage, &message_label(message), tokens, false));
+    }
+
+    let mut block = String::new();
+    for section in editor_sections(sources) {
+        pack_section(section, budget, tokenizer, &mut report, &mut block);
+    }
+
+    // Conversation turns, newest first, without gaps. If they don't all fit, a tenth of
+    // the budget is kept back for symbols, edits and summaries of the older turns.
+    let history_tokens: usize = earlier.iter().map(|message| tokenizer.count(&message.content)).sum();
+    let history_budget = if report.used + history_tokens <= budget { budget } else { budget - budget / 10 };
+    let mut kept = earlier.len();
+    for (index, message) in earlier.iter().enumerate().rev() {
+        let tokens = tokenizer.count(&message.content);
+        if report.used + tokens > history_budget {
+            break;
+        }
+        report.used += tokens;
+        report.included.push(entry(ContextKind::History, &message_label(message), tokens, false));
+        kept = index;
+    }
+    let (older, recent) = earlier.split_at(kept);
+    for message in older {
+        report.dropped.push(entry(ContextKind::History, &message_label(message), tokenizer.count(&message.content), false));
+    }
+
+    for section in workspace_sections(sources) {
+        pack_section(section, budget, tokenizer, &mut report, &mut block);
+    }
+
+    // What's left goes to summaries of the dropped turns, most recent kept if they don't all fit.
+    let summary: Vec<String> = older.iter().filter(|message| message.role != MessageRole::System).map(summary_line).collect();
+    if !summary.is_empty() {
+        let label = format!("{} earlier messages", summary.len());
+        let heading = "Earlier conversation (summarised)";
+        let mut lines = summary.as_slice();
+        let room = budget.saturating_sub(report.used);
+        while !lines.is_empty() && tokenizer.count(&format!("## {}\n{}\n", heading, lines.join("\n"))) > room {
+            lines = &lines[1..];
+        }
+        if lines.is_empty() {
+            report.dropped.push(entry(ContextKind::HistorySummary, &label, tokenizer.count(&summary.join("\n")), false));
+        } else {
+            let text = format!("## {}\n{}\n", heading, lines.join("\n"));
+            let tokens = tokenizer.count(&text);
+            block.push_str(&text);
+            report.used += tokens;
+            report.included.push(entry(ContextKind::HistorySummary, &label, tokens, lines.len() < summary.len()));
+        }
+    }
+
+    if !block.is_empty() {
+        messages.push(system_message(block.trim_end()));
+    }
+    messages.extend(recent.iter().filter(|message| message.role != MessageRole::System).cloned());
+    messages.extend(latest.iter().cloned());
+    BuiltContext { messages, report }
+}
+
+/// Add a section whole, or cut to the room left, or record it as dropped.
+fn pack_section(section: Section, budget: usize, tokenizer: &dyn Tokenizer, report: &mut ContextReport, block: &mut String) {
+    let full = format!("## {}\n{}\n", section.heading, section.body);
+    let tokens = tokenizer.count(&full);
+    let room = budget.saturating_sub(report.used);
+    if tokens <= room {
+        block.push_str(&full);
+        report.used += tokens;
+        report.included.push(entry(section.kind, &section.label, tokens, false));
+    } else if section.truncatable && room >= MIN_TRUNCATED_TOKENS {
+        let cut = truncate_to_tokens(&section.body, room.saturating_sub(tokenizer.count(&section.heading) + 8), tokenizer);
+        let text = format!("## {}\n{}\n", section.heading, cut);
+        let used = tokenizer.count(&text);
+        block.push_str(&text);
+        report.used += used;
+        report.included.push(entry(section.kind, &section.label, used, true));
+    } else {
+        report.dropped.push(entry(section.kind, &section.label, tokens, false));
+    }
+}
+
+/// The selection and pinned files, which outrank the conversation.
+fn editor_sections(sources: &ContextSources) -> Vec<Section> {
+    let mut sections = Vec::new();
+    if let Some(selection) = &sources.selection {
+        let label = format!("{}:{}-{}", selection.path, selection.start_line, selection.end_line);
+        sections.push(Section {
+            kind: ContextKind::Selection,
+            heading: format!("Selected in the editor ({})", label),
+            label,
+            body: fenced(&selection.path, &selection.text),
+            truncatable: true,
+        });
+    }
+    for file in &sources.pinned_files {
+        sections.push(Section {
+            kind: ContextKind::PinnedFile,
+            label: file.path.clone(),
+            heading: format!("Pinned file {}", file.path),
+            body: fenced(&file.path, &file.content),
+            truncatable: true,
+        });
+    }
+    sections
+}
+
+/// Memory, retrieved code, symbols and recent edits, packed after the conversation.
+fn workspace_sections(sources: &ContextSources) -> Vec<Section> {
+    let mut sections = Vec::new();
+    if !sources.memories.is_empty() {
+        let body = sources
+            .memories
+            .iter()
+            .map(|entry| {
+                let tags: String = entry.tags.iter().map(|tag| format!(" #{}", tag)).collect();
+                format!("- ({}) {}{}", entry.kind.name(), entry.content, tags)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        sections.push(Section {
+            kind: ContextKind::Memory,
+            label: format!("{} memory entries", sources.memories.len()),
+            heading: "Project memory".to_string(),
+            body,
+            truncatable: true,
+        });
+    }
+    for chunk in &sources.retrieved {
+        let path = relative(&sources.workspace, &chunk.path);
+        let label = format!("{}:{}-{}", path, chunk.start_line, chunk.end_line);
+        sections.push(Section {
+            kind: ContextKind::Retrieved,
+            heading: format!("Related code in {}", label),
+            label,
+            body: fenced(&path, &chunk.text),
+            truncatable: true,
+        });
+    }
+    if !sources.symbols.is_empty() {
+        let body = sources
+            .symbols
+            .iter()
+            .map(|m| {
+                let path = relative(&sources.workspace, &m.path);
+                let docs = m.symbol.docs.as_deref().and_then(|docs| docs.lines().next()).map(|line| format!(" — {}", line.trim())).unwrap_or_default();
+                format!("- {:?} `{}` ({}:{}){}", m.symbol.kind, m.symbol.name, path, m.symbol.line, docs)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        sections.push(Section {
+            kind: ContextKind::Symbols,
+            label: format!("{} symbols", sources.symbols.len()),
+            heading: "Relevant symbols".to_string(),
+            body,
+            truncatable: true,
+        });
+    }
+    for change in &sources.recent_edits {
+        let diff = change.to_unified_diff(&sources.workspace);
+        let path = relative(&sources.workspace, &change.path);
+        sections.push(Section {
+            kind: ContextKind::RecentEdit,
+            label: format!("{} by {}", path, change.author),
+            heading: format!("Recent edit to {} by {}", path, change.author),
+            body: format!("```diff\n{}```", diff),
+            truncatable: true,
+        });
+    }
+    sections
+}
+
+fn entry(kind: ContextKind, label: &str, tokens: usize, truncated: bool) -> ContextEntry {
+    ContextEntry { kind, label: label.to_string(), tokens, truncated }
+}
+
+fn system_message(content: &str) -> ChatMessage {
+    ChatMessage {
+        id: String::new(),
+        role: MessageRole::System,
+        content: content.to_string(),
+        timestamp: chrono::Utc::now(),
+        metadata: MessageMetadata::default(),
+    }
+}
+
+fn message_label(message: &ChatMessage) -> String {
+    format!("{:?} at {}", message.role, message.timestamp.format("%H:%M:%S"))
+}
+
+fn summary_line(message: &ChatMessage) -> String {
+    let line = message.content.lines().map(str::trim).find(|line| !line.is_empty()).unwrap_or_default();
+    let line = match line.char_indices().nth(SUMMARY_LINE_LEN) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.to_string(),
+    };
+    let role = if message.role == MessageRole::User { "User" } else { "Assistant" };
+    format!("- {}: {}", role, line)
+}
+
+fn fenced(path: &str, text: &str) -> String {
+    let language = Path::new(path).extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+    format!("```{}\n{}\n```", language, text.trim_end_matches('\n'))
+}
+
+fn relative(workspace: &Path, path: &Path) -> String {
+    path.strip_prefix(workspace).unwrap_or(path).to_string_lossy().replace('\\', "/")
+}
+
+/// Cut text to about `tokens` tokens at a line boundary, closing an open code fence.
+fn truncate_to_tokens(text: &str, tokens: usize, tokenizer: &dyn Tokenizer) -> String {
+    let mut out = String::new();
+    let mut used = 0;
+    for line in text.split_inclusive('\n') {
+        used += tokenizer.count(line);
+        if used > tokens {
+            break;
+        }
+        out.push_str(line);
+    }
+    if out.matches("```").count() % 2 == 1 {
+        out.push_str("...\n```");
+    } else {
+        out.push_str("...");
+    }
+    out.push_str("\n(truncated)");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::code_agent::memory::agent_memory_logic::{MemoryKind, MemorySource};
+    use crate::backend::code_agent::tokenizer::HeuristicTokenizer;
+
+    fn message(role: MessageRole, content: &str) -> ChatMessage {
+        ChatMessage { role, ..system_message(content) }
+    }
+
+    #[test]
+    fn test_packs_by_rank_and_reports_what_was_dropped() {
+        let mut history: Vec<ChatMessage> = (0..20)
+            .map(|i| message(if i % 2 == 0 { MessageRole::User } else { MessageRole::Assistant }, &format!("turn {} {}", i, "words ".repeat(40))))
+            .collect();
+        history.push(message(MessageRole::User, "Why does parse_config fail?"));
+        let sources = ContextSources {
+            system_prompt: "You are a coding assistant.".to_string(),
+            history,
+            selection: Some(Selection { path: "src/config.rs".to_string(), start_line: 3, end_line: 4, text: "fn parse_config() {}\n".to_string() }),
+            pinned_files: vec![PinnedFile { path: "big.rs".to_string(), content: "// filler line\n".repeat(400) }],
+            ..Default::default()
+        };
+
+        let built = build_context(&sources, 800, &HeuristicTokenizer);
+        let report = &built.report;
+        assert!(report.used <= report.budget, "{:?}", report);
+        let kinds: Vec<ContextKind> = report.included.iter().map(|entry| entry.kind).collect();
+        assert_eq!(&kinds[..4], &[ContextKind::SystemPrompt, ContextKind::LatestMessage, ContextKind::Selection, ContextKind::PinnedFile]);
+        // The big file was cut to fit, leaving no room for history, which is summarised instead.
+        assert!(report.included[3].truncated);
+        assert_eq!(report.dropped.iter().filter(|entry| entry.kind == ContextKind::History).count(), 20);
+
+        // With more room, recent turns go in whole and only older ones are summarised.
+        let retrieved = vec![SemanticMatch {
+            path: PathBuf::from("/work/src/config.rs"),
+            start_line: 10,
+            end_line: 12,
+            symbol: Some("load".to_string()),
+            score: 0.8,
+            text: "fn load() {\n    parse_config();\n}".to_string(),
+        }];
+        let now = chrono::Utc::now();
+        let memories = vec![MemoryEntry {
+            id: 1,
+            kind: MemoryKind::Convention,
+            content: "Config errors are returned as strings.".to_string(),
+            tags: vec!["config".to_string()],
+            source: MemorySource::User,
+            created: now,
+            updated: now,
+        }];
+        let sources = ContextSources {
+            instructions: "# Instructions\nUse four spaces.".to_string(),
+            instruction_files: 1,
+            pinned_files: Vec::new(),
+            memories,
+            retrieved,
+            workspace: PathBuf::from("/work"),
+            ..sources
+        };
+        let built = build_context(&sources, 800, &HeuristicTokenizer);
+        let kept = built.report.included.iter().filter(|entry| entry.kind == ContextKind::History).count();
+        assert!(kept > 0 && kept < 20);
+        assert!(built.report.included.iter().any(|entry| entry.kind == ContextKind::HistorySummary));
+        assert_eq!(built.messages.last().unwrap().content, "Why does parse_config fail?");
+        assert!(built.messages[1].content.contains("## Earlier conversation (summarised)\n- "));
+        assert_eq!(built.messages[0].content, "You are a coding assistant.\n\n# Instructions\nUse four spaces.");
+        assert_eq!(built.report.included[1].kind, ContextKind::Instructions);
+        assert!(built.messages[1].content.contains("## Project memory\n- (convention) Config errors are returned as strings. #config\n"));
+        assert!(built.messages[1].content.contains("## Related code in src/config.rs:10-12\n```rs\nfn load() {"));
+        assert_eq!(built.messages.len(), 2 + kept + 1);
+    }
+}
@@ -0,0 +1,525 @@
+//! Line diffs, unified-diff hunks and patch parsing/applying.
+
+/// Lines of context kept around each change in a hunk.
+pub const DEFAULT_CONTEXT: usize = 3;
+
+/// Above this many edits the diff gives up on finding a minimal script and
+/// replaces the differing middle wholesale, bounding time and memory.
+const MAX_EDIT_DISTANCE: usize = 4000;
+
+const NO_NEWLINE_MARKER: &str = "\\ No newline at end of file";
+
+/// One step of an edit script, with 0-based line indices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffOp {
+    Equal { old: usize, new: usize },
+    Delete { old: usize },
+    Insert { new: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HunkLineKind {
+    Context,
+    Removed,
+    Added,
+}
+
+/// A line in a hunk. `text` keeps its trailing newline, if it had one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HunkLine {
+    pub kind: HunkLineKind,
+    pub text: String,
+}
+
+/// A group of nearby changes with surrounding context. Starts are 1-based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_count: usize,
+    pub new_start: usize,
+    pub new_count: usize,
+    pub lines: Vec<HunkLine>,
+}
+
+impl Hunk {
+    /// The `@@ -a,b +c,d @@` header line, without a newline.
+    pub fn header(&self) -> String {
+        format!("@@ -{} +{} @@", format_range(self.old_start, self.old_count), format_range(self.new_start, self.new_count))
+    }
+
+    /// The hunk in unified-diff form: header, then prefixed lines.
+    pub fn to_unified(&self) -> String {
+        let mut out = self.header();
+        out.push('\n');
+        for line in &self.lines {
+            out.push(match line.kind {
+                HunkLineKind::Context => ' ',
+                HunkLineKind::Removed => '-',
+                HunkLineKind::Added => '+',
+            });
+            out.push_str(&line.text);
+            if !line.text.ends_with('\n') {
+                out.push('\n');
+                out.push_str(NO_NEWLINE_MARKER);
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    pub fn added(&self) -> usize {
+        self.lines.iter().filter(|line| line.kind == HunkLineKind::Added).count()
+    }
+
+    pub fn removed(&self) -> usize {
+        self.lines.iter().filter(|line| line.kind == HunkLineKind::Removed).count()
+    }
+}
+
+/// Empty ranges point at the line before them, as in `diff -u`.
+fn format_range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start.saturating_sub(1)),
+        1 => start.to_string(),
+        _ => format!("{},{}", start, count),
+    }
+}
+
+/// Split text into lines, keeping each line's newline so a missing final newline counts as a change.
+pub fn split_lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// Compute a shortest edit script from `old` to `new` (Myers' algorithm).
+pub fn diff_lines(old: &[&str], new: &[&str]) -> Vec<DiffOp> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut ops: Vec<DiffOp> = (0..prefix).map(|i| DiffOp::Equal { old: i, new: i }).collect();
+    let middle = myers(old_middle, new_middle).unwrap_or_else(|| {
+        let deletes = (0..old_middle.len()).map(|old| DiffOp::Delete { old });
+        deletes.chain((0..new_middle.len()).map(|new| DiffOp::Insert { new })).collect()
+    });
+    ops.extend(middle.into_iter().map(|op| match op {
+        DiffOp::Equal { old, new } => DiffOp::Equal { old: old + prefix, new: new + prefix },
+        DiffOp::Delete { old } => DiffOp::Delete { old: old + prefix },
+        DiffOp::Insert { new } => DiffOp::Insert { new: new + prefix },
+    }));
+    let (old_end, new_end) = (old.len() - suffix, new.len() - suffix);
+    ops.extend((0..suffix).map(|i| DiffOp::Equal { old: old_end + i, new: new_end + i }));
+    ops
+}
+
+/// Myers' O(ND) diff. Returns `None` past `MAX_EDIT_DISTANCE`.
+fn myers(old: &[&str], new: &[&str]) -> Option<Vec<DiffOp>> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    // Furthest-reaching x per diagonal after each round, kept only for diagonals -d..=d.
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'search: for d in 0..=max.min(MAX_EDIT_DISTANCE) as isize {
+        for k in (-d..=d).step_by(2) {
+            let down = k == -d || (k != d && v[(offset + k - 1) as usize] < v[(offset + k + 1) as usize]);
+            let mut x = if down { v[(offset + k + 1) as usize] } else { v[(offset + k - 1) as usize] + 1 };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[(offset + k) as usize] = x;
+            if x >= n && y >= m {
+                trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+                break 'search;
+            }
+        }
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+    }
+    let reached = trace.last().is_some_and(|last| {
+        let d = (last.len() / 2) as isize;
+        let k = n - m;
+        (-d..=d).contains(&k) && last[(k + d) as usize] >= n
+    });
+    if !reached {
+        return None;
+    }
+
+    // Walk back from the end, one edit per round.
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (1..trace.len() as isize).rev() {
+        let previous = &trace[(d - 1) as usize];
+        let at = |k: isize| previous[(k + d - 1) as usize];
+        let k = x - y;
+        let previous_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) { k + 1 } else { k - 1 };
+        let previous_x = at(previous_k);
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            x -= 1;
+            y -= 1;
+            ops.push(DiffOp::Equal { old: x as usize, new: y as usize });
+        }
+        if x == previous_x {
+            ops.push(DiffOp::Insert { new: (y - 1) as usize });
+        } else {
+            ops.push(DiffOp::Delete { old: (x - 1) as usize });
+        }
+        x = previous_x;
+        y = previous_y;
+    }
+    while x > 0 && y > 0 {
+        x -= 1;
+        y -= 1;
+        ops.push(DiffOp::Equal { old: x as usize, new: y as usize });
+    }
+    ops.reverse();
+    Some(ops)
+}
+
+/// Diff two texts into hunks with `context` lines around each change.
+pub fn hunks(old_text: &str, new_text: &str, context: usize) -> Vec<Hunk> {
+    let old = split_lines(old_text);
+    let new = split_lines(new_text);
+    let ops = diff_lines(&old, &new);
+
+    let changes: Vec<usize> = ops.iter().enumerate().filter(|(_, op)| !matches!(op, DiffOp::Equal { .. })).map(|(i, _)| i).collect();
+    let mut hunks = Vec::new();
+    let mut i = 0;
+    while i < changes.len() {
+        // Extend the group while the gap to the next change could share context.
+        let mut j = i;
+        while j + 1 < changes.len() && changes[j + 1] - changes[j] <= 2 * context + 1 {
+            j += 1;
+        }
+        let start = changes[i].saturating_sub(context);
+        let end = (changes[j] + context + 1).min(ops.len());
+        let old_before = ops[..start].iter().filter(|op| !matches!(op, DiffOp::Insert { .. })).count();
+        let new_before = ops[..start].iter().filter(|op| !matches!(op, DiffOp::Delete { .. })).count();
+        hunks.push(build_hunk(&ops[start..end], &old, &new, old_before, new_before));
+        i = j + 1;
+    }
+    hunks
+}
+
+fn build_hunk(ops: &[DiffOp], old: &[&str], new: &[&str], old_before: usize, new_before: usize) -> Hunk {
+    let mut hunk = Hunk { old_start: old_before + 1, old_count: 0, new_start: new_before + 1, new_count: 0, lines: Vec::new() };
+    for op in ops {
+        let (kind, text) = match *op {
+            DiffOp::Equal { old: index, .. } => {
+                hunk.old_count += 1;
+                hunk.new_count += 1;
+                (HunkLineKind::Context, old[index])
+            }
+            DiffOp::Delete { old: index } => {
+                hunk.old_count += 1;
+                (HunkLineKind::Removed, old[index])
+            }
+            DiffOp::Insert { new: index } => {
+                hunk.new_count += 1;
+                (HunkLineKind::Added, new[index])
+            }
+        };
+        hunk.lines.push(HunkLine { kind, text: text.to_string() });
+    }
+    hunk
+}
+
+/// Apply hunks computed against `old_text`, e.g. the accepted subset of a diff.
+///
+/// Hunks must be in order and not overlap; lines outside them are kept as they are.
+pub fn apply_hunks<'a>(old_text: &str, hunks: impl IntoIterator<Item = &'a Hunk>) -> String {
+    let old = split_lines(old_text);
+    let mut out = String::with_capacity(old_text.len());
+    let mut next = 0;
+    for hunk in hunks {
+        let start = hunk.old_start - 1;
+        out.extend(old[next..start.max(next)].iter().copied());
+        for line in &hunk.lines {
+            if line.kind != HunkLineKind::Removed {
+                out.push_str(&line.text);
+            }
+        }
+        next = start + hunk.old_count;
+    }
+    out.extend(old[next.min(old.len())..].iter().copied());
+    out
+}
+
+/// Render hunks as a unified diff with `---`/`+++` headers. Use `/dev/null` for a missing side.
+pub fn unified_diff(old_path: &str, new_path: &str, hunks: &[Hunk]) -> String {
+    let mut out = format!("--- {}\n+++ {}\n", old_path, new_path);
+    for hunk in hunks {
+        out.push_str(&hunk.to_unified());
+    }
+    out
+}
+
+/// One file's section of a patch. A missing side is a created or deleted file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePatch {
+    pub old_path: Option<String>,
+    pub new_path: Option<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+/// Parse a unified diff as written by `git diff` or `diff -u`.
+///
+/// `a/` and `b/` prefixes are stripped from paths, as `git apply` does by default.
+pub fn parse_patch(text: &str) -> Result<Vec<FilePatch>, String> {
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut current: Option<FilePatch> = None;
+    let mut lines = text.split_inclusive('\n').enumerate().peekable();
+
+    while let Some((number, raw)) = lines.next() {
+        let line = raw.trim_end_matches(['\n', '\r']);
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            files.extend(current.take());
+            // Good enough for paths without " b/" in them; `---`/`+++` lines take precedence.
+            let (old, new) = paths.split_once(" b/").unwrap_or((paths, paths));
+            current = Some(FilePatch { old_path: parse_path(old), new_path: parse_path(new), hunks: Vec::new() });
+        } else if let Some(path) = line.strip_prefix("--- ") {
+            if current.as_ref().is_none_or(|file| !file.hunks.is_empty()) {
+                files.extend(current.take());
+                current = Some(FilePatch { old_path: None, new_path: None, hunks: Vec::new() });
+            }
+            if let Some(file) = &mut current {
+                file.old_path = parse_path(path);
+            }
+        } else if let Some(path) = line.strip_prefix("+++ ") {
+            let file = current.as_mut().ok_or_else(|| format!("Line {}: '+++' without '---'", number + 1))?;
+            file.new_path = parse_path(path);
+        } else if let (Some(file), true) = (current.as_mut(), line.starts_with("new file mode")) {
+            file.old_path = None;
+        } else if let (Some(file), true) = (current.as_mut(), line.starts_with("deleted file mode")) {
+            file.new_path = None;
+        } else if let (Some(file), Some(path)) = (current.as_mut(), line.strip_prefix("rename from ")) {
+            file.old_path = Some(unquote(path));
+        } else if let (Some(file), Some(path)) = (current.as_mut(), line.strip_prefix("rename to ")) {
+            file.new_path = Some(unquote(path));
+        } else if line.starts_with("@@ ") {
+            let file = current.as_mut().ok_or_else(|| format!("Line {}: hunk before any file header", number + 1))?;
+            let mut hunk = parse_hunk_header(line).ok_or_else(|| format!("Line {}: bad hunk header '{}'", number + 1, line))?;
+            let (mut old_seen, mut new_seen) = (0, 0);
+            while old_seen < hunk.old_count || new_seen < hunk.new_count {
+                let Some((_, body)) = lines.next() else {
+                    return Err(format!("Patch ends inside hunk '{}'", hunk.header()));
+                };
+                let (kind, text) = match body.chars().next() {
+                    Some(' ') => (HunkLineKind::Context, &body[1..]),
+                    Some('-') => (HunkLineKind::Removed, &body[1..]),
+                    Some('+') => (HunkLineKind::Added, &body[1..]),
+                    // Some tools strip the space from empty context lines.
+                    Some('\n') | Some('\r') => (HunkLineKind::Context, body),
+                    Some('\\') => continue,
+                    _ => return Err(format!("Unexpected line in hunk '{}': {}", hunk.header(), body.trim_end())),
+                };
+                if kind != HunkLineKind::Added {
+                    old_seen += 1;
+                }
+                if kind != HunkLineKind::Removed {
+                    new_seen += 1;
+                }
+                let mut text = text.to_string();
+                if !text.ends_with('\n') {
+                    text.push('\n');
+                }
+                hunk.lines.push(HunkLine { kind, text });
+                if lines.peek().is_some_and(|(_, next)| next.starts_with('\\')) {
+                    lines.next();
+                    if let Some(last) = hunk.lines.last_mut() {
+                        last.text.pop();
+                    }
+                }
+            }
+            file.hunks.push(hunk);
+        }
+        // Anything else (index lines, mode changes, commit messages) is ignored.
+    }
+    files.extend(current);
+    Ok(files)
+}
+
+/// A path from a patch header: quotes, timestamps and the `a/`/`b/` prefix removed.
+fn parse_path(text: &str) -> Option<String> {
+    let path = text.split('\t').next().unwrap_or(text).trim();
+    let path = unquote(path);
+    if path == "/dev/null" {
+        return None;
+    }
+    Some(path.strip_prefix("a/").or_else(|| path.strip_prefix("b/")).unwrap_or(&path).to_string())
+}
+
+fn unquote(path: &str) -> String {
+    path.strip_prefix('"').and_then(|path| path.strip_suffix('"')).unwrap_or(path).replace("\\\"", "\"")
+}
+
+/// Parse `@@ -a,b +c,d @@`. Empty ranges are stored 1-based like those from [`hunks`].
+fn parse_hunk_header(line: &str) -> Option<Hunk> {
+    let ranges = line.strip_prefix("@@ -")?.split(" @@").next()?;
+    let (old, new) = ranges.split_once(" +")?;
+    let parse_range = |range: &str| -> Option<(usize, usize)> {
+        let (start, count) = match range.split_once(',') {
+            Some((start, count)) => (start.parse().ok()?, count.parse().ok()?),
+            None => (range.parse().ok()?, 1),
+        };
+        Some((if count == 0 { start + 1 } else { start }, count))
+    };
+    let (old_start, old_count) = parse_range(old)?;
+    let (new_start, new_count) = parse_range(new)?;
+    Some(Hunk { old_start, old_count, new_start, new_count, lines: Vec::new() })
+}
+
+/// How one hunk of a patch applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HunkOutcome {
+    /// Applied `offset` lines away from its header position, ignoring up to `fuzz` context lines at each end.
+    Applied { offset: isize, fuzz: usize },
+    /// Nothing in the file matched; the hunk was skipped.
+    Conflict,
+}
+
+/// The result of [`apply_patch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchOutcome {
+    /// The text with every hunk that matched applied.
+    pub content: String,
+    /// One outcome per hunk, in order.
+    pub hunks: Vec<HunkOutcome>,
+}
+
+impl PatchOutcome {
+    pub fn has_conflicts(&self) -> bool {
+        self.hunks.contains(&HunkOutcome::Conflict)
+    }
+}
+
+/// Apply hunks to text that may have drifted since the patch was made.
+///
+/// Each hunk is searched for nearest its expected line first. If its lines don't match
+/// exactly anywhere, up to `max_fuzz` context lines are dropped from each end and the
+/// search repeated. Hunks that still don't match are reported as conflicts and skipped.
+pub fn apply_patch(text: &str, hunks: &[Hunk], max_fuzz: usize) -> PatchOutcome {
+    let lines = split_lines(text);
+    let mut content = String::with_capacity(text.len());
+    let mut outcomes = Vec::with_capacity(hunks.len());
+    let mut next = 0;
+    let mut offset = 0isize;
+
+    for hunk in hunks {
+        let Some((position, fuzz, lead, trail)) = (0..=max_fuzz).find_map(|fuzz| {
+            let lead = hunk.lines.iter().take_while(|line| line.kind == HunkLineKind::Context).count().min(fuzz);
+            let trail = hunk.lines[lead..].iter().rev().take_while(|line| line.kind == HunkLineKind::Context).count().min(fuzz);
+            let body = &hunk.lines[lead..hunk.lines.len() - trail];
+            let expected = (hunk.old_start - 1 + lead) as isize + offset;
+            locate(&lines, body, next, expected).map(|position| (position, fuzz, lead, trail))
+        }) else {
+            outcomes.push(HunkOutcome::Conflict);
+            continue;
+        };
+
+        content.extend(lines[next..position].iter().copied());
+        let mut at = position;
+        for line in &hunk.lines[lead..hunk.lines.len() - trail] {
+            match line.kind {
+                HunkLineKind::Context => {
+                    content.push_str(lines[at]);
+                    at += 1;
+                }
+                HunkLineKind::Removed => at += 1,
+                HunkLineKind::Added => content.push_str(&line.text),
+            }
+        }
+        next = at;
+        offset = position as isize - (hunk.old_start - 1 + lead) as isize;
+        outcomes.push(HunkOutcome::Applied { offset, fuzz });
+    }
+
+    content.extend(lines[next..].iter().copied());
+    PatchOutcome { content, hunks: outcomes }
+}
+
+/// Find where the old side of `body` matches `lines` at or after `from`, nearest `expected` first.
+fn locate(lines: &[&str], body: &[HunkLine], from: usize, expected: isize) -> Option<usize> {
+    let old: Vec<&str> = body.iter().filter(|line| line.kind != HunkLineKind::Added).map(|line| line.text.as_str()).collect();
+    let last = lines.len().checked_sub(old.len())?;
+    if from > last {
+        return None;
+    }
+    let expected = expected.clamp(from as isize, last as isize) as usize;
+    let matches = |position: usize| lines[position..position + old.len()] == old[..];
+    (0..=last - from).find_map(|distance| {
+        let after = expected + distance;
+        let before = expected.checked_sub(distance).filter(|&position| position >= from);
+        [Some(after).filter(|&position| position <= last), before].into_iter().flatten().find(|&position| matches(position))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_at_top_is_one_added_line() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\n";
+        let new = "top\na\nb\nc\nd\nE\nf\ng\nh";
+        let hunks = hunks(old, new, 1);
+
+        let summary: Vec<(String, usize, usize)> = hunks.iter().map(|h| (h.header(), h.added(), h.removed())).collect();
+        // Changes two lines apart share their context and merge.
+        assert_eq!(summary, vec![("@@ -1 +1,2 @@".to_string(), 1, 0), ("@@ -4,5 +5,5 @@".to_string(), 2, 2)]);
+        // The last line lost its newline.
+        assert!(hunks[1].to_unified().ends_with("+h\n\\ No newline at end of file\n"));
+
+        assert_eq!(apply_hunks(old, &hunks), new);
+        assert_eq!(apply_hunks(old, &hunks[..1]), format!("top\n{}", old));
+        assert_eq!(super::hunks("", "x\n", 3)[0].header(), "@@ -0,0 +1 @@");
+    }
+
+    #[test]
+    fn test_diff_is_minimal() {
+        let old: Vec<String> = (0..200).map(|i| format!("line {}\n", i)).collect();
+        let mut new = old.clone();
+        new.remove(50);
+        new.insert(120, "inserted\n".to_string());
+        new[180] = "changed\n".to_string();
+        let old: Vec<&str> = old.iter().map(String::as_str).collect();
+        let new: Vec<&str> = new.iter().map(String::as_str).collect();
+
+        let ops = diff_lines(&old, &new);
+        let edits = ops.iter().filter(|op| !matches!(op, DiffOp::Equal { .. })).count();
+        assert_eq!(edits, 4);
+    }
+
+    #[test]
+    fn test_patch_round_trip_with_offset_fuzz_and_conflict() {
+        let old: String = (1..=30).map(|i| format!("line {}\n", i)).collect();
+        let new = old.replace("line 5\n", "five\n").replace("line 25\n", "twenty-five\n");
+        let patch = format!("diff --git a/src/x.txt b/src/x.txt\nindex 1234567..89abcde 100644\n{}", unified_diff("a/src/x.txt", "b/src/x.txt", &hunks(&old, &new, 3)));
+
+        let files = parse_patch(&patch).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!((files[0].old_path.as_deref(), files[0].new_path.as_deref()), (Some("src/x.txt"), Some("src/x.txt")));
+        assert_eq!(files[0].hunks, hunks(&old, &new, 3));
+
+        // Two lines added at the top shift both hunks.
+        let drifted = format!("new a\nnew b\n{}", old);
+        let outcome = apply_patch(&drifted, &files[0].hunks, 0);
+        assert_eq!(outcome.content, format!("new a\nnew b\n{}", new));
+        assert_eq!(outcome.hunks, vec![HunkOutcome::Applied { offset: 2, fuzz: 0 }; 2]);
+
+        // A changed context line needs fuzz; a changed removed line is a conflict.
+        let edited = old.replace("line 3\n", "three\n").replace("line 25\n", "25\n");
+        assert!(apply_patch(&edited, &files[0].hunks, 0).hunks.iter().all(|outcome| *outcome == HunkOutcome::Conflict));
+        let outcome = apply_patch(&edited, &files[0].hunks, 2);
+        assert_eq!(outcome.hunks, vec![HunkOutcome::Applied { offset: 0, fuzz: 2 }, HunkOutcome::Conflict]);
+        assert_eq!(outcome.content, edited.replace("line 5\n", "five\n"));
+
+        // New files, and the no-newline marker.
+        let created = parse_patch("--- /dev/null\n+++ b/new.txt\n@@ -0,0 +1,2 @@\n+a\n+b\n\\ No newline at end of file\n").unwrap();
+        assert_eq!(created[0].old_path, None);
+        assert_eq!(apply_patch("", &created[0].hunks, 0).content, "a\nb");
+    }
+}
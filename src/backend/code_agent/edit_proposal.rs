@@ -0,0 +1,306 @@
+//! Agent file edits, held for review before they touch disk.
+//!
+//! The agent's `write_file` tool stages content in [`StagedEdits`]. When the run
+//! ends the staged files become an [`EditProposal`]: one diff per file, with an
+//! accept/reject decision per hunk. Applying it writes only the accepted hunks and
+//! returns an [`AppliedEdit`] that can put every file back in one step.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use super::diff::{self, Hunk, DEFAULT_CONTEXT};
+use super::files_changed::FileChangeTracker;
+
+/// Author recorded in the [`FileChangeTracker`] for applied agent edits.
+pub const AGENT_AUTHOR: &str = "Agent";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HunkDecision {
+    Pending,
+    Accepted,
+    Rejected,
+}
+
+/// A proposed change to one file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileEdit {
+    /// Path relative to the workspace root.
+    pub path: PathBuf,
+    /// Content when the edit was proposed; `None` for a new file.
+    pub original: Option<String>,
+    pub proposed: String,
+    pub hunks: Vec<Hunk>,
+    /// One decision per hunk.
+    pub decisions: Vec<HunkDecision>,
+}
+
+impl FileEdit {
+    pub fn new(path: PathBuf, original: Option<String>, proposed: String) -> Self {
+        let hunks = diff::hunks(original.as_deref().unwrap_or(""), &proposed, DEFAULT_CONTEXT);
+        let decisions = vec![HunkDecision::Pending; hunks.len()];
+        Self { path, original, proposed, hunks, decisions }
+    }
+
+    pub fn is_new_file(&self) -> bool {
+        self.original.is_none()
+    }
+
+    /// The edit as a unified diff with `a/` and `b/` paths.
+    pub fn unified_diff(&self) -> String {
+        let path = self.path.to_string_lossy().replace('\\', "/");
+        let old_path = if self.is_new_file() { "/dev/null".to_string() } else { format!("a/{}", path) };
+        diff::unified_diff(&old_path, &format!("b/{}", path), &self.hunks)
+    }
+
+    /// The file with only the accepted hunks applied, or `None` if no hunk was accepted.
+    pub fn accepted_content(&self) -> Option<String> {
+        if !self.decisions.contains(&HunkDecision::Accepted) {
+            return None;
+        }
+        let accepted = self
+            .hunks
+            .iter()
+            .zip(&self.decisions)
+            .filter(|(_, decision)| **decision == HunkDecision::Accepted)
+            .map(|(hunk, _)| hunk);
+        Some(diff::apply_hunks(self.original.as_deref().unwrap_or(""), accepted))
+    }
+}
+
+/// Everything the agent wanted to change in one turn.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditProposal {
+    /// Shown in the review panel and recorded with each applied change.
+    pub description: String,
+    pub workspace: PathBuf,
+    /// Sorted by path.
+    pub files: Vec<FileEdit>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl EditProposal {
+    pub fn set_decision(&mut self, file: usize, hunk: usize, decision: HunkDecision) {
+        if let Some(slot) = self.files.get_mut(file).and_then(|file| file.decisions.get_mut(hunk)) {
+            *slot = decision;
+        }
+    }
+
+    pub fn accept_all(&mut self) {
+        self.decide_all(HunkDecision::Accepted);
+    }
+
+    pub fn reject_all(&mut self) {
+        self.decide_all(HunkDecision::Rejected);
+    }
+
+    fn decide_all(&mut self, decision: HunkDecision) {
+        for file in &mut self.files {
+            file.decisions.iter_mut().for_each(|slot| *slot = decision);
+        }
+    }
+
+    /// Number of hunks across all files.
+    pub fn hunk_count(&self) -> usize {
+        self.files.iter().map(|file| file.hunks.len()).sum()
+    }
+
+    pub fn unified_diff(&self) -> String {
+        self.files.iter().map(FileEdit::unified_diff).collect()
+    }
+
+    /// Write the accepted hunks and record each file in `tracker` as an agent change.
+    ///
+    /// Fails without writing anything if a file changed on disk since the proposal
+    /// was made. If a write fails, files already written are restored.
+    pub fn apply(&self, tracker: &mut FileChangeTracker) -> Result<AppliedEdit, String> {
+        let mut writes = Vec::new();
+        for file in &self.files {
+            let Some(content) = file.accepted_content() else { continue };
+            let current = read_optional(&self.workspace.join(&file.path))?;
+            if current != file.original {
+                return Err(format!("{} changed since the edit was proposed", file.path.display()));
+            }
+            writes.push(AppliedFile { path: file.path.clone(), before: file.original.clone(), after: content });
+        }
+
+        let mut applied = AppliedEdit { description: self.description.clone(), workspace: self.workspace.clone(), files: Vec::new() };
+        for file in writes {
+            if let Err(error) = write_optional(&self.workspace.join(&file.path), Some(&file.after)) {
+                for written in applied.files.iter().rev() {
+                    let _ = write_optional(&self.workspace.join(&written.path), written.before.as_deref());
+                }
+                return Err(error);
+            }
+            applied.files.push(file);
+        }
+
+        for file in &applied.files {
+            let path = self.workspace.join(&file.path);
+            tracker.track_edit(path, file.before.as_deref(), Some(&file.after), AGENT_AUTHOR, Some(self.description.clone()));
+        }
+        Ok(applied)
+    }
+}
+
+/// One file written by [`EditProposal::apply`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppliedFile {
+    pub path: PathBuf,
+    /// `None` if the file was created.
+    pub before: Option<String>,
+    pub after: String,
+}
+
+/// The result of applying a proposal, kept so the whole turn can be undone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppliedEdit {
+    pub description: String,
+    pub workspace: PathBuf,
+    pub files: Vec<AppliedFile>,
+}
+
+impl AppliedEdit {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Put every file back as it was, deleting files the edit created.
+    ///
+    /// Fails without touching anything if a file was changed after the edit was applied.
+    pub fn revert(&self, tracker: &mut FileChangeTracker) -> Result<(), String> {
+        for file in &self.files {
+            if read_optional(&self.workspace.join(&file.path))?.as_deref() != Some(file.after.as_str()) {
+                return Err(format!("{} changed since the edit was applied", file.path.display()));
+            }
+        }
+        for file in &self.files {
+            let path = self.workspace.join(&file.path);
+            write_optional(&path, file.before.as_deref())?;
+            tracker.track_edit(path, Some(&file.after), file.before.as_deref(), "User", Some(format!("Reverted: {}", self.description)));
+        }
+        Ok(())
+    }
+}
+
+/// File writes made by the agent during a run, kept in memory until reviewed.
+#[derive(Debug, Default)]
+pub struct StagedEdits {
+    files: Mutex<BTreeMap<PathBuf, String>>,
+}
+
+impl StagedEdits {
+    /// Stage the full new content of `path` (absolute), replacing anything staged for it before.
+    pub fn stage(&self, path: PathBuf, content: String) {
+        self.files.lock().unwrap().insert(path, content);
+    }
+
+    /// The staged content of `path`, if any.
+    pub fn get(&self, path: &Path) -> Option<String> {
+        self.files.lock().unwrap().get(path).cloned()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.lock().unwrap().is_empty()
+    }
+
+    pub fn clear(&self) {
+        self.files.lock().unwrap().clear();
+    }
+
+    /// Diff everything staged against the files on disk and clear the stage.
+    ///
+    /// Returns `None` if nothing staged differs from disk.
+    pub fn take_proposal(&self, workspace: &Path, description: &str) -> Option<EditProposal> {
+        let staged = std::mem::take(&mut *self.files.lock().unwrap());
+        let files: Vec<FileEdit> = staged
+            .into_iter()
+            .filter_map(|(path, proposed)| {
+                // An unreadable file is proposed as new; `apply` then refuses to overwrite it.
+                let original = read_optional(&path).ok().flatten();
+                if original.as_deref() == Some(proposed.as_str()) {
+                    return None;
+                }
+                let relative = path.strip_prefix(workspace).unwrap_or(&path).to_path_buf();
+                Some(FileEdit::new(relative, original, proposed))
+            })
+            .filter(|edit| !edit.hunks.is_empty())
+            .collect();
+
+        if files.is_empty() {
+            return None;
+        }
+        Some(EditProposal { description: description.to_string(), workspace: workspace.to_path_buf(), files, created_at: Utc::now() })
+    }
+}
+
+/// Read a file, treating a missing file as `None`.
+fn read_optional(path: &Path) -> Result<Option<String>, String> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+/// Write a file, creating parent directories, or delete it for `None`.
+fn write_optional(path: &Path, content: Option<&str>) -> Result<(), String> {
+    match content {
+        Some(content) => {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+            }
+            fs::write(path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+        }
+        None => fs::remove_file(path).map_err(|e| format!("Failed to delete {}: {}", path.display(), e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::code_agent::test_support::TempDir;
+
+    #[test]
+    fn test_apply_accepted_hunks_then_revert() {
+        let temp = TempDir::new("edit_proposal");
+        let workspace = temp.path().to_path_buf();
+        let original: String = (1..=20).map(|i| format!("line {}\n", i)).collect();
+        std::fs::write(workspace.join("lib.rs"), &original).unwrap();
+
+        let staged = StagedEdits::default();
+        staged.stage(workspace.join("lib.rs"), original.replace("line 2\n", "two\n").replace("line 19\n", "nineteen\n"));
+        staged.stage(workspace.join("src/new.rs"), "fn main() {}\n".to_string());
+        staged.stage(workspace.join("same.rs"), String::new());
+        let mut proposal = staged.take_proposal(&workspace, "Rename lines").unwrap();
+        assert!(staged.is_empty());
+
+        // The empty new file has no hunks and is dropped; the rest is sorted by path.
+        let paths: Vec<&Path> = proposal.files.iter().map(|file| file.path.as_path()).collect();
+        assert_eq!(paths, vec![Path::new("lib.rs"), Path::new("src/new.rs")]);
+        assert_eq!(proposal.hunk_count(), 3);
+        assert!(proposal.files[1].unified_diff().starts_with("--- /dev/null\n+++ b/src/new.rs\n@@ -0,0 +1 @@\n"));
+
+        proposal.set_decision(0, 0, HunkDecision::Accepted);
+        proposal.set_decision(0, 1, HunkDecision::Rejected);
+        proposal.set_decision(1, 0, HunkDecision::Accepted);
+        let mut tracker = FileChangeTracker::new();
+        let applied = proposal.apply(&mut tracker).unwrap();
+
+        let on_disk = std::fs::read_to_string(workspace.join("lib.rs")).unwrap();
+        assert_eq!(on_disk, original.replace("line 2\n", "two\n"));
+        assert!(workspace.join("src/new.rs").exists());
+        let recent = tracker.get_recent_changes(10);
+        assert_eq!(recent.len(), 2);
+        assert!(recent.iter().all(|change| change.author == AGENT_AUTHOR));
+
+        // Applying again is refused: the files no longer match what was proposed against.
+        assert!(proposal.apply(&mut tracker).is_err());
+
+        applied.revert(&mut tracker).unwrap();
+        assert_eq!(std::fs::read_to_string(workspace.join("lib.rs")).unwrap(), original);
+        assert!(!workspace.join("src/new.rs").exists());
+    }
+}
@@ -4,8 +4,10 @@
 // FIXME: 
 
 use std::collections::{HashMap, VecDeque};
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 use chrono::{DateTime, Utc};
+use super::diff::{self, Hunk, HunkLineKind, HunkOutcome, DEFAULT_CONTEXT};
 
 #[derive(Debug, Clone)]
 pub struct FileChange {
@@ -15,6 +17,48 @@ pub struct FileChange {
     pub line_changes: Vec<LineChange>,
     pub author: String,
     pub description: Option<String>,
+    /// The change as diff hunks with context, for export.
+    pub hunks: Vec<Hunk>,
+}
+
+impl FileChange {
+    /// The change as a git-style unified diff, with paths made relative to `root` where possible.
+    pub fn to_unified_diff(&self, root: &Path) -> String {
+        let relative = |path: &Path| path.strip_prefix(root).unwrap_or(path).to_string_lossy().replace('\\', "/");
+        let new_path = relative(&self.path);
+        let old_path = match &self.change_type {
+            ChangeType::Renamed(old) | ChangeType::Moved(old) => relative(old),
+            _ => new_path.clone(),
+        };
+
+        let mut out = format!("diff --git a/{} b/{}\n", old_path, new_path);
+        match self.change_type {
+            ChangeType::Created => out.push_str("new file mode 100644\n"),
+            ChangeType::Deleted => out.push_str("deleted file mode 100644\n"),
+            ChangeType::Renamed(_) | ChangeType::Moved(_) => out.push_str(&format!("rename from {}\nrename to {}\n", old_path, new_path)),
+            ChangeType::Modified => {}
+        }
+        if !self.hunks.is_empty() {
+            let old_header = if self.change_type == ChangeType::Created { "/dev/null".to_string() } else { format!("a/{}", old_path) };
+            let new_header = if self.change_type == ChangeType::Deleted { "/dev/null".to_string() } else { format!("b/{}", new_path) };
+            out.push_str(&diff::unified_diff(&old_header, &new_header, &self.hunks));
+        }
+        out
+    }
+}
+
+/// How one file of a patch applied, from [`FileChangeTracker::apply_patch`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchFileReport {
+    pub path: PathBuf,
+    /// One outcome per hunk; all `Conflict` if the file was missing, already existed or would not be emptied by a deletion.
+    pub hunks: Vec<HunkOutcome>,
+}
+
+impl PatchFileReport {
+    pub fn has_conflicts(&self) -> bool {
+        self.hunks.contains(&HunkOutcome::Conflict)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -41,7 +85,6 @@ pub enum LineChangeKind {
     Modified,
 }
 
-#[derive(Debug)]
 pub struct FileChangeTracker {
     changes: HashMap<PathBuf, VecDeque<FileChange>>,
     recent_changes: VecDeque<FileChange>,
@@ -70,55 +113,38 @@ impl FileChangeTracker {
     }
     
     pub fn track_file_created(&mut self, path: PathBuf, content: &str) {
-        let change = FileChange {
-            path: path.clone(),
-            change_type: ChangeType::Created,
-            timestamp: Utc::now(),
-            line_changes: content.lines().enumerate().map(|(i, line)| {
-                LineChange {
-                    line_number: i + 1,
-                    change_kind: LineChangeKind::Added,
-                    old_content: None,
-                    new_content: Some(line.to_string()),
-                }
-            }).collect(),
-            author: "User".to_string(),
-            description: Some("File created".to_string()),
-        };
-        
-        self.add_change(change);
+        self.track_edit(path, None, Some(content), "User", Some("File created".to_string()));
     }
     
     pub fn track_file_modified(&mut self, path: PathBuf, old_content: &str, new_content: &str) {
-        let line_changes = self.compute_line_changes(old_content, new_content);
-        
-        let change = FileChange {
-            path: path.clone(),
-            change_type: ChangeType::Modified,
-            timestamp: Utc::now(),
-            line_changes,
-            author: "User".to_string(),
-            description: None,
-        };
-        
-        self.add_change(change);
+        self.track_edit(path, Some(old_content), Some(new_content), "User", None);
     }
     
     pub fn track_file_deleted(&mut self, path: PathBuf, content: &str) {
+        self.track_edit(path, Some(content), None, "User", Some("File deleted".to_string()));
+    }
+    
+    /// Record a create (`old` is `None`), delete (`new` is `None`) or modification made by `author`.
+    pub fn track_edit(&mut self, path: PathBuf, old: Option<&str>, new: Option<&str>, author: &str, description: Option<String>) {
+        let change_type = match (old, new) {
+            (None, Some(_)) => ChangeType::Created,
+            (Some(_), None) => ChangeType::Deleted,
+            (Some(_), Some(_)) => ChangeType::Modified,
+            (None, None) => return,
+        };
+        self.record(path, change_type, old.unwrap_or(""), new.unwrap_or(""), author, description);
+    }
+    
+    fn record(&mut self, path: PathBuf, change_type: ChangeType, old: &str, new: &str, author: &str, description: Option<String>) {
+        let hunks = diff::hunks(old, new, DEFAULT_CONTEXT);
         let change = FileChange {
-            path: path.clone(),
-            change_type: ChangeType::Deleted,
+            path,
+            change_type,
             timestamp: Utc::now(),
-            line_changes: content.lines().enumerate().map(|(i, line)| {
-                LineChange {
-                    line_number: i + 1,
-                    change_kind: LineChangeKind::Deleted,
-                    old_content: Some(line.to_string()),
-                    new_content: None,
-                }
-            }).collect(),
-            author: "User".to_string(),
-            description: Some("File deleted".to_string()),
+            line_changes: Self::compute_line_changes(&hunks),
+            author: author.to_string(),
+            description,
+            hunks,
         };
         
         self.add_change(change);
@@ -132,6 +158,7 @@ impl FileChangeTracker {
             line_changes: Vec::new(),
             author: "User".to_string(),
             description: Some(format!("Renamed from {:?}", old_path)),
+            hunks: Vec::new(),
         };
         
         self.add_change(change);
@@ -161,45 +188,121 @@ impl FileChangeTracker {
         }
     }
     
-    fn compute_line_changes(&self, old_content: &str, new_content: &str) -> Vec<LineChange> {
-        let old_lines: Vec<&str> = old_content.lines().collect();
-        let new_lines: Vec<&str> = new_content.lines().collect();
+    /// Line changes from diff hunks. A removed line and an added line in the same
+    /// run pair up as a modification; the rest are plain additions or deletions.
+    /// Added and modified lines are numbered in the new file, deleted lines in the old one.
+    fn compute_line_changes(hunks: &[Hunk]) -> Vec<LineChange> {
         let mut changes = Vec::new();
-        
-        // Simple line-by-line comparison (could be improved with proper diff algorithm)
-        let max_lines = old_lines.len().max(new_lines.len());
-        
-        for i in 0..max_lines {
-            match (old_lines.get(i), new_lines.get(i)) {
-                (Some(old), Some(new)) if old != new => {
-                    changes.push(LineChange {
-                        line_number: i + 1,
-                        change_kind: LineChangeKind::Modified,
-                        old_content: Some(old.to_string()),
-                        new_content: Some(new.to_string()),
-                    });
+        for hunk in hunks {
+            let (mut old_line, mut new_line) = (hunk.old_start, hunk.new_start);
+            let mut removed: Vec<(usize, &str)> = Vec::new();
+            let mut added: Vec<(usize, &str)> = Vec::new();
+            for line in &hunk.lines {
+                match line.kind {
+                    HunkLineKind::Context => {
+                        Self::push_run(&mut changes, &mut removed, &mut added);
+                        old_line += 1;
+                        new_line += 1;
+                    }
+                    HunkLineKind::Removed => {
+                        removed.push((old_line, &line.text));
+                        old_line += 1;
+                    }
+                    HunkLineKind::Added => {
+                        added.push((new_line, &line.text));
+                        new_line += 1;
+                    }
                 }
-                (Some(old), None) => {
-                    changes.push(LineChange {
-                        line_number: i + 1,
-                        change_kind: LineChangeKind::Deleted,
-                        old_content: Some(old.to_string()),
-                        new_content: None,
-                    });
-                }
-                (None, Some(new)) => {
-                    changes.push(LineChange {
-                        line_number: i + 1,
-                        change_kind: LineChangeKind::Added,
-                        old_content: None,
-                        new_content: Some(new.to_string()),
-                    });
+            }
+            Self::push_run(&mut changes, &mut removed, &mut added);
+        }
+        changes
+    }
+    
+    fn push_run(changes: &mut Vec<LineChange>, removed: &mut Vec<(usize, &str)>, added: &mut Vec<(usize, &str)>) {
+        let text = |line: &str| line.strip_suffix('\n').map_or(line, |line| line.strip_suffix('\r').unwrap_or(line)).to_string();
+        let paired = removed.len().min(added.len());
+        for (&(_, old), &(line_number, new)) in removed.iter().zip(added.iter()) {
+            changes.push(LineChange {
+                line_number,
+                change_kind: LineChangeKind::Modified,
+                old_content: Some(text(old)),
+                new_content: Some(text(new)),
+            });
+        }
+        for &(line_number, old) in &removed[paired..] {
+            changes.push(LineChange { line_number, change_kind: LineChangeKind::Deleted, old_content: Some(text(old)), new_content: None });
+        }
+        for &(line_number, new) in &added[paired..] {
+            changes.push(LineChange { line_number, change_kind: LineChangeKind::Added, old_content: None, new_content: Some(text(new)) });
+        }
+        removed.clear();
+        added.clear();
+    }
+    
+    /// Apply a unified diff to files under `root`, like `git apply`, recording each file changed.
+    ///
+    /// Hunks may land up to `max_fuzz` context lines off. Nothing is written unless every
+    /// hunk of every file applies; the report says which hunks conflicted.
+    pub fn apply_patch(&mut self, root: &Path, patch: &str, max_fuzz: usize, author: &str) -> Result<Vec<PatchFileReport>, String> {
+        let mut reports = Vec::new();
+        let mut writes = Vec::new();
+        for file in diff::parse_patch(patch)? {
+            let old_path = file.old_path.as_deref().map(|name| Self::patch_path(root, name)).transpose()?;
+            let new_path = file.new_path.as_deref().map(|name| Self::patch_path(root, name)).transpose()?;
+            let path = new_path.clone().or_else(|| old_path.clone()).ok_or("Patch section has no file name")?;
+            let old = match &old_path {
+                Some(old_path) => fs::read_to_string(old_path).ok(),
+                None if path.exists() => None,
+                None => Some(String::new()),
+            };
+            
+            let mut hunks = vec![HunkOutcome::Conflict; file.hunks.len().max(1)];
+            if let Some(old) = old {
+                let outcome = diff::apply_patch(&old, &file.hunks, max_fuzz);
+                // A deletion must remove everything, or the file no longer matches the patch.
+                if new_path.is_some() || outcome.content.is_empty() {
+                    hunks = outcome.hunks;
+                    let change_type = match (&old_path, &new_path) {
+                        (None, _) => ChangeType::Created,
+                        (_, None) => ChangeType::Deleted,
+                        (Some(from), Some(to)) if from != to => ChangeType::Renamed(from.clone()),
+                        _ => ChangeType::Modified,
+                    };
+                    writes.push((path.clone(), change_type, old, outcome.content));
                 }
-                _ => {} // Lines are the same, no change
             }
+            reports.push(PatchFileReport { path, hunks });
+        }
+        if reports.iter().any(PatchFileReport::has_conflicts) {
+            return Ok(reports);
         }
         
-        changes
+        for (path, change_type, old, new) in writes {
+            match &change_type {
+                ChangeType::Deleted => fs::remove_file(&path).map_err(|e| format!("Failed to delete {}: {}", path.display(), e))?,
+                _ => {
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+                    }
+                    fs::write(&path, &new).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+                }
+            }
+            if let ChangeType::Renamed(from) = &change_type {
+                fs::remove_file(from).map_err(|e| format!("Failed to remove {}: {}", from.display(), e))?;
+            }
+            self.record(path, change_type, &old, &new, author, Some("Applied patch".to_string()));
+        }
+        Ok(reports)
+    }
+    
+    /// Resolve a path from a patch under `root`, refusing paths that leave it.
+    fn patch_path(root: &Path, name: &str) -> Result<PathBuf, String> {
+        let relative = Path::new(name);
+        if relative.is_absolute() || relative.components().any(|c| matches!(c, std::path::Component::ParentDir)) {
+            return Err(format!("Patch path '{}' is outside the workspace", name));
+        }
+        Ok(root.join(relative))
     }
     
     pub fn get_file_history(&self, path: &PathBuf) -> Option<&VecDeque<FileChange>> {
@@ -273,4 +376,54 @@ pub struct ChangeStatistics {
     pub lines_added: usize,
     pub lines_deleted: usize,
     pub lines_modified: usize,
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::code_agent::test_support::TempDir;
+
+    #[test]
+    fn test_insert_at_top_counts_one_added_line() {
+        let old: String = (1..=10).map(|i| format!("line {}\n", i)).collect();
+        let mut tracker = FileChangeTracker::new();
+        tracker.track_file_modified(PathBuf::from("/work/src/lib.rs"), &old, &format!("// header\n{}", old.replace("line 7", "seven")));
+
+        let stats = tracker.get_statistics();
+        assert_eq!((stats.lines_added, stats.lines_deleted, stats.lines_modified), (1, 0, 1));
+        let change = tracker.get_recent_changes(1)[0];
+        assert_eq!(change.line_changes[1].line_number, 8);
+        assert_eq!(change.line_changes[1].old_content.as_deref(), Some("line 7"));
+
+        let patch = change.to_unified_diff(Path::new("/work"));
+        assert!(patch.starts_with("diff --git a/src/lib.rs b/src/lib.rs\n--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,10 +1,11 @@\n+// header\n"));
+    }
+
+    #[test]
+    fn test_apply_patch_writes_only_when_every_hunk_applies() {
+        let temp = TempDir::new("apply_patch");
+        let root = temp.path().to_path_buf();
+        let old: String = (1..=20).map(|i| format!("line {}\n", i)).collect();
+        let new = old.replace("line 3\n", "three\n").replace("line 18\n", "eighteen\n");
+        let mut exporter = FileChangeTracker::new();
+        exporter.track_file_modified(root.join("a.txt"), &old, &new);
+        exporter.track_file_created(root.join("docs/b.txt"), "hello\n");
+        let patch: String = exporter.get_recent_changes(2).iter().rev().map(|change| change.to_unified_diff(&root)).collect();
+
+        // The file drifted: one line moved the first hunk and another broke the second.
+        std::fs::write(root.join("a.txt"), format!("extra\n{}", old.replace("line 18\n", "18\n"))).unwrap();
+        let mut tracker = FileChangeTracker::new();
+        let reports = tracker.apply_patch(&root, &patch, 0, "User").unwrap();
+        assert_eq!(reports[0].hunks, vec![HunkOutcome::Applied { offset: 1, fuzz: 0 }, HunkOutcome::Conflict]);
+        assert!(!root.join("docs/b.txt").exists());
+        assert!(tracker.get_recent_changes(10).is_empty());
+
+        std::fs::write(root.join("a.txt"), format!("extra\n{}", old)).unwrap();
+        let reports = tracker.apply_patch(&root, &patch, 0, "User").unwrap();
+        assert!(reports.iter().all(|report| !report.has_conflicts()));
+        assert_eq!(std::fs::read_to_string(root.join("a.txt")).unwrap(), format!("extra\n{}", new));
+        assert_eq!(std::fs::read_to_string(root.join("docs/b.txt")).unwrap(), "hello\n");
+        assert_eq!(tracker.get_recent_changes(10)[0].change_type, ChangeType::Created);
+
+        assert!(tracker.apply_patch(&root, "--- a/../x\n+++ b/../x\n", 0, "User").is_err());
+    }
+}
@@ -16,7 +16,6 @@ pub struct HotSwapConfig {
     pub preserve_state: bool,
 }
 
-#[derive(Debug)]
 pub struct HotSwapper {
     config: HotSwapConfig,
     watched_files: HashMap<PathBuf, FileWatchInfo>,
@@ -94,7 +93,7 @@ impl HotSwapper {
         // Calculate checksum
         let content = std::fs::read_to_string(&path)
             .map_err(|e| format!("Failed to read file: {}", e))?;
-        let checksum = self.calculate_checksum(&content);
+        let checksum = Self::calculate_checksum(&content);
         
         let watch_info = FileWatchInfo {
             last_modified,
@@ -124,7 +123,7 @@ impl HotSwapper {
                     if modified > watch_info.last_modified {
                         // File has been modified
                         if let Ok(content) = std::fs::read_to_string(path) {
-                            let new_checksum = self.calculate_checksum(&content);
+                            let new_checksum = Self::calculate_checksum(&content);
                             
                             if new_checksum != watch_info.checksum {
                                 // Content actually changed
@@ -220,7 +219,7 @@ impl HotSwapper {
         Ok(())
     }
     
-    fn calculate_checksum(&self, content: &str) -> String {
+    fn calculate_checksum(content: &str) -> String {
         // Simple checksum using hash
         use std::collections::hash_map::DefaultHasher;
         use std::hash::{Hash, Hasher};
@@ -12,6 +12,12 @@ pub struct AgentInstructionQueue {
     queue: VecDeque<AgentInstruction>,
 }
 
+impl Default for AgentInstructionQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AgentInstructionQueue {
     /// Create a new, empty instruction queue.
     pub fn new() -> Self {
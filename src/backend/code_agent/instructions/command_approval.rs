@@ -0,0 +1,156 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+use super::agent_instruction_logic::AgentInstruction;
+
+/// How long a command waits for the user before it counts as rejected.
+pub const DEFAULT_APPROVAL_WAIT: Duration = Duration::from_secs(600);
+
+/// A command the agent wants to run, waiting for the user.
+#[derive(Debug, Clone)]
+pub struct PendingCommand {
+    pub id: u64,
+    /// The command line, and when the agent asked to run it.
+    pub instruction: AgentInstruction,
+    pub dir: PathBuf,
+    /// Why the policy didn't let it run on its own.
+    pub reason: String,
+}
+
+/// What the user chose to do with a pending command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApprovalDecision {
+    Approve,
+    /// Run this command line instead.
+    Edit(String),
+    /// Don't run it; the reason is passed back to the model.
+    Reject(String),
+}
+
+#[derive(Debug, Default)]
+struct ApprovalState {
+    next_id: u64,
+    pending: VecDeque<PendingCommand>,
+    decisions: HashMap<u64, ApprovalDecision>,
+}
+
+/// Commands waiting for approval, in the order the agent asked for them.
+///
+/// The agent's tool thread blocks in [`request`](Self::request) until the UI calls
+/// [`decide`](Self::decide) for its command.
+#[derive(Debug)]
+pub struct CommandApprovalQueue {
+    state: Mutex<ApprovalState>,
+    decided: Condvar,
+    wait_limit: Duration,
+}
+
+impl Default for CommandApprovalQueue {
+    fn default() -> Self {
+        Self::new(DEFAULT_APPROVAL_WAIT)
+    }
+}
+
+impl CommandApprovalQueue {
+    pub fn new(wait_limit: Duration) -> Self {
+        Self { state: Mutex::new(ApprovalState::default()), decided: Condvar::new(), wait_limit }
+    }
+
+    /// Queue `command` and wait for the user's decision.
+    pub fn request(&self, command: &str, dir: PathBuf, reason: &str) -> ApprovalDecision {
+        let mut state = self.state.lock().unwrap();
+        state.next_id += 1;
+        let id = state.next_id;
+        let instruction = AgentInstruction { text: command.to_string(), timestamp: std::time::SystemTime::now() };
+        state.pending.push_back(PendingCommand { id, instruction, dir, reason: reason.to_string() });
+
+        let deadline = Instant::now() + self.wait_limit;
+        loop {
+            if let Some(decision) = state.decisions.remove(&id) {
+                return decision;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                state.pending.retain(|pending| pending.id != id);
+                return ApprovalDecision::Reject("No decision before the approval timed out".to_string());
+            }
+            state = self.decided.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+
+    /// Commands waiting for a decision, oldest first.
+    pub fn pending(&self) -> Vec<PendingCommand> {
+        self.state.lock().unwrap().pending.iter().cloned().collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.lock().unwrap().pending.is_empty()
+    }
+
+    /// Answer the pending command `id`.
+    pub fn decide(&self, id: u64, decision: ApprovalDecision) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        let before = state.pending.len();
+        state.pending.retain(|pending| pending.id != id);
+        if state.pending.len() == before {
+            return Err(format!("No pending command {}", id));
+        }
+        state.decisions.insert(id, decision);
+        self.decided.notify_all();
+        Ok(())
+    }
+
+    /// Reject everything waiting, e.g. when the user stops the agent.
+    pub fn reject_all(&self, reason: &str) {
+        let mut state = self.state.lock().unwrap();
+        let ids: Vec<u64> = state.pending.drain(..).map(|pending| pending.id).collect();
+        for id in ids {
+            state.decisions.insert(id, ApprovalDecision::Reject(reason.to_string()));
+        }
+        self.decided.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ask for `command` on another thread, answer it with `decide` once it is pending,
+    /// and return what the asking thread got back.
+    fn request_and_decide(queue: &CommandApprovalQueue, command: &str, decide: impl FnOnce(&PendingCommand) -> ApprovalDecision) -> ApprovalDecision {
+        std::thread::scope(|scope| {
+            let asking = scope.spawn(|| queue.request(command, PathBuf::from("/work"), "not on the allow list"));
+            let pending = loop {
+                if let Some(pending) = queue.pending().into_iter().next() {
+                    break pending;
+                }
+                std::thread::sleep(Duration::from_millis(1));
+            };
+            assert_eq!((pending.instruction.text.as_str(), pending.reason.as_str()), (command, "not on the allow list"));
+            queue.decide(pending.id, decide(&pending)).unwrap();
+            asking.join().unwrap()
+        })
+    }
+
+    #[test]
+    fn test_request_returns_the_users_decision() {
+        let queue = CommandApprovalQueue::default();
+        assert_eq!(request_and_decide(&queue, "cargo test", |_| ApprovalDecision::Approve), ApprovalDecision::Approve);
+        let edited = request_and_decide(&queue, "rm -rf target", |_| ApprovalDecision::Edit("cargo clean".to_string()));
+        assert_eq!(edited, ApprovalDecision::Edit("cargo clean".to_string()));
+        let rejected = request_and_decide(&queue, "git push", |_| ApprovalDecision::Reject("not yet".to_string()));
+        assert_eq!(rejected, ApprovalDecision::Reject("not yet".to_string()));
+
+        assert!(queue.is_empty());
+        assert!(queue.decide(1, ApprovalDecision::Approve).is_err());
+    }
+
+    #[test]
+    fn test_request_times_out_as_rejected() {
+        let queue = CommandApprovalQueue::new(Duration::from_millis(20));
+        let decision = queue.request("cargo test", PathBuf::from("/work"), "not on the allow list");
+        assert!(matches!(decision, ApprovalDecision::Reject(reason) if reason.contains("timed out")));
+        assert!(queue.is_empty());
+    }
+}
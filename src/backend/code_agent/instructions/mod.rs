@@ -0,0 +1,3 @@
+pub mod agent_instruction_logic;
+pub mod command_approval;
+pub mod project_instructions;
@@ -0,0 +1,216 @@
+//! Instruction files added to every agent prompt.
+//!
+//! Markdown files are looked up in three places, lowest priority first:
+//! the user's `~/.jadio/JADIO.md` and `~/.jadio/instructions/*.md`, the
+//! workspace's `.jadio/instructions/*.md` and `JADIO.md`, and the `JADIO.md` of
+//! each directory between the workspace root and the file being edited. They
+//! are merged in that order so the most specific instructions come last and
+//! win. Files are re-read whenever they change on disk.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Name of an instruction file in a workspace or any of its directories.
+pub const INSTRUCTION_FILE: &str = "JADIO.md";
+/// Directory of further instruction files, in the workspace's `.jadio` and in `~/.jadio`.
+const INSTRUCTION_DIR: &str = "instructions";
+/// Longest instruction file read, in characters; the rest is cut off.
+const MAX_INSTRUCTION_LEN: usize = 16_000;
+
+/// Where an instruction file was found, lowest priority first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InstructionScope {
+    User,
+    Project,
+    /// A directory inside the workspace that holds the edited file.
+    Directory,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstructionFile {
+    pub path: PathBuf,
+    pub scope: InstructionScope,
+    pub content: String,
+    /// Cut to [`MAX_INSTRUCTION_LEN`].
+    pub truncated: bool,
+    modified: Option<SystemTime>,
+}
+
+/// The instruction files that apply to the current workspace and file.
+#[derive(Debug, Clone, Default)]
+pub struct ProjectInstructions {
+    user_dir: Option<PathBuf>,
+    root: Option<PathBuf>,
+    files: Vec<InstructionFile>,
+}
+
+impl ProjectInstructions {
+    /// Instructions from `user_dir` (usually [`Self::default_user_dir`]) and, once set, a workspace.
+    pub fn new(user_dir: Option<PathBuf>) -> Self {
+        Self { user_dir, root: None, files: Vec::new() }
+    }
+
+    /// `~/.jadio`.
+    pub fn default_user_dir() -> Option<PathBuf> {
+        dirs::home_dir().map(|home| home.join(".jadio"))
+    }
+
+    pub fn set_root(&mut self, root: Option<PathBuf>) {
+        self.root = root;
+    }
+
+    /// Look for instruction files again, reading the ones that are new or changed.
+    /// Returns whether the instructions changed.
+    pub fn refresh(&mut self, current_file: Option<&Path>) -> bool {
+        let found = self.discover(current_file);
+        let mut changed = found.len() != self.files.len();
+        let mut files = Vec::with_capacity(found.len());
+        for (index, (path, scope)) in found.into_iter().enumerate() {
+            let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+            let cached = self.files.iter().position(|file| file.path == path && file.scope == scope && file.modified == modified);
+            match cached {
+                Some(cached) => {
+                    changed |= cached != index;
+                    files.push(self.files[cached].clone());
+                }
+                None => {
+                    // Unreadable files are skipped until they change.
+                    let Ok(text) = fs::read_to_string(&path) else { continue };
+                    let (content, truncated) = match text.char_indices().nth(MAX_INSTRUCTION_LEN) {
+                        Some((end, _)) => (text[..end].to_string(), true),
+                        None => (text, false),
+                    };
+                    changed = true;
+                    files.push(InstructionFile { path, scope, content, truncated, modified });
+                }
+            }
+        }
+        self.files = files;
+        changed
+    }
+
+    /// Active files, lowest priority first.
+    pub fn files(&self) -> &[InstructionFile] {
+        &self.files
+    }
+
+    /// All instructions as one block for the system prompt, empty if there are none.
+    pub fn merged(&self) -> String {
+        let files: Vec<&InstructionFile> = self.files.iter().filter(|file| !file.content.trim().is_empty()).collect();
+        if files.is_empty() {
+            return String::new();
+        }
+        let mut merged = String::from("# Instructions\nFollow these instructions from the user and the project. Where they disagree, later ones take precedence.\n");
+        for file in files {
+            merged.push_str(&format!("\n## From {}\n{}\n", self.display_path(&file.path), file.content.trim()));
+            if file.truncated {
+                merged.push_str("(truncated)\n");
+            }
+        }
+        merged
+    }
+
+    /// A path relative to the workspace, or with `~` for the user's directory.
+    pub fn display_path(&self, path: &Path) -> String {
+        if let Some(relative) = self.root.as_deref().and_then(|root| path.strip_prefix(root).ok()) {
+            return relative.to_string_lossy().replace('\\', "/");
+        }
+        if let Some(relative) = self.user_dir.as_deref().and_then(|dir| path.strip_prefix(dir).ok()) {
+            return format!("~/.jadio/{}", relative.to_string_lossy().replace('\\', "/"));
+        }
+        path.display().to_string()
+    }
+
+    /// Instruction files in priority order, lowest first.
+    fn discover(&self, current_file: Option<&Path>) -> Vec<(PathBuf, InstructionScope)> {
+        let mut found = Vec::new();
+        if let Some(user_dir) = &self.user_dir {
+            push_file(user_dir.join(INSTRUCTION_FILE), InstructionScope::User, &mut found);
+            push_markdown_files(&user_dir.join(INSTRUCTION_DIR), InstructionScope::User, &mut found);
+        }
+        let Some(root) = &self.root else {
+            return found;
+        };
+        // The root's JADIO.md comes after `.jadio/instructions`, as the file people edit most.
+        push_markdown_files(&root.join(".jadio").join(INSTRUCTION_DIR), InstructionScope::Project, &mut found);
+        push_file(root.join(INSTRUCTION_FILE), InstructionScope::Project, &mut found);
+
+        // Directories between the root and the current file, outermost first.
+        let Some(relative) = current_file.and_then(|file| file.strip_prefix(root).ok()).and_then(Path::parent) else {
+            return found;
+        };
+        let mut dir = root.clone();
+        for component in relative.components() {
+            dir.push(component);
+            push_file(dir.join(INSTRUCTION_FILE), InstructionScope::Directory, &mut found);
+        }
+        found
+    }
+}
+
+fn push_file(path: PathBuf, scope: InstructionScope, found: &mut Vec<(PathBuf, InstructionScope)>) {
+    if path.is_file() {
+        found.push((path, scope));
+    }
+}
+
+/// The Markdown files in `dir`, by name.
+fn push_markdown_files(dir: &Path, scope: InstructionScope, found: &mut Vec<(PathBuf, InstructionScope)>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("md")))
+        .collect();
+    files.sort();
+    found.extend(files.into_iter().map(|path| (path, scope)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::code_agent::test_support::TempDir;
+
+    #[test]
+    fn test_merges_user_project_and_directory_instructions() {
+        let temp = TempDir::new("instructions");
+        let base = temp.path().to_path_buf();
+        let (home, root) = (base.join("home"), base.join("project"));
+        fs::create_dir_all(home.join("instructions")).unwrap();
+        fs::create_dir_all(root.join(".jadio/instructions")).unwrap();
+        fs::create_dir_all(root.join("src/ui")).unwrap();
+        fs::write(home.join("JADIO.md"), "Answer briefly.").unwrap();
+        fs::write(home.join("instructions/notes.txt"), "Not markdown.").unwrap();
+        fs::write(root.join(".jadio/instructions/b-testing.md"), "Run cargo test.").unwrap();
+        fs::write(root.join(".jadio/instructions/a-style.md"), "Use four spaces.").unwrap();
+        fs::write(root.join("JADIO.md"), "Errors are strings.").unwrap();
+        fs::write(root.join("src/JADIO.md"), "Backend code has no egui.").unwrap();
+        fs::write(root.join("src/ui/JADIO.md"), "Panels take &mut Ui.").unwrap();
+
+        let mut instructions = ProjectInstructions::new(Some(home.clone()));
+        instructions.set_root(Some(root.clone()));
+        assert!(instructions.refresh(Some(&root.join("src/main.rs"))));
+        let paths: Vec<String> = instructions.files().iter().map(|file| instructions.display_path(&file.path)).collect();
+        assert_eq!(paths, vec!["~/.jadio/JADIO.md", ".jadio/instructions/a-style.md", ".jadio/instructions/b-testing.md", "JADIO.md", "src/JADIO.md"]);
+        let merged = instructions.merged();
+        assert!(merged.find("Answer briefly.").unwrap() < merged.find("Backend code has no egui.").unwrap());
+        assert!(merged.contains("## From src/JADIO.md\nBackend code has no egui.\n"));
+        assert!(!instructions.refresh(Some(&root.join("src/main.rs"))));
+
+        // Nested files follow the edited file, and edits are picked up.
+        assert!(instructions.refresh(Some(&root.join("src/ui/panel.rs"))));
+        assert_eq!(instructions.files().last().unwrap().scope, InstructionScope::Directory);
+        fs::write(root.join("JADIO.md"), "Errors are Box<dyn Error>.").unwrap();
+        // Set the time explicitly, as a quick rewrite can keep the same timestamp.
+        let file = fs::File::options().write(true).open(root.join("JADIO.md")).unwrap();
+        file.set_modified(SystemTime::now() + std::time::Duration::from_secs(5)).unwrap();
+        assert!(instructions.refresh(Some(&root.join("src/ui/panel.rs"))));
+        assert!(instructions.merged().contains("Errors are Box<dyn Error>."));
+        fs::remove_file(root.join("src/JADIO.md")).unwrap();
+        assert!(instructions.refresh(Some(&root.join("src/ui/panel.rs"))));
+        assert_eq!(instructions.files().len(), 5);
+    }
+}
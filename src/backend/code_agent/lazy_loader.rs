@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::path::PathBuf;
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct LazyResource<T> {
     pub id: String,
     pub loader: Arc<dyn Fn() -> Result<T, String> + Send + Sync>,
@@ -1,87 +1,284 @@
+//! Long-term agent memory, kept per project in `.jadio/memory.json`.
+//!
+//! Entries are short typed notes — facts about the code, conventions to follow,
+//! snippets worth reusing and decisions already made — written by the agent
+//! through the `remember` tool or by the user. The entries most related to a
+//! request are added to its prompt.
+
+use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+const MEMORY_FILE: &str = ".jadio/memory.json";
+/// Longest entry, in characters; memory is for notes, not whole files.
+pub const MAX_ENTRY_LEN: usize = 2000;
+/// Words too common to say whether an entry is related to a request.
+const STOP_WORDS: &[&str] = &["the", "and", "for", "with", "this", "that", "from", "what", "how", "why", "are", "not", "use", "should"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MemoryKind {
+    /// Something true about the project, e.g. where settings are stored.
+    Fact,
+    /// A rule the code follows, e.g. how errors are returned.
+    Convention,
+    /// Code worth reusing.
+    Snippet,
+    /// A choice that was made, and why.
+    Decision,
+}
+
+impl MemoryKind {
+    pub const ALL: [MemoryKind; 4] = [MemoryKind::Fact, MemoryKind::Convention, MemoryKind::Snippet, MemoryKind::Decision];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MemoryKind::Fact => "fact",
+            MemoryKind::Convention => "convention",
+            MemoryKind::Snippet => "snippet",
+            MemoryKind::Decision => "decision",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+/// Who wrote an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MemorySource {
+    Agent,
+    User,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryEntry {
+    pub id: u64,
+    pub kind: MemoryKind,
+    pub content: String,
+    /// Lowercase, without `#`.
+    pub tags: Vec<String>,
+    pub source: MemorySource,
+    pub created: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+}
 
-/// AgentMemory manages a temporary folder for storing files when memory is enabled.
+/// A project's memory entries, saved to its memory file on every change.
+#[derive(Debug, Clone, Default)]
 pub struct AgentMemory {
-    enabled: bool,
-    temp_dir: Option<PathBuf>,
+    /// `None` keeps entries in memory only.
+    path: Option<PathBuf>,
+    entries: Vec<MemoryEntry>,
+    /// Why the memory file couldn't be loaded. While set, nothing is saved over it.
+    load_error: Option<String>,
 }
 
 impl AgentMemory {
-    /// Create a new AgentMemory, disabled by default.
-    pub fn new() -> Self {
-        Self {
-            enabled: false,
-            temp_dir: None,
+    /// Load the project's memory; a missing file gives an empty memory.
+    ///
+    /// A file that can't be read or parsed also gives an empty memory, but is
+    /// left alone: changes fail until it is fixed or purged.
+    pub fn for_project(root: &Path) -> Self {
+        let path = root.join(MEMORY_FILE);
+        let loaded = match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| format!("{} is not valid memory: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        match loaded {
+            Ok(entries) => Self { path: Some(path), entries, load_error: None },
+            Err(e) => Self { path: Some(path), entries: Vec::new(), load_error: Some(e) },
         }
     }
 
-    /// Enable memory: create a temp folder if not already present.
-    pub fn enable(&mut self) -> std::io::Result<()> {
-        if !self.enabled {
-            let dir = std::env::temp_dir().join("jadio_agent_memory");
-            if !dir.exists() {
-                fs::create_dir_all(&dir)?;
-            }
-            self.temp_dir = Some(dir);
-            self.enabled = true;
-        }
-        Ok(())
+    /// Why the memory file couldn't be loaded, if it couldn't.
+    pub fn load_error(&self) -> Option<&str> {
+        self.load_error.as_deref()
     }
 
-    /// Disable memory: remove the temp folder and clear state.
-    pub fn disable(&mut self) -> std::io::Result<()> {
-        if let Some(ref dir) = self.temp_dir {
-            if dir.exists() {
-                fs::remove_dir_all(dir)?;
+    /// Oldest first.
+    pub fn entries(&self) -> &[MemoryEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, id: u64) -> Option<&MemoryEntry> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    /// Add an entry and return its id. Adding what is already remembered only merges the tags.
+    pub fn add(&mut self, kind: MemoryKind, content: &str, tags: &[String], source: MemorySource) -> Result<u64, String> {
+        let content = check_content(content)?;
+        let tags = normalize_tags(tags);
+        let now = Utc::now();
+        let id = match self.entries.iter_mut().find(|entry| entry.kind == kind && entry.content == content) {
+            Some(existing) => {
+                for tag in tags {
+                    if !existing.tags.contains(&tag) {
+                        existing.tags.push(tag);
+                    }
+                }
+                existing.updated = now;
+                existing.id
             }
+            None => {
+                let id = self.entries.iter().map(|entry| entry.id).max().unwrap_or(0) + 1;
+                self.entries.push(MemoryEntry { id, kind, content, tags, source, created: now, updated: now });
+                id
+            }
+        };
+        self.save()?;
+        Ok(id)
+    }
+
+    /// Replace an entry's kind, content and tags.
+    pub fn update(&mut self, id: u64, kind: MemoryKind, content: &str, tags: &[String]) -> Result<(), String> {
+        let content = check_content(content)?;
+        let entry = self.entries.iter_mut().find(|entry| entry.id == id).ok_or_else(|| format!("No memory entry {}", id))?;
+        entry.kind = kind;
+        entry.content = content;
+        entry.tags = normalize_tags(tags);
+        entry.updated = Utc::now();
+        self.save()
+    }
+
+    pub fn remove(&mut self, id: u64) -> Result<(), String> {
+        let before = self.entries.len();
+        self.entries.retain(|entry| entry.id != id);
+        if self.entries.len() == before {
+            return Err(format!("No memory entry {}", id));
         }
-        self.temp_dir = None;
-        self.enabled = false;
-        Ok(())
+        self.save()
     }
 
-    /// Store a file in the temp folder (if enabled).
-    pub fn store_file(&self, filename: &str, contents: &[u8]) -> std::io::Result<()> {
-        if let (true, Some(ref dir)) = (self.enabled, &self.temp_dir) {
-            let file_path = dir.join(filename);
-            fs::write(file_path, contents)?;
+    /// Forget everything, deleting the memory file even if it couldn't be loaded.
+    pub fn purge(&mut self) -> Result<(), String> {
+        self.entries.clear();
+        if let Some(path) = self.path.as_ref().filter(|path| path.exists()) {
+            fs::remove_file(path).map_err(|e| format!("Failed to delete {}: {}", path.display(), e))?;
         }
+        self.load_error = None;
         Ok(())
     }
 
-    /// List files in the temp folder (if enabled).
-    pub fn list_files(&self) -> std::io::Result<Vec<String>> {
-        if let (true, Some(ref dir)) = (self.enabled, &self.temp_dir) {
-            let mut files = Vec::new();
-            for entry in fs::read_dir(dir)? {
-                let entry = entry?;
-                if entry.file_type()?.is_file() {
-                    if let Some(name) = entry.file_name().to_str() {
-                        files.push(name.to_string());
-                    }
+    /// The entries most related to `query`, best first.
+    ///
+    /// Entries score by the query words in their content, with tags counting double.
+    /// Conventions apply everywhere, so they are included even without a match
+    /// while there is room.
+    pub fn relevant(&self, query: &str, limit: usize) -> Vec<MemoryEntry> {
+        let words = keywords(query);
+        let mut scored: Vec<(f32, &MemoryEntry)> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let content = keywords(&entry.content);
+                let mut score = words.iter().filter(|word| content.contains(*word)).count() as f32;
+                score += 2.0 * words.iter().filter(|word| entry.tags.contains(word)).count() as f32;
+                if entry.kind == MemoryKind::Convention {
+                    score += 0.5;
                 }
-            }
-            Ok(files)
-        } else {
-            Ok(Vec::new())
+                (score, entry)
+            })
+            .filter(|(score, _)| *score > 0.0)
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then(b.1.updated.cmp(&a.1.updated)));
+        scored.into_iter().take(limit).map(|(_, entry)| entry.clone()).collect()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(e) = &self.load_error {
+            return Err(format!("{}; fix or purge it before changing the memory", e));
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
         }
+        let json = serde_json::to_string_pretty(&self.entries).map_err(|e| format!("Failed to serialize memory: {}", e))?;
+        // Write then rename so a crash never leaves a truncated memory file.
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, json).map_err(|e| format!("Failed to write {}: {}", temp.display(), e))?;
+        fs::rename(&temp, path).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+fn check_content(content: &str) -> Result<String, String> {
+    let content = content.trim();
+    if content.is_empty() {
+        return Err("Memory entry is empty".to_string());
+    }
+    if content.chars().count() > MAX_ENTRY_LEN {
+        return Err(format!("Memory entry is longer than {} characters", MAX_ENTRY_LEN));
     }
+    Ok(content.to_string())
+}
 
-    /// Read a file from the temp folder (if enabled).
-    pub fn read_file(&self, filename: &str) -> std::io::Result<Option<Vec<u8>>> {
-        if let (true, Some(ref dir)) = (self.enabled, &self.temp_dir) {
-            let file_path = dir.join(filename);
-            if file_path.exists() {
-                let data = fs::read(file_path)?;
-                return Ok(Some(data));
-            }
+fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().trim_start_matches('#').to_lowercase();
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
         }
-        Ok(None)
     }
+    normalized
+}
+
+/// Lowercase words of three or more letters, without stop words.
+fn keywords(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .map(str::to_lowercase)
+        .filter(|word| word.chars().count() >= 3 && !STOP_WORDS.contains(&word.as_str()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::code_agent::test_support::TempDir;
+
+    #[test]
+    fn test_memory_persists_merges_and_ranks_entries() {
+        let temp = TempDir::new("memory");
+        let root = temp.path().to_path_buf();
+        let mut memory = AgentMemory::for_project(&root);
+        let tags = |tags: &[&str]| tags.iter().map(|tag| tag.to_string()).collect::<Vec<_>>();
+        let errors = memory.add(MemoryKind::Convention, "Return errors as Result<_, String>.", &tags(&["Errors"]), MemorySource::User).unwrap();
+        let settings = memory.add(MemoryKind::Fact, "Settings are saved to settings.json in the config dir.", &tags(&["#settings"]), MemorySource::Agent).unwrap();
+        memory.add(MemoryKind::Decision, "Chats are stored as JSON Lines so appends are cheap.", &[], MemorySource::Agent).unwrap();
+        // Remembering the same thing again only adds tags.
+        assert_eq!(memory.add(MemoryKind::Fact, " Settings are saved to settings.json in the config dir. ", &tags(&["config"]), MemorySource::Agent), Ok(settings));
+        assert!(memory.add(MemoryKind::Fact, "  ", &[], MemorySource::Agent).is_err());
+
+        let memory = AgentMemory::for_project(&root);
+        assert_eq!(memory.entries().len(), 3);
+        assert_eq!(memory.get(settings).unwrap().tags, vec!["settings", "config"]);
+        let ids = |entries: Vec<MemoryEntry>| entries.into_iter().map(|entry| entry.id).collect::<Vec<_>>();
+        // The tag match ranks first; the convention comes along as there is room.
+        assert_eq!(ids(memory.relevant("Where are the settings saved?", 5)), vec![settings, errors]);
+        assert_eq!(ids(memory.relevant("Where are the settings saved?", 1)), vec![settings]);
+
+        let mut memory = memory;
+        memory.update(errors, MemoryKind::Convention, "Return errors as Box<dyn Error> in the UI layer.", &tags(&["errors", "ui"])).unwrap();
+        memory.remove(settings).unwrap();
+        assert!(memory.remove(settings).is_err());
+        let reloaded = AgentMemory::for_project(&root);
+        assert_eq!((reloaded.entries().len(), reloaded.get(errors).unwrap().tags.len()), (2, 2));
+        memory.purge().unwrap();
+        assert!(AgentMemory::for_project(&root).entries().is_empty());
 
-    /// Check if memory is enabled.
-    pub fn is_enabled(&self) -> bool {
-        self.enabled
+        // A file that doesn't parse is reported and never saved over.
+        fs::write(root.join(MEMORY_FILE), "[{\"id\": 1, \"kind\": \"fact\"").unwrap();
+        let mut broken = AgentMemory::for_project(&root);
+        assert!(broken.load_error().is_some());
+        assert!(broken.add(MemoryKind::Fact, "Overwrites the file.", &[], MemorySource::Agent).is_err());
+        assert!(fs::read_to_string(root.join(MEMORY_FILE)).unwrap().starts_with("[{\"id\": 1,"));
+        broken.purge().unwrap();
+        assert!(broken.add(MemoryKind::Fact, "Starts over.", &[], MemorySource::Agent).is_ok());
     }
 }
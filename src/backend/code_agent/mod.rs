@@ -1,13 +1,29 @@
 pub mod agent;
+pub mod agent_loop;
 pub mod agent_server_logic;
 pub mod autoprompt;
 pub mod chat;
+pub mod chat_store;
 pub mod code_agent_logic;
+pub mod command_policy;
+pub mod completion;
 pub mod context;
+pub mod context_builder;
+pub mod diff;
+pub mod edit_proposal;
 pub mod files_changed;
 pub mod hot_swapper;
 pub mod lazy_loader;
 pub mod model_loader;
+pub mod providers;
+pub mod semantic_index;
+pub mod server_auth;
+pub mod symbol_index;
+#[cfg(test)]
+pub mod test_support;
+pub mod tokenizer;
+pub mod usage;
+pub mod websocket;
 pub mod instructions;
 pub mod memory;
 pub mod tools;
@@ -1,6 +1,8 @@
 use std::path::PathBuf;
 use std::collections::HashMap;
+use std::sync::Arc;
 use serde::{Serialize, Deserialize};
+use super::providers::{self, Provider};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelConfig {
@@ -34,7 +36,8 @@ pub struct ModelParameters {
 pub struct ModelLoader {
     models: HashMap<String, ModelConfig>,
     active_model: Option<String>,
-    model_cache: HashMap<String, Box<dyn std::any::Any + Send + Sync>>,
+    /// Clients for models that have been loaded, by model name.
+    model_cache: HashMap<String, Arc<dyn Provider>>,
 }
 
 impl Default for ModelParameters {
@@ -126,6 +129,7 @@ impl ModelLoader {
     }
     
     pub fn register_model(&mut self, config: ModelConfig) {
+        self.model_cache.remove(&config.name);
         self.models.insert(config.name.clone(), config);
     }
     
@@ -143,12 +147,21 @@ impl ModelLoader {
                     }
                 }
                 ModelProvider::Local => {
-                    if config.model_path.is_none() {
-                        return Err(format!("Model path required for local model '{}'", name));
+                    if config.model_path.is_none() && config.api_endpoint.is_none() {
+                        return Err(format!("Model path or server endpoint required for local model '{}'", name));
                     }
                 }
                 _ => {}
             }
+            
+            if !self.model_cache.contains_key(name) {
+                let client = providers::create_provider(
+                    &config.provider,
+                    config.api_key.as_deref().unwrap_or_default(),
+                    config.api_endpoint.as_deref(),
+                );
+                self.model_cache.insert(name.to_string(), client);
+            }
         }
         
         self.active_model = Some(name.to_string());
@@ -160,9 +173,18 @@ impl ModelLoader {
             .and_then(|name| self.models.get(name))
     }
     
+    /// The client for the active model.
+    pub fn get_active_provider(&self) -> Option<Arc<dyn Provider>> {
+        self.active_model.as_ref()
+            .and_then(|name| self.model_cache.get(name))
+            .cloned()
+    }
+    
     pub fn set_model_api_key(&mut self, model_name: &str, api_key: String) -> Result<(), String> {
         if let Some(config) = self.models.get_mut(model_name) {
             config.api_key = Some(api_key);
+            // Rebuild the client with the new key next time the model is loaded
+            self.model_cache.remove(model_name);
             Ok(())
         } else {
             Err(format!("Model '{}' not found", model_name))
@@ -208,8 +230,8 @@ impl ModelLoader {
                 }
             }
             ModelProvider::Local => {
-                if config.model_path.is_none() {
-                    errors.push("Model path required for local models".to_string());
+                if config.model_path.is_none() && config.api_endpoint.is_none() {
+                    errors.push("Model path or server endpoint required for local models".to_string());
                 } else if let Some(path) = &config.model_path {
                     if !path.exists() {
                         errors.push(format!("Model file not found: {:?}", path));
@@ -257,4 +279,29 @@ impl ModelLoader {
     pub fn clear_cache(&mut self) {
         self.model_cache.clear();
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_model_switches_client() {
+        let mut loader = ModelLoader::new();
+        loader.register_model(ModelConfig {
+            name: "llama3".to_string(),
+            provider: ModelProvider::Local,
+            api_endpoint: Some("http://localhost:11434/api/chat".to_string()),
+            api_key: None,
+            model_path: None,
+            parameters: ModelParameters::default(),
+        });
+        loader.set_model_api_key("gpt-4", "sk-test".to_string()).unwrap();
+
+        loader.load_model("llama3").unwrap();
+        assert!(format!("{:?}", loader.get_active_provider().unwrap()).starts_with("OllamaClient"));
+        loader.load_model("gpt-4").unwrap();
+        assert!(format!("{:?}", loader.get_active_provider().unwrap()).starts_with("OpenAiClient"));
+        assert!(loader.load_model("claude-3-opus").is_err());
+        assert_eq!(loader.get_active_model().unwrap().name, "gpt-4");
+    }
+}
@@ -0,0 +1,369 @@
+use std::time::Duration;
+use serde_json::{json, Value};
+use super::sse::{SseEvent, SseParser};
+use super::{CompletionRequest, CompletionResponse, PendingToolCalls, Provider, ProviderError, ProviderFuture, RequestMessage};
+use crate::backend::code_agent::chat::MessageRole;
+
+pub const DEFAULT_ENDPOINT: &str = "https://api.anthropic.com/v1/messages";
+const API_VERSION: &str = "2023-06-01";
+
+/// Streaming client for the Anthropic Messages API.
+#[derive(Clone)]
+pub struct AnthropicClient {
+    http: reqwest::Client,
+    endpoint: String,
+    api_key: String,
+    /// Retries for rate limits, overload and 5xx errors before any text has streamed.
+    pub max_retries: u32,
+    /// First backoff delay when the server gives no `retry-after`; doubles each retry.
+    pub retry_base_delay: Duration,
+}
+
+impl std::fmt::Debug for AnthropicClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // Keep the API key out of logs.
+        f.debug_struct("AnthropicClient")
+            .field("endpoint", &self.endpoint)
+            .field("has_api_key", &!self.api_key.is_empty())
+            .field("max_retries", &self.max_retries)
+            .finish()
+    }
+}
+
+impl AnthropicClient {
+    /// Create a client. An empty key falls back to the `ANTHROPIC_API_KEY` environment variable.
+    pub fn new(api_key: &str, endpoint: Option<&str>) -> Self {
+        let api_key = match api_key.trim() {
+            "" => std::env::var("ANTHROPIC_API_KEY").unwrap_or_default(),
+            key => key.to_string(),
+        };
+        Self {
+            http: reqwest::Client::new(),
+            endpoint: endpoint.unwrap_or(DEFAULT_ENDPOINT).to_string(),
+            api_key,
+            max_retries: 3,
+            retry_base_delay: Duration::from_secs(1),
+        }
+    }
+
+    /// Build the JSON body for a streamed Messages API call.
+    ///
+    /// Only `temperature` is sent: the API rejects setting both it and `top_p` on newer models.
+    pub fn request_body(request: &CompletionRequest) -> Value {
+        let mut turns: Vec<(&str, Vec<Value>)> = Vec::new();
+        for message in &request.messages {
+            let role = match message.role {
+                MessageRole::Assistant => "assistant",
+                _ => "user",
+            };
+            let blocks = Self::content_blocks(message);
+            // The API requires alternating roles, so merge consecutive turns from the same side.
+            match turns.last_mut() {
+                Some((last_role, last_blocks)) if *last_role == role => {
+                    for block in blocks {
+                        match (last_blocks.last_mut(), block["type"].as_str()) {
+                            (Some(last), Some("text")) if last["type"] == "text" => {
+                                let merged = format!("{}\n\n{}", last["text"].as_str().unwrap_or_default(), block["text"].as_str().unwrap_or_default());
+                                last["text"] = Value::String(merged);
+                            }
+                            _ => last_blocks.push(block),
+                        }
+                    }
+                }
+                _ => turns.push((role, blocks)),
+            }
+        }
+        // ...and start with a user turn.
+        if turns.first().is_some_and(|(role, _)| *role == "assistant") {
+            turns.remove(0);
+        }
+        let messages: Vec<Value> = turns
+            .into_iter()
+            .map(|(role, mut blocks)| {
+                // Plain text turns are sent as a string.
+                let content = match blocks.as_mut_slice() {
+                    [block] if block["type"] == "text" => block["text"].take(),
+                    _ => Value::Array(blocks),
+                };
+                json!({ "role": role, "content": content })
+            })
+            .collect();
+
+        let mut body = json!({
+            "model": request.model,
+            "max_tokens": request.max_tokens,
+            "temperature": request.temperature,
+            "messages": messages,
+            "stream": true,
+        });
+        if let Some(system) = &request.system {
+            body["system"] = Value::String(system.clone());
+        }
+        if !request.stop_sequences.is_empty() {
+            body["stop_sequences"] = json!(request.stop_sequences);
+        }
+        if !request.tools.is_empty() {
+            let tools: Vec<Value> = request
+                .tools
+                .iter()
+                .map(|tool| json!({ "name": tool.name, "description": tool.description, "input_schema": tool.input_schema }))
+                .collect();
+            body["tools"] = Value::Array(tools);
+        }
+        body
+    }
+
+    /// Content blocks for one message. Tool results go first, as the API requires.
+    fn content_blocks(message: &RequestMessage) -> Vec<Value> {
+        let mut blocks: Vec<Value> = message
+            .tool_results
+            .iter()
+            .map(|result| json!({ "type": "tool_result", "tool_use_id": result.call_id, "content": result.content, "is_error": result.is_error }))
+            .collect();
+        if !message.content.is_empty() || (message.tool_calls.is_empty() && message.tool_results.is_empty()) {
+            blocks.push(json!({ "type": "text", "text": message.content }));
+        }
+        for call in &message.tool_calls {
+            blocks.push(json!({ "type": "tool_use", "id": call.id, "name": call.name, "input": call.input }));
+        }
+        blocks
+    }
+
+    /// Send a request and stream the reply, calling `on_text` with each text delta as it arrives.
+    pub async fn stream<F: FnMut(&str)>(&self, request: &CompletionRequest, mut on_text: F) -> Result<CompletionResponse, ProviderError> {
+        if self.api_key.is_empty() {
+            return Err(ProviderError::MissingApiKey);
+        }
+        let body = Self::request_body(request);
+        let response = super::send_with_retries(self.max_retries, self.retry_base_delay, || self.send(&body)).await?;
+        Self::read_stream(response, &mut on_text).await
+    }
+
+    async fn send(&self, body: &Value) -> Result<reqwest::Response, ProviderError> {
+        let response = self
+            .http
+            .post(&self.endpoint)
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", API_VERSION)
+            .header("accept", "text/event-stream")
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| ProviderError::Network(e.to_string()))?;
+        super::check_response(response).await
+    }
+
+    async fn read_stream<F: FnMut(&str)>(mut response: reqwest::Response, on_text: &mut F) -> Result<CompletionResponse, ProviderError> {
+        let mut parser = SseParser::new();
+        let mut result = CompletionResponse::default();
+        let mut tool_calls = PendingToolCalls::default();
+        loop {
+            let chunk = response.chunk().await.map_err(|e| ProviderError::Network(e.to_string()))?;
+            let events = match &chunk {
+                Some(bytes) => parser.feed(bytes),
+                None => parser.finish().into_iter().collect(),
+            };
+            for event in events {
+                if Self::handle_event(&event, &mut result, &mut tool_calls, on_text)? {
+                    result.tool_calls = tool_calls.finish()?;
+                    return Ok(result);
+                }
+            }
+            if chunk.is_none() {
+                return Err(ProviderError::Stream("Connection closed before the message finished".to_string()));
+            }
+        }
+    }
+
+    /// Apply one stream event. Returns `true` at `message_stop`.
+    fn handle_event<F: FnMut(&str)>(
+        event: &SseEvent,
+        result: &mut CompletionResponse,
+        tool_calls: &mut PendingToolCalls,
+        on_text: &mut F,
+    ) -> Result<bool, ProviderError> {
+        let data: Value = serde_json::from_str(&event.data)
+            .map_err(|e| ProviderError::Stream(format!("Invalid event data: {}", e)))?;
+        match data["type"].as_str().or(event.event.as_deref()).unwrap_or_default() {
+            "message_start" => {
+                let usage = &data["message"]["usage"];
+                result.usage.input_tokens = usage["input_tokens"].as_u64().unwrap_or(0) as u32;
+                result.usage.output_tokens = usage["output_tokens"].as_u64().unwrap_or(0) as u32;
+            }
+            "content_block_start" => {
+                let block = &data["content_block"];
+                if block["type"] == "tool_use" {
+                    let index = data["index"].as_u64().unwrap_or_default();
+                    tool_calls.update(index, block["id"].as_str(), block["name"].as_str(), None);
+                }
+            }
+            "content_block_delta" => {
+                let delta = &data["delta"];
+                match delta["type"].as_str() {
+                    Some("text_delta") => {
+                        if let Some(text) = delta["text"].as_str() {
+                            result.text.push_str(text);
+                            on_text(text);
+                        }
+                    }
+                    Some("input_json_delta") => {
+                        let index = data["index"].as_u64().unwrap_or_default();
+                        tool_calls.update(index, None, None, delta["partial_json"].as_str());
+                    }
+                    _ => {}
+                }
+            }
+            "message_delta" => {
+                if let Some(reason) = data["delta"]["stop_reason"].as_str() {
+                    result.stop_reason = Some(reason.to_string());
+                }
+                if let Some(tokens) = data["usage"]["output_tokens"].as_u64() {
+                    result.usage.output_tokens = tokens as u32;
+                }
+            }
+            "message_stop" => return Ok(true),
+            "error" => {
+                let message = data["error"]["message"].as_str().unwrap_or("Unknown error").to_string();
+                return Err(match data["error"]["type"].as_str() {
+                    Some("overloaded_error") => ProviderError::Overloaded(message),
+                    Some("rate_limit_error") => ProviderError::RateLimited { retry_after: None, message },
+                    _ => ProviderError::Stream(message),
+                });
+            }
+            _ => {}
+        }
+        Ok(false)
+    }
+}
+
+impl Provider for AnthropicClient {
+    fn stream<'a>(&'a self, request: &'a CompletionRequest, on_text: &'a mut (dyn FnMut(&str) + Send)) -> ProviderFuture<'a> {
+        Box::pin(AnthropicClient::stream(self, request, on_text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::code_agent::providers::test_server::{http_response, mock_server, request_body};
+    use crate::backend::code_agent::providers::{RequestMessage, ToolCall, ToolDefinition, ToolResult};
+
+    fn sse_response(events: &[Value]) -> String {
+        let body: String = events
+            .iter()
+            .map(|event| format!("event: {}\ndata: {}\n\n", event["type"].as_str().unwrap(), event))
+            .collect();
+        format!("HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n{}", body)
+    }
+
+    fn error_response(status: &str, headers: &str, error_type: &str, message: &str) -> String {
+        let body = json!({"type": "error", "error": {"type": error_type, "message": message}}).to_string();
+        http_response(status, headers, "application/json", &body)
+    }
+
+    fn request() -> CompletionRequest {
+        CompletionRequest {
+            model: "claude-test".to_string(),
+            system: Some("Be brief.".to_string()),
+            messages: vec![
+                RequestMessage::text(MessageRole::Assistant, "Hi!"),
+                RequestMessage::text(MessageRole::User, "Hello"),
+                RequestMessage::text(MessageRole::User, "Are you there?"),
+            ],
+            max_tokens: 64,
+            temperature: 0.2,
+            top_p: 0.9,
+            stop_sequences: vec!["END".to_string()],
+            tools: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_streams_text_after_rate_limit_retry() {
+        let rate_limited = error_response("429 Too Many Requests", "retry-after: 0\r\n", "rate_limit_error", "Slow down");
+        let stream = sse_response(&[
+            json!({"type": "message_start", "message": {"usage": {"input_tokens": 12, "output_tokens": 1}}}),
+            json!({"type": "ping"}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Yes, "}}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "I'm here."}}),
+            json!({"type": "message_delta", "delta": {"stop_reason": "end_turn"}, "usage": {"output_tokens": 6}}),
+            json!({"type": "message_stop"}),
+        ]);
+        let (url, server) = mock_server(vec![rate_limited, stream]);
+        let client = AnthropicClient::new("test-key", Some(&format!("{}/v1/messages", url)));
+
+        let mut deltas = Vec::new();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let response = runtime.block_on(client.stream(&request(), |text| deltas.push(text.to_string()))).unwrap();
+
+        assert_eq!(deltas, vec!["Yes, ", "I'm here."]);
+        assert_eq!(response.text, "Yes, I'm here.");
+        assert_eq!(response.stop_reason.as_deref(), Some("end_turn"));
+        assert_eq!((response.usage.input_tokens, response.usage.output_tokens), (12, 6));
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 2);
+        let request = requests[1].to_lowercase();
+        assert!(request.contains("x-api-key: test-key"));
+        assert!(request.contains("anthropic-version: 2023-06-01"));
+        let body = request_body(&requests[1]);
+        assert_eq!(body["system"], "Be brief.");
+        assert_eq!(body["stream"], true);
+        assert_eq!(body["messages"], json!([{"role": "user", "content": "Hello\n\nAre you there?"}]));
+        assert_eq!(body["stop_sequences"], json!(["END"]));
+    }
+
+    #[test]
+    fn test_reports_api_errors() {
+        let unauthorized = error_response("401 Unauthorized", "", "authentication_error", "invalid x-api-key");
+        let overloaded_mid_stream = sse_response(&[
+            json!({"type": "message_start", "message": {"usage": {"input_tokens": 3}}}),
+            json!({"type": "error", "error": {"type": "overloaded_error", "message": "Overloaded"}}),
+        ]);
+        let (url, server) = mock_server(vec![unauthorized, overloaded_mid_stream]);
+        let client = AnthropicClient::new("bad-key", Some(&format!("{}/v1/messages", url)));
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let error = runtime.block_on(client.stream(&request(), |_| {})).unwrap_err();
+        assert_eq!(error, ProviderError::Http { status: 401, message: "invalid x-api-key".to_string() });
+        let error = runtime.block_on(client.stream(&request(), |_| {})).unwrap_err();
+        assert_eq!(error, ProviderError::Overloaded("Overloaded".to_string()));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_tool_use_round_trip() {
+        let stream = sse_response(&[
+            json!({"type": "message_start", "message": {"usage": {"input_tokens": 20}}}),
+            json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Checking."}}),
+            json!({"type": "content_block_start", "index": 1, "content_block": {"type": "tool_use", "id": "toolu_1", "name": "read_file", "input": {}}}),
+            json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "{\"path\": \"src/"}}),
+            json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "main.rs\"}"}}),
+            json!({"type": "content_block_stop", "index": 1}),
+            json!({"type": "message_delta", "delta": {"stop_reason": "tool_use"}, "usage": {"output_tokens": 15}}),
+            json!({"type": "message_stop"}),
+        ]);
+        let (url, server) = mock_server(vec![stream]);
+        let client = AnthropicClient::new("test-key", Some(&format!("{}/v1/messages", url)));
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let response = runtime.block_on(client.stream(&request(), |_| {})).unwrap();
+        assert_eq!(response.text, "Checking.");
+        assert_eq!(response.tool_calls, vec![ToolCall { id: "toolu_1".to_string(), name: "read_file".to_string(), input: json!({"path": "src/main.rs"}) }]);
+        server.join().unwrap();
+
+        // Sending the call and its result back uses content blocks.
+        let mut request = request();
+        request.tools = vec![ToolDefinition { name: "read_file".to_string(), description: "Read".to_string(), input_schema: json!({"type": "object"}) }];
+        request.messages.push(RequestMessage { tool_calls: response.tool_calls.clone(), ..RequestMessage::text(MessageRole::Assistant, "Checking.") });
+        request.messages.push(RequestMessage {
+            tool_results: vec![ToolResult { call_id: "toolu_1".to_string(), name: "read_file".to_string(), content: "fn main() {}".to_string(), is_error: false }],
+            ..RequestMessage::text(MessageRole::User, "")
+        });
+        let body = AnthropicClient::request_body(&request);
+        assert_eq!(body["tools"][0]["input_schema"], json!({"type": "object"}));
+        assert_eq!(body["messages"][1]["content"][1], json!({"type": "tool_use", "id": "toolu_1", "name": "read_file", "input": {"path": "src/main.rs"}}));
+        assert_eq!(body["messages"][2]["content"], json!([{"type": "tool_result", "tool_use_id": "toolu_1", "content": "fn main() {}", "is_error": false}]));
+    }
+}
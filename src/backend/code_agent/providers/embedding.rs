@@ -0,0 +1,145 @@
+//! Text embeddings from the local model server, for semantic code search.
+//!
+//! Ollama serves them at `/api/embed`; OpenAI-compatible local servers
+//! (llama.cpp, vLLM, LM Studio) at `/v1/embeddings`.
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use serde_json::{json, Value};
+use super::ProviderError;
+use crate::backend::code_agent::model_loader::ModelProvider;
+
+pub const OLLAMA_EMBED_ENDPOINT: &str = "http://localhost:11434/api/embed";
+/// Longest an embedding request may take, so a stalled server fails searches instead of hanging them.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub type EmbedFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<Vec<f32>>, ProviderError>> + Send + 'a>>;
+
+/// A model backend that turns texts into vectors, one per input in order.
+pub trait EmbeddingProvider: fmt::Debug + Send + Sync {
+    fn embed<'a>(&'a self, model: &'a str, inputs: &'a [String]) -> EmbedFuture<'a>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EmbeddingApi {
+    Ollama,
+    OpenAi,
+}
+
+/// Create an embedding client for local and custom servers.
+///
+/// Code is only sent to a model running where the user configured it, so hosted providers get `None`.
+pub fn create_embedding_provider(provider: &ModelProvider, api_key: &str, endpoint: Option<&str>) -> Option<Arc<dyn EmbeddingProvider>> {
+    match provider {
+        ModelProvider::Local | ModelProvider::Custom(_) => Some(Arc::new(EmbeddingClient::new(api_key, endpoint))),
+        ModelProvider::Anthropic | ModelProvider::OpenAI => None,
+    }
+}
+
+#[derive(Clone)]
+pub struct EmbeddingClient {
+    http: reqwest::Client,
+    endpoint: String,
+    api_key: String,
+    api: EmbeddingApi,
+}
+
+impl fmt::Debug for EmbeddingClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Keep the API key out of logs.
+        f.debug_struct("EmbeddingClient")
+            .field("endpoint", &self.endpoint)
+            .field("api", &self.api)
+            .field("has_api_key", &!self.api_key.is_empty())
+            .finish()
+    }
+}
+
+impl EmbeddingClient {
+    /// Create a client for the server behind a chat `endpoint`, as [`super::fim::FimClient::new`] does.
+    pub fn new(api_key: &str, endpoint: Option<&str>) -> Self {
+        let (endpoint, api) = match endpoint.map(|url| url.trim_end_matches('/')) {
+            None => (OLLAMA_EMBED_ENDPOINT.to_string(), EmbeddingApi::Ollama),
+            Some(url) if super::ollama::is_native_endpoint(url) => (format!("{}/api/embed", url.trim_end_matches("/api/chat")), EmbeddingApi::Ollama),
+            Some(url) if url.ends_with("/api/embed") => (url.to_string(), EmbeddingApi::Ollama),
+            Some(url) => {
+                let base = url.trim_end_matches("/chat/completions").trim_end_matches("/completions").trim_end_matches("/embeddings");
+                (format!("{}/embeddings", base), EmbeddingApi::OpenAi)
+            }
+        };
+        let http = reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build().unwrap_or_default();
+        Self { http, endpoint, api_key: api_key.trim().to_string(), api }
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    pub async fn embed(&self, model: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>, ProviderError> {
+        let mut builder = self.http.post(&self.endpoint).json(&json!({ "model": model, "input": inputs }));
+        if !self.api_key.is_empty() {
+            builder = builder.bearer_auth(&self.api_key);
+        }
+        let response = builder.send().await.map_err(|e| ProviderError::Network(e.to_string()))?;
+        let response = super::check_response(response).await?;
+        let data: Value = response.json().await.map_err(|e| ProviderError::Stream(format!("Invalid embedding response: {}", e)))?;
+
+        let vectors: Option<Vec<Vec<f32>>> = match self.api {
+            EmbeddingApi::Ollama => data["embeddings"].as_array().map(|vectors| vectors.iter().filter_map(to_vector).collect()),
+            EmbeddingApi::OpenAi => data["data"].as_array().map(|items| {
+                let mut items: Vec<&Value> = items.iter().collect();
+                items.sort_by_key(|item| item["index"].as_u64().unwrap_or_default());
+                items.into_iter().filter_map(|item| to_vector(&item["embedding"])).collect()
+            }),
+        };
+        match vectors {
+            Some(vectors) if vectors.len() == inputs.len() => Ok(vectors),
+            _ => Err(ProviderError::Stream(format!("Expected {} embeddings in response", inputs.len()))),
+        }
+    }
+}
+
+fn to_vector(value: &Value) -> Option<Vec<f32>> {
+    value.as_array()?.iter().map(|x| x.as_f64().map(|x| x as f32)).collect()
+}
+
+impl EmbeddingProvider for EmbeddingClient {
+    fn embed<'a>(&'a self, model: &'a str, inputs: &'a [String]) -> EmbedFuture<'a> {
+        Box::pin(EmbeddingClient::embed(self, model, inputs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::code_agent::providers::test_server::{http_response, mock_server, request_body};
+
+    #[tokio::test]
+    async fn test_embeddings_from_ollama_and_openai_compatible_servers() {
+        let ollama = http_response("200 OK", "", "application/json", &json!({"embeddings": [[0.5, 1.0], [0.0, -1.0]]}).to_string());
+        let openai = http_response(
+            "200 OK",
+            "",
+            "application/json",
+            &json!({"data": [{"index": 1, "embedding": [0.0, -1.0]}, {"index": 0, "embedding": [0.5, 1.0]}]}).to_string(),
+        );
+        let (url, server) = mock_server(vec![ollama, openai]);
+        let inputs = vec!["fn a() {}".to_string(), "fn b() {}".to_string()];
+
+        let client = EmbeddingClient::new("", Some(&format!("{}/api/chat", url)));
+        assert_eq!(client.endpoint(), format!("{}/api/embed", url));
+        assert_eq!(client.embed("nomic-embed-text", &inputs).await.unwrap(), vec![vec![0.5, 1.0], vec![0.0, -1.0]]);
+
+        let client = EmbeddingClient::new("local-key", Some(&format!("{}/v1/chat/completions", url)));
+        assert_eq!(client.embed("nomic-embed-text", &inputs).await.unwrap()[0], vec![0.5, 1.0]);
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("POST /api/embed "));
+        assert_eq!(request_body(&requests[0])["input"][1], "fn b() {}");
+        assert!(requests[1].starts_with("POST /v1/embeddings "));
+        assert!(create_embedding_provider(&ModelProvider::OpenAI, "key", None).is_none());
+    }
+}
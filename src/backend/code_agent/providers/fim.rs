@@ -0,0 +1,178 @@
+//! Raw text completions for fill-in-the-middle prompts.
+//!
+//! Chat APIs wrap the prompt in the model's chat template, which breaks FIM
+//! control tokens, so these requests go to the raw endpoints instead: Ollama's
+//! `/api/generate` with `raw: true`, or `/v1/completions` on OpenAI-compatible
+//! local servers (llama.cpp, vLLM, LM Studio).
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use serde_json::{json, Value};
+use super::ProviderError;
+use crate::backend::code_agent::model_loader::ModelProvider;
+
+pub const OLLAMA_GENERATE_ENDPOINT: &str = "http://localhost:11434/api/generate";
+
+pub type FimFuture<'a> = Pin<Box<dyn Future<Output = Result<String, ProviderError>> + Send + 'a>>;
+
+/// A model backend that continues a raw prompt.
+pub trait FimProvider: fmt::Debug + Send + Sync {
+    fn complete<'a>(&'a self, request: &'a FimRequest) -> FimFuture<'a>;
+}
+
+/// A prompt already wrapped in the model's FIM tokens.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FimRequest {
+    pub model: String,
+    pub prompt: String,
+    pub max_tokens: u32,
+    pub temperature: f32,
+    pub stop: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FimApi {
+    OllamaGenerate,
+    OpenAiCompletions,
+}
+
+/// Create a raw completion client for local and custom servers.
+///
+/// Hosted providers only offer chat APIs for their current models, so they get `None`.
+pub fn create_fim_provider(provider: &ModelProvider, api_key: &str, endpoint: Option<&str>) -> Option<Arc<dyn FimProvider>> {
+    match provider {
+        ModelProvider::Local | ModelProvider::Custom(_) => Some(Arc::new(FimClient::new(api_key, endpoint))),
+        ModelProvider::Anthropic | ModelProvider::OpenAI => None,
+    }
+}
+
+/// Non-streaming client for raw completions; the whole suggestion is shown at once.
+#[derive(Clone)]
+pub struct FimClient {
+    http: reqwest::Client,
+    endpoint: String,
+    api_key: String,
+    api: FimApi,
+}
+
+impl fmt::Debug for FimClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Keep the API key out of logs.
+        f.debug_struct("FimClient")
+            .field("endpoint", &self.endpoint)
+            .field("api", &self.api)
+            .field("has_api_key", &!self.api_key.is_empty())
+            .finish()
+    }
+}
+
+impl FimClient {
+    /// Create a client for the server behind a chat `endpoint`.
+    ///
+    /// Ollama (no endpoint, or `/api/chat`) is asked at `/api/generate`; any other
+    /// server at the `/completions` endpoint next to its `/chat/completions`.
+    pub fn new(api_key: &str, endpoint: Option<&str>) -> Self {
+        let (endpoint, api) = match endpoint.map(|url| url.trim_end_matches('/')) {
+            None => (OLLAMA_GENERATE_ENDPOINT.to_string(), FimApi::OllamaGenerate),
+            Some(url) if super::ollama::is_native_endpoint(url) => (format!("{}/api/generate", url.trim_end_matches("/api/chat")), FimApi::OllamaGenerate),
+            Some(url) if url.ends_with("/api/generate") => (url.to_string(), FimApi::OllamaGenerate),
+            Some(url) => {
+                let base = url.trim_end_matches("/chat/completions").trim_end_matches("/completions");
+                (format!("{}/completions", base), FimApi::OpenAiCompletions)
+            }
+        };
+        Self { http: reqwest::Client::new(), endpoint, api_key: api_key.trim().to_string(), api }
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    pub fn request_body(&self, request: &FimRequest) -> Value {
+        match self.api {
+            FimApi::OllamaGenerate => json!({
+                "model": request.model,
+                "prompt": request.prompt,
+                "raw": true,
+                "stream": false,
+                "options": {
+                    "temperature": request.temperature,
+                    "num_predict": request.max_tokens,
+                    "stop": request.stop,
+                },
+            }),
+            FimApi::OpenAiCompletions => json!({
+                "model": request.model,
+                "prompt": request.prompt,
+                "max_tokens": request.max_tokens,
+                "temperature": request.temperature,
+                "stop": request.stop,
+                "stream": false,
+            }),
+        }
+    }
+
+    pub async fn complete(&self, request: &FimRequest) -> Result<String, ProviderError> {
+        let mut builder = self.http.post(&self.endpoint).json(&self.request_body(request));
+        if !self.api_key.is_empty() {
+            builder = builder.bearer_auth(&self.api_key);
+        }
+        let response = builder.send().await.map_err(|e| ProviderError::Network(e.to_string()))?;
+        let response = super::check_response(response).await?;
+        let data: Value = response.json().await.map_err(|e| ProviderError::Stream(format!("Invalid completion response: {}", e)))?;
+        let text = match self.api {
+            FimApi::OllamaGenerate => &data["response"],
+            FimApi::OpenAiCompletions => &data["choices"][0]["text"],
+        };
+        text.as_str().map(str::to_string).ok_or_else(|| ProviderError::Stream("No completion text in response".to_string()))
+    }
+}
+
+impl FimProvider for FimClient {
+    fn complete<'a>(&'a self, request: &'a FimRequest) -> FimFuture<'a> {
+        Box::pin(FimClient::complete(self, request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::code_agent::providers::test_server::{http_response, mock_server, request_body};
+
+    fn request() -> FimRequest {
+        FimRequest {
+            model: "qwen2.5-coder:1.5b".to_string(),
+            prompt: "<|fim_prefix|>fn add(a: i32, b: i32) -> i32 {\n    <|fim_suffix|>\n}<|fim_middle|>".to_string(),
+            max_tokens: 64,
+            temperature: 0.2,
+            stop: vec!["<|endoftext|>".to_string()],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_raw_completion_against_ollama_and_openai_compatible_servers() {
+        let ollama = http_response("200 OK", "", "application/json", &json!({"response": "a + b", "done": true}).to_string());
+        let openai = http_response("200 OK", "", "application/json", &json!({"choices": [{"text": "a + b"}]}).to_string());
+        let (url, server) = mock_server(vec![ollama, openai]);
+
+        let client = FimClient::new("", Some(&format!("{}/api/chat", url)));
+        assert_eq!(client.endpoint(), format!("{}/api/generate", url));
+        assert_eq!(client.complete(&request()).await.unwrap(), "a + b");
+
+        let client = FimClient::new("local-key", Some(&format!("{}/v1/chat/completions", url)));
+        assert_eq!(client.complete(&request()).await.unwrap(), "a + b");
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("POST /api/generate "));
+        let body = request_body(&requests[0]);
+        assert_eq!((body["raw"].as_bool(), body["stream"].as_bool()), (Some(true), Some(false)));
+        assert_eq!(body["options"]["num_predict"], 64);
+        assert!(requests[1].starts_with("POST /v1/completions "));
+        assert!(requests[1].to_lowercase().contains("authorization: bearer local-key"));
+        assert_eq!(request_body(&requests[1])["prompt"], request().prompt);
+
+        assert!(create_fim_provider(&ModelProvider::Anthropic, "key", None).is_none());
+    }
+}
@@ -0,0 +1,389 @@
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use serde_json::Value;
+use super::agent::AgentConfig;
+use super::chat::{ChatMessage, MessageRole};
+use super::model_loader::{ModelParameters, ModelProvider};
+
+pub mod anthropic;
+pub mod embedding;
+pub mod fim;
+pub mod ollama;
+pub mod openai;
+pub mod scripted;
+pub mod sse;
+
+use anthropic::AnthropicClient;
+use ollama::OllamaClient;
+use openai::OpenAiClient;
+
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+pub type ProviderFuture<'a> = Pin<Box<dyn Future<Output = Result<CompletionResponse, ProviderError>> + Send + 'a>>;
+
+/// A chat model backend that streams completions.
+pub trait Provider: fmt::Debug + Send + Sync {
+    /// Send a request and stream the reply, calling `on_text` with each text delta as it arrives.
+    fn stream<'a>(&'a self, request: &'a CompletionRequest, on_text: &'a mut (dyn FnMut(&str) + Send)) -> ProviderFuture<'a>;
+}
+
+/// Create the client for a provider.
+///
+/// Local and custom servers are assumed to speak the OpenAI chat-completions
+/// format (llama.cpp, vLLM, LM Studio...) unless the endpoint is Ollama's
+/// native `/api/chat`. Without an endpoint they default to a local Ollama.
+pub fn create_provider(provider: &ModelProvider, api_key: &str, endpoint: Option<&str>) -> Arc<dyn Provider> {
+    match provider {
+        ModelProvider::Anthropic => Arc::new(AnthropicClient::new(api_key, endpoint)),
+        ModelProvider::OpenAI => Arc::new(OpenAiClient::new(api_key, endpoint)),
+        ModelProvider::Local | ModelProvider::Custom(_) => match endpoint {
+            Some(url) if !ollama::is_native_endpoint(url) => Arc::new(OpenAiClient::new(api_key, Some(url))),
+            _ => Arc::new(OllamaClient::new(endpoint)),
+        },
+    }
+}
+
+/// One turn of the conversation sent to a model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestMessage {
+    pub role: MessageRole,
+    pub content: String,
+    /// Tools the assistant called in this turn.
+    pub tool_calls: Vec<ToolCall>,
+    /// Results of the previous assistant turn's tool calls, sent in a user turn.
+    pub tool_results: Vec<ToolResult>,
+}
+
+impl RequestMessage {
+    pub fn text(role: MessageRole, content: &str) -> Self {
+        Self { role, content: content.to_string(), tool_calls: Vec::new(), tool_results: Vec::new() }
+    }
+}
+
+/// A tool the model may call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: String,
+    /// JSON schema of the tool's input object.
+    pub input_schema: Value,
+}
+
+/// A tool call requested by the model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub input: Value,
+}
+
+/// The output of a tool call, sent back to the model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolResult {
+    pub call_id: String,
+    pub name: String,
+    pub content: String,
+    pub is_error: bool,
+}
+
+/// A provider-independent completion request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompletionRequest {
+    pub model: String,
+    pub system: Option<String>,
+    pub messages: Vec<RequestMessage>,
+    pub max_tokens: u32,
+    pub temperature: f32,
+    pub top_p: f32,
+    pub stop_sequences: Vec<String>,
+    pub tools: Vec<ToolDefinition>,
+}
+
+impl CompletionRequest {
+    /// Build a request from the agent config, model parameters and chat history.
+    ///
+    /// The model, system prompt, temperature and token limit come from the
+    /// agent config; `top_p` and stop sequences from the model parameters.
+    /// System messages in the history are appended to the system prompt.
+    pub fn from_chat(config: &AgentConfig, parameters: &ModelParameters, history: &[ChatMessage]) -> Self {
+        let mut system: Vec<&str> = Vec::new();
+        if !config.system_prompt.trim().is_empty() {
+            system.push(&config.system_prompt);
+        }
+        let mut messages = Vec::new();
+        for message in history {
+            if message.role == MessageRole::System {
+                if !system.contains(&message.content.as_str()) {
+                    system.push(&message.content);
+                }
+                continue;
+            }
+            messages.push(RequestMessage::text(message.role.clone(), &message.content));
+        }
+        Self {
+            model: config.model.clone(),
+            system: (!system.is_empty()).then(|| system.join("\n\n")),
+            messages,
+            max_tokens: config.max_tokens,
+            temperature: config.temperature,
+            top_p: parameters.top_p,
+            stop_sequences: parameters.stop_sequences.clone(),
+            tools: Vec::new(),
+        }
+    }
+}
+
+/// Token counts reported by the provider.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Usage {
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+}
+
+/// The full result of a streamed completion.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CompletionResponse {
+    pub text: String,
+    /// Tools the model wants called before it continues.
+    pub tool_calls: Vec<ToolCall>,
+    pub stop_reason: Option<String>,
+    pub usage: Usage,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProviderError {
+    MissingApiKey,
+    /// 429 after all retries; `retry_after` is the server's last hint.
+    RateLimited { retry_after: Option<Duration>, message: String },
+    /// The provider is temporarily overloaded (e.g. Anthropic's 529).
+    Overloaded(String),
+    /// Any other non-success status, with the provider's error message.
+    Http { status: u16, message: String },
+    /// Connection or I/O failure.
+    Network(String),
+    /// The stream was malformed or the provider sent an error event mid-stream.
+    Stream(String),
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProviderError::MissingApiKey => write!(f, "No API key configured"),
+            ProviderError::RateLimited { retry_after: Some(wait), message } => {
+                write!(f, "Rate limited, retry in {}s: {}", wait.as_secs().max(1), message)
+            }
+            ProviderError::RateLimited { retry_after: None, message } => write!(f, "Rate limited: {}", message),
+            ProviderError::Overloaded(message) => write!(f, "Provider overloaded: {}", message),
+            ProviderError::Http { status, message } => write!(f, "HTTP {}: {}", status, message),
+            ProviderError::Network(message) => write!(f, "Network error: {}", message),
+            ProviderError::Stream(message) => write!(f, "Stream error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+impl ProviderError {
+    /// Check if retrying the same request later may succeed.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ProviderError::RateLimited { .. } | ProviderError::Overloaded(_) | ProviderError::Network(_)
+        ) || matches!(self, ProviderError::Http { status, .. } if *status >= 500)
+    }
+}
+
+/// Tool calls whose JSON input streams in fragments, keyed by the provider's block or call index.
+#[derive(Debug, Default)]
+struct PendingToolCalls {
+    calls: Vec<(u64, ToolCall, String)>,
+}
+
+impl PendingToolCalls {
+    /// Start or extend the call at `index`. Ids and names are taken from whichever fragment carries them.
+    fn update(&mut self, index: u64, id: Option<&str>, name: Option<&str>, input_json: Option<&str>) {
+        let position = match self.calls.iter().position(|(i, _, _)| *i == index) {
+            Some(position) => position,
+            None => {
+                let call = ToolCall { id: String::new(), name: String::new(), input: Value::Null };
+                self.calls.push((index, call, String::new()));
+                self.calls.len() - 1
+            }
+        };
+        let (_, call, json) = &mut self.calls[position];
+        if let Some(id) = id.filter(|id| !id.is_empty()) {
+            call.id = id.to_string();
+        }
+        if let Some(name) = name.filter(|name| !name.is_empty()) {
+            call.name.push_str(name);
+        }
+        if let Some(fragment) = input_json {
+            json.push_str(fragment);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.calls.len()
+    }
+
+    /// Parse the accumulated inputs. Calls without an id get a generated one.
+    fn finish(self) -> Result<Vec<ToolCall>, ProviderError> {
+        self.calls
+            .into_iter()
+            .enumerate()
+            .map(|(n, (_, mut call, json))| {
+                call.input = match json.trim() {
+                    "" => Value::Object(Default::default()),
+                    json => serde_json::from_str(json)
+                        .map_err(|e| ProviderError::Stream(format!("Invalid input for tool '{}': {}", call.name, e)))?,
+                };
+                if call.id.is_empty() {
+                    call.id = format!("call_{}", n);
+                }
+                Ok(call)
+            })
+            .collect()
+    }
+}
+
+/// Send a request, retrying rate limits, overload and 5xx errors with exponential backoff.
+///
+/// A `retry-after` hint from the server takes precedence over the backoff.
+async fn send_with_retries<F, Fut>(max_retries: u32, base_delay: Duration, mut send: F) -> Result<reqwest::Response, ProviderError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<reqwest::Response, ProviderError>>,
+{
+    let mut attempt = 0;
+    loop {
+        match send().await {
+            Ok(response) => return Ok(response),
+            Err(error) if error.is_retryable() && attempt < max_retries => {
+                let delay = match &error {
+                    ProviderError::RateLimited { retry_after: Some(wait), .. } => *wait,
+                    _ => base_delay * 2u32.pow(attempt),
+                };
+                tokio::time::sleep(delay.min(MAX_RETRY_DELAY)).await;
+                attempt += 1;
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// Pass through a successful response, or turn a failed one into a `ProviderError`.
+async fn check_response(response: reqwest::Response) -> Result<reqwest::Response, ProviderError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let retry_after = parse_retry_after(response.headers());
+    let text = response.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<Value>(&text)
+        .ok()
+        .and_then(|body| error_message(&body))
+        .unwrap_or_else(|| status.canonical_reason().unwrap_or("Request failed").to_string());
+    Err(match status.as_u16() {
+        429 => ProviderError::RateLimited { retry_after, message },
+        529 => ProviderError::Overloaded(message),
+        status => ProviderError::Http { status, message },
+    })
+}
+
+/// The message of an error body: `{"error": {"message": ...}}` (Anthropic, OpenAI) or `{"error": "..."}` (Ollama).
+fn error_message(body: &Value) -> Option<String> {
+    body["error"]["message"].as_str().or(body["error"].as_str()).map(str::to_string)
+}
+
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?;
+    value.trim().parse::<f64>().ok().filter(|secs| *secs >= 0.0).map(Duration::from_secs_f64)
+}
+
+#[cfg(test)]
+pub(crate) mod test_server {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+    use serde_json::Value;
+
+    /// Serve one canned response per connection and return the raw requests received.
+    ///
+    /// Returns the server's base URL, e.g. `http://127.0.0.1:1234`.
+    pub fn mock_server(responses: Vec<String>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut request = String::new();
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(length) = line.to_lowercase().strip_prefix("content-length:") {
+                        content_length = length.trim().parse().unwrap();
+                    }
+                    request.push_str(&line);
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                request.push_str(&String::from_utf8(body).unwrap());
+                requests.push(request);
+
+                let mut stream = reader.into_inner();
+                // Write in two pieces so events arrive split across chunks.
+                let (head, tail) = response.split_at(response.len() / 2);
+                stream.write_all(head.as_bytes()).unwrap();
+                stream.flush().unwrap();
+                thread::sleep(Duration::from_millis(20));
+                stream.write_all(tail.as_bytes()).unwrap();
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    /// The JSON body of a raw request captured by `mock_server`.
+    pub fn request_body(request: &str) -> Value {
+        serde_json::from_str(&request[request.find("\r\n\r\n").unwrap() + 4..]).unwrap()
+    }
+
+    pub fn http_response(status: &str, headers: &str, content_type: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\n{}Content-Type: {}\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            headers,
+            content_type,
+            body.len(),
+            body
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_servers_pick_wire_format_from_endpoint() {
+        let debug = |provider: ModelProvider, endpoint: Option<&str>| format!("{:?}", create_provider(&provider, "", endpoint));
+
+        assert!(debug(ModelProvider::Local, None).starts_with("OllamaClient"));
+        assert!(debug(ModelProvider::Local, Some("http://gpu-box:11434/api/chat/")).starts_with("OllamaClient"));
+        assert!(debug(ModelProvider::Local, Some("http://localhost:8080/v1/chat/completions")).starts_with("OpenAiClient"));
+        assert!(debug(ModelProvider::Custom("vllm".to_string()), Some("http://vllm:8000/v1/chat/completions")).starts_with("OpenAiClient"));
+        assert!(debug(ModelProvider::OpenAI, None).contains("api.openai.com"));
+    }
+}
@@ -0,0 +1,215 @@
+use std::time::Duration;
+use serde_json::{json, Value};
+use super::{CompletionRequest, CompletionResponse, PendingToolCalls, Provider, ProviderError, ProviderFuture};
+use crate::backend::code_agent::chat::MessageRole;
+
+pub const DEFAULT_ENDPOINT: &str = "http://localhost:11434/api/chat";
+
+/// Check if an endpoint is Ollama's native chat API rather than an OpenAI-compatible one.
+pub fn is_native_endpoint(endpoint: &str) -> bool {
+    endpoint.trim_end_matches('/').ends_with("/api/chat")
+}
+
+/// Streaming client for Ollama's native `/api/chat` API.
+#[derive(Debug, Clone)]
+pub struct OllamaClient {
+    http: reqwest::Client,
+    endpoint: String,
+    /// Retries for 5xx errors (e.g. while a model is still loading) before any text has streamed.
+    pub max_retries: u32,
+    /// First backoff delay; doubles each retry.
+    pub retry_base_delay: Duration,
+}
+
+impl OllamaClient {
+    pub fn new(endpoint: Option<&str>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            endpoint: endpoint.unwrap_or(DEFAULT_ENDPOINT).to_string(),
+            max_retries: 2,
+            retry_base_delay: Duration::from_secs(1),
+        }
+    }
+
+    /// Build the JSON body for a streamed chat call.
+    pub fn request_body(request: &CompletionRequest) -> Value {
+        let mut messages: Vec<Value> = Vec::new();
+        if let Some(system) = &request.system {
+            messages.push(json!({ "role": "system", "content": system }));
+        }
+        for message in &request.messages {
+            for result in &message.tool_results {
+                messages.push(json!({ "role": "tool", "tool_name": result.name, "content": result.content }));
+            }
+            let role = match message.role {
+                MessageRole::User => "user",
+                MessageRole::Assistant => "assistant",
+                MessageRole::System => "system",
+            };
+            if !message.tool_calls.is_empty() {
+                let calls: Vec<Value> = message
+                    .tool_calls
+                    .iter()
+                    .map(|call| json!({ "function": { "name": call.name, "arguments": call.input } }))
+                    .collect();
+                messages.push(json!({ "role": role, "content": message.content, "tool_calls": calls }));
+            } else if !message.content.is_empty() || message.tool_results.is_empty() {
+                messages.push(json!({ "role": role, "content": message.content }));
+            }
+        }
+
+        let mut options = json!({
+            "temperature": request.temperature,
+            "top_p": request.top_p,
+            "num_predict": request.max_tokens,
+        });
+        if !request.stop_sequences.is_empty() {
+            options["stop"] = json!(request.stop_sequences);
+        }
+        let mut body = json!({
+            "model": request.model,
+            "messages": messages,
+            "stream": true,
+            "options": options,
+        });
+        if !request.tools.is_empty() {
+            body["tools"] = super::openai::tool_definitions(request);
+        }
+        body
+    }
+
+    /// Send a request and stream the reply, calling `on_text` with each text delta as it arrives.
+    pub async fn stream<F: FnMut(&str)>(&self, request: &CompletionRequest, mut on_text: F) -> Result<CompletionResponse, ProviderError> {
+        let body = Self::request_body(request);
+        let response = super::send_with_retries(self.max_retries, self.retry_base_delay, || self.send(&body)).await?;
+        Self::read_stream(response, &mut on_text).await
+    }
+
+    async fn send(&self, body: &Value) -> Result<reqwest::Response, ProviderError> {
+        let response = self
+            .http
+            .post(&self.endpoint)
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| ProviderError::Network(e.to_string()))?;
+        super::check_response(response).await
+    }
+
+    /// Read the newline-delimited JSON stream.
+    async fn read_stream<F: FnMut(&str)>(mut response: reqwest::Response, on_text: &mut F) -> Result<CompletionResponse, ProviderError> {
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut result = CompletionResponse::default();
+        let mut tool_calls = PendingToolCalls::default();
+        loop {
+            let chunk = response.chunk().await.map_err(|e| ProviderError::Network(e.to_string()))?;
+            match &chunk {
+                Some(bytes) => buffer.extend_from_slice(bytes),
+                // Treat whatever is left as a final line without its newline.
+                None => buffer.push(b'\n'),
+            }
+            while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                if line.trim().is_empty() {
+                    continue;
+                }
+                if Self::handle_line(&line, &mut result, &mut tool_calls, on_text)? {
+                    result.tool_calls = tool_calls.finish()?;
+                    return Ok(result);
+                }
+            }
+            if chunk.is_none() {
+                return Err(ProviderError::Stream("Connection closed before the message finished".to_string()));
+            }
+        }
+    }
+
+    /// Apply one stream line. Returns `true` once the server reports `done`.
+    fn handle_line<F: FnMut(&str)>(
+        line: &str,
+        result: &mut CompletionResponse,
+        tool_calls: &mut PendingToolCalls,
+        on_text: &mut F,
+    ) -> Result<bool, ProviderError> {
+        let data: Value = serde_json::from_str(line)
+            .map_err(|e| ProviderError::Stream(format!("Invalid stream line: {}", e)))?;
+        if let Some(message) = super::error_message(&data) {
+            return Err(ProviderError::Stream(message));
+        }
+
+        if let Some(text) = data["message"]["content"].as_str().filter(|text| !text.is_empty()) {
+            result.text.push_str(text);
+            on_text(text);
+        }
+        // Tool calls arrive whole, with parsed arguments and no ids.
+        for call in data["message"]["tool_calls"].as_array().into_iter().flatten() {
+            let function = &call["function"];
+            let arguments = function["arguments"].to_string();
+            tool_calls.update(tool_calls.len() as u64, None, function["name"].as_str(), Some(&arguments));
+        }
+        if data["done"].as_bool() != Some(true) {
+            return Ok(false);
+        }
+        result.stop_reason = data["done_reason"].as_str().map(str::to_string);
+        result.usage.input_tokens = data["prompt_eval_count"].as_u64().unwrap_or(0) as u32;
+        result.usage.output_tokens = data["eval_count"].as_u64().unwrap_or(0) as u32;
+        Ok(true)
+    }
+}
+
+impl Provider for OllamaClient {
+    fn stream<'a>(&'a self, request: &'a CompletionRequest, on_text: &'a mut (dyn FnMut(&str) + Send)) -> ProviderFuture<'a> {
+        Box::pin(OllamaClient::stream(self, request, on_text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::code_agent::providers::test_server::{mock_server, request_body};
+    use crate::backend::code_agent::providers::RequestMessage;
+
+    fn ndjson_response(lines: &[Value]) -> String {
+        let body: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+        format!("HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nConnection: close\r\n\r\n{}", body)
+    }
+
+    fn request() -> CompletionRequest {
+        CompletionRequest {
+            model: "llama3".to_string(),
+            system: None,
+            messages: vec![RequestMessage::text(MessageRole::User, "Hello")],
+            max_tokens: 32,
+            temperature: 0.5,
+            top_p: 0.75,
+            stop_sequences: vec!["</s>".to_string()],
+            tools: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_streams_ndjson_and_reports_stream_errors() {
+        let reply = ndjson_response(&[
+            json!({"model": "llama3", "message": {"role": "assistant", "content": "Hel"}, "done": false}),
+            json!({"model": "llama3", "message": {"role": "assistant", "content": "lo!"}, "done": false}),
+            json!({"model": "llama3", "message": {"role": "assistant", "content": ""}, "done": true, "done_reason": "stop", "prompt_eval_count": 7, "eval_count": 3}),
+        ]);
+        let failure = ndjson_response(&[json!({"error": "model 'llama3' not found"})]);
+        let (url, server) = mock_server(vec![reply, failure]);
+        let client = OllamaClient::new(Some(&format!("{}/api/chat", url)));
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let mut deltas = Vec::new();
+        let response = runtime.block_on(client.stream(&request(), |text| deltas.push(text.to_string()))).unwrap();
+        assert_eq!(deltas, vec!["Hel", "lo!"]);
+        assert_eq!(response.stop_reason.as_deref(), Some("stop"));
+        assert_eq!((response.usage.input_tokens, response.usage.output_tokens), (7, 3));
+
+        let error = runtime.block_on(client.stream(&request(), |_| {})).unwrap_err();
+        assert_eq!(error, ProviderError::Stream("model 'llama3' not found".to_string()));
+
+        let body = request_body(&server.join().unwrap()[0]);
+        assert_eq!(body["options"], json!({"temperature": 0.5, "top_p": 0.75, "num_predict": 32, "stop": ["</s>"]}));
+    }
+}
@@ -0,0 +1,293 @@
+use std::time::Duration;
+use serde_json::{json, Value};
+use super::sse::{SseEvent, SseParser};
+use super::{CompletionRequest, CompletionResponse, PendingToolCalls, Provider, ProviderError, ProviderFuture};
+use crate::backend::code_agent::chat::MessageRole;
+
+pub const DEFAULT_ENDPOINT: &str = "https://api.openai.com/v1/chat/completions";
+
+/// Streaming client for the OpenAI chat-completions API.
+///
+/// Most local servers (llama.cpp, vLLM, LM Studio, Ollama's `/v1`) expose the
+/// same wire format, so this also covers them given their endpoint.
+#[derive(Clone)]
+pub struct OpenAiClient {
+    http: reqwest::Client,
+    endpoint: String,
+    api_key: String,
+    /// Retries for rate limits and 5xx errors before any text has streamed.
+    pub max_retries: u32,
+    /// First backoff delay when the server gives no `retry-after`; doubles each retry.
+    pub retry_base_delay: Duration,
+}
+
+impl std::fmt::Debug for OpenAiClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // Keep the API key out of logs.
+        f.debug_struct("OpenAiClient")
+            .field("endpoint", &self.endpoint)
+            .field("has_api_key", &!self.api_key.is_empty())
+            .field("max_retries", &self.max_retries)
+            .finish()
+    }
+}
+
+impl OpenAiClient {
+    /// Create a client.
+    ///
+    /// Against the default OpenAI endpoint an empty key falls back to the
+    /// `OPENAI_API_KEY` environment variable. Local servers usually need no key,
+    /// in which case no `Authorization` header is sent.
+    pub fn new(api_key: &str, endpoint: Option<&str>) -> Self {
+        let endpoint = endpoint.unwrap_or(DEFAULT_ENDPOINT).to_string();
+        let api_key = match api_key.trim() {
+            "" if endpoint == DEFAULT_ENDPOINT => std::env::var("OPENAI_API_KEY").unwrap_or_default(),
+            key => key.to_string(),
+        };
+        Self {
+            http: reqwest::Client::new(),
+            endpoint,
+            api_key,
+            max_retries: 3,
+            retry_base_delay: Duration::from_secs(1),
+        }
+    }
+
+    /// Build the JSON body for a streamed chat-completions call.
+    pub fn request_body(request: &CompletionRequest) -> Value {
+        let mut messages: Vec<Value> = Vec::new();
+        if let Some(system) = &request.system {
+            messages.push(json!({ "role": "system", "content": system }));
+        }
+        for message in &request.messages {
+            // Each tool result is its own `tool` message.
+            for result in &message.tool_results {
+                messages.push(json!({ "role": "tool", "tool_call_id": result.call_id, "content": result.content }));
+            }
+            let role = match message.role {
+                MessageRole::User => "user",
+                MessageRole::Assistant => "assistant",
+                MessageRole::System => "system",
+            };
+            if !message.tool_calls.is_empty() {
+                let calls: Vec<Value> = message
+                    .tool_calls
+                    .iter()
+                    .map(|call| json!({
+                        "id": call.id,
+                        "type": "function",
+                        "function": { "name": call.name, "arguments": call.input.to_string() },
+                    }))
+                    .collect();
+                let content = Some(&message.content).filter(|content| !content.is_empty());
+                messages.push(json!({ "role": role, "content": content, "tool_calls": calls }));
+            } else if !message.content.is_empty() || message.tool_results.is_empty() {
+                messages.push(json!({ "role": role, "content": message.content }));
+            }
+        }
+
+        let mut body = json!({
+            "model": request.model,
+            "messages": messages,
+            "max_tokens": request.max_tokens,
+            "temperature": request.temperature,
+            "top_p": request.top_p,
+            "stream": true,
+            "stream_options": { "include_usage": true },
+        });
+        if !request.stop_sequences.is_empty() {
+            body["stop"] = json!(request.stop_sequences);
+        }
+        if !request.tools.is_empty() {
+            body["tools"] = tool_definitions(request);
+        }
+        body
+    }
+
+    /// Send a request and stream the reply, calling `on_text` with each text delta as it arrives.
+    pub async fn stream<F: FnMut(&str)>(&self, request: &CompletionRequest, mut on_text: F) -> Result<CompletionResponse, ProviderError> {
+        let body = Self::request_body(request);
+        let response = super::send_with_retries(self.max_retries, self.retry_base_delay, || self.send(&body)).await?;
+        Self::read_stream(response, &mut on_text).await
+    }
+
+    async fn send(&self, body: &Value) -> Result<reqwest::Response, ProviderError> {
+        let mut request = self.http.post(&self.endpoint).header("accept", "text/event-stream").json(body);
+        if !self.api_key.is_empty() {
+            request = request.bearer_auth(&self.api_key);
+        }
+        let response = request.send().await.map_err(|e| ProviderError::Network(e.to_string()))?;
+        super::check_response(response).await
+    }
+
+    async fn read_stream<F: FnMut(&str)>(mut response: reqwest::Response, on_text: &mut F) -> Result<CompletionResponse, ProviderError> {
+        let mut parser = SseParser::new();
+        let mut result = CompletionResponse::default();
+        let mut tool_calls = PendingToolCalls::default();
+        loop {
+            let chunk = response.chunk().await.map_err(|e| ProviderError::Network(e.to_string()))?;
+            let events = match &chunk {
+                Some(bytes) => parser.feed(bytes),
+                None => parser.finish().into_iter().collect(),
+            };
+            for event in events {
+                if Self::handle_event(&event, &mut result, &mut tool_calls, on_text)? {
+                    result.tool_calls = tool_calls.finish()?;
+                    return Ok(result);
+                }
+            }
+            if chunk.is_none() {
+                // Some servers close without `[DONE]`; that's fine once a finish reason arrived.
+                if result.stop_reason.is_none() {
+                    return Err(ProviderError::Stream("Connection closed before the message finished".to_string()));
+                }
+                result.tool_calls = tool_calls.finish()?;
+                return Ok(result);
+            }
+        }
+    }
+
+    /// Apply one stream chunk. Returns `true` at `[DONE]`.
+    fn handle_event<F: FnMut(&str)>(
+        event: &SseEvent,
+        result: &mut CompletionResponse,
+        tool_calls: &mut PendingToolCalls,
+        on_text: &mut F,
+    ) -> Result<bool, ProviderError> {
+        if event.data.trim() == "[DONE]" {
+            return Ok(true);
+        }
+        let data: Value = serde_json::from_str(&event.data)
+            .map_err(|e| ProviderError::Stream(format!("Invalid event data: {}", e)))?;
+        if let Some(message) = super::error_message(&data) {
+            return Err(ProviderError::Stream(message));
+        }
+
+        let choice = &data["choices"][0];
+        if let Some(text) = choice["delta"]["content"].as_str().filter(|text| !text.is_empty()) {
+            result.text.push_str(text);
+            on_text(text);
+        }
+        for call in choice["delta"]["tool_calls"].as_array().into_iter().flatten() {
+            let function = &call["function"];
+            let index = call["index"].as_u64().unwrap_or_default();
+            tool_calls.update(index, call["id"].as_str(), function["name"].as_str(), function["arguments"].as_str());
+        }
+        if let Some(reason) = choice["finish_reason"].as_str() {
+            result.stop_reason = Some(reason.to_string());
+        }
+        // Sent in a final chunk with no choices when `include_usage` is set.
+        if let Some(usage) = data["usage"].as_object() {
+            result.usage.input_tokens = usage.get("prompt_tokens").and_then(Value::as_u64).unwrap_or(0) as u32;
+            result.usage.output_tokens = usage.get("completion_tokens").and_then(Value::as_u64).unwrap_or(0) as u32;
+        }
+        Ok(false)
+    }
+}
+
+/// Tool definitions in the `{"type": "function", ...}` form, shared with Ollama.
+pub fn tool_definitions(request: &CompletionRequest) -> Value {
+    request
+        .tools
+        .iter()
+        .map(|tool| json!({
+            "type": "function",
+            "function": { "name": tool.name, "description": tool.description, "parameters": tool.input_schema },
+        }))
+        .collect()
+}
+
+impl Provider for OpenAiClient {
+    fn stream<'a>(&'a self, request: &'a CompletionRequest, on_text: &'a mut (dyn FnMut(&str) + Send)) -> ProviderFuture<'a> {
+        Box::pin(OpenAiClient::stream(self, request, on_text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::code_agent::providers::test_server::{http_response, mock_server, request_body};
+    use crate::backend::code_agent::providers::RequestMessage;
+
+    fn sse_response(chunks: &[&str]) -> String {
+        let body: String = chunks.iter().map(|chunk| format!("data: {}\n\n", chunk)).collect();
+        format!("HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n{}", body)
+    }
+
+    fn request() -> CompletionRequest {
+        CompletionRequest {
+            model: "qwen2.5-coder".to_string(),
+            system: Some("Be brief.".to_string()),
+            messages: vec![RequestMessage::text(MessageRole::User, "Hello")],
+            max_tokens: 64,
+            temperature: 0.2,
+            top_p: 0.9,
+            stop_sequences: Vec::new(),
+            tools: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_streams_chat_completion_chunks() {
+        let chunks = [
+            json!({"choices": [{"index": 0, "delta": {"role": "assistant", "content": ""}}]}).to_string(),
+            json!({"choices": [{"index": 0, "delta": {"content": "Hi"}}]}).to_string(),
+            json!({"choices": [{"index": 0, "delta": {"content": " there"}}]}).to_string(),
+            json!({"choices": [{"index": 0, "delta": {}, "finish_reason": "stop"}]}).to_string(),
+            json!({"choices": [], "usage": {"prompt_tokens": 9, "completion_tokens": 2}}).to_string(),
+            "[DONE]".to_string(),
+        ];
+        let chunks: Vec<&str> = chunks.iter().map(String::as_str).collect();
+        let (url, server) = mock_server(vec![sse_response(&chunks)]);
+        let client = OpenAiClient::new("", Some(&format!("{}/v1/chat/completions", url)));
+
+        let mut deltas = Vec::new();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let response = runtime.block_on(client.stream(&request(), |text| deltas.push(text.to_string()))).unwrap();
+
+        assert_eq!(deltas, vec!["Hi", " there"]);
+        assert_eq!(response.text, "Hi there");
+        assert_eq!(response.stop_reason.as_deref(), Some("stop"));
+        assert_eq!((response.usage.input_tokens, response.usage.output_tokens), (9, 2));
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("POST /v1/chat/completions"));
+        assert!(!requests[0].to_lowercase().contains("authorization:"));
+        let body = request_body(&requests[0]);
+        assert_eq!(body["model"], "qwen2.5-coder");
+        assert_eq!(body["messages"], json!([{"role": "system", "content": "Be brief."}, {"role": "user", "content": "Hello"}]));
+    }
+
+    #[test]
+    fn test_reports_errors_with_bearer_key() {
+        let body = json!({"error": {"message": "Incorrect API key provided", "type": "invalid_request_error"}}).to_string();
+        let (url, server) = mock_server(vec![http_response("401 Unauthorized", "", "application/json", &body)]);
+        let client = OpenAiClient::new("sk-test", Some(&url));
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let error = runtime.block_on(client.stream(&request(), |_| {})).unwrap_err();
+        assert_eq!(error, ProviderError::Http { status: 401, message: "Incorrect API key provided".to_string() });
+        assert!(server.join().unwrap()[0].to_lowercase().contains("authorization: bearer sk-test"));
+    }
+
+    #[test]
+    fn test_tool_call_fragments_are_assembled() {
+        let chunks = [
+            json!({"choices": [{"index": 0, "delta": {"tool_calls": [{"index": 0, "id": "call_a", "type": "function", "function": {"name": "search", "arguments": ""}}]}}]}).to_string(),
+            json!({"choices": [{"index": 0, "delta": {"tool_calls": [{"index": 0, "function": {"arguments": "{\"query\":"}}]}}]}).to_string(),
+            json!({"choices": [{"index": 0, "delta": {"tool_calls": [{"index": 0, "function": {"arguments": " \"TODO\"}"}}]}}]}).to_string(),
+            json!({"choices": [{"index": 0, "delta": {}, "finish_reason": "tool_calls"}]}).to_string(),
+        ];
+        let chunks: Vec<&str> = chunks.iter().map(String::as_str).collect();
+        // No `[DONE]`: the stream just closes after the finish reason.
+        let (url, server) = mock_server(vec![sse_response(&chunks)]);
+        let client = OpenAiClient::new("", Some(&url));
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let response = runtime.block_on(client.stream(&request(), |_| {})).unwrap();
+        assert_eq!(response.tool_calls.len(), 1);
+        assert_eq!((response.tool_calls[0].id.as_str(), response.tool_calls[0].name.as_str()), ("call_a", "search"));
+        assert_eq!(response.tool_calls[0].input, json!({"query": "TODO"}));
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,61 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use super::{CompletionRequest, CompletionResponse, Provider, ProviderError, ProviderFuture, ToolCall};
+
+/// A fake model that replays canned responses, for running the agent offline.
+#[derive(Debug, Default)]
+pub struct ScriptedProvider {
+    responses: Mutex<VecDeque<CompletionResponse>>,
+    requests: Mutex<Vec<CompletionRequest>>,
+}
+
+impl ScriptedProvider {
+    pub fn new(responses: Vec<CompletionResponse>) -> Self {
+        Self {
+            responses: Mutex::new(responses.into()),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// A final answer with no tool calls.
+    pub fn reply(text: &str) -> CompletionResponse {
+        CompletionResponse {
+            text: text.to_string(),
+            stop_reason: Some("end_turn".to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// A turn that calls one tool, with optional text before the call.
+    pub fn tool_call(text: &str, id: &str, name: &str, input: serde_json::Value) -> CompletionResponse {
+        CompletionResponse {
+            text: text.to_string(),
+            tool_calls: vec![ToolCall { id: id.to_string(), name: name.to_string(), input }],
+            stop_reason: Some("tool_use".to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Requests received so far, oldest first.
+    pub fn requests(&self) -> Vec<CompletionRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.responses.lock().unwrap().len()
+    }
+}
+
+impl Provider for ScriptedProvider {
+    fn stream<'a>(&'a self, request: &'a CompletionRequest, on_text: &'a mut (dyn FnMut(&str) + Send)) -> ProviderFuture<'a> {
+        self.requests.lock().unwrap().push(request.clone());
+        let response = self.responses.lock().unwrap().pop_front();
+        Box::pin(async move {
+            let response = response.ok_or_else(|| ProviderError::Stream("Script has no more responses".to_string()))?;
+            if !response.text.is_empty() {
+                on_text(&response.text);
+            }
+            Ok(response)
+        })
+    }
+}
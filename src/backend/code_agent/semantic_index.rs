@@ -0,0 +1,629 @@
+//! Semantic code search over the workspace.
+//!
+//! Source files are cut into chunks at symbol boundaries, each chunk is embedded
+//! by the configured local model, and the vectors are kept in
+//! `.jadio/embeddings.json`. A background thread rescans the workspace and
+//! re-embeds only the chunks whose text changed. Queries are embedded the same
+//! way and ranked by cosine similarity.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tokio::runtime::Handle;
+use super::agent::AgentConfig;
+use super::context::ContextManager;
+use super::providers::embedding::{self, EmbeddingProvider};
+use super::symbol_index::{self, INDEX_DIR};
+
+const INDEX_FILE: &str = "embeddings.json";
+const INDEX_VERSION: u32 = 1;
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// Longest chunk; larger symbols are split at their children, or into windows.
+const MAX_CHUNK_LINES: usize = 60;
+/// Characters of a chunk sent to the model; embedding models have small context windows.
+const MAX_EMBED_CHARS: usize = 6000;
+const EMBED_BATCH: usize = 16;
+/// Save progress every this many files during a long first scan.
+const SAVE_EVERY: usize = 50;
+const INDEXED_EXTENSIONS: &[&str] = &[
+    "rs", "py", "js", "jsx", "ts", "tsx", "go", "java", "kt", "c", "h", "cpp", "hpp", "cc", "cs", "rb", "php", "swift", "lua", "sh", "md", "toml",
+];
+
+/// An embedding model and the server that runs it.
+#[derive(Debug)]
+pub struct Embedder {
+    provider: Arc<dyn EmbeddingProvider>,
+    model: String,
+}
+
+impl Embedder {
+    pub fn new(provider: Arc<dyn EmbeddingProvider>, model: &str) -> Self {
+        Self { provider, model: model.to_string() }
+    }
+
+    /// An embedder on the agent's model server, if it is a local or custom one.
+    pub fn for_config(config: &AgentConfig) -> Option<Self> {
+        if config.embedding_model.trim().is_empty() {
+            return None;
+        }
+        let provider = embedding::create_embedding_provider(&config.provider, &config.api_key, config.api_endpoint.as_deref())?;
+        Some(Self::new(provider, config.embedding_model.trim()))
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Unit-length vectors for `inputs`, so cosine similarity is a dot product.
+    pub async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        let vectors = self.provider.embed(&self.model, inputs).await.map_err(|e| format!("Embedding failed: {}", e))?;
+        Ok(vectors.into_iter().map(normalized).collect())
+    }
+}
+
+/// A span of a source file that is embedded as one piece.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeChunk {
+    /// 1-based, inclusive.
+    pub start_line: usize,
+    pub end_line: usize,
+    /// The symbol the chunk holds, `None` for code between symbols.
+    pub symbol: Option<String>,
+    pub text: String,
+}
+
+/// A chunk found by a query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SemanticMatch {
+    pub path: PathBuf,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub symbol: Option<String>,
+    /// Cosine similarity to the query, higher is closer.
+    pub score: f32,
+    /// The chunk as it is on disk now.
+    pub text: String,
+}
+
+/// Progress of the background indexer, for display.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IndexStatus {
+    /// The first scan has finished.
+    pub ready: bool,
+    pub files: usize,
+    pub chunks: usize,
+    /// Why the last scan stopped early, e.g. the model server is down.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredChunk {
+    start_line: usize,
+    end_line: usize,
+    symbol: Option<String>,
+    /// Hash of the embedded text; unchanged chunks keep their vectors.
+    hash: u64,
+    vector: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EmbeddedFile {
+    /// Modification time in milliseconds since the epoch.
+    modified: u64,
+    size: u64,
+    chunks: Vec<StoredChunk>,
+}
+
+/// The persisted index: chunk vectors by workspace-relative path.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct VectorData {
+    version: u32,
+    /// Vectors from different models can't be compared, so the index is rebuilt when this changes.
+    model: String,
+    files: HashMap<String, EmbeddedFile>,
+}
+
+enum IndexCommand {
+    File(PathBuf),
+    Rescan,
+    Shutdown,
+}
+
+/// What a scan needs besides the index itself.
+struct Worker {
+    root: PathBuf,
+    data: Arc<RwLock<VectorData>>,
+    status: Arc<Mutex<IndexStatus>>,
+    embedder: Arc<Embedder>,
+    runtime: Handle,
+    /// Set when the index is dropped, so a long scan stops between files.
+    stopping: Arc<AtomicBool>,
+}
+
+/// Workspace-wide vector index kept up to date by a background thread.
+///
+/// Embedding calls run on `runtime` but are driven from the index's own thread,
+/// so a slow model server never blocks the UI.
+#[derive(Debug)]
+pub struct SemanticIndex {
+    root: PathBuf,
+    data: Arc<RwLock<VectorData>>,
+    status: Arc<Mutex<IndexStatus>>,
+    embedder: Arc<Embedder>,
+    commands: Sender<IndexCommand>,
+    stopping: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl SemanticIndex {
+    /// Open the index for a workspace and start embedding in the background.
+    pub fn open(root: PathBuf, embedder: Embedder, runtime: Handle) -> Self {
+        Self::with_poll_interval(root, embedder, runtime, DEFAULT_POLL_INTERVAL)
+    }
+
+    pub fn with_poll_interval(root: PathBuf, embedder: Embedder, runtime: Handle, poll_interval: Duration) -> Self {
+        let data = load_index(&root).filter(|data| data.model == embedder.model).unwrap_or_else(|| VectorData {
+            model: embedder.model.clone(),
+            ..VectorData::default()
+        });
+        let data = Arc::new(RwLock::new(data));
+        let status = Arc::new(Mutex::new(IndexStatus::default()));
+        let embedder = Arc::new(embedder);
+        let stopping = Arc::new(AtomicBool::new(false));
+        let (commands, receiver) = mpsc::channel();
+
+        let worker = Worker {
+            root: root.clone(),
+            data: data.clone(),
+            status: status.clone(),
+            embedder: embedder.clone(),
+            runtime,
+            stopping: stopping.clone(),
+        };
+        let worker = thread::spawn(move || {
+            let mut command = IndexCommand::Rescan;
+            loop {
+                let result = match command {
+                    IndexCommand::File(path) => worker.index_file(&path),
+                    IndexCommand::Rescan => worker.scan(),
+                    IndexCommand::Shutdown => break,
+                };
+                worker.finish(result);
+                command = match receiver.recv_timeout(poll_interval) {
+                    Ok(command) => command,
+                    Err(RecvTimeoutError::Timeout) => IndexCommand::Rescan,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
+            }
+        });
+
+        Self { root, data, status, embedder, commands, stopping, worker: Some(worker) }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn model(&self) -> &str {
+        self.embedder.model()
+    }
+
+    pub fn status(&self) -> IndexStatus {
+        let mut status = self.status.lock().map(|status| status.clone()).unwrap_or_default();
+        if let Ok(data) = self.data.read() {
+            status.files = data.files.len();
+            status.chunks = data.files.values().map(|file| file.chunks.len()).sum();
+        }
+        status
+    }
+
+    /// Re-embed a file right away, e.g. after it was saved.
+    pub fn refresh_file<P: AsRef<Path>>(&self, path: P) {
+        let _ = self.commands.send(IndexCommand::File(path.as_ref().to_path_buf()));
+    }
+
+    /// Rescan the whole workspace now.
+    pub fn refresh(&self) {
+        let _ = self.commands.send(IndexCommand::Rescan);
+    }
+
+    /// The chunks closest in meaning to `query`, best first.
+    pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<SemanticMatch>, String> {
+        let query = self.embedder.embed(&[query.to_string()]).await?.pop().ok_or("No embedding for the query")?;
+        let query = query.as_slice();
+        let mut scored: Vec<(f32, String, StoredChunk)> = {
+            let data = self.data.read().map_err(|_| "Semantic index lock poisoned")?;
+            let mut scored: Vec<(f32, &String, &StoredChunk)> = data
+                .files
+                .iter()
+                .flat_map(|(path, file)| file.chunks.iter().map(move |chunk| (dot(query, &chunk.vector), path, chunk)))
+                .collect();
+            scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+            scored.truncate(limit);
+            scored.into_iter().map(|(score, path, chunk)| (score, path.clone(), chunk.clone())).collect()
+        };
+
+        // Read the text now rather than storing it, so results show the file as it is.
+        let mut files: HashMap<String, Vec<String>> = HashMap::new();
+        let matches = scored
+            .drain(..)
+            .map(|(score, key, chunk)| {
+                let path = self.root.join(&key);
+                let lines = files.entry(key).or_insert_with(|| fs::read_to_string(&path).map(|text| text.lines().map(str::to_string).collect()).unwrap_or_default());
+                let end = chunk.end_line.min(lines.len());
+                let text = lines.get(chunk.start_line.saturating_sub(1)..end).map(|lines| lines.join("\n")).unwrap_or_default();
+                SemanticMatch { path, start_line: chunk.start_line, end_line: chunk.end_line, symbol: chunk.symbol, score, text }
+            })
+            .collect();
+        Ok(matches)
+    }
+}
+
+impl Drop for SemanticIndex {
+    fn drop(&mut self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        let _ = self.commands.send(IndexCommand::Shutdown);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Worker {
+    /// Record how the last command went and save if anything changed.
+    fn finish(&self, result: Result<bool, String>) {
+        let (changed, error) = match result {
+            Ok(changed) => (changed, None),
+            Err(e) => (true, Some(e)),
+        };
+        if changed {
+            if let Err(e) = save_index(&self.root, &self.data) {
+                eprintln!("Failed to save semantic index: {}", e);
+            }
+        }
+        if let Ok(mut status) = self.status.lock() {
+            status.ready = true;
+            status.error = error;
+        }
+    }
+
+    /// Embed new and modified files and drop deleted ones. Stops at the first
+    /// embedding failure; the remaining files are retried on the next scan.
+    fn scan(&self) -> Result<bool, String> {
+        let mut found = Vec::new();
+        symbol_index::collect_files(&self.root, &is_indexed, &mut found);
+
+        let mut updates = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        {
+            let data = self.data.read().map_err(|_| "Semantic index lock poisoned")?;
+            for (path, metadata) in found {
+                let Some(key) = symbol_index::relative_key(&self.root, &path) else { continue };
+                let (modified, size) = (symbol_index::file_stamp(&metadata), metadata.len());
+                if !data.files.get(&key).is_some_and(|file| file.modified == modified && file.size == size) {
+                    updates.push((key.clone(), path, modified, size));
+                }
+                seen.insert(key);
+            }
+        }
+
+        let mut changed = {
+            let mut data = self.data.write().map_err(|_| "Semantic index lock poisoned")?;
+            let before = data.files.len();
+            data.files.retain(|key, _| seen.contains(key));
+            data.files.len() != before
+        };
+        for (done, (key, path, modified, size)) in updates.into_iter().enumerate() {
+            if self.stopping.load(Ordering::SeqCst) {
+                break;
+            }
+            self.embed_file(key, &path, modified, size)?;
+            changed = true;
+            if done % SAVE_EVERY == SAVE_EVERY - 1 {
+                let _ = save_index(&self.root, &self.data);
+            }
+        }
+        Ok(changed)
+    }
+
+    fn index_file(&self, path: &Path) -> Result<bool, String> {
+        let path = if path.is_absolute() { path.to_path_buf() } else { self.root.join(path) };
+        let Some(key) = symbol_index::relative_key(&self.root, &path) else { return Ok(false) };
+        match fs::metadata(&path) {
+            Ok(metadata) if metadata.is_file() && is_indexed(&path) && metadata.len() <= symbol_index::MAX_FILE_SIZE => {
+                self.embed_file(key, &path, symbol_index::file_stamp(&metadata), metadata.len())?;
+            }
+            _ => {
+                self.data.write().map_err(|_| "Semantic index lock poisoned")?.files.remove(&key);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Chunk a file and embed the chunks that have no vector yet.
+    fn embed_file(&self, key: String, path: &Path, modified: u64, size: u64) -> Result<(), String> {
+        // Files that aren't UTF-8 are recorded with no chunks so they aren't retried.
+        let content = fs::read_to_string(path).unwrap_or_default();
+        let chunks = chunk_source(&key, &content);
+        let inputs: Vec<String> = chunks.iter().map(|chunk| embed_input(&key, chunk)).collect();
+        let hashes: Vec<u64> = inputs.iter().map(|input| fnv1a(input.as_bytes())).collect();
+
+        let mut known: HashMap<u64, Vec<f32>> = {
+            let data = self.data.read().map_err(|_| "Semantic index lock poisoned")?;
+            data.files.get(&key).map(|file| file.chunks.iter().map(|chunk| (chunk.hash, chunk.vector.clone())).collect()).unwrap_or_default()
+        };
+        let missing: Vec<usize> = (0..chunks.len()).filter(|i| !known.contains_key(&hashes[*i])).collect();
+        for batch in missing.chunks(EMBED_BATCH) {
+            let batch_inputs: Vec<String> = batch.iter().map(|i| inputs[*i].clone()).collect();
+            let vectors = self.runtime.block_on(self.embedder.embed(&batch_inputs))?;
+            for (i, vector) in batch.iter().zip(vectors) {
+                known.insert(hashes[*i], vector);
+            }
+        }
+
+        let stored = chunks
+            .into_iter()
+            .zip(hashes)
+            .map(|(chunk, hash)| StoredChunk {
+                start_line: chunk.start_line,
+                end_line: chunk.end_line,
+                symbol: chunk.symbol,
+                hash,
+                vector: known.get(&hash).cloned().unwrap_or_default(),
+            })
+            .collect();
+        let mut data = self.data.write().map_err(|_| "Semantic index lock poisoned")?;
+        data.files.insert(key, EmbeddedFile { modified, size, chunks: stored });
+        Ok(())
+    }
+}
+
+fn is_indexed(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| INDEXED_EXTENSIONS.contains(&ext))
+}
+
+/// The text embedded for a chunk: where it is, then the code.
+fn embed_input(path: &str, chunk: &CodeChunk) -> String {
+    let mut input = match &chunk.symbol {
+        Some(symbol) => format!("{} {}\n{}", path, symbol, chunk.text),
+        None => format!("{}\n{}", path, chunk.text),
+    };
+    if let Some((end, _)) = input.char_indices().nth(MAX_EMBED_CHARS) {
+        input.truncate(end);
+    }
+    input
+}
+
+/// A definition's lines, from its docs and attributes to its last line.
+#[derive(Debug, Clone)]
+struct Span {
+    start: usize,
+    end: usize,
+    column: usize,
+    name: String,
+}
+
+/// Cut a file into chunks: one per top-level symbol, with symbols longer than
+/// [`MAX_CHUNK_LINES`] split at their children (e.g. the methods of an `impl`),
+/// and the code between symbols in chunks of its own. Files the parser doesn't
+/// handle are cut into fixed windows.
+pub fn chunk_source(path: &str, content: &str) -> Vec<CodeChunk> {
+    let lines: Vec<&str> = content.lines().collect();
+    let spans = symbol_spans(path, content, &lines);
+    let mut chunks = Vec::new();
+    let mut next_line = 1;
+    let mut i = 0;
+    while i < spans.len() {
+        let span = &spans[i];
+        let nested = spans[i + 1..].iter().take_while(|inner| inner.start <= span.end).count();
+        push_range(&mut chunks, &lines, next_line, span.start - 1, None);
+        if span.end - span.start >= MAX_CHUNK_LINES && nested > 0 {
+            let mut cursor = span.start;
+            for child in &spans[i + 1..=i + nested] {
+                // Only direct children; grandchildren are inside the child's chunk.
+                if child.start < cursor {
+                    continue;
+                }
+                push_range(&mut chunks, &lines, cursor, child.start - 1, Some(&span.name));
+                push_range(&mut chunks, &lines, child.start, child.end, Some(&child.name));
+                cursor = child.end + 1;
+            }
+            push_range(&mut chunks, &lines, cursor, span.end, Some(&span.name));
+        } else {
+            push_range(&mut chunks, &lines, span.start, span.end, Some(&span.name));
+        }
+        next_line = span.end + 1;
+        i += 1 + nested;
+    }
+    push_range(&mut chunks, &lines, next_line, lines.len(), None);
+    chunks
+}
+
+fn symbol_spans(path: &str, content: &str, lines: &[&str]) -> Vec<Span> {
+    let Some(symbols) = symbol_index::language_for(Path::new(path)).and_then(|language| ContextManager::extract_symbols(content, language)) else {
+        return Vec::new();
+    };
+    let mut spans: Vec<Span> = symbols
+        .into_iter()
+        .filter(|symbol| symbol.line >= 1 && symbol.line <= lines.len())
+        .map(|symbol| Span { start: symbol.line, end: symbol.end_line.clamp(symbol.line, lines.len()), column: symbol.column, name: symbol.name })
+        .collect();
+    spans.sort_by_key(|span| (span.start, Reverse(span.end)));
+
+    // Symbols the parser gives no end (Python) run until the next one at the same or lower indent.
+    for i in 0..spans.len() {
+        if spans[i].end == spans[i].start {
+            let mut end = spans[i + 1..].iter().find(|next| next.column <= spans[i].column).map_or(lines.len(), |next| next.start - 1);
+            while end > spans[i].start && lines[end - 1].trim().is_empty() {
+                end -= 1;
+            }
+            spans[i].end = end;
+        }
+    }
+    // Docs, attributes and decorators belong to the definition below them.
+    for span in &mut spans {
+        while span.start > 1 {
+            let above = lines[span.start - 2].trim_start();
+            if above.starts_with("///") || above.starts_with("#[") || above.starts_with('@') {
+                span.start -= 1;
+            } else {
+                break;
+            }
+        }
+    }
+    spans.sort_by_key(|span| (span.start, Reverse(span.end)));
+    spans
+}
+
+/// Add lines `start..=end` as chunks of at most [`MAX_CHUNK_LINES`], without surrounding blank lines.
+fn push_range(chunks: &mut Vec<CodeChunk>, lines: &[&str], start: usize, end: usize, symbol: Option<&str>) {
+    let mut window_start = start;
+    while window_start <= end {
+        let window_end = (window_start + MAX_CHUNK_LINES - 1).min(end);
+        let blank = |line: &usize| lines[line - 1].trim().is_empty();
+        let first = (window_start..=window_end).find(|line| !blank(line));
+        let last = (window_start..=window_end).rev().find(|line| !blank(line));
+        if let (Some(first), Some(last)) = (first, last) {
+            chunks.push(CodeChunk {
+                start_line: first,
+                end_line: last,
+                symbol: symbol.map(str::to_string),
+                text: lines[first - 1..last].join("\n"),
+            });
+        }
+        window_start = window_end + 1;
+    }
+}
+
+fn normalized(mut vector: Vec<f32>) -> Vec<f32> {
+    let length = dot(&vector, &vector).sqrt();
+    if length > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= length);
+    }
+    vector
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// FNV-1a, which unlike the std hasher is stable across Rust versions, so stored hashes stay valid.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+fn index_path(root: &Path) -> PathBuf {
+    root.join(INDEX_DIR).join(INDEX_FILE)
+}
+
+fn load_index(root: &Path) -> Option<VectorData> {
+    let content = fs::read_to_string(index_path(root)).ok()?;
+    let data: VectorData = serde_json::from_str(&content).ok()?;
+    (data.version == INDEX_VERSION).then_some(data)
+}
+
+fn save_index(root: &Path, data: &RwLock<VectorData>) -> Result<(), String> {
+    let path = index_path(root);
+    fs::create_dir_all(root.join(INDEX_DIR)).map_err(|e| format!("Failed to create index directory: {}", e))?;
+    let json = {
+        let mut data = data.write().map_err(|_| "Semantic index lock poisoned")?;
+        data.version = INDEX_VERSION;
+        serde_json::to_string(&*data).map_err(|e| format!("Failed to serialize semantic index: {}", e))?
+    };
+    // Write then rename so a crash never leaves a truncated index.
+    let temp = path.with_extension("json.tmp");
+    fs::write(&temp, json).map_err(|e| format!("Failed to write semantic index: {}", e))?;
+    fs::rename(&temp, &path).map_err(|e| format!("Failed to write semantic index: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::code_agent::test_support::TempDir;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Instant;
+    use crate::backend::code_agent::providers::embedding::EmbedFuture;
+
+    /// Embeds text as counts of a few keywords, and counts the texts it was given.
+    #[derive(Debug, Default)]
+    struct KeywordEmbedder {
+        embedded: AtomicUsize,
+    }
+
+    impl EmbeddingProvider for KeywordEmbedder {
+        fn embed<'a>(&'a self, _model: &'a str, inputs: &'a [String]) -> EmbedFuture<'a> {
+            self.embedded.fetch_add(inputs.len(), Ordering::SeqCst);
+            let vectors = inputs
+                .iter()
+                .map(|input| ["parse", "token", "render", "pixel"].iter().map(|word| input.matches(word).count() as f32 + 0.01).collect())
+                .collect();
+            Box::pin(async move { Ok(vectors) })
+        }
+    }
+
+    fn wait_until(condition: impl Fn() -> bool) {
+        let start = Instant::now();
+        while !condition() {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out waiting for the index");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_chunks_at_symbol_boundaries() {
+        let methods: String = (0..70).map(|i| format!("    fn step_{}(&self) {{}}\n", i)).collect();
+        let source = format!("use std::fmt;\n\n/// Reads tokens.\npub fn parse() {{\n}}\n\npub struct Big;\n\nimpl Big {{\n{}}}\n", methods);
+        let chunks = chunk_source("src/lib.rs", &source);
+        assert_eq!((chunks[0].start_line, chunks[0].symbol.as_deref()), (1, None));
+        assert_eq!((chunks[1].start_line, chunks[1].end_line, chunks[1].symbol.as_deref()), (3, 5, Some("parse")));
+        assert_eq!(chunks[2].symbol.as_deref(), Some("Big"));
+        // The long impl is split at its methods rather than cut mid-function.
+        assert_eq!(chunks[3].symbol.as_deref(), Some("impl Big"));
+        assert_eq!(chunks[4].symbol.as_deref(), Some("step_0"));
+        assert_eq!(chunks.iter().filter(|chunk| chunk.symbol.as_deref().is_some_and(|s| s.starts_with("step_"))).count(), 70);
+
+        let python = chunk_source("tool.py", "import os\n\n@cached\ndef load():\n    return 1\n\nclass Tool:\n    def run(self):\n        pass\n");
+        let spans: Vec<_> = python.iter().map(|chunk| (chunk.start_line, chunk.end_line, chunk.symbol.clone())).collect();
+        assert_eq!(spans, vec![(1, 1, None), (3, 5, Some("load".to_string())), (7, 9, Some("Tool".to_string()))]);
+        assert_eq!(chunk_source("notes.md", &"line\n".repeat(130)).len(), 3);
+    }
+
+    #[test]
+    fn test_index_embeds_changed_chunks_and_ranks_by_meaning() {
+        let temp = TempDir::new("semantic_index");
+        let root = temp.path().to_path_buf();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/lexer.rs"), "/// Split input into tokens.\npub fn parse_tokens() {\n    let token = 1;\n}\n").unwrap();
+        fs::write(root.join("src/draw.rs"), "pub fn render() {\n    let pixel = 0;\n}\n\npub fn render_pixel_row() {}\n").unwrap();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let provider = Arc::new(KeywordEmbedder::default());
+        let embedder = Embedder::new(provider.clone(), "keywords");
+        let index = SemanticIndex::with_poll_interval(root.clone(), embedder, runtime.handle().clone(), Duration::from_millis(20));
+        wait_until(|| index.status().ready);
+        assert_eq!((index.status().files, index.status().chunks, provider.embedded.load(Ordering::SeqCst)), (2, 3, 3));
+
+        let matches = runtime.block_on(index.search("how are tokens parsed", 2)).unwrap();
+        assert_eq!((matches[0].path.clone(), matches[0].symbol.as_deref()), (root.join("src/lexer.rs"), Some("parse_tokens")));
+        assert!(matches[0].text.starts_with("/// Split input into tokens."));
+        assert!(matches[0].score > matches[1].score);
+
+        // Only the edited chunk is embedded again.
+        fs::write(root.join("src/draw.rs"), "pub fn render() {\n    let pixel = 0;\n}\n\npub fn render_pixel_column() {}\n").unwrap();
+        index.refresh_file(root.join("src/draw.rs"));
+        wait_until(|| provider.embedded.load(Ordering::SeqCst) == 4);
+        fs::remove_file(root.join("src/lexer.rs")).unwrap();
+        wait_until(|| index.status().files == 1);
+        drop(index);
+
+        let reopened = load_index(&root).unwrap();
+        assert_eq!((reopened.model.as_str(), reopened.files["src/draw.rs"].chunks[1].symbol.as_deref()), ("keywords", Some("render_pixel_column")));
+    }
+}
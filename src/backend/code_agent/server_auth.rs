@@ -0,0 +1,259 @@
+//! Access control for the agent server: API tokens scoped to capabilities, and an
+//! audit log of every request the server handles.
+//!
+//! Tokens live in `agent_tokens.json` in the Jadio config dir. The first start creates
+//! a `default` token with every capability, so local tools can connect by reading it.
+
+use std::collections::hash_map::RandomState;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use super::tools::registry::Capability;
+
+const TOKENS_FILE: &str = "agent_tokens.json";
+const AUDIT_FILE: &str = "agent_server_audit.jsonl";
+const DEFAULT_TOKEN: &str = "default";
+/// Audit entries kept in memory for display.
+const RECENT_AUDIT_ENTRIES: usize = 200;
+
+fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("jadio-ide"))
+}
+
+/// A secret that grants its capabilities to whoever presents it.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub name: String,
+    pub secret: String,
+    pub capabilities: Vec<Capability>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl fmt::Debug for ApiToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Keep the secret out of logs.
+        f.debug_struct("ApiToken")
+            .field("name", &self.name)
+            .field("capabilities", &self.capabilities)
+            .field("created_at", &self.created_at)
+            .finish()
+    }
+}
+
+impl ApiToken {
+    pub fn new(name: &str, capabilities: &[Capability]) -> Self {
+        Self { name: name.to_string(), secret: generate_secret(), capabilities: capabilities.to_vec(), created_at: Utc::now() }
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+}
+
+/// The tokens the server accepts.
+#[derive(Debug, Default)]
+pub struct TokenStore {
+    /// `None` keeps tokens in memory only.
+    path: Option<PathBuf>,
+    tokens: Vec<ApiToken>,
+}
+
+impl TokenStore {
+    pub fn default_path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join(TOKENS_FILE))
+    }
+
+    /// Load the token file, creating it with a full-access `default` token if it has none.
+    pub fn load_or_create(path: &Path) -> Result<Self, String> {
+        let tokens = match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| format!("Invalid token file {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        let mut store = Self { path: Some(path.to_path_buf()), tokens };
+        if store.tokens.is_empty() {
+            store.create(DEFAULT_TOKEN, &Capability::ALL)?;
+        }
+        Ok(store)
+    }
+
+    pub fn tokens(&self) -> &[ApiToken] {
+        &self.tokens
+    }
+
+    /// Issue a token, replacing any token with the same name.
+    pub fn create(&mut self, name: &str, capabilities: &[Capability]) -> Result<ApiToken, String> {
+        let token = ApiToken::new(name, capabilities);
+        self.tokens.retain(|existing| existing.name != name);
+        self.tokens.push(token.clone());
+        self.save()?;
+        Ok(token)
+    }
+
+    /// Remove a token; returns whether it existed.
+    pub fn revoke(&mut self, name: &str) -> Result<bool, String> {
+        let before = self.tokens.len();
+        self.tokens.retain(|token| token.name != name);
+        let removed = self.tokens.len() != before;
+        if removed {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    /// The token with this secret, if any.
+    pub fn authenticate(&self, secret: &str) -> Option<&ApiToken> {
+        self.tokens.iter().find(|token| constant_time_eq(token.secret.as_bytes(), secret.as_bytes()))
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let json = serde_json::to_string_pretty(&self.tokens).map_err(|e| format!("Failed to serialize tokens: {}", e))?;
+        // The file holds secrets, so it is private to the user from the moment it exists,
+        // where permissions allow. Write then rename so the old file is never half-replaced.
+        let temp = path.with_extension("json.tmp");
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let write = || -> std::io::Result<()> {
+            let mut file = options.open(&temp)?;
+            // A temp file left over from before keeps its old mode.
+            #[cfg(unix)]
+            file.set_permissions(fs::Permissions::from_mode(0o600))?;
+            file.write_all(json.as_bytes())
+        };
+        write().map_err(|e| format!("Failed to write {}: {}", temp.display(), e))?;
+        fs::rename(&temp, path).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+/// A 256-bit secret in hex.
+///
+/// Each `RandomState` is keyed from the OS random source, so hashing with fresh
+/// states gives unpredictable output without pulling in an RNG crate.
+pub fn generate_secret() -> String {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos());
+    (0..4u64)
+        .map(|part| {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u64(part);
+            hasher.write_u128(nanos);
+            format!("{:016x}", hasher.finish())
+        })
+        .collect()
+}
+
+/// Compare secrets in time that doesn't depend on where they differ.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// One handled request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    /// Address of the client, `None` for requests made in-process.
+    pub client: Option<String>,
+    /// Name of the token the request was made with.
+    pub token: Option<String>,
+    pub method: String,
+    pub id: Value,
+    /// `"ok"`, or the JSON-RPC error code and message.
+    pub outcome: String,
+    pub duration_ms: u64,
+}
+
+/// Appends every request to the audit file and keeps the latest in memory.
+#[derive(Debug, Default)]
+pub struct AuditLog {
+    /// `None` keeps entries in memory only.
+    path: Option<PathBuf>,
+    recent: Mutex<VecDeque<AuditEntry>>,
+}
+
+impl AuditLog {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self { path, recent: Mutex::new(VecDeque::new()) }
+    }
+
+    pub fn default_path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join(AUDIT_FILE))
+    }
+
+    pub fn record(&self, entry: AuditEntry) {
+        if let Some(path) = &self.path {
+            if let Err(e) = Self::append(path, &entry) {
+                eprintln!("Failed to write agent server audit log: {}", e);
+            }
+        }
+        let mut recent = self.recent.lock().unwrap();
+        recent.push_back(entry);
+        if recent.len() > RECENT_AUDIT_ENTRIES {
+            recent.pop_front();
+        }
+    }
+
+    /// The latest entries, oldest first.
+    pub fn recent(&self) -> Vec<AuditEntry> {
+        self.recent.lock().unwrap().iter().cloned().collect()
+    }
+
+    fn append(path: &Path, entry: &AuditEntry) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let line = serde_json::to_string(entry).map_err(|e| format!("Failed to serialize audit entry: {}", e))?;
+        let mut file = OpenOptions::new().create(true).append(true).open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        writeln!(file, "{}", line).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::code_agent::test_support::TempDir;
+
+    #[test]
+    fn test_token_store_persists_scoped_tokens() {
+        let temp = TempDir::new("tokens");
+        let dir = temp.path().to_path_buf();
+        let path = dir.join(TOKENS_FILE);
+        let mut store = TokenStore::load_or_create(&path).unwrap();
+        let default = store.tokens()[0].clone();
+        assert_eq!((default.name.as_str(), default.secret.len()), ("default", 64));
+        #[cfg(unix)]
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert!(Capability::ALL.iter().all(|capability| default.allows(*capability)));
+
+        let reader = store.create("ci", &[Capability::Read]).unwrap();
+        assert_ne!(reader.secret, default.secret);
+        assert!(!format!("{:?}", reader).contains(&reader.secret));
+
+        let store = TokenStore::load_or_create(&path).unwrap();
+        assert_eq!(store.tokens().len(), 2);
+        let found = store.authenticate(&reader.secret).unwrap();
+        assert!(found.allows(Capability::Read) && !found.allows(Capability::Write));
+        assert!(store.authenticate("").is_none());
+        assert!(store.authenticate(&reader.secret[..63]).is_none());
+
+        let mut store = store;
+        assert!(store.revoke("ci").unwrap());
+        assert!(TokenStore::load_or_create(&path).unwrap().authenticate(&reader.secret).is_none());
+    }
+}
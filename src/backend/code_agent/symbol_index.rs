@@ -0,0 +1,466 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use super::context::{ContextManager, Symbol};
+
+pub(crate) const INDEX_DIR: &str = ".jadio";
+const INDEX_FILE: &str = "symbols.json";
+const INDEX_VERSION: u32 = 1;
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Files larger than this are skipped; they are almost always generated.
+pub(crate) const MAX_FILE_SIZE: u64 = 1024 * 1024;
+pub(crate) const SKIPPED_DIRS: &[&str] = &["target", "node_modules", "__pycache__", "dist", "build", "venv"];
+
+/// A symbol found by a workspace query.
+#[derive(Debug, Clone)]
+pub struct SymbolMatch {
+    pub path: PathBuf,
+    pub symbol: Symbol,
+    pub score: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedFile {
+    /// Modification time in milliseconds since the epoch.
+    modified: u64,
+    size: u64,
+    symbols: Vec<Symbol>,
+    /// `char_mask` of each symbol name, rebuilt on load.
+    #[serde(skip)]
+    masks: Vec<u64>,
+}
+
+impl IndexedFile {
+    fn set_symbols(&mut self, symbols: Vec<Symbol>) {
+        self.masks = symbols.iter().map(|symbol| char_mask(&symbol.name)).collect();
+        self.symbols = symbols;
+    }
+}
+
+/// The persisted index: symbols by workspace-relative path.
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexData {
+    /// Always [`INDEX_VERSION`]; older indexes are dropped on load.
+    version: u32,
+    files: HashMap<String, IndexedFile>,
+}
+
+impl Default for IndexData {
+    fn default() -> Self {
+        Self { version: INDEX_VERSION, files: HashMap::new() }
+    }
+}
+
+enum IndexCommand {
+    /// Re-index one file now instead of waiting for the next scan.
+    File(PathBuf),
+    Rescan,
+    Shutdown,
+}
+
+/// Workspace-wide symbol index kept up to date by a background thread.
+///
+/// The index is loaded from `.jadio/symbols.json` on open, so only files that
+/// changed since the last session are re-parsed. After that the workspace is
+/// rescanned periodically and changed files are re-indexed.
+#[derive(Debug)]
+pub struct SymbolIndex {
+    root: PathBuf,
+    data: Arc<RwLock<IndexData>>,
+    ready: Arc<AtomicBool>,
+    commands: Sender<IndexCommand>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl SymbolIndex {
+    /// Open the index for a workspace and start indexing in the background.
+    pub fn open(root: PathBuf) -> Self {
+        Self::with_poll_interval(root, DEFAULT_POLL_INTERVAL)
+    }
+
+    pub fn with_poll_interval(root: PathBuf, poll_interval: Duration) -> Self {
+        let data = Arc::new(RwLock::new(load_index(&root).unwrap_or_default()));
+        let ready = Arc::new(AtomicBool::new(false));
+        let (commands, receiver) = mpsc::channel();
+
+        let worker = {
+            let (root, data, ready) = (root.clone(), Arc::clone(&data), Arc::clone(&ready));
+            thread::spawn(move || {
+                let mut command = IndexCommand::Rescan;
+                loop {
+                    let changed = match command {
+                        IndexCommand::File(path) => index_file(&root, &path, &data),
+                        IndexCommand::Rescan => scan_workspace(&root, &data),
+                        IndexCommand::Shutdown => break,
+                    };
+                    if changed || !ready.load(Ordering::Relaxed) {
+                        let _ = save_index(&root, &data);
+                    }
+                    ready.store(true, Ordering::Relaxed);
+                    command = match receiver.recv_timeout(poll_interval) {
+                        Ok(command) => command,
+                        Err(RecvTimeoutError::Timeout) => IndexCommand::Rescan,
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    };
+                }
+            })
+        };
+
+        Self { root, data, ready, commands, worker: Some(worker) }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Check if the first scan of the workspace has finished.
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Relaxed)
+    }
+
+    pub fn file_count(&self) -> usize {
+        self.data.read().map(|data| data.files.len()).unwrap_or(0)
+    }
+
+    /// Re-index a file right away, e.g. after it was saved.
+    pub fn refresh_file<P: AsRef<Path>>(&self, path: P) {
+        let _ = self.commands.send(IndexCommand::File(path.as_ref().to_path_buf()));
+    }
+
+    /// Rescan the whole workspace now.
+    pub fn refresh(&self) {
+        let _ = self.commands.send(IndexCommand::Rescan);
+    }
+
+    /// Get the indexed symbols of a file.
+    pub fn symbols_in_file<P: AsRef<Path>>(&self, path: P) -> Vec<Symbol> {
+        let Some(key) = relative_key(&self.root, path.as_ref()) else {
+            return Vec::new();
+        };
+        self.data
+            .read()
+            .ok()
+            .and_then(|data| data.files.get(&key).map(|file| file.symbols.clone()))
+            .unwrap_or_default()
+    }
+
+    /// Fuzzy-search symbol names across the workspace, best matches first.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SymbolMatch> {
+        let query: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_lowercase).collect();
+        let query_mask = char_mask(&query.iter().collect::<String>());
+        let Ok(data) = self.data.read() else {
+            return Vec::new();
+        };
+
+        let mut scored: Vec<(i64, &String, &Symbol)> = data
+            .files
+            .iter()
+            .flat_map(|(path, file)| file.symbols.iter().zip(&file.masks).map(move |symbol| (path, symbol)))
+            .filter(|(_, (_, mask))| *mask & query_mask == query_mask)
+            .map(|(path, (symbol, _))| (path, symbol))
+            .filter_map(|(path, symbol)| fuzzy_score(&query, &symbol.name).map(|score| (score, path, symbol)))
+            .collect();
+        rank_matches(&mut scored, limit, |(score, _, symbol)| (*score, &symbol.name));
+        scored
+            .into_iter()
+            .map(|(score, path, symbol)| SymbolMatch {
+                path: self.root.join(path),
+                symbol: symbol.clone(),
+                score,
+            })
+            .collect()
+    }
+}
+
+impl Drop for SymbolIndex {
+    fn drop(&mut self) {
+        let _ = self.commands.send(IndexCommand::Shutdown);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Keep the best `limit` matches, sorted by score, then shorter and alphabetically earlier names.
+pub fn rank_matches<T>(matches: &mut Vec<T>, limit: usize, key: impl Fn(&T) -> (i64, &String)) {
+    let compare = |a: &T, b: &T| {
+        let ((score_a, name_a), (score_b, name_b)) = (key(a), key(b));
+        score_b.cmp(&score_a).then(name_a.len().cmp(&name_b.len())).then(name_a.cmp(name_b))
+    };
+    // Partition first so a short query matching most of a large index doesn't sort everything.
+    if limit > 0 && matches.len() > limit {
+        matches.select_nth_unstable_by(limit - 1, compare);
+    }
+    matches.truncate(limit);
+    matches.sort_by(compare);
+}
+
+/// Bitset of the ASCII letters and digits in `text`, ignoring case. A name can
+/// only match a query if its mask contains the query's, which rejects most
+/// names without scoring them.
+fn char_mask(text: &str) -> u64 {
+    text.bytes().fold(0, |mask, byte| match byte.to_ascii_lowercase() {
+        c @ b'a'..=b'z' => mask | 1 << (c - b'a'),
+        c @ b'0'..=b'9' => mask | 1 << (26 + c - b'0'),
+        _ => mask,
+    })
+}
+
+/// Score `candidate` against a lowercased query whose characters must all
+/// appear in order. Matches at word starts and runs of consecutive matches
+/// score higher. Returns `None` if the query doesn't match.
+pub fn fuzzy_score(query: &[char], candidate: &str) -> Option<i64> {
+    if query.is_empty() {
+        return Some(0);
+    }
+    // Nearly all identifiers are ASCII; avoid per-char Unicode case mapping for them.
+    if candidate.is_ascii() {
+        score_chars(query, candidate.bytes().map(char::from), |c| c.to_ascii_lowercase())
+    } else {
+        score_chars(query, candidate.chars(), |c| c.to_lowercase().next().unwrap_or(c))
+    }
+}
+
+fn score_chars(query: &[char], candidate: impl Iterator<Item = char>, lowercase: impl Fn(char) -> char) -> Option<i64> {
+    let mut score = 0i64;
+    let mut matched = 0;
+    let mut last_match: Option<usize> = None;
+    let mut previous: Option<char> = None;
+    let mut length = 0;
+
+    for (i, c) in candidate.enumerate() {
+        length += 1;
+        if matched < query.len() && lowercase(c) == query[matched] {
+            let word_start = match previous {
+                None => true,
+                Some(p) => !p.is_alphanumeric() || (p.is_lowercase() && c.is_uppercase()),
+            };
+            score += 1;
+            if word_start {
+                score += 8;
+            }
+            if last_match.is_some_and(|last| last + 1 == i) {
+                score += 5;
+            } else if let Some(last) = last_match {
+                score -= ((i - last - 1) as i64).min(5);
+            }
+            last_match = Some(i);
+            matched += 1;
+        }
+        previous = Some(c);
+    }
+
+    if matched < query.len() {
+        return None;
+    }
+    if length == query.len() {
+        score += 20;
+    }
+    Some(score)
+}
+
+fn index_path(root: &Path) -> PathBuf {
+    root.join(INDEX_DIR).join(INDEX_FILE)
+}
+
+fn load_index(root: &Path) -> Option<IndexData> {
+    let content = fs::read_to_string(index_path(root)).ok()?;
+    let mut data: IndexData = serde_json::from_str(&content).ok()?;
+    for file in data.files.values_mut() {
+        let symbols = std::mem::take(&mut file.symbols);
+        file.set_symbols(symbols);
+    }
+    (data.version == INDEX_VERSION).then_some(data)
+}
+
+fn save_index(root: &Path, data: &RwLock<IndexData>) -> Result<(), String> {
+    let path = index_path(root);
+    fs::create_dir_all(root.join(INDEX_DIR)).map_err(|e| format!("Failed to create index directory: {}", e))?;
+    // Searches only read too, so they aren't held up while the index is written.
+    let json = {
+        let data = data.read().map_err(|_| "Symbol index lock poisoned")?;
+        serde_json::to_string(&*data).map_err(|e| format!("Failed to serialize symbol index: {}", e))?
+    };
+    // Write then rename so a crash never leaves a truncated index.
+    let temp = path.with_extension("json.tmp");
+    fs::write(&temp, json).map_err(|e| format!("Failed to write symbol index: {}", e))?;
+    fs::rename(&temp, &path).map_err(|e| format!("Failed to write symbol index: {}", e))
+}
+
+pub(crate) fn relative_key(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    if relative.is_absolute() {
+        return None;
+    }
+    Some(relative.to_string_lossy().replace('\\', "/"))
+}
+
+pub(crate) fn language_for(path: &Path) -> Option<&'static str> {
+    match path.extension()?.to_str()? {
+        "rs" => Some("rust"),
+        "py" => Some("python"),
+        _ => None,
+    }
+}
+
+pub(crate) fn file_stamp(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
+
+/// Walk the workspace, re-indexing new and modified files and dropping deleted ones.
+/// Returns whether anything changed.
+fn scan_workspace(root: &Path, data: &RwLock<IndexData>) -> bool {
+    let mut found = Vec::new();
+    collect_source_files(root, &mut found);
+
+    let mut updates = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    {
+        let Ok(current) = data.read() else { return false };
+        for (path, metadata) in found {
+            let Some(key) = relative_key(root, &path) else { continue };
+            let (modified, size) = (file_stamp(&metadata), metadata.len());
+            let unchanged = current.files.get(&key).is_some_and(|file| file.modified == modified && file.size == size);
+            if !unchanged {
+                updates.push((key.clone(), path, modified, size));
+            }
+            seen.insert(key);
+        }
+    }
+
+    // Parse outside the lock so searches aren't blocked by a large first scan.
+    let parsed: Vec<(String, u64, u64, Option<Vec<Symbol>>)> = updates
+        .into_iter()
+        .map(|(key, path, modified, size)| (key, modified, size, parse_file(&path)))
+        .collect();
+
+    let Ok(mut data) = data.write() else { return false };
+    let before = data.files.len();
+    data.files.retain(|key, _| seen.contains(key));
+    let mut changed = data.files.len() != before;
+    for (key, modified, size, symbols) in parsed {
+        changed = true;
+        apply_update(&mut data, key, modified, size, symbols);
+    }
+    changed
+}
+
+fn index_file(root: &Path, path: &Path, data: &RwLock<IndexData>) -> bool {
+    let path = if path.is_absolute() { path.to_path_buf() } else { root.join(path) };
+    let Some(key) = relative_key(root, &path) else { return false };
+    let metadata = fs::metadata(&path).ok().filter(|m| m.is_file() && language_for(&path).is_some());
+    let symbols = metadata.as_ref().map(|_| parse_file(&path));
+    let Ok(mut data) = data.write() else { return false };
+    match (metadata, symbols) {
+        (Some(metadata), Some(symbols)) => apply_update(&mut data, key, file_stamp(&metadata), metadata.len(), symbols),
+        _ => {
+            data.files.remove(&key);
+        }
+    }
+    true
+}
+
+/// Store freshly parsed symbols. A file that no longer parses (e.g. saved
+/// mid-edit) keeps its previous symbols until it parses again.
+fn apply_update(data: &mut IndexData, key: String, modified: u64, size: u64, symbols: Option<Vec<Symbol>>) {
+    let entry = data.files.entry(key).or_insert_with(|| IndexedFile { modified, size, symbols: Vec::new(), masks: Vec::new() });
+    entry.modified = modified;
+    entry.size = size;
+    if let Some(symbols) = symbols {
+        entry.set_symbols(symbols);
+    }
+}
+
+fn parse_file(path: &Path) -> Option<Vec<Symbol>> {
+    let content = fs::read_to_string(path).ok()?;
+    ContextManager::extract_symbols(&content, language_for(path)?)
+}
+
+fn collect_source_files(dir: &Path, files: &mut Vec<(PathBuf, fs::Metadata)>) {
+    collect_files(dir, &|path| language_for(path).is_some(), files);
+}
+
+/// Find the files under `dir` that `include` accepts, skipping hidden and build
+/// directories and files over [`MAX_FILE_SIZE`].
+pub(crate) fn collect_files(dir: &Path, include: &dyn Fn(&Path) -> bool, files: &mut Vec<(PathBuf, fs::Metadata)>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(metadata) = entry.metadata() else { continue };
+        let name = entry.file_name().to_string_lossy().to_string();
+        if metadata.is_dir() {
+            if !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_str()) {
+                collect_files(&path, include, files);
+            }
+        } else if metadata.len() <= MAX_FILE_SIZE && include(&path) {
+            files.push((path, metadata));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn wait_until(condition: impl Fn() -> bool) {
+        let start = Instant::now();
+        while !condition() {
+            assert!(start.elapsed() < Duration::from_secs(5), "timed out waiting for the index");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_fuzzy_score_prefers_word_starts() {
+        let query: Vec<char> = "gts".chars().collect();
+        let boundary = fuzzy_score(&query, "get_text_span").unwrap();
+        let scattered = fuzzy_score(&query, "gutters").unwrap();
+        assert!(boundary > scattered);
+        assert!(fuzzy_score(&query, "span").is_none());
+        assert!(fuzzy_score(&"ctx".chars().collect::<Vec<_>>(), "ContextManager").is_some());
+    }
+
+    #[test]
+    fn test_index_updates_incrementally_and_persists() {
+        let root = std::env::temp_dir().join(format!("jadio_symbol_index_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join("src/lib.rs"), "pub struct WorkspaceIndex;\nimpl WorkspaceIndex {\n    pub fn rebuild(&self) {}\n}\n").unwrap();
+        fs::write(root.join("tool.py"), "def run_tool():\n    pass\n").unwrap();
+        fs::write(root.join("target/generated.rs"), "fn skipped() {}\n").unwrap();
+
+        let index = SymbolIndex::with_poll_interval(root.clone(), Duration::from_millis(20));
+        wait_until(|| index.is_ready());
+        assert_eq!(index.file_count(), 2);
+        let matches = index.search("wsidx", 10);
+        assert_eq!(matches[0].symbol.name, "WorkspaceIndex");
+        assert_eq!(matches[0].path, root.join("src/lib.rs"));
+        assert_eq!(index.search("rebuild", 10)[0].symbol.scope, "WorkspaceIndex");
+        assert!(index.search("skipped", 10).is_empty());
+
+        fs::write(root.join("src/lib.rs"), "pub fn renamed_entry() {}\n").unwrap();
+        index.refresh_file(root.join("src/lib.rs"));
+        wait_until(|| !index.search("renamed_entry", 10).is_empty());
+        assert!(index.search("rebuild", 10).is_empty());
+
+        fs::remove_file(root.join("tool.py")).unwrap();
+        wait_until(|| index.file_count() == 1);
+        drop(index);
+
+        let reopened = load_index(&root).unwrap();
+        assert_eq!(reopened.files["src/lib.rs"].symbols[0].name, "renamed_entry");
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
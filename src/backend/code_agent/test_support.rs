@@ -0,0 +1,25 @@
+use std::path::{Path, PathBuf};
+
+/// A fresh directory under the system temp dir that is removed, with everything in it,
+/// when dropped, so a failing test does not leave it behind.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Create an empty directory named after `name` and the process id.
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("jadio_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
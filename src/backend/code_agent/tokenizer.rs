@@ -0,0 +1,320 @@
+//! Token counting per model.
+//!
+//! A model is counted with a byte-pair-encoding vocabulary when one is found on
+//! disk (tiktoken's `.tiktoken` format: one base64 token and its rank per line),
+//! and with a character-based estimate otherwise. Vocabularies are looked up in
+//! the tokenizer directory as `<model name>.tiktoken`, then under the name of the
+//! encoding the model is known to use, so any model can be given its own file.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use super::chat::ChatMessage;
+use super::context_builder::estimate_tokens;
+use super::model_loader::{ModelConfig, ModelProvider};
+
+/// Tokens added per chat message for role and separators.
+pub const MESSAGE_OVERHEAD: usize = 4;
+const VOCABULARY_EXTENSION: &str = "tiktoken";
+/// Longest piece, in characters, handed to the merge loop, which is quadratic in
+/// the piece length. Longer runs of letters or spaces are split.
+const MAX_PIECE_CHARS: usize = 64;
+
+pub trait Tokenizer: Send + Sync + fmt::Debug {
+    /// Vocabulary name, or `"estimate"` for the fallback.
+    fn name(&self) -> &str;
+    fn count(&self, text: &str) -> usize;
+    /// False when counts are only an estimate.
+    fn is_exact(&self) -> bool {
+        true
+    }
+}
+
+/// Tokens for a list of chat messages, including per-message overhead.
+pub fn count_messages(tokenizer: &dyn Tokenizer, messages: &[ChatMessage]) -> usize {
+    messages.iter().map(|message| tokenizer.count(&message.content) + MESSAGE_OVERHEAD).sum()
+}
+
+/// Roughly four characters per token; used when no vocabulary is available.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HeuristicTokenizer;
+
+impl Tokenizer for HeuristicTokenizer {
+    fn name(&self) -> &str {
+        "estimate"
+    }
+
+    fn count(&self, text: &str) -> usize {
+        estimate_tokens(text)
+    }
+
+    fn is_exact(&self) -> bool {
+        false
+    }
+}
+
+/// A byte-pair-encoding vocabulary: byte sequences ranked by merge priority.
+pub struct BpeTokenizer {
+    name: String,
+    ranks: HashMap<Vec<u8>, u32>,
+}
+
+impl fmt::Debug for BpeTokenizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BpeTokenizer").field("name", &self.name).field("tokens", &self.ranks.len()).finish()
+    }
+}
+
+impl BpeTokenizer {
+    /// Parse a vocabulary in tiktoken's format: `<base64 bytes> <rank>` per line.
+    pub fn from_tiktoken(name: &str, text: &str) -> Result<Self, String> {
+        let mut ranks = HashMap::new();
+        for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let bad_line = || format!("{}: bad vocabulary line {}", name, number + 1);
+            let (token, rank) = line.trim().split_once(' ').ok_or_else(bad_line)?;
+            let token = decode_base64(token).ok_or_else(bad_line)?;
+            let rank = rank.trim().parse().map_err(|_| bad_line())?;
+            ranks.insert(token, rank);
+        }
+        if ranks.is_empty() {
+            return Err(format!("{}: vocabulary is empty", name));
+        }
+        Ok(Self { name: name.to_string(), ranks })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        Self::from_tiktoken(&name, &text)
+    }
+
+    /// Tokens for one pre-split piece: merge adjacent parts, lowest rank first, until no pair is in the vocabulary.
+    fn count_piece(&self, piece: &[u8]) -> usize {
+        if self.ranks.contains_key(piece) {
+            return 1;
+        }
+        let mut bounds: Vec<usize> = (0..=piece.len()).collect();
+        loop {
+            let best = bounds
+                .windows(3)
+                .enumerate()
+                .filter_map(|(i, window)| self.ranks.get(&piece[window[0]..window[2]]).map(|rank| (*rank, i)))
+                .min();
+            match best {
+                Some((_, i)) => {
+                    bounds.remove(i + 1);
+                }
+                None => return bounds.len() - 1,
+            }
+        }
+    }
+}
+
+impl Tokenizer for BpeTokenizer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn count(&self, text: &str) -> usize {
+        pieces(text).into_iter().map(|piece| self.count_piece(piece.as_bytes())).sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Letter,
+    Digit,
+    Space,
+    Other,
+}
+
+fn class_of(c: char) -> CharClass {
+    if c.is_alphabetic() {
+        CharClass::Letter
+    } else if c.is_numeric() {
+        CharClass::Digit
+    } else if c.is_whitespace() {
+        CharClass::Space
+    } else {
+        CharClass::Other
+    }
+}
+
+/// Split text the way GPT-style vocabularies expect before merging: words and
+/// punctuation runs with their leading space, digits in groups of three, and
+/// whitespace runs that leave their last space to the word after them.
+fn pieces(text: &str) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let offset = |i: usize| chars.get(i).map_or(text.len(), |(offset, _)| *offset);
+    let mut pieces = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        let mut class = class_of(chars[i].1);
+        if chars[i].1 == ' ' && chars.get(i + 1).is_some_and(|(_, next)| matches!(class_of(*next), CharClass::Letter | CharClass::Other)) {
+            i += 1;
+            class = class_of(chars[i].1);
+        }
+        let limit = if class == CharClass::Digit { 3 } else { MAX_PIECE_CHARS };
+        let mut len = 1;
+        i += 1;
+        while i < chars.len() && len < limit && class_of(chars[i].1) == class {
+            i += 1;
+            len += 1;
+        }
+        if class == CharClass::Space && i < chars.len() && i - start > 1 && chars[i - 1].1 == ' ' && class_of(chars[i].1) != CharClass::Digit {
+            i -= 1;
+        }
+        pieces.push(&text[offset(start)..offset(i)]);
+    }
+    pieces
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    };
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in text.trim_end_matches('=').bytes() {
+        buffer = (buffer << 6) | value(c)? as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// The published encoding a model family uses, for models without their own vocabulary file.
+fn encoding_for(model: &ModelConfig) -> Option<&'static str> {
+    let name = model.name.to_lowercase();
+    match model.provider {
+        ModelProvider::OpenAI if name.starts_with("gpt-4o") || name.starts_with("o1") || name.starts_with("o3") || name.starts_with("gpt-4.1") => Some("o200k_base"),
+        ModelProvider::OpenAI if name.starts_with("gpt-4") || name.starts_with("gpt-3.5") || name.starts_with("text-embedding") => Some("cl100k_base"),
+        _ => None,
+    }
+}
+
+/// Picks a tokenizer for each model and keeps loaded vocabularies for reuse.
+#[derive(Debug)]
+pub struct TokenCounter {
+    dir: Option<PathBuf>,
+    loaded: Mutex<HashMap<String, Arc<dyn Tokenizer>>>,
+}
+
+impl Default for TokenCounter {
+    fn default() -> Self {
+        Self::new(Self::default_dir())
+    }
+}
+
+impl TokenCounter {
+    /// Look for vocabularies in `dir`; `None` always estimates.
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self { dir, loaded: Mutex::new(HashMap::new()) }
+    }
+
+    /// `tokenizers` next to the IDE settings file.
+    pub fn default_dir() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("jadio-ide").join("tokenizers"))
+    }
+
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// The tokenizer for `model`, loading its vocabulary on first use.
+    pub fn for_model(&self, model: &ModelConfig) -> Arc<dyn Tokenizer> {
+        let key = format!("{:?}/{}", model.provider, model.name);
+        let mut loaded = self.loaded.lock().unwrap();
+        loaded.entry(key).or_insert_with(|| self.load(model)).clone()
+    }
+
+    /// Forget loaded vocabularies, e.g. after files were added to the directory.
+    pub fn reload(&self) {
+        self.loaded.lock().unwrap().clear();
+    }
+
+    fn load(&self, model: &ModelConfig) -> Arc<dyn Tokenizer> {
+        let Some(dir) = &self.dir else {
+            return Arc::new(HeuristicTokenizer);
+        };
+        let candidates = std::iter::once(model.name.as_str()).chain(encoding_for(model));
+        for name in candidates {
+            let path = dir.join(format!("{}.{}", name.replace(['/', '\\', ':'], "_"), VOCABULARY_EXTENSION));
+            if !path.exists() {
+                continue;
+            }
+            match BpeTokenizer::load(&path) {
+                Ok(tokenizer) => return Arc::new(tokenizer),
+                Err(e) => eprintln!("Failed to load tokenizer: {}", e),
+            }
+        }
+        Arc::new(HeuristicTokenizer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::code_agent::test_support::TempDir;
+
+    fn base64(bytes: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut out = String::new();
+        for chunk in bytes.chunks(3) {
+            let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+            for i in 0..=chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 63] as char);
+            }
+            out.push_str(&"=".repeat(3 - chunk.len()));
+        }
+        out
+    }
+
+    #[test]
+    fn test_bpe_vocabulary_from_file_with_fallback() {
+        let temp = TempDir::new("tokenizer");
+        let dir = temp.path().to_path_buf();
+        // Every byte, then merges that build " hello" and "12".
+        let mut tokens: Vec<Vec<u8>> = (0..=255u8).map(|b| vec![b]).collect();
+        tokens.extend([b"he".to_vec(), b"ll".to_vec(), b"hell".to_vec(), b"hello".to_vec(), b" hello".to_vec(), b"12".to_vec()]);
+        let vocabulary: String = tokens.iter().enumerate().map(|(rank, token)| format!("{} {}\n", base64(token), rank)).collect();
+        std::fs::write(dir.join("cl100k_base.tiktoken"), vocabulary).unwrap();
+
+        let counter = TokenCounter::new(Some(dir.clone()));
+        let mut model = ModelConfig {
+            name: "gpt-4-turbo".to_string(),
+            provider: ModelProvider::OpenAI,
+            api_endpoint: None,
+            api_key: None,
+            model_path: None,
+            parameters: Default::default(),
+        };
+        let tokenizer = counter.for_model(&model);
+        assert_eq!(tokenizer.name(), "cl100k_base");
+        assert!(tokenizer.is_exact());
+        // " hello" is one token; "world" has no merges; "1234" splits into "123" and "4", then "12" merges.
+        assert_eq!(pieces("hi hello  world 1234"), vec!["hi", " hello", " ", " world", " ", "123", "4"]);
+        assert_eq!(tokenizer.count(" hello"), 1);
+        // Long runs are split before merging, so pasted walls of text stay cheap to count.
+        assert_eq!(pieces(&"a".repeat(200)).len(), 4);
+        assert_eq!(tokenizer.count("hi hello  world 1234"), 2 + 1 + 1 + 6 + 1 + 2 + 1);
+
+        model.provider = ModelProvider::Anthropic;
+        let fallback = counter.for_model(&model);
+        assert!(!fallback.is_exact());
+        assert_eq!(fallback.count("twelve chars"), 3);
+        assert!(BpeTokenizer::from_tiktoken("bad", "not-base64! 1").is_err());
+    }
+}
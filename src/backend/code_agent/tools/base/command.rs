@@ -0,0 +1,194 @@
+use std::io::Read;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use serde_json::{json, Value};
+use crate::backend::code_agent::tools::registry::{resolve_path, str_arg, AgentTool, Capability, ToolContext};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long to keep reading output once the command is done or killed.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Represents the result of a shell command.
+#[derive(Debug, Clone)]
+pub struct CommandResult {
+    /// `None` if the command was killed after timing out.
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub timed_out: bool,
+    /// Bytes dropped from stdout and stderr beyond the output cap.
+    pub truncated: usize,
+}
+
+/// Tool for running shell commands in the workspace, as far as the command policy allows.
+pub struct RunCommandTool;
+
+impl RunCommandTool {
+    /// Run `command` through the platform shell in `dir`, killing it after `timeout`.
+    ///
+    /// On unix the command gets its own process group, which is killed once the
+    /// shell exits or times out, so background jobs it started don't outlive it
+    /// or hold its output open. Keeps at most `max_output` bytes of each of
+    /// stdout and stderr.
+    pub fn run(command: &str, dir: &Path, timeout: Duration, max_output: usize) -> std::io::Result<CommandResult> {
+        let mut shell = if cfg!(windows) {
+            let mut shell = Command::new("cmd");
+            shell.arg("/C");
+            shell
+        } else {
+            let mut shell = Command::new("sh");
+            shell.arg("-c");
+            #[cfg(unix)]
+            std::os::unix::process::CommandExt::process_group(&mut shell, 0);
+            shell
+        };
+        let mut child = shell
+            .arg(command)
+            .current_dir(dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        // Drain the pipes on threads so a chatty command can't block on a full pipe.
+        let stdout = read_pipe(child.stdout.take(), max_output);
+        let stderr = read_pipe(child.stderr.take(), max_output);
+        let (exit_code, timed_out) = wait_with_timeout(&mut child, timeout)?;
+        kill_process_group(&child);
+        // A process that escaped the group may still hold the pipes; stop waiting for it.
+        let drained_by = Instant::now() + DRAIN_TIMEOUT;
+        let drain = |pipe: mpsc::Receiver<(String, usize)>| pipe.recv_timeout(drained_by.saturating_duration_since(Instant::now())).unwrap_or_default();
+        let (stdout, stdout_dropped) = drain(stdout);
+        let (stderr, stderr_dropped) = drain(stderr);
+        Ok(CommandResult { exit_code, stdout, stderr, timed_out, truncated: stdout_dropped + stderr_dropped })
+    }
+}
+
+/// Read a pipe to the end on a thread, keeping the first `limit` bytes and counting the rest.
+fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>, limit: usize) -> mpsc::Receiver<(String, usize)> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        let mut dropped = 0;
+        if let Some(mut pipe) = pipe {
+            let mut buffer = [0; 8192];
+            // Keep draining past the limit so the command doesn't block on a full pipe.
+            while let Ok(read) = pipe.read(&mut buffer) {
+                if read == 0 {
+                    break;
+                }
+                let kept = read.min(limit - output.len());
+                output.extend_from_slice(&buffer[..kept]);
+                dropped += read - kept;
+            }
+        }
+        let _ = sender.send((String::from_utf8_lossy(&output).to_string(), dropped));
+    });
+    receiver
+}
+
+fn wait_with_timeout(child: &mut Child, timeout: Duration) -> std::io::Result<(Option<i32>, bool)> {
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok((status.code(), false));
+        }
+        if started.elapsed() >= timeout {
+            kill_process_group(child);
+            child.kill()?;
+            child.wait()?;
+            return Ok((None, true));
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+/// Kill whatever is left of the command's process group.
+#[cfg(unix)]
+fn kill_process_group(child: &Child) {
+    // The group id is the shell's pid, and stays reserved while the group has members.
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(_child: &Child) {}
+
+impl AgentTool for RunCommandTool {
+    fn name(&self) -> &'static str {
+        "run_command"
+    }
+
+    fn capability(&self) -> Capability {
+        Capability::Execute
+    }
+
+    fn description(&self) -> &'static str {
+        "Run a shell command in the workspace and return its exit code, stdout and stderr. \
+         Commands outside the allow list wait for the user's approval; destructive ones and paths outside the workspace are refused."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "command": { "type": "string" },
+                "cwd": { "type": "string", "description": "Directory to run in, relative to the workspace root" },
+                "timeout_secs": { "type": "integer", "minimum": 1 }
+            },
+            "required": ["command"]
+        })
+    }
+
+    fn call(&self, context: &ToolContext, input: &Value) -> Result<String, String> {
+        let dir = match input["cwd"].as_str() {
+            Some(cwd) => resolve_path(context.workspace, cwd)?,
+            None => context.workspace.to_path_buf(),
+        };
+        let command = context.commands.authorize(str_arg(input, "command")?, context.workspace, &dir)?;
+        let policy = context.commands.policy();
+        let timeout = input["timeout_secs"].as_u64().map_or(DEFAULT_TIMEOUT, Duration::from_secs).min(policy.max_timeout);
+        let result = Self::run(&command, &dir, timeout, policy.max_output).map_err(|e| format!("Failed to run command: {}", e))?;
+
+        let status = match result.exit_code {
+            _ if result.timed_out => format!("timed out after {}s", timeout.as_secs()),
+            Some(code) => format!("exit code {}", code),
+            None => "killed by signal".to_string(),
+        };
+        let mut output = format!("{}\n--- stdout ---\n{}\n--- stderr ---\n{}", status, result.stdout, result.stderr);
+        if result.truncated > 0 {
+            output.push_str(&format!("\n... ({} more bytes of output dropped)", result.truncated));
+        }
+        if command != input["command"].as_str().unwrap_or_default() {
+            output = format!("The user changed the command to `{}`\n{}", command, output);
+        }
+        if result.exit_code == Some(0) {
+            Ok(output)
+        } else {
+            Err(output)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_background_jobs_do_not_hold_the_command_open() {
+        let started = Instant::now();
+        let result = RunCommandTool::run("sleep 30 & echo started", &std::env::temp_dir(), Duration::from_secs(10), 1024).unwrap();
+        assert_eq!(result.exit_code, Some(0));
+        assert_eq!(result.stdout.trim(), "started");
+
+        let result = RunCommandTool::run("sleep 30 & sleep 30", &std::env::temp_dir(), Duration::from_millis(200), 1024).unwrap();
+        assert!(result.timed_out);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
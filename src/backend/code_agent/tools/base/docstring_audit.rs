@@ -1,59 +1,53 @@
-use std::fs;
 use std::path::Path;
+use serde_json::{json, Value};
+use super::parse::{ItemKind, ParseTool, ParsedItem};
+use crate::backend::code_agent::tools::registry::{resolve_path, str_arg, AgentTool, ToolContext};
 
 /// Represents the result of a docstring audit.
 #[derive(Debug, Clone)]
 pub struct DocstringAuditResult {
     pub file: String,
-    pub missing_docstrings: Vec<String>,
+    /// Functions and methods without a doc comment, with their spans.
+    pub missing_docstrings: Vec<ParsedItem>,
     pub total_functions: usize,
     pub documented_functions: usize,
+    /// Syntax error that left part of the file out of the audit, if any.
+    pub parse_error: Option<String>,
 }
 
 /// Tool for auditing and suggesting docstrings for Rust source files.
 pub struct DocstringAuditTool;
 
 impl DocstringAuditTool {
-    /// Audit a Rust file for missing docstrings on functions.
+    /// Audit a Rust file for missing docstrings on functions and methods, including those in impls, traits and nested modules.
+    ///
+    /// Methods in trait impls are skipped, since they inherit the trait's documentation.
     pub fn audit_file<P: AsRef<Path>>(path: P) -> std::io::Result<DocstringAuditResult> {
-        let content = fs::read_to_string(&path)?;
-        let mut missing_docstrings = Vec::new();
-        let mut total_functions = 0;
-        let mut documented_functions = 0;
-        let mut lines = content.lines().peekable();
-        while let Some(line) = lines.next() {
-            let trimmed = line.trim_start();
-            if trimmed.starts_with("fn ") {
-                total_functions += 1;
-                // Look for docstring above
-                let mut has_doc = false;
-                for prev in content[..content.find(line).unwrap_or(0)].lines().rev() {
-                    let prev_trim = prev.trim_start();
-                    if prev_trim.is_empty() {
-                        continue;
-                    }
-                    if prev_trim.starts_with("///") || prev_trim.starts_with("/**") {
-                        has_doc = true;
-                        break;
-                    }
-                    if !prev_trim.starts_with("#") && !prev_trim.starts_with("pub") && !prev_trim.starts_with("fn ") {
-                        break;
-                    }
-                }
-                if has_doc {
-                    documented_functions += 1;
-                } else {
-                    // Extract function signature
-                    let sig = trimmed.split('{').next().unwrap_or("").trim().to_string();
-                    missing_docstrings.push(sig);
-                }
-            }
-        }
+        let parsed = ParseTool::parse_file(&path)?;
+        let trait_impls: Vec<String> = parsed
+            .items
+            .iter()
+            .filter(|item| item.kind == ItemKind::Impl && item.name.contains(" for "))
+            .map(|item| match &item.parent {
+                Some(parent) => format!("{}::{}", parent, item.name),
+                None => item.name.clone(),
+            })
+            .collect();
+
+        let functions: Vec<ParsedItem> = parsed
+            .items
+            .into_iter()
+            .filter(|item| matches!(item.kind, ItemKind::Function | ItemKind::Method))
+            .filter(|item| item.parent.as_ref().is_none_or(|parent| !trait_impls.contains(parent)))
+            .collect();
+        let total_functions = functions.len();
+        let missing_docstrings: Vec<ParsedItem> = functions.into_iter().filter(|item| item.docs.is_none()).collect();
         Ok(DocstringAuditResult {
-            file: path.as_ref().to_string_lossy().to_string(),
-            missing_docstrings,
+            file: parsed.file,
             total_functions,
-            documented_functions,
+            documented_functions: total_functions - missing_docstrings.len(),
+            missing_docstrings,
+            parse_error: parsed.error,
         })
     }
 
@@ -62,3 +56,68 @@ impl DocstringAuditTool {
         format!("/// TODO: Document this function\n{}", signature)
     }
 }
+
+impl AgentTool for DocstringAuditTool {
+    fn name(&self) -> &'static str {
+        "docstring_audit"
+    }
+
+    fn description(&self) -> &'static str {
+        "Find functions and methods without doc comments in a Rust file."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": { "path": { "type": "string", "description": "Path relative to the workspace root" } },
+            "required": ["path"]
+        })
+    }
+
+    fn call(&self, context: &ToolContext, input: &Value) -> Result<String, String> {
+        let path = resolve_path(context.workspace, str_arg(input, "path")?)?;
+        let result = Self::audit_file(&path).map_err(|e| format!("Failed to audit {}: {}", path.display(), e))?;
+        let mut output = format!("{} of {} functions documented", result.documented_functions, result.total_functions);
+        for item in &result.missing_docstrings {
+            output.push_str(&format!("\n{}: {}", item.span.start_line, item.signature));
+        }
+        if let Some(error) = &result.parse_error {
+            output.push_str(&format!("\n{} (the item containing it was not audited)", error));
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_finds_undocumented_methods() {
+        let test_file = "test_docstring_audit_input.rs";
+        std::fs::write(test_file, r#"
+/// Documented.
+pub fn documented() {}
+
+pub(crate) async fn undocumented() {}
+
+struct Foo;
+
+impl Foo {
+    /** Block doc. */
+    fn documented_method(&self) {}
+    fn undocumented_method(&self) {}
+}
+
+impl Clone for Foo {
+    fn clone(&self) -> Self { Foo }
+}
+"#).unwrap();
+
+        let result = DocstringAuditTool::audit_file(test_file).unwrap();
+        std::fs::remove_file(test_file).unwrap();
+        assert_eq!((result.total_functions, result.documented_functions), (4, 2));
+        let missing: Vec<(&str, usize)> = result.missing_docstrings.iter().map(|i| (i.name.as_str(), i.span.start_line)).collect();
+        assert_eq!(missing, vec![("undocumented", 5), ("undocumented_method", 12)]);
+    }
+}
@@ -1,5 +1,7 @@
 use std::fs;
 use std::path::Path;
+use serde_json::{json, Value};
+use crate::backend::code_agent::tools::registry::{resolve_path, str_arg, AgentTool, ToolContext};
 
 /// Represents a documentation generation result.
 #[derive(Debug, Clone)]
@@ -37,7 +39,7 @@ impl DocumentationTool {
             let trimmed = line.trim_start();
             if trimmed.starts_with("///") {
                 doc.push_str(trimmed.trim_start_matches("///").trim());
-                doc.push_str("\n");
+                doc.push('\n');
             }
         }
         Ok(doc)
@@ -52,3 +54,31 @@ impl DocumentationTool {
         }
     }
 }
+
+impl AgentTool for DocumentationTool {
+    fn name(&self) -> &'static str {
+        "extract_docs"
+    }
+
+    fn description(&self) -> &'static str {
+        "List the doc comments in a Rust file."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": { "path": { "type": "string", "description": "Path relative to the workspace root" } },
+            "required": ["path"]
+        })
+    }
+
+    fn call(&self, context: &ToolContext, input: &Value) -> Result<String, String> {
+        let path = resolve_path(context.workspace, str_arg(input, "path")?)?;
+        let result = Self::extract_doc_comments(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if result.doc_comments.is_empty() {
+            Ok("No doc comments found".to_string())
+        } else {
+            Ok(result.doc_comments.join("\n"))
+        }
+    }
+}
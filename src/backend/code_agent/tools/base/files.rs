@@ -0,0 +1,91 @@
+use std::fs;
+use serde_json::{json, Value};
+use crate::backend::code_agent::tools::registry::{resolve_path, str_arg, AgentTool, Capability, ToolContext};
+
+/// Tool for reading a workspace file, optionally a range of lines.
+pub struct ReadFileTool;
+
+/// Tool for proposing new content for a workspace file; nothing is written until the user accepts it.
+pub struct WriteFileTool;
+
+impl AgentTool for ReadFileTool {
+    fn name(&self) -> &'static str {
+        "read_file"
+    }
+
+    fn description(&self) -> &'static str {
+        "Read a text file in the workspace. Use start_line/end_line (1-based, inclusive) to read part of a large file."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Path relative to the workspace root" },
+                "start_line": { "type": "integer", "minimum": 1 },
+                "end_line": { "type": "integer", "minimum": 1 }
+            },
+            "required": ["path"]
+        })
+    }
+
+    fn call(&self, context: &ToolContext, input: &Value) -> Result<String, String> {
+        let path = resolve_path(context.workspace, str_arg(input, "path")?)?;
+        let content = match context.staged.get(&path) {
+            Some(staged) => staged,
+            None => fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?,
+        };
+        let start = input["start_line"].as_u64().map(|line| line.max(1) as usize);
+        let end = input["end_line"].as_u64().map(|line| line as usize);
+        if start.is_none() && end.is_none() {
+            return Ok(content);
+        }
+
+        let start = start.unwrap_or(1);
+        let end = end.unwrap_or(usize::MAX);
+        Ok(content
+            .split_inclusive('\n')
+            .enumerate()
+            .filter(|(index, _)| (start..=end).contains(&(index + 1)))
+            .map(|(_, line)| line)
+            .collect())
+    }
+}
+
+impl AgentTool for WriteFileTool {
+    fn name(&self) -> &'static str {
+        "write_file"
+    }
+
+    fn capability(&self) -> Capability {
+        Capability::Write
+    }
+
+    fn description(&self) -> &'static str {
+        "Create or overwrite a text file in the workspace with the given content. The change is shown to the user as a diff and written once they accept it; read_file already sees the new content."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Path relative to the workspace root" },
+                "content": { "type": "string", "description": "The complete new file content" }
+            },
+            "required": ["path", "content"]
+        })
+    }
+
+    fn call(&self, context: &ToolContext, input: &Value) -> Result<String, String> {
+        let path = resolve_path(context.workspace, str_arg(input, "path")?)?;
+        let content = str_arg(input, "content")?;
+        if path.is_dir() {
+            return Err(format!("{} is a directory", path.display()));
+        }
+        if path.exists() {
+            fs::read_to_string(&path).map_err(|e| format!("Cannot edit {}: {}", path.display(), e))?;
+        }
+        context.staged.stage(path, content.to_string());
+        Ok(format!("Proposed {} bytes for {}; the user will review the change", content.len(), str_arg(input, "path")?))
+    }
+}
@@ -1,5 +1,7 @@
 use std::process::{Command, Output};
 use std::path::Path;
+use serde_json::{json, Value};
+use crate::backend::code_agent::tools::registry::{AgentTool, ToolContext};
 
 /// Represents the result of a lint run.
 #[derive(Debug, Clone)]
@@ -28,3 +30,28 @@ impl LintTool {
         })
     }
 }
+
+impl AgentTool for LintTool {
+    fn name(&self) -> &'static str {
+        "lint"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run cargo clippy on the whole workspace and return its diagnostics."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({ "type": "object", "properties": {} })
+    }
+
+    fn call(&self, context: &ToolContext, _input: &Value) -> Result<String, String> {
+        let result = Self::run_lint(context.workspace).map_err(|e| format!("Failed to run cargo clippy: {}", e))?;
+        // Clippy writes diagnostics to stderr.
+        let output = format!("{}{}", result.stdout, result.stderr);
+        if result.success {
+            Ok(output)
+        } else {
+            Err(output)
+        }
+    }
+}
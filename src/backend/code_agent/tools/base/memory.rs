@@ -0,0 +1,43 @@
+use serde_json::{json, Value};
+use crate::backend::code_agent::memory::agent_memory_logic::{MemoryKind, MemorySource, MAX_ENTRY_LEN};
+use crate::backend::code_agent::tools::registry::{str_arg, AgentTool, Capability, ToolContext};
+
+/// Tool for saving a note to the project's long-term memory.
+pub struct RememberTool;
+
+impl AgentTool for RememberTool {
+    fn name(&self) -> &'static str {
+        "remember"
+    }
+
+    fn capability(&self) -> Capability {
+        Capability::Write
+    }
+
+    fn description(&self) -> &'static str {
+        "Save a short note to the project's long-term memory, to be recalled in later conversations. \
+         Use it for facts about the code, conventions it follows, snippets worth reusing and decisions the user made. \
+         Keep each note to one self-contained point."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "kind": { "type": "string", "enum": MemoryKind::ALL.map(|kind| kind.name()) },
+                "content": { "type": "string", "description": format!("The note, at most {} characters", MAX_ENTRY_LEN) },
+                "tags": { "type": "array", "items": { "type": "string" }, "description": "Topics to find the note by, e.g. module names" }
+            },
+            "required": ["kind", "content"]
+        })
+    }
+
+    fn call(&self, context: &ToolContext, input: &Value) -> Result<String, String> {
+        let kind_name = str_arg(input, "kind")?;
+        let kind = MemoryKind::parse(kind_name).ok_or_else(|| format!("Unknown memory kind '{}'", kind_name))?;
+        let tags: Vec<String> = input["tags"].as_array().map(|tags| tags.iter().filter_map(|tag| tag.as_str().map(str::to_string)).collect()).unwrap_or_default();
+        let mut memory = context.memory.lock().map_err(|_| "Memory lock poisoned")?;
+        let id = memory.add(kind, str_arg(input, "content")?, &tags, MemorySource::Agent)?;
+        Ok(format!("Remembered as {} #{}", kind.name(), id))
+    }
+}
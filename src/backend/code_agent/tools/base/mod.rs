@@ -0,0 +1,8 @@
+pub mod command;
+pub mod docstring_audit;
+pub mod document;
+pub mod files;
+pub mod lint;
+pub mod memory;
+pub mod parse;
+pub mod search;
@@ -1,13 +1,69 @@
 use std::fs;
+use std::io;
+use std::ops::Range;
 use std::path::Path;
+use proc_macro2::{LineColumn, Span};
+use serde_json::{json, Value};
+use syn::spanned::Spanned;
+use crate::backend::code_agent::tools::registry::{resolve_path, str_arg, AgentTool, ToolContext};
 
-/// Represents a parsed Rust item (function, struct, enum, etc.).
-#[derive(Debug, Clone)]
-pub enum ParsedItem {
-    Function { signature: String },
-    Struct { name: String },
-    Enum { name: String },
-    Other { line: String },
+/// The kind of a parsed Rust item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    Function,
+    /// A function inside an `impl` or `trait` block.
+    Method,
+    Struct,
+    Enum,
+    Union,
+    Trait,
+    Impl,
+    Const,
+    Static,
+    TypeAlias,
+    Module,
+    /// A `macro_rules!` definition or an item-level macro invocation.
+    Macro,
+}
+
+/// Declared visibility of an item.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ItemVisibility {
+    Private,
+    Public,
+    /// `pub(crate)`
+    Crate,
+    /// `pub(super)`, `pub(self)` or `pub(in path)`, holding the text inside the parentheses.
+    Restricted(String),
+}
+
+/// Source range of an item. Lines are 1-based, columns are 0-based character offsets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineSpan {
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+/// A parsed Rust item (function, method, struct, trait, impl, ...).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedItem {
+    pub kind: ItemKind,
+    /// Item name; impls are named after their type, e.g. `Foo` or `Display for Foo`.
+    pub name: String,
+    pub visibility: ItemVisibility,
+    /// Whole item, including attributes and doc comments.
+    pub span: LineSpan,
+    /// Line and column of the item's name.
+    pub name_line: usize,
+    pub name_column: usize,
+    /// Enclosing modules and impl/trait, joined with `::`. `None` at file level.
+    pub parent: Option<String>,
+    /// Text of the attached `///` or `/** */` doc comments.
+    pub docs: Option<String>,
+    /// Declaration up to the body, with whitespace collapsed, e.g. `pub fn add(a: i32, b: i32) -> i32`.
+    pub signature: String,
 }
 
 /// Result of parsing a Rust file.
@@ -15,42 +71,455 @@ pub enum ParsedItem {
 pub struct ParseResult {
     pub file: String,
     pub items: Vec<ParsedItem>,
+    /// First syntax error, if the file did not parse as a whole. `items` then holds
+    /// the items outside the ones that failed.
+    pub error: Option<String>,
 }
 
 /// Tool for parsing Rust source files for high-level items.
 pub struct ParseTool;
 
 impl ParseTool {
-    /// Parse a Rust file and extract top-level items (functions, structs, enums).
-    pub fn parse_file<P: AsRef<Path>>(path: P) -> std::io::Result<ParseResult> {
+    /// Parse a Rust file and extract its items, including those nested in modules, impls and traits.
+    ///
+    /// A file with syntax errors still yields the top-level items that parse on their own.
+    pub fn parse_file<P: AsRef<Path>>(path: P) -> io::Result<ParseResult> {
         let content = fs::read_to_string(&path)?;
-        let mut items = Vec::new();
-        for line in content.lines() {
-            let trimmed = line.trim_start();
-            if trimmed.starts_with("fn ") || trimmed.starts_with("pub fn ") {
-                let sig = trimmed.split('{').next().unwrap_or("").trim().to_string();
-                items.push(ParsedItem::Function { signature: sig });
-            } else if trimmed.starts_with("struct ") || trimmed.starts_with("pub struct ") {
-                let name = trimmed.split_whitespace().nth(1).unwrap_or("").to_string();
-                items.push(ParsedItem::Struct { name });
-            } else if trimmed.starts_with("enum ") || trimmed.starts_with("pub enum ") {
-                let name = trimmed.split_whitespace().nth(1).unwrap_or("").to_string();
-                items.push(ParsedItem::Enum { name });
-            } else if !trimmed.is_empty() {
-                items.push(ParsedItem::Other { line: trimmed.to_string() });
-            }
-        }
+        let (items, error) = Self::parse_source_partial(&content);
         Ok(ParseResult {
             file: path.as_ref().to_string_lossy().to_string(),
             items,
+            error,
         })
     }
+
+    /// Parse Rust source text. Fails with the position of the first syntax error.
+    pub fn parse_source(source: &str) -> Result<Vec<ParsedItem>, String> {
+        let file = syn::parse_file(source).map_err(|e| {
+            let start = e.span().start();
+            format!("Syntax error at {}:{}: {}", start.line, start.column + 1, e)
+        })?;
+        let mut collector = ItemCollector::new(source);
+        collector.visit_items(&file.items);
+        Ok(collector.items)
+    }
+
+    /// Parse Rust source text that may not compile, e.g. a file in the middle of an edit.
+    ///
+    /// Top-level items that fail to parse are blanked out, keeping every other item at its
+    /// line and column. Returns the items and the first syntax error, if there was one.
+    pub fn parse_source_partial(source: &str) -> (Vec<ParsedItem>, Option<String>) {
+        let error = match Self::parse_source(source) {
+            Ok(items) => return (items, None),
+            Err(error) => error,
+        };
+        let mut blanked = String::with_capacity(source.len());
+        for chunk in top_level_chunks(source) {
+            let text = &source[chunk];
+            if syn::parse_file(text).is_ok() {
+                blanked.push_str(text);
+            } else {
+                blanked.extend(text.chars().map(|c| if c == '\n' { '\n' } else { ' ' }));
+            }
+        }
+        let items = Self::parse_source(&blanked).unwrap_or_default();
+        (items, Some(error))
+    }
+}
+
+impl AgentTool for ParseTool {
+    fn name(&self) -> &'static str {
+        "parse_rust"
+    }
+
+    fn description(&self) -> &'static str {
+        "List the items (functions, structs, impls, traits, ...) in a Rust file with their line ranges and signatures."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": { "path": { "type": "string", "description": "Path relative to the workspace root" } },
+            "required": ["path"]
+        })
+    }
+
+    fn call(&self, context: &ToolContext, input: &Value) -> Result<String, String> {
+        let path = resolve_path(context.workspace, str_arg(input, "path")?)?;
+        let content = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let (items, error) = Self::parse_source_partial(&content);
+        if let (true, Some(error)) = (items.is_empty(), &error) {
+            return Err(error.clone());
+        }
+        let mut lines: Vec<String> = items
+            .iter()
+            .map(|item| match &item.parent {
+                Some(parent) => format!("{}-{}: {} (in {})", item.span.start_line, item.span.end_line, item.signature, parent),
+                None => format!("{}-{}: {}", item.span.start_line, item.span.end_line, item.signature),
+            })
+            .collect();
+        if let Some(error) = error {
+            lines.push(format!("{} (the item containing it was skipped)", error));
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+/// Walks a syntax tree, tracking the enclosing scope.
+struct ItemCollector<'a> {
+    source: &'a str,
+    line_starts: Vec<usize>,
+    scope: Vec<String>,
+    items: Vec<ParsedItem>,
+}
+
+impl<'a> ItemCollector<'a> {
+    fn new(source: &'a str) -> Self {
+        let line_starts = std::iter::once(0).chain(source.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        Self { source, line_starts, scope: Vec::new(), items: Vec::new() }
+    }
+
+    fn visit_items(&mut self, items: &[syn::Item]) {
+        for item in items {
+            self.visit_item(item);
+        }
+    }
+
+    fn visit_item(&mut self, item: &syn::Item) {
+        use syn::Item;
+        match item {
+            Item::Fn(f) => {
+                let header = self.header(&f.vis, f.sig.span(), Some(f.block.brace_token.span.open()), item.span());
+                self.push(ItemKind::Function, &f.sig.ident, &f.vis, &f.attrs, item.span(), header);
+            }
+            Item::Struct(s) => {
+                let body = match &s.fields {
+                    syn::Fields::Named(fields) => Some(fields.brace_token.span.open()),
+                    _ => None,
+                };
+                let header = self.header(&s.vis, s.struct_token.span, body, item.span());
+                self.push(ItemKind::Struct, &s.ident, &s.vis, &s.attrs, item.span(), header);
+            }
+            Item::Enum(e) => {
+                let header = self.header(&e.vis, e.enum_token.span, Some(e.brace_token.span.open()), item.span());
+                self.push(ItemKind::Enum, &e.ident, &e.vis, &e.attrs, item.span(), header);
+            }
+            Item::Union(u) => {
+                let header = self.header(&u.vis, u.union_token.span, Some(u.fields.brace_token.span.open()), item.span());
+                self.push(ItemKind::Union, &u.ident, &u.vis, &u.attrs, item.span(), header);
+            }
+            Item::Const(c) => {
+                let header = self.header(&c.vis, c.const_token.span, None, item.span());
+                self.push(ItemKind::Const, &c.ident, &c.vis, &c.attrs, item.span(), header);
+            }
+            Item::Static(s) => {
+                let header = self.header(&s.vis, s.static_token.span, None, item.span());
+                self.push(ItemKind::Static, &s.ident, &s.vis, &s.attrs, item.span(), header);
+            }
+            Item::Type(t) => {
+                let header = self.header(&t.vis, t.type_token.span, None, item.span());
+                self.push(ItemKind::TypeAlias, &t.ident, &t.vis, &t.attrs, item.span(), header);
+            }
+            Item::Mod(m) => {
+                let body = m.content.as_ref().map(|(brace, _)| brace.span.open());
+                let header = self.header(&m.vis, m.mod_token.span, body, item.span());
+                self.push(ItemKind::Module, &m.ident, &m.vis, &m.attrs, item.span(), header);
+                if let Some((_, items)) = &m.content {
+                    self.scope.push(m.ident.to_string());
+                    self.visit_items(items);
+                    self.scope.pop();
+                }
+            }
+            Item::Trait(t) => {
+                let start = t.unsafety.map_or(t.trait_token.span, |u| u.span);
+                let header = self.header(&t.vis, start, Some(t.brace_token.span.open()), item.span());
+                self.push(ItemKind::Trait, &t.ident, &t.vis, &t.attrs, item.span(), header);
+                self.scope.push(t.ident.to_string());
+                for trait_item in &t.items {
+                    self.visit_trait_item(trait_item, &t.vis);
+                }
+                self.scope.pop();
+            }
+            Item::Impl(i) => self.visit_impl(i),
+            Item::Macro(m) => {
+                let (name, name_span) = match &m.ident {
+                    Some(ident) => (ident.to_string(), ident.span()),
+                    None => (format!("{}!", self.text(m.mac.path.span())), m.mac.path.span()),
+                };
+                let body = match &m.mac.delimiter {
+                    syn::MacroDelimiter::Paren(p) => p.span.open(),
+                    syn::MacroDelimiter::Brace(b) => b.span.open(),
+                    syn::MacroDelimiter::Bracket(b) => b.span.open(),
+                };
+                let header = self.header(&syn::Visibility::Inherited, m.mac.path.span(), Some(body), item.span());
+                self.push_named(ItemKind::Macro, name, name_span, ItemVisibility::Private, &m.attrs, item.span(), header);
+            }
+            _ => {}
+        }
+    }
+
+    fn visit_impl(&mut self, item: &syn::ItemImpl) {
+        let self_ty = self.text(item.self_ty.span());
+        let name = match &item.trait_ {
+            Some((bang, path, _)) => {
+                let negation = if bang.is_some() { "!" } else { "" };
+                format!("{}{} for {}", negation, self.text(path.span()), self_ty)
+            }
+            None => self_ty,
+        };
+        let start = item.unsafety.map_or(item.impl_token.span, |u| u.span);
+        let header = self.header(&syn::Visibility::Inherited, start, Some(item.brace_token.span.open()), item.span());
+        self.push_named(ItemKind::Impl, name.clone(), item.self_ty.span(), ItemVisibility::Private, &item.attrs, item.span(), header);
+
+        self.scope.push(name);
+        for impl_item in &item.items {
+            match impl_item {
+                syn::ImplItem::Fn(f) => {
+                    let header = self.header(&f.vis, f.sig.span(), Some(f.block.brace_token.span.open()), impl_item.span());
+                    self.push(ItemKind::Method, &f.sig.ident, &f.vis, &f.attrs, impl_item.span(), header);
+                }
+                syn::ImplItem::Const(c) => {
+                    let header = self.header(&c.vis, c.const_token.span, None, impl_item.span());
+                    self.push(ItemKind::Const, &c.ident, &c.vis, &c.attrs, impl_item.span(), header);
+                }
+                syn::ImplItem::Type(t) => {
+                    let header = self.header(&t.vis, t.type_token.span, None, impl_item.span());
+                    self.push(ItemKind::TypeAlias, &t.ident, &t.vis, &t.attrs, impl_item.span(), header);
+                }
+                _ => {}
+            }
+        }
+        self.scope.pop();
+    }
+
+    /// Trait items have no visibility of their own, so they take the trait's.
+    fn visit_trait_item(&mut self, item: &syn::TraitItem, vis: &syn::Visibility) {
+        match item {
+            syn::TraitItem::Fn(f) => {
+                let body = f.default.as_ref().map(|block| block.brace_token.span.open());
+                let header = self.header(&syn::Visibility::Inherited, f.sig.span(), body, item.span());
+                self.push(ItemKind::Method, &f.sig.ident, vis, &f.attrs, item.span(), header);
+            }
+            syn::TraitItem::Const(c) => {
+                let header = self.header(&syn::Visibility::Inherited, c.const_token.span, None, item.span());
+                self.push(ItemKind::Const, &c.ident, vis, &c.attrs, item.span(), header);
+            }
+            syn::TraitItem::Type(t) => {
+                let header = self.header(&syn::Visibility::Inherited, t.type_token.span, None, item.span());
+                self.push(ItemKind::TypeAlias, &t.ident, vis, &t.attrs, item.span(), header);
+            }
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, kind: ItemKind, ident: &syn::Ident, vis: &syn::Visibility, attrs: &[syn::Attribute], span: Span, signature: String) {
+        let visibility = self.visibility(vis);
+        self.push_named(kind, ident.to_string(), ident.span(), visibility, attrs, span, signature);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn push_named(
+        &mut self,
+        kind: ItemKind,
+        name: String,
+        name_span: Span,
+        visibility: ItemVisibility,
+        attrs: &[syn::Attribute],
+        span: Span,
+        signature: String,
+    ) {
+        let (start, end, name_start) = (span.start(), span.end(), name_span.start());
+        self.items.push(ParsedItem {
+            kind,
+            name,
+            visibility,
+            span: LineSpan {
+                start_line: start.line,
+                start_column: start.column,
+                end_line: end.line,
+                end_column: end.column,
+            },
+            name_line: name_start.line,
+            name_column: name_start.column,
+            parent: (!self.scope.is_empty()).then(|| self.scope.join("::")),
+            docs: doc_comments(attrs),
+            signature,
+        });
+    }
+
+    fn visibility(&self, vis: &syn::Visibility) -> ItemVisibility {
+        match vis {
+            syn::Visibility::Public(_) => ItemVisibility::Public,
+            syn::Visibility::Restricted(r) if r.in_token.is_none() && r.path.is_ident("crate") => ItemVisibility::Crate,
+            syn::Visibility::Restricted(r) => {
+                let path = self.text(r.path.span());
+                ItemVisibility::Restricted(if r.in_token.is_some() { format!("in {}", path) } else { path })
+            }
+            syn::Visibility::Inherited => ItemVisibility::Private,
+        }
+    }
+
+    /// Declaration text from the visibility (or `keyword` when there is none) up to the body,
+    /// or to the end of the item when it has no body.
+    fn header(&self, vis: &syn::Visibility, keyword: Span, body: Option<Span>, item: Span) -> String {
+        let start = match vis {
+            syn::Visibility::Inherited => keyword.start(),
+            _ => vis.span().start(),
+        };
+        let end = body.map_or(item.end(), |b| b.start());
+        let text = &self.source[self.offset(start)..self.offset(end).max(self.offset(start))];
+        text.split_whitespace().collect::<Vec<_>>().join(" ").trim_end_matches(';').trim_end().to_string()
+    }
+
+    fn text(&self, span: Span) -> String {
+        let (start, end) = (self.offset(span.start()), self.offset(span.end()));
+        self.source[start..end.max(start)].split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    fn offset(&self, position: LineColumn) -> usize {
+        let Some(&line_start) = self.line_starts.get(position.line.saturating_sub(1)) else {
+            return self.source.len();
+        };
+        let line = &self.source[line_start..];
+        line_start + line.char_indices().nth(position.column).map_or(line.len(), |(i, _)| i)
+    }
+}
+
+/// Byte ranges of the top-level items in `source`, found by matching brackets outside
+/// comments, strings and character literals. An item ends at a `;` or `}` outside any
+/// brackets; text left after the last one, such as an unclosed item, is the final range.
+fn top_level_chunks(source: &str) -> Vec<Range<usize>> {
+    let bytes = source.as_bytes();
+    let (mut chunks, mut start, mut depth, mut i) = (Vec::new(), 0, 0usize, 0);
+    while i < bytes.len() {
+        match bytes[i] {
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                i = source[i..].find('\n').map_or(bytes.len(), |n| i + n);
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = skip_block_comment(bytes, i);
+                continue;
+            }
+            b'"' => {
+                i = skip_string(bytes, i + 1);
+                continue;
+            }
+            b'r' if raw_string_hashes(bytes, i + 1).is_some() => {
+                i = skip_raw_string(bytes, i + 1);
+                continue;
+            }
+            b'\'' => {
+                i = skip_char_literal(source, i);
+                continue;
+            }
+            b'{' | b'(' | b'[' => depth += 1,
+            b'}' | b')' | b']' => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 && bytes[i] == b'}' {
+                    chunks.push(start..i + 1);
+                    start = i + 1;
+                }
+            }
+            b';' if depth == 0 => {
+                chunks.push(start..i + 1);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    if start < bytes.len() {
+        chunks.push(start..bytes.len());
+    }
+    chunks
+}
+
+/// Skip a possibly nested `/* */` comment starting at `i`.
+fn skip_block_comment(bytes: &[u8], mut i: usize) -> usize {
+    let mut depth = 0;
+    while i < bytes.len() {
+        match (bytes[i], bytes.get(i + 1)) {
+            (b'/', Some(b'*')) => {
+                depth += 1;
+                i += 2;
+            }
+            (b'*', Some(b'/')) => {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Skip the rest of a `"..."` string whose body starts at `i`.
+fn skip_string(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Number of `#`s if a raw string's `#`s and opening quote start at `i`.
+fn raw_string_hashes(bytes: &[u8], i: usize) -> Option<usize> {
+    let hashes = bytes[i.min(bytes.len())..].iter().take_while(|&&b| b == b'#').count();
+    (bytes.get(i + hashes) == Some(&b'"')).then_some(hashes)
+}
+
+/// Skip a raw string whose `#`s and opening quote start at `i`.
+fn skip_raw_string(bytes: &[u8], i: usize) -> usize {
+    let hashes = raw_string_hashes(bytes, i).unwrap_or(0);
+    let body = i + hashes + 1;
+    let closing: Vec<u8> = std::iter::once(b'"').chain(std::iter::repeat_n(b'#', hashes)).collect();
+    bytes[body..]
+        .windows(closing.len())
+        .position(|window| window == closing.as_slice())
+        .map_or(bytes.len(), |n| body + n + closing.len())
+}
+
+/// Skip a character literal at `i`, or just the quote of a lifetime such as `'a`.
+fn skip_char_literal(source: &str, i: usize) -> usize {
+    let rest = &source[i + 1..];
+    if rest.starts_with('\\') {
+        // The escaped character may itself be a quote, so the closing quote comes after it.
+        return rest.get(2..).and_then(|tail| tail.find('\'')).map_or(source.len(), |n| i + n + 4);
+    }
+    match rest.chars().next() {
+        Some(c) if rest[c.len_utf8()..].starts_with('\'') => i + c.len_utf8() + 2,
+        _ => i + 1,
+    }
+}
+
+/// Collect `#[doc = "..."]` attributes, which is what `///` and `/** */` comments become.
+fn doc_comments(attrs: &[syn::Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                value: syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(doc), .. }),
+                ..
+            }) => Some(doc.value()),
+            _ => None,
+        })
+        .flat_map(|doc| doc.lines().map(|line| line.strip_prefix(' ').unwrap_or(line).to_string()).collect::<Vec<_>>())
+        .collect();
+    let docs = lines.join("\n").trim().to_string();
+    (!docs.is_empty()).then_some(docs)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::Path;
 
     #[test]
     fn test_parse_tool() {
@@ -66,20 +535,82 @@ mod tests {
         "#).unwrap();
 
         let result = ParseTool::parse_file(Path::new(test_file)).unwrap();
-        let mut found_struct = false;
-        let mut found_enum = false;
-        let mut found_fn = false;
-        for item in result.items {
-            match item {
-                ParsedItem::Function { .. } => found_fn = true,
-                ParsedItem::Struct { .. } => found_struct = true,
-                ParsedItem::Enum { .. } => found_enum = true,
-                _ => {}
-            }
-        }
-        assert!(found_struct);
-        assert!(found_enum);
-        assert!(found_fn);
         std::fs::remove_file(test_file).unwrap();
+        let kinds: Vec<(ItemKind, &str)> = result.items.iter().map(|i| (i.kind, i.name.as_str())).collect();
+        assert_eq!(kinds, vec![
+            (ItemKind::Struct, "Foo"),
+            (ItemKind::Struct, "Bar"),
+            (ItemKind::Enum, "Baz"),
+            (ItemKind::Function, "private_func"),
+            (ItemKind::Function, "public_func"),
+        ]);
+        assert_eq!(result.items[4].signature, "pub fn public_func(x: i32) -> i32");
+        assert!(result.error.is_some_and(|error| error.starts_with("Syntax error at 8:")));
+    }
+
+    #[test]
+    fn test_nested_items_have_scope_docs_and_spans() {
+        let source = r#"
+/// Networking.
+pub mod net {
+    /// A server.
+    pub(crate) struct Server;
+
+    impl Server {
+        /// Start listening.
+        pub async fn listen(&self) {}
+        fn helper() {}
+    }
+
+    impl std::fmt::Display for Server {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result { Ok(()) }
+    }
+
+    pub trait Handler {
+        const NAME: &'static str;
+        fn handle(&self);
+    }
+
+    macro_rules! log { () => {} }
+}
+"#;
+        let items = ParseTool::parse_source(source).unwrap();
+        let find = |name: &str| items.iter().find(|i| i.name == name).unwrap();
+
+        let module = find("net");
+        assert_eq!((module.kind, module.span.start_line, module.span.end_line), (ItemKind::Module, 2, 23));
+        assert_eq!(module.docs.as_deref(), Some("Networking."));
+
+        let server = find("Server");
+        assert_eq!(server.visibility, ItemVisibility::Crate);
+        assert_eq!(server.parent.as_deref(), Some("net"));
+
+        let listen = find("listen");
+        assert_eq!(listen.kind, ItemKind::Method);
+        assert_eq!(listen.parent.as_deref(), Some("net::Server"));
+        assert_eq!(listen.docs.as_deref(), Some("Start listening."));
+        assert_eq!(listen.signature, "pub async fn listen(&self)");
+        assert_eq!((listen.span.start_line, listen.name_line), (8, 9));
+
+        assert_eq!(find("fmt").parent.as_deref(), Some("net::std::fmt::Display for Server"));
+        assert_eq!(find("handle").visibility, ItemVisibility::Public);
+        assert_eq!(find("NAME").parent.as_deref(), Some("net::Handler"));
+        assert_eq!(find("log").kind, ItemKind::Macro);
+    }
+
+    #[test]
+    fn test_partial_parse_skips_broken_items() {
+        let source = "/// Docs.\npub fn before() {}\n\nfn editing(s: &str) -> char {\n    let c = '{';\n    let q = '\\'';\n    s.\n}\n\nconst ARRAY: [u8; 2] = [1, 2];\nstruct After<'a>(&'a str);\nfn unclosed() {\n";
+        let (items, error) = ParseTool::parse_source_partial(source);
+        let names: Vec<(&str, usize)> = items.iter().map(|i| (i.name.as_str(), i.name_line)).collect();
+        assert_eq!(names, vec![("before", 2), ("ARRAY", 10), ("After", 11)]);
+        assert_eq!(items[0].docs.as_deref(), Some("Docs."));
+        assert!(error.is_some());
+    }
+
+    #[test]
+    fn test_syntax_error_reports_position() {
+        let error = ParseTool::parse_source("fn main() {\n    let x = ;\n}").unwrap_err();
+        assert!(error.starts_with("Syntax error at 2:"), "{}", error);
     }
 }
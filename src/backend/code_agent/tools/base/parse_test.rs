@@ -1,4 +1,4 @@
-use code_agent::tools::base::parse::{ParseTool, ParsedItem};
+use code_agent::tools::base::parse::ParseTool;
 use std::path::Path;
 
 fn main() {
@@ -9,18 +9,27 @@ fn main() {
         pub enum Baz { A, B }
         fn private_func() {}
         pub fn public_func(x: i32) -> i32 { x }
+        impl Foo {
+            /// Not a top-level function
+            pub(crate) async fn method(&self) {}
+        }
         // Not a function
         let x = 5;
     "#).unwrap();
 
     let result = ParseTool::parse_file(Path::new(test_file)).unwrap();
     println!("File: {}", result.file);
+    if let Some(error) = &result.error {
+        println!("Error: {}", error);
+    }
     for item in result.items {
-        match item {
-            ParsedItem::Function { signature } => println!("Function: {}", signature),
-            ParsedItem::Struct { name } => println!("Struct: {}", name),
-            ParsedItem::Enum { name } => println!("Enum: {}", name),
-            ParsedItem::Other { line } => println!("Other: {}", line),
+        let scope = item.parent.map(|p| format!(" in {}", p)).unwrap_or_default();
+        println!(
+            "{:?} {} ({:?}){} at {}-{}: {}",
+            item.kind, item.name, item.visibility, scope, item.span.start_line, item.span.end_line, item.signature
+        );
+        if let Some(docs) = item.docs {
+            println!("    docs: {}", docs);
         }
     }
     std::fs::remove_file(test_file).unwrap();
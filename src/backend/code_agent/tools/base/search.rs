@@ -0,0 +1,99 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde_json::{json, Value};
+use crate::backend::code_agent::tools::registry::{resolve_path, str_arg, AgentTool, ToolContext};
+
+const DEFAULT_MAX_RESULTS: usize = 100;
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules", "__pycache__", "dist", "build", "venv"];
+
+/// Tool for case-insensitive text search across the workspace.
+pub struct SearchTool;
+
+impl SearchTool {
+    /// Find lines containing `query`, ignoring case, as `path:line: text` with paths relative to `workspace`.
+    pub fn search(workspace: &Path, dir: &Path, query: &str, max_results: usize) -> Vec<String> {
+        let query = query.to_lowercase();
+        let mut files = Vec::new();
+        collect_files(dir, &mut files);
+        files.sort();
+
+        let mut results = Vec::new();
+        for path in files {
+            // Skips binary and non-UTF-8 files.
+            let Ok(content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let relative = path.strip_prefix(workspace).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+            for (index, line) in content.lines().enumerate() {
+                if line.to_lowercase().contains(&query) {
+                    results.push(format!("{}:{}: {}", relative, index + 1, line.trim()));
+                    if results.len() >= max_results {
+                        return results;
+                    }
+                }
+            }
+        }
+        results
+    }
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_dir() {
+            if !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_str()) {
+                collect_files(&path, files);
+            }
+        } else if metadata.len() <= MAX_FILE_SIZE {
+            files.push(path);
+        }
+    }
+}
+
+impl AgentTool for SearchTool {
+    fn name(&self) -> &'static str {
+        "search"
+    }
+
+    fn description(&self) -> &'static str {
+        "Search workspace files for lines containing a string (case-insensitive). Returns path:line: text matches."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": { "type": "string" },
+                "path": { "type": "string", "description": "Directory to search, relative to the workspace root. Defaults to the whole workspace." },
+                "max_results": { "type": "integer", "minimum": 1 }
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn call(&self, context: &ToolContext, input: &Value) -> Result<String, String> {
+        let query = str_arg(input, "query")?;
+        if query.is_empty() {
+            return Err("Query must not be empty".to_string());
+        }
+        let dir = match input["path"].as_str() {
+            Some(path) => resolve_path(context.workspace, path)?,
+            None => context.workspace.to_path_buf(),
+        };
+        let max_results = input["max_results"].as_u64().map_or(DEFAULT_MAX_RESULTS, |n| n as usize);
+        let results = Self::search(context.workspace, &dir, query, max_results);
+        if results.is_empty() {
+            Ok(format!("No matches for '{}'", query))
+        } else {
+            Ok(results.join("\n"))
+        }
+    }
+}
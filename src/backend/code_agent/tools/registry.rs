@@ -0,0 +1,273 @@
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::backend::code_agent::command_policy::CommandGate;
+use crate::backend::code_agent::edit_proposal::{EditProposal, StagedEdits};
+use crate::backend::code_agent::memory::agent_memory_logic::AgentMemory;
+use crate::backend::code_agent::providers::{ToolCall, ToolDefinition, ToolResult};
+use super::base::{
+    command::RunCommandTool,
+    docstring_audit::DocstringAuditTool,
+    document::DocumentationTool,
+    files::{ReadFileTool, WriteFileTool},
+    lint::LintTool,
+    memory::RememberTool,
+    parse::ParseTool,
+    search::SearchTool,
+};
+
+/// Longest tool output sent back to the model, in bytes.
+pub const MAX_OUTPUT_LEN: usize = 20_000;
+
+/// What a tool may affect, so callers can be limited to some of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Capability {
+    /// Look at the workspace without changing it.
+    Read,
+    /// Propose file edits.
+    Write,
+    /// Run commands.
+    Execute,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 3] = [Capability::Read, Capability::Write, Capability::Execute];
+}
+
+/// A tool the model can call.
+pub trait AgentTool: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+    fn capability(&self) -> Capability {
+        Capability::Read
+    }
+    /// JSON schema of the input object.
+    fn input_schema(&self) -> Value;
+    /// Run the tool. Paths in `input` are relative to the context's workspace.
+    fn call(&self, context: &ToolContext, input: &Value) -> Result<String, String>;
+}
+
+/// What a tool call can see besides its input.
+pub struct ToolContext<'a> {
+    pub workspace: &'a Path,
+    /// File writes proposed earlier in the run, not yet on disk.
+    pub staged: &'a StagedEdits,
+    /// Policy and approvals for shell commands.
+    pub commands: &'a CommandGate,
+    /// The project's long-term memory.
+    pub memory: &'a Mutex<AgentMemory>,
+}
+
+/// The tools available to the agent, all scoped to one workspace.
+pub struct ToolRegistry {
+    workspace: PathBuf,
+    tools: Vec<Box<dyn AgentTool>>,
+    staged: StagedEdits,
+    commands: Arc<CommandGate>,
+    memory: Arc<Mutex<AgentMemory>>,
+}
+
+impl fmt::Debug for ToolRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ToolRegistry")
+            .field("workspace", &self.workspace)
+            .field("tools", &self.tools.iter().map(|tool| tool.name()).collect::<Vec<_>>())
+            .field("staged", &self.staged)
+            .field("commands", &self.commands)
+            .field("memory", &self.memory)
+            .finish()
+    }
+}
+
+impl ToolRegistry {
+    /// An empty registry; the agent gets no tools.
+    ///
+    /// Commands are checked against the default policy, with no one to approve them,
+    /// and memory is kept only for the registry's lifetime.
+    pub fn new(workspace: PathBuf) -> Self {
+        Self { workspace, tools: Vec::new(), staged: StagedEdits::default(), commands: Arc::default(), memory: Arc::default() }
+    }
+
+    /// A registry with the built-in file, search, command, memory and Rust analysis tools.
+    pub fn with_default_tools(workspace: PathBuf) -> Self {
+        let mut registry = Self::new(workspace);
+        registry.register(Box::new(ReadFileTool));
+        registry.register(Box::new(WriteFileTool));
+        registry.register(Box::new(SearchTool));
+        registry.register(Box::new(RunCommandTool));
+        registry.register(Box::new(ParseTool));
+        registry.register(Box::new(LintTool));
+        registry.register(Box::new(DocumentationTool));
+        registry.register(Box::new(DocstringAuditTool));
+        registry.register(Box::new(RememberTool));
+        registry
+    }
+
+    pub fn workspace(&self) -> &Path {
+        &self.workspace
+    }
+
+    /// Add a tool, replacing any tool with the same name.
+    pub fn register(&mut self, tool: Box<dyn AgentTool>) {
+        self.tools.retain(|existing| existing.name() != tool.name());
+        self.tools.push(tool);
+    }
+
+    /// Check commands against `gate`'s policy and send the ones it doesn't allow to its approval queue.
+    pub fn set_command_gate(&mut self, gate: Arc<CommandGate>) {
+        self.commands = gate;
+    }
+
+    pub fn command_gate(&self) -> &Arc<CommandGate> {
+        &self.commands
+    }
+
+    /// Save the `remember` tool's notes to `memory`.
+    pub fn set_memory(&mut self, memory: Arc<Mutex<AgentMemory>>) {
+        self.memory = memory;
+    }
+
+    /// Drop the tools that need a capability outside `allowed`.
+    pub fn retain_capabilities(&mut self, allowed: &[Capability]) {
+        self.tools.retain(|tool| allowed.contains(&tool.capability()));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn AgentTool> {
+        self.tools.iter().find(|tool| tool.name() == name).map(|tool| tool.as_ref())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    /// Definitions to send with a completion request.
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        self.tools
+            .iter()
+            .map(|tool| ToolDefinition {
+                name: tool.name().to_string(),
+                description: tool.description().to_string(),
+                input_schema: tool.input_schema(),
+            })
+            .collect()
+    }
+
+    /// Run a tool call. Failures are returned as error results for the model to see.
+    pub fn execute(&self, call: &ToolCall) -> ToolResult {
+        let output = match self.get(&call.name) {
+            Some(tool) => {
+                let context = ToolContext { workspace: &self.workspace, staged: &self.staged, commands: &self.commands, memory: &self.memory };
+                tool.call(&context, &call.input)
+            }
+            None => Err(format!("Unknown tool '{}'", call.name)),
+        };
+        let (content, is_error) = match output {
+            Ok(content) => (truncate_output(content), false),
+            Err(error) => (truncate_output(error), true),
+        };
+        ToolResult { call_id: call.id.clone(), name: call.name.clone(), content, is_error }
+    }
+
+    /// Drop file writes staged by an earlier run.
+    pub fn clear_staged(&self) {
+        self.staged.clear();
+    }
+
+    /// Turn the file writes staged so far into a proposal for review.
+    pub fn take_proposal(&self, description: &str) -> Option<EditProposal> {
+        self.staged.take_proposal(&self.workspace, description)
+    }
+}
+
+/// Get a required string field from a tool input.
+pub fn str_arg<'a>(input: &'a Value, key: &str) -> Result<&'a str, String> {
+    input[key].as_str().ok_or_else(|| format!("Missing string argument '{}'", key))
+}
+
+/// Resolve a path from a tool input against the workspace, refusing paths that leave it.
+pub fn resolve_path(workspace: &Path, path: &str) -> Result<PathBuf, String> {
+    let relative = match Path::new(path).strip_prefix(workspace) {
+        Ok(relative) => relative,
+        Err(_) if Path::new(path).is_absolute() => return Err(format!("Path '{}' is outside the workspace", path)),
+        Err(_) => Path::new(path),
+    };
+    let mut resolved = workspace.to_path_buf();
+    let mut depth = 0;
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => {
+                resolved.push(part);
+                depth += 1;
+            }
+            Component::ParentDir if depth > 0 => {
+                resolved.pop();
+                depth -= 1;
+            }
+            Component::CurDir => {}
+            _ => return Err(format!("Path '{}' is outside the workspace", path)),
+        }
+    }
+    Ok(resolved)
+}
+
+/// Shorten long output, keeping the start and noting how much was cut.
+pub fn truncate_output(mut output: String) -> String {
+    if output.len() <= MAX_OUTPUT_LEN {
+        return output;
+    }
+    let mut end = MAX_OUTPUT_LEN;
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    let omitted = output.len() - end;
+    output.truncate(end);
+    output.push_str(&format!("\n... ({} more bytes truncated)", omitted));
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::code_agent::command_policy::CommandPolicy;
+    use crate::backend::code_agent::edit_proposal::HunkDecision;
+    use crate::backend::code_agent::files_changed::FileChangeTracker;
+    use crate::backend::code_agent::test_support::TempDir;
+    use serde_json::json;
+
+    #[test]
+    fn test_tools_stay_inside_workspace() {
+        let temp = TempDir::new("tools");
+        let workspace = temp.path().to_path_buf();
+        let registry = ToolRegistry::with_default_tools(workspace.clone());
+        let call = |name: &str, input: Value| registry.execute(&ToolCall { id: "1".to_string(), name: name.to_string(), input });
+
+        let written = call("write_file", json!({"path": "src/notes.txt", "content": "alpha\nbeta needle\n"}));
+        assert!(!written.is_error, "{}", written.content);
+        // Writes are staged for review; reads see them before they reach disk.
+        assert_eq!(call("read_file", json!({"path": "./src/notes.txt", "start_line": 2})).content, "beta needle\n");
+        assert!(!workspace.join("src/notes.txt").exists());
+        let mut proposal = registry.take_proposal("Add notes").unwrap();
+        assert_eq!(proposal.files[0].decisions, vec![HunkDecision::Pending]);
+        proposal.accept_all();
+        proposal.apply(&mut FileChangeTracker::new()).unwrap();
+        assert_eq!(call("search", json!({"query": "NEEDLE"})).content, "src/notes.txt:2: beta needle");
+
+        let escaped = call("read_file", json!({"path": "src/../../secret"}));
+        assert!(escaped.is_error);
+        assert!(escaped.content.contains("outside the workspace"));
+        assert!(call("no_such_tool", json!({})).is_error);
+        assert_eq!(registry.definitions().len(), 9);
+
+        // Commands go through the policy, and output past its cap is dropped.
+        let mut limited = ToolRegistry::with_default_tools(workspace.clone());
+        limited.set_command_gate(Arc::new(CommandGate::new(CommandPolicy { max_output: 5, ..CommandPolicy::default() }, None)));
+        let run = |command: &str| limited.execute(&ToolCall { id: "2".to_string(), name: "run_command".to_string(), input: json!({"command": command}) });
+        let echoed = run("echo hello world");
+        assert!(echoed.content.contains("--- stdout ---\nhello\n") && echoed.content.contains("more bytes of output dropped"), "{}", echoed.content);
+        assert!(run("npm install").content.contains("no one is available to approve it"));
+        assert!(run("cat ../secret").content.contains("outside the project"));
+    }
+}
@@ -0,0 +1,248 @@
+//! Token usage and cost per request, kept per project in `.jadio/usage.jsonl`.
+//!
+//! Each model request appends one [`UsageRecord`]. Totals per chat session and for
+//! the whole project are summed from the records, and checked against the
+//! [`UsageBudget`] from the AI settings before the next request is sent.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use super::model_loader::{ModelConfig, ModelProvider};
+use crate::backend::settings_manager::AISettings;
+
+const USAGE_FILE: &str = ".jadio/usage.jsonl";
+/// Share of a budget at which a warning is shown before it is exceeded.
+const WARN_AT: f64 = 0.9;
+
+/// Price of a model in US dollars per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pricing {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+impl Pricing {
+    pub fn cost(&self, input_tokens: u64, output_tokens: u64) -> f64 {
+        (input_tokens as f64 * self.input_per_million + output_tokens as f64 * self.output_per_million) / 1_000_000.0
+    }
+}
+
+/// Published list prices, matched by model name prefix; more specific names first.
+const PRICES: &[(&str, f64, f64)] = &[
+    ("claude-3-opus", 15.0, 75.0),
+    ("claude-opus-4", 15.0, 75.0),
+    ("claude-3-5-haiku", 0.8, 4.0),
+    ("claude-3-haiku", 0.25, 1.25),
+    ("claude-3-sonnet", 3.0, 15.0),
+    ("claude-3-5-sonnet", 3.0, 15.0),
+    ("claude-3-7-sonnet", 3.0, 15.0),
+    ("claude-sonnet-4", 3.0, 15.0),
+    ("gpt-4o-mini", 0.15, 0.6),
+    ("gpt-4o", 2.5, 10.0),
+    ("gpt-4-turbo", 10.0, 30.0),
+    ("gpt-4", 30.0, 60.0),
+    ("gpt-3.5-turbo", 0.5, 1.5),
+];
+
+/// The price of `model`, free for local models, `None` if unknown.
+pub fn pricing_for(model: &ModelConfig) -> Option<Pricing> {
+    if matches!(model.provider, ModelProvider::Local) {
+        return Some(Pricing { input_per_million: 0.0, output_per_million: 0.0 });
+    }
+    let name = model.name.to_lowercase();
+    PRICES
+        .iter()
+        .find(|(prefix, _, _)| name.starts_with(prefix))
+        .map(|(_, input, output)| Pricing { input_per_million: *input, output_per_million: *output })
+}
+
+/// One model request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub timestamp: DateTime<Utc>,
+    pub session_id: Option<String>,
+    pub model: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// `None` when the model's price is unknown.
+    pub cost: Option<f64>,
+    /// Counted locally because the provider reported no usage.
+    #[serde(default)]
+    pub estimated: bool,
+}
+
+impl UsageRecord {
+    pub fn new(model: &ModelConfig, session_id: Option<String>, input_tokens: u64, output_tokens: u64, estimated: bool) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            session_id,
+            model: model.name.clone(),
+            input_tokens,
+            output_tokens,
+            cost: pricing_for(model).map(|pricing| pricing.cost(input_tokens, output_tokens)),
+            estimated,
+        }
+    }
+}
+
+/// Usage summed over a set of records.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct UsageTotals {
+    pub requests: usize,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// Cost of the priced requests.
+    pub cost: f64,
+    /// Requests to models without a known price.
+    pub unpriced: usize,
+}
+
+impl UsageTotals {
+    pub fn tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+
+    fn add(&mut self, record: &UsageRecord) {
+        self.requests += 1;
+        self.input_tokens += record.input_tokens;
+        self.output_tokens += record.output_tokens;
+        match record.cost {
+            Some(cost) => self.cost += cost,
+            None => self.unpriced += 1,
+        }
+    }
+}
+
+/// Limits from the AI settings; `None` means unlimited.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct UsageBudget {
+    pub session_tokens: Option<u64>,
+    pub project_cost: Option<f64>,
+}
+
+impl From<&AISettings> for UsageBudget {
+    fn from(settings: &AISettings) -> Self {
+        Self {
+            session_tokens: Some(settings.session_token_budget).filter(|budget| *budget > 0),
+            project_cost: Some(settings.project_cost_budget).filter(|budget| *budget > 0.0),
+        }
+    }
+}
+
+/// Every request made in a project, appended to its usage file as it happens.
+#[derive(Debug, Clone, Default)]
+pub struct UsageLedger {
+    /// `None` keeps records in memory only.
+    path: Option<PathBuf>,
+    records: Vec<UsageRecord>,
+}
+
+impl UsageLedger {
+    /// Load the project's ledger; lines that fail to parse are skipped.
+    pub fn for_project(root: &Path) -> Self {
+        let path = root.join(USAGE_FILE);
+        let records = fs::read_to_string(&path)
+            .map(|text| text.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+            .unwrap_or_default();
+        Self { path: Some(path), records }
+    }
+
+    pub fn records(&self) -> &[UsageRecord] {
+        &self.records
+    }
+
+    /// Add a record and append it to the usage file.
+    pub fn record(&mut self, record: UsageRecord) -> Result<(), String> {
+        let saved = match &self.path {
+            Some(path) => Self::append(path, &record),
+            None => Ok(()),
+        };
+        self.records.push(record);
+        saved
+    }
+
+    fn append(path: &Path, record: &UsageRecord) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let line = serde_json::to_string(record).map_err(|e| format!("Failed to serialize usage: {}", e))?;
+        let mut file = OpenOptions::new().create(true).append(true).open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        writeln!(file, "{}", line).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub fn session_totals(&self, session_id: &str) -> UsageTotals {
+        self.totals(|record| record.session_id.as_deref() == Some(session_id))
+    }
+
+    pub fn project_totals(&self) -> UsageTotals {
+        self.totals(|_| true)
+    }
+
+    fn totals(&self, filter: impl Fn(&UsageRecord) -> bool) -> UsageTotals {
+        let mut totals = UsageTotals::default();
+        self.records.iter().filter(|record| filter(record)).for_each(|record| totals.add(record));
+        totals
+    }
+
+    /// Warnings for a request of `prompt_tokens` costing at least `prompt_cost`, if it
+    /// would exceed a budget or bring usage near one.
+    pub fn budget_warnings(&self, budget: &UsageBudget, session_id: Option<&str>, prompt_tokens: u64, prompt_cost: f64) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if let (Some(limit), Some(id)) = (budget.session_tokens, session_id) {
+            let projected = self.session_totals(id).tokens() + prompt_tokens;
+            if projected > limit {
+                warnings.push(format!("This request takes the session to {} tokens, over its budget of {}", projected, limit));
+            } else if projected as f64 >= limit as f64 * WARN_AT {
+                warnings.push(format!("Session at {} of its {} token budget", projected, limit));
+            }
+        }
+        if let Some(limit) = budget.project_cost {
+            let projected = self.project_totals().cost + prompt_cost;
+            if projected > limit {
+                warnings.push(format!("This request takes the project to ${:.2}, over its budget of ${:.2}", projected, limit));
+            } else if projected >= limit * WARN_AT {
+                warnings.push(format!("Project at ${:.2} of its ${:.2} budget", projected, limit));
+            }
+        }
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::code_agent::test_support::TempDir;
+    use crate::backend::code_agent::model_loader::ModelParameters;
+
+    fn model(provider: ModelProvider, name: &str) -> ModelConfig {
+        ModelConfig { name: name.to_string(), provider, api_endpoint: None, api_key: None, model_path: None, parameters: ModelParameters::default() }
+    }
+
+    #[test]
+    fn test_ledger_totals_persist_and_warn_before_budget() {
+        let temp = TempDir::new("usage");
+        let root = temp.path().to_path_buf();
+        let sonnet = model(ModelProvider::Anthropic, "claude-3-sonnet-20240229");
+        let mut ledger = UsageLedger::for_project(&root);
+        ledger.record(UsageRecord::new(&sonnet, Some("a".to_string()), 1_000_000, 100_000, false)).unwrap();
+        ledger.record(UsageRecord::new(&model(ModelProvider::Local, "llama3"), Some("b".to_string()), 500, 20, true)).unwrap();
+        ledger.record(UsageRecord::new(&model(ModelProvider::Custom("x".into()), "mystery"), Some("a".to_string()), 10, 10, false)).unwrap();
+
+        // Reloaded from disk.
+        let ledger = UsageLedger::for_project(&root);
+        let session = ledger.session_totals("a");
+        assert_eq!((session.requests, session.tokens(), session.unpriced), (2, 1_100_020, 1));
+        assert!((session.cost - 4.5).abs() < 1e-9);
+        let project = ledger.project_totals();
+        assert_eq!((project.requests, project.input_tokens), (3, 1_000_510));
+
+        let budget = UsageBudget { session_tokens: Some(1_250_000), project_cost: Some(6.0) };
+        assert!(ledger.budget_warnings(&budget, Some("a"), 1_000, 0.0).is_empty());
+        let warnings = ledger.budget_warnings(&budget, Some("a"), 50_000, 1.0);
+        assert_eq!(warnings, vec!["Session at 1150020 of its 1250000 token budget".to_string(), "Project at $5.50 of its $6.00 budget".to_string()]);
+        let warnings = ledger.budget_warnings(&budget, Some("b"), 10, 2.0);
+        assert_eq!(warnings, vec!["This request takes the project to $6.50, over its budget of $6.00".to_string()]);
+    }
+}
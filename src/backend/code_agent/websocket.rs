@@ -0,0 +1,318 @@
+//! The server side of WebSocket (RFC 6455), enough for the agent server: the opening
+//! handshake, masked client frames, fragmented messages and control frames.
+
+use std::collections::HashMap;
+use std::io;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt};
+
+/// Appended to the client's key before hashing, per the RFC.
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const MAX_HANDSHAKE_BYTES: usize = 8 * 1024;
+/// Largest message accepted from a client, after reassembling fragments.
+pub const MAX_MESSAGE_BYTES: usize = 16 * 1024 * 1024;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    Close,
+}
+
+/// The HTTP request that opens a WebSocket connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Handshake {
+    pub method: String,
+    pub path: String,
+    /// Header names are lowercased.
+    pub headers: HashMap<String, String>,
+}
+
+impl Handshake {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_lowercase()).map(String::as_str)
+    }
+
+    /// A parameter from the query string of the request path, undecoded.
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        let (_, query) = self.path.split_once('?')?;
+        query.split('&').find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+    }
+
+    pub fn origin(&self) -> Option<&str> {
+        self.header("origin")
+    }
+
+    /// The `101 Switching Protocols` reply, or why the request can't be upgraded.
+    pub fn accept(&self) -> Result<String, String> {
+        let upgrade = self.header("upgrade").is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+        if self.method != "GET" || !upgrade {
+            return Err("Expected a WebSocket upgrade request".to_string());
+        }
+        if self.header("sec-websocket-version") != Some("13") {
+            return Err("Unsupported WebSocket version".to_string());
+        }
+        let key = self.header("sec-websocket-key").ok_or("Missing Sec-WebSocket-Key")?;
+        Ok(format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            accept_key(key)
+        ))
+    }
+}
+
+/// A plain HTTP error reply for a request that won't be upgraded.
+pub fn http_error(status: &str, message: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        message.len(),
+        message
+    )
+}
+
+/// Read the request line and headers of the opening handshake.
+pub async fn read_handshake<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Handshake> {
+    let mut read = 0;
+    let request_line = read_line(reader, &mut read).await?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(invalid("Malformed request line"));
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+    let mut headers = HashMap::new();
+    loop {
+        let line = read_line(reader, &mut read).await?;
+        if line.is_empty() {
+            return Ok(Handshake { method, path, headers });
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+}
+
+/// One handshake line without its line ending, counting bytes read against the limit.
+///
+/// Reads no further than the limit, so a client can't make the server buffer an endless line.
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R, read: &mut usize) -> io::Result<String> {
+    let mut line = Vec::new();
+    let limit = (MAX_HANDSHAKE_BYTES - *read) as u64;
+    *read += reader.take(limit).read_until(b'\n', &mut line).await?;
+    if !line.ends_with(b"\n") && *read >= MAX_HANDSHAKE_BYTES {
+        return Err(invalid("Handshake too large"));
+    }
+    if line.is_empty() {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    let line = String::from_utf8(line).map_err(|_| invalid("Handshake is not UTF-8"))?;
+    Ok(line.trim_end().to_string())
+}
+
+/// The `Sec-WebSocket-Accept` value for a client's `Sec-WebSocket-Key`.
+pub fn accept_key(key: &str) -> String {
+    base64_encode(&sha1(format!("{}{}", key.trim(), ACCEPT_GUID).as_bytes()))
+}
+
+/// Reads client messages, reassembling fragments and unmasking payloads.
+pub struct WebSocketReader<R> {
+    inner: R,
+    /// Opcode and data of a fragmented message still being received.
+    fragments: Option<(u8, Vec<u8>)>,
+}
+
+impl<R: AsyncRead + Unpin> WebSocketReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner, fragments: None }
+    }
+
+    /// The next message, or `None` if the connection closed between frames.
+    ///
+    /// Control frames are returned as they arrive, even in the middle of a fragmented message.
+    pub async fn next(&mut self) -> io::Result<Option<Message>> {
+        loop {
+            let mut head = [0u8; 2];
+            match self.inner.read_exact(&mut head).await {
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                result => result?,
+            };
+            let fin = head[0] & 0x80 != 0;
+            let opcode = head[0] & 0x0F;
+            if head[1] & 0x80 == 0 {
+                return Err(invalid("Client frames must be masked"));
+            }
+            let len = match head[1] & 0x7F {
+                126 => self.inner.read_u16().await? as u64,
+                127 => self.inner.read_u64().await?,
+                len => len as u64,
+            };
+            let buffered = self.fragments.as_ref().map_or(0, |(_, data)| data.len());
+            if len > (MAX_MESSAGE_BYTES - buffered) as u64 {
+                return Err(invalid("Message too large"));
+            }
+            let mut mask = [0u8; 4];
+            self.inner.read_exact(&mut mask).await?;
+            let mut payload = vec![0u8; len as usize];
+            self.inner.read_exact(&mut payload).await?;
+            payload.iter_mut().enumerate().for_each(|(i, byte)| *byte ^= mask[i % 4]);
+
+            match opcode {
+                OP_CLOSE => return Ok(Some(Message::Close)),
+                OP_PING => return Ok(Some(Message::Ping(payload))),
+                OP_PONG => return Ok(Some(Message::Pong(payload))),
+                OP_CONTINUATION => match &mut self.fragments {
+                    Some((_, data)) => data.extend_from_slice(&payload),
+                    None => return Err(invalid("Continuation frame without a message")),
+                },
+                OP_TEXT | OP_BINARY if self.fragments.is_none() => self.fragments = Some((opcode, payload)),
+                OP_TEXT | OP_BINARY => return Err(invalid("New message before the last one finished")),
+                _ => return Err(invalid("Unknown opcode")),
+            }
+            if fin {
+                if let Some((opcode, data)) = self.fragments.take() {
+                    return Ok(Some(match opcode {
+                        OP_TEXT => Message::Text(String::from_utf8(data).map_err(|_| invalid("Text message is not UTF-8"))?),
+                        _ => Message::Binary(data),
+                    }));
+                }
+            }
+        }
+    }
+}
+
+/// Encode a single-frame message; servers send unmasked frames, clients pass a `mask`.
+pub fn encode(message: &Message, mask: Option<[u8; 4]>) -> Vec<u8> {
+    let (opcode, payload) = match message {
+        Message::Text(text) => (OP_TEXT, text.as_bytes()),
+        Message::Binary(data) => (OP_BINARY, data.as_slice()),
+        Message::Ping(data) => (OP_PING, data.as_slice()),
+        Message::Pong(data) => (OP_PONG, data.as_slice()),
+        Message::Close => (OP_CLOSE, &[][..]),
+    };
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len if len < 126 => frame.push(mask_bit | len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(mask_bit | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(mask_bit | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    match mask {
+        Some(mask) => {
+            frame.extend_from_slice(&mask);
+            frame.extend(payload.iter().enumerate().map(|(i, byte)| byte ^ mask[i % 4]));
+        }
+        None => frame.extend_from_slice(payload),
+    }
+    frame
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut words = [0u32; 80];
+        for (word, bytes) in words.iter_mut().zip(block.chunks(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for i in 16..80 {
+            words[i] = (words[i - 3] ^ words[i - 8] ^ words[i - 14] ^ words[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (i, word) in words.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let next = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+            (e, d, c, b, a) = (d, c, b.rotate_left(30), a, next);
+        }
+        for (value, add) in state.iter_mut().zip([a, b, c, d, e]) {
+            *value = value.wrapping_add(add);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (bytes, value) in digest.chunks_mut(4).zip(state) {
+        bytes.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_handshake_and_fragmented_masked_frames() {
+        // The example from RFC 6455, section 1.3.
+        let request = "GET /rpc?token=abc&x=1 HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                       Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\nOrigin: http://localhost\r\n\r\n";
+        let handshake = read_handshake(&mut request.as_bytes()).await.unwrap();
+        assert_eq!((handshake.query_param("token"), handshake.origin()), (Some("abc"), Some("http://localhost")));
+        assert!(handshake.accept().unwrap().contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+        let mask = Some([1, 2, 3, 4]);
+        let mut first = encode(&Message::Text("hel".to_string()), mask);
+        first[0] &= 0x7F; // not the final fragment
+        let mut second = encode(&Message::Text("lo".repeat(100)), mask);
+        second[0] = 0x80 | OP_CONTINUATION;
+        let mut bytes = first;
+        bytes.extend(encode(&Message::Ping(b"p".to_vec()), mask));
+        bytes.extend(second);
+        bytes.extend(encode(&Message::Close, mask));
+
+        let mut reader = WebSocketReader::new(bytes.as_slice());
+        assert_eq!(reader.next().await.unwrap(), Some(Message::Ping(b"p".to_vec())));
+        assert_eq!(reader.next().await.unwrap(), Some(Message::Text(format!("hel{}", "lo".repeat(100)))));
+        assert_eq!(reader.next().await.unwrap(), Some(Message::Close));
+        assert_eq!(reader.next().await.unwrap(), None);
+
+        let endless = "GET /".to_string() + &"a".repeat(MAX_HANDSHAKE_BYTES);
+        assert_eq!(read_handshake(&mut endless.as_bytes()).await.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let unmasked = encode(&Message::Text("hi".to_string()), None);
+        assert_eq!(unmasked, vec![0x81, 2, b'h', b'i']);
+        assert!(WebSocketReader::new(unmasked.as_slice()).next().await.is_err());
+    }
+}
@@ -1,14 +1,209 @@
+use std::ops::Range;
 use std::path::PathBuf;
+use crate::backend::code_editor::lsp::protocol::{self, TextEdit, WorkspaceEdit};
 use crate::backend::code_editor::saving::CodeEditorSaver;
-use crate::backend::code_editor::syntax_highlighting::{SyntaxHighlighter, HighlightedToken};
+use crate::backend::code_editor::syntax_highlighting::{SyntaxHighlighter, HighlightedToken, LineState};
+use crate::backend::code_editor::selection::{self, Selection};
+use crate::backend::code_editor::text_buffer::{TextBuffer, LineEdit};
+use crate::backend::code_editor::theme::EditorTheme;
+use crate::backend::code_editor::undo_history::{EditKind, TextChange, UndoHistory};
 
 /// Represents the state of a code editor tab.
 #[derive(Debug, Clone)]
 pub struct EditorTab {
     pub file_path: Option<PathBuf>,
-    pub content: String,
+    /// Language used for highlighting, e.g. `"rust"` or `"text"`.
+    pub language: &'static str,
+    pub buffer: TextBuffer,
     pub is_dirty: bool,
     pub highlighted: Vec<Vec<HighlightedToken>>,
+    /// Lexer state at the end of each line, parallel to `highlighted`.
+    pub line_states: Vec<LineState>,
+    /// Identifies each highlighted line, parallel to `highlighted`. A line gets a new
+    /// id whenever it is lexed again, so anything derived from its tokens can be
+    /// cached by id until the theme changes.
+    pub line_ids: Vec<u64>,
+    next_line_id: u64,
+    pub selections: Vec<Selection>,
+    pub history: UndoHistory,
+    /// Bumped on every change to the text or its highlighting, e.g. to cache layouts.
+    pub revision: u64,
+    /// Text changes not yet sent to the language server, each ranged in the text before it.
+    content_changes: Vec<TextEdit>,
+}
+
+impl EditorTab {
+    /// Create a tab for the given text and highlight every line.
+    fn new(file_path: Option<PathBuf>, content: String, highlighter: &SyntaxHighlighter) -> Self {
+        let language = file_path.as_deref().map_or("text", |path| highlighter.language_for_path(path));
+        let mut tab = Self {
+            file_path,
+            language,
+            buffer: TextBuffer::from_text(content),
+            is_dirty: false,
+            highlighted: Vec::new(),
+            line_states: Vec::new(),
+            line_ids: Vec::new(),
+            next_line_id: 0,
+            selections: vec![Selection::caret(0)],
+            history: UndoHistory::new(),
+            revision: 0,
+            content_changes: Vec::new(),
+        };
+        tab.highlight_all(highlighter);
+        tab
+    }
+
+    /// Replace a byte range without touching history, returning the change made.
+    fn replace_range(&mut self, highlighter: &SyntaxHighlighter, range: Range<usize>, text: &str) -> TextChange {
+        let start = range.start.min(self.buffer.len());
+        let end = range.end.clamp(start, self.buffer.len());
+        let deleted = self.buffer.slice(start..end);
+        let range = protocol::Range {
+            start: protocol::offset_to_position(&self.buffer, start),
+            end: protocol::offset_to_position(&self.buffer, end),
+        };
+        self.content_changes.push(TextEdit { range, new_text: text.to_string() });
+        let edit = self.buffer.replace(start..end, text);
+        self.rehighlight(highlighter, edit);
+        self.revision += 1;
+        TextChange { offset: start, deleted, inserted: text.to_string() }
+    }
+
+    /// Apply an edit and record it in the undo history.
+    fn edit(&mut self, highlighter: &SyntaxHighlighter, range: Range<usize>, text: &str, kind: EditKind) {
+        if range.is_empty() && text.is_empty() {
+            return;
+        }
+        let selections_before = self.selections.clone();
+        let change = self.replace_range(highlighter, range, text);
+        self.selections = vec![Selection::caret(change.offset + text.len())];
+        self.history.record(kind, vec![change], selections_before, self.selections.clone());
+        self.is_dirty = self.history.is_dirty();
+    }
+
+    /// Replace every selection at once and record the result as one undo step.
+    ///
+    /// `replacement` maps each selection (by index in position order and range)
+    /// to the range and text that replace it.
+    fn edit_selections<F>(&mut self, highlighter: &SyntaxHighlighter, kind: EditKind, replacement: F)
+    where
+        F: FnMut(usize, Range<usize>) -> (Range<usize>, String),
+    {
+        let selections_before = self.selections.clone();
+        let (edits, carets) = selection::plan_edits(&self.selections, replacement);
+        let changes: Vec<TextChange> = edits
+            .into_iter()
+            .filter(|(range, text)| !range.is_empty() || !text.is_empty())
+            .map(|(range, text)| self.replace_range(highlighter, range, &text))
+            .collect();
+        if changes.is_empty() {
+            return;
+        }
+        self.selections = carets;
+        selection::normalize_selections(&mut self.selections);
+        self.history.record(kind, changes, selections_before, self.selections.clone());
+        self.is_dirty = self.history.is_dirty();
+    }
+
+    /// Take the text changes made since the last call, oldest first, for `didChange`.
+    pub fn take_content_changes(&mut self) -> Vec<TextEdit> {
+        std::mem::take(&mut self.content_changes)
+    }
+
+    /// Get the full text of the tab.
+    pub fn content(&self) -> String {
+        self.buffer.to_string()
+    }
+
+    /// Get the `file://` URI of the tab's file, if it has one.
+    pub fn uri(&self) -> Option<String> {
+        let path = self.file_path.as_ref()?;
+        Some(protocol::path_to_uri(&path.canonicalize().unwrap_or_else(|_| path.clone())))
+    }
+
+    /// Apply language server edits as one undo step.
+    ///
+    /// Edits are applied from the end of the text backwards so earlier
+    /// positions stay valid; inserts at the same position keep their order.
+    fn apply_text_edits(&mut self, highlighter: &SyntaxHighlighter, edits: &[TextEdit]) {
+        let mut ranges: Vec<(usize, Range<usize>, &str)> = edits
+            .iter()
+            .enumerate()
+            .map(|(i, edit)| {
+                let start = protocol::position_to_offset(&self.buffer, edit.range.start);
+                let end = protocol::position_to_offset(&self.buffer, edit.range.end);
+                (i, start..end.max(start), edit.new_text.as_str())
+            })
+            .collect();
+        ranges.sort_by_key(|(i, range, _)| (std::cmp::Reverse(range.start), std::cmp::Reverse(*i)));
+
+        let selections_before = self.selections.clone();
+        let changes: Vec<TextChange> = ranges
+            .into_iter()
+            .filter(|(_, range, text)| !range.is_empty() || !text.is_empty())
+            .map(|(_, range, text)| self.replace_range(highlighter, range, text))
+            .collect();
+        if changes.is_empty() {
+            return;
+        }
+        let len = self.buffer.len();
+        self.selections = selections_before.iter().map(|s| Selection::caret(s.head.min(len))).collect();
+        selection::normalize_selections(&mut self.selections);
+        self.history.record(EditKind::Other, changes, selections_before, self.selections.clone());
+        self.is_dirty = self.history.is_dirty();
+    }
+
+    fn highlight_all(&mut self, highlighter: &SyntaxHighlighter) {
+        let mut state = LineState::Normal;
+        self.highlighted.clear();
+        self.line_states.clear();
+        self.line_ids.clear();
+        for line in 0..self.buffer.line_count() {
+            let (tokens, end_state) = highlighter.highlight_line_with_state(self.language, &self.buffer.line(line), &state);
+            self.highlighted.push(tokens);
+            self.line_states.push(end_state.clone());
+            let id = self.new_line_id();
+            self.line_ids.push(id);
+            state = end_state;
+        }
+        self.revision += 1;
+    }
+
+    fn new_line_id(&mut self) -> u64 {
+        self.next_line_id += 1;
+        self.next_line_id
+    }
+
+    /// Re-highlight the lines touched by an edit, continuing past them only
+    /// while the lexer state flowing into the next line differs from before.
+    fn rehighlight(&mut self, highlighter: &SyntaxHighlighter, edit: LineEdit) {
+        let old_lines = edit.start_line..edit.old_end_line + 1;
+        let inserted = edit.new_end_line - edit.start_line + 1;
+        let mut old_state_in = self.line_states.get(edit.old_end_line).cloned().unwrap_or_default();
+        self.highlighted.splice(old_lines.clone(), std::iter::repeat_with(Vec::new).take(inserted));
+        self.line_states.splice(old_lines.clone(), std::iter::repeat_with(LineState::default).take(inserted));
+        self.line_ids.splice(old_lines, std::iter::repeat_n(0, inserted));
+
+        let mut state = match edit.start_line {
+            0 => LineState::Normal,
+            line => self.line_states[line - 1].clone(),
+        };
+        for line in edit.start_line..self.buffer.line_count() {
+            let past_edit = line > edit.new_end_line;
+            if past_edit && state == old_state_in {
+                break;
+            }
+            let (tokens, end_state) = highlighter.highlight_line_with_state(self.language, &self.buffer.line(line), &state);
+            self.highlighted[line] = tokens;
+            self.line_ids[line] = self.new_line_id();
+            let previous = std::mem::replace(&mut self.line_states[line], end_state.clone());
+            if past_edit {
+                old_state_in = previous;
+            }
+            state = end_state;
+        }
+    }
 }
 
 /// Main code editor logic, managing tabs, saving, and highlighting.
@@ -18,6 +213,12 @@ pub struct CodeEditorLogic {
     pub highlighter: SyntaxHighlighter,
 }
 
+impl Default for CodeEditorLogic {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CodeEditorLogic {
     /// Create a new code editor logic instance.
     pub fn new() -> Self {
@@ -31,45 +232,245 @@ impl CodeEditorLogic {
     /// Open a file in a new tab.
     pub fn open_file(&mut self, path: PathBuf) -> std::io::Result<()> {
         let content = std::fs::read_to_string(&path)?;
-        let highlighted = content.lines().map(|l| self.highlighter.highlight_line(l)).collect();
-        self.tabs.push(EditorTab {
-            file_path: Some(path),
-            content,
-            is_dirty: false,
-            highlighted,
-        });
+        self.tabs.push(EditorTab::new(Some(path), content, &self.highlighter));
         self.current_tab = self.tabs.len() - 1;
         Ok(())
     }
 
+    /// Open a tab with text read elsewhere, or a new file that is not saved yet.
+    /// The language is taken from the path's extension.
+    pub fn open_text(&mut self, file_path: Option<PathBuf>, content: String) {
+        self.tabs.push(EditorTab::new(file_path, content, &self.highlighter));
+        self.current_tab = self.tabs.len() - 1;
+    }
+
+    /// Close a tab, keeping the current tab where it was if it stays open.
+    pub fn close_tab(&mut self, index: usize) -> Option<EditorTab> {
+        if index >= self.tabs.len() {
+            return None;
+        }
+        let tab = self.tabs.remove(index);
+        if self.current_tab > index || self.current_tab >= self.tabs.len() {
+            self.current_tab = self.current_tab.saturating_sub(1);
+        }
+        Some(tab)
+    }
+
+    /// Get the current tab.
+    pub fn current(&self) -> Option<&EditorTab> {
+        self.tabs.get(self.current_tab)
+    }
+
     /// Create a new empty tab.
     pub fn new_tab(&mut self) {
-        self.tabs.push(EditorTab {
-            file_path: None,
-            content: String::new(),
-            is_dirty: false,
-            highlighted: Vec::new(),
-        });
+        self.tabs.push(EditorTab::new(None, String::new(), &self.highlighter));
         self.current_tab = self.tabs.len() - 1;
     }
 
+    /// Replace a byte range of the current tab with new text.
+    pub fn apply_edit(&mut self, range: Range<usize>, text: &str) {
+        self.apply_edit_with_kind(range, text, EditKind::Other);
+    }
+
+    /// Replace a byte range of the current tab, grouping it in history according to `kind`.
+    pub fn apply_edit_with_kind(&mut self, range: Range<usize>, text: &str, kind: EditKind) {
+        if let Some(tab) = self.tabs.get_mut(self.current_tab) {
+            tab.edit(&self.highlighter, range, text, kind);
+        }
+    }
+
+    /// Insert text at a byte offset in the current tab.
+    pub fn insert_at(&mut self, offset: usize, text: &str) {
+        self.apply_edit(offset..offset, text);
+    }
+
+    /// Delete a byte range from the current tab.
+    pub fn delete_range(&mut self, range: Range<usize>) {
+        self.apply_edit(range, "");
+    }
+
+    /// Type text at every caret, replacing selected text.
+    pub fn type_text(&mut self, text: &str) {
+        if let Some(tab) = self.tabs.get_mut(self.current_tab) {
+            tab.edit_selections(&self.highlighter, EditKind::Typing, |_, range| (range, text.to_string()));
+        }
+    }
+
+    /// Paste text at every caret as a single undo step.
+    ///
+    /// When the clipboard has exactly one line per caret, each caret gets its own line.
+    pub fn paste(&mut self, text: &str) {
+        if let Some(tab) = self.tabs.get_mut(self.current_tab) {
+            let lines: Vec<&str> = text.lines().collect();
+            let per_caret = tab.selections.len() > 1 && lines.len() == tab.selections.len();
+            tab.edit_selections(&self.highlighter, EditKind::Paste, |i, range| {
+                let inserted = if per_caret { lines[i] } else { text };
+                (range, inserted.to_string())
+            });
+        }
+    }
+
+    /// Apply an edit made by the code agent as a single undo step.
+    pub fn apply_agent_edit(&mut self, range: Range<usize>, text: &str) {
+        self.apply_edit_with_kind(range, text, EditKind::Agent);
+    }
+
+    /// Delete every selection, or the character before each empty caret.
+    pub fn delete_backward(&mut self) {
+        if let Some(tab) = self.tabs.get_mut(self.current_tab) {
+            let mut sorted = tab.selections.clone();
+            selection::normalize_selections(&mut sorted);
+            let ranges: Vec<Range<usize>> = sorted
+                .iter()
+                .map(|s| {
+                    let range = s.range();
+                    if !range.is_empty() {
+                        return range;
+                    }
+                    let head = range.start.min(tab.buffer.len());
+                    let (line, col) = tab.buffer.offset_to_line_col(head);
+                    let before = tab.buffer.slice(head - col..head);
+                    let start = match before.chars().next_back() {
+                        Some(c) => head - c.len_utf8(),
+                        None if line > 0 && head >= 2 && tab.buffer.slice(head - 2..head) == "\r\n" => head - 2,
+                        None if line > 0 => head - 1,
+                        None => head,
+                    };
+                    start..head
+                })
+                .collect();
+            tab.edit_selections(&self.highlighter, EditKind::Deletion, |i, _| (ranges[i].clone(), String::new()));
+        }
+    }
+
+    /// Undo the last step in the current tab. Returns false if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(tab) = self.tabs.get_mut(self.current_tab) else {
+            return false;
+        };
+        let Some(step) = tab.history.undo() else {
+            return false;
+        };
+        for change in step.changes.iter().rev() {
+            let end = change.offset + change.inserted.len();
+            tab.replace_range(&self.highlighter, change.offset..end, &change.deleted);
+        }
+        tab.selections = step.selections_before;
+        tab.is_dirty = tab.history.is_dirty();
+        true
+    }
+
+    /// Redo the last undone step in the current tab. Returns false if there was nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        let Some(tab) = self.tabs.get_mut(self.current_tab) else {
+            return false;
+        };
+        let Some(step) = tab.history.redo() else {
+            return false;
+        };
+        for change in &step.changes {
+            let end = change.offset + change.deleted.len();
+            tab.replace_range(&self.highlighter, change.offset..end, &change.inserted);
+        }
+        tab.selections = step.selections_after;
+        tab.is_dirty = tab.history.is_dirty();
+        true
+    }
+
+    /// Get the primary selection of the current tab.
+    pub fn primary_selection(&self) -> Option<Selection> {
+        self.tabs.get(self.current_tab).and_then(|t| t.selections.last().copied())
+    }
+
+    /// Get all selections of the current tab.
+    pub fn selections(&self) -> &[Selection] {
+        self.tabs.get(self.current_tab).map_or(&[], |t| t.selections.as_slice())
+    }
+
+    /// Move the caret/selection in the current tab, dropping any extra carets.
+    /// Ends the current typing group.
+    pub fn set_selection(&mut self, selection: Selection) {
+        if let Some(tab) = self.tabs.get_mut(self.current_tab) {
+            tab.selections = vec![selection];
+            tab.history.seal();
+        }
+    }
+
+    /// Replace all selections, the primary one last. Ends the current typing group if they moved.
+    pub fn set_selections(&mut self, selections: Vec<Selection>) {
+        if let Some(tab) = self.tabs.get_mut(self.current_tab) {
+            let (mut old, mut new) = (tab.selections.clone(), selections.clone());
+            selection::normalize_selections(&mut old);
+            selection::normalize_selections(&mut new);
+            if old != new {
+                tab.history.seal();
+            }
+            tab.selections = selections;
+        }
+    }
+
+    /// Add another caret at the given offset.
+    pub fn add_cursor(&mut self, offset: usize) {
+        if let Some(tab) = self.tabs.get_mut(self.current_tab) {
+            tab.selections.push(Selection::caret(offset.min(tab.buffer.len())));
+            selection::normalize_selections(&mut tab.selections);
+            tab.history.seal();
+        }
+    }
+
+    /// Select the next occurrence of the primary selection's text. Returns false if none was found.
+    pub fn add_next_occurrence(&mut self) -> bool {
+        let Some(tab) = self.tabs.get_mut(self.current_tab) else {
+            return false;
+        };
+        tab.history.seal();
+        selection::add_next_occurrence(&tab.buffer.to_string(), &mut tab.selections)
+    }
+
+    /// Replace the selections with a column (box) selection between two offsets.
+    pub fn set_column_selection(&mut self, anchor: usize, head: usize) {
+        if let Some(tab) = self.tabs.get_mut(self.current_tab) {
+            tab.selections = selection::column_selections(&tab.buffer.to_string(), anchor, head);
+            tab.history.seal();
+        }
+    }
+
+    /// Collapse back to the primary selection only.
+    pub fn clear_secondary_selections(&mut self) {
+        if let Some(primary) = self.primary_selection() {
+            self.set_selection(primary);
+        }
+    }
+
     /// Edit the content of the current tab.
+    ///
+    /// Only the span between the common prefix and suffix of the old and new
+    /// content is applied as an edit, so highlighting stays incremental.
     pub fn edit_current(&mut self, new_content: String) {
-        if let Some(tab) = self.tabs.get_mut(self.current_tab) {
-            tab.content = new_content.clone();
-            tab.is_dirty = true;
-            tab.highlighted = new_content.lines().map(|l| self.highlighter.highlight_line(l)).collect();
+        let Some(tab) = self.tabs.get(self.current_tab) else {
+            return;
+        };
+        let old_content = tab.content();
+        let (old, new) = (old_content.as_bytes(), new_content.as_bytes());
+        let mut prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+        while !new_content.is_char_boundary(prefix) {
+            prefix -= 1;
+        }
+        let max_suffix = old.len().min(new.len()) - prefix;
+        let mut suffix = old.iter().rev().zip(new.iter().rev()).take(max_suffix).take_while(|(a, b)| a == b).count();
+        while !new_content.is_char_boundary(new.len() - suffix) {
+            suffix -= 1;
         }
+        self.apply_edit(prefix..old.len() - suffix, &new_content[prefix..new.len() - suffix]);
     }
 
     /// Save the current tab (if it has a file path).
     pub fn save_current(&mut self) -> std::io::Result<bool> {
         if let Some(tab) = self.tabs.get_mut(self.current_tab) {
             if let Some(ref path) = tab.file_path {
-                let changed = CodeEditorSaver::save_if_changed(path, &tab.content)?;
-                if changed {
-                    tab.is_dirty = false;
-                }
+                let changed = CodeEditorSaver::save_if_changed(path, &tab.content())?;
+                tab.history.mark_saved();
+                tab.is_dirty = false;
                 return Ok(changed);
             }
         }
@@ -77,12 +478,74 @@ impl CodeEditorLogic {
     }
 
     /// Get the content of the current tab.
-    pub fn current_content(&self) -> Option<&str> {
-        self.tabs.get(self.current_tab).map(|t| t.content.as_str())
+    pub fn current_content(&self) -> Option<String> {
+        self.tabs.get(self.current_tab).map(|t| t.content())
     }
 
     /// Get highlighted lines for the current tab.
     pub fn current_highlighted(&self) -> Option<&[Vec<HighlightedToken>]> {
         self.tabs.get(self.current_tab).map(|t| t.highlighted.as_slice())
     }
+
+    /// Apply language server edits (e.g. formatting) to the current tab as one undo step.
+    pub fn apply_text_edits(&mut self, edits: &[TextEdit]) {
+        if let Some(tab) = self.tabs.get_mut(self.current_tab) {
+            tab.apply_text_edits(&self.highlighter, edits);
+        }
+    }
+
+    /// Apply a workspace edit (e.g. a rename) to every open tab it touches.
+    ///
+    /// Returns the URIs of files the edit touches that are not open.
+    pub fn apply_workspace_edit(&mut self, edit: &WorkspaceEdit) -> Vec<String> {
+        let mut not_open = Vec::new();
+        for (uri, edits) in &edit.changes {
+            match self.tabs.iter_mut().find(|tab| tab.uri().as_deref() == Some(uri.as_str())) {
+                Some(tab) => tab.apply_text_edits(&self.highlighter, edits),
+                None => not_open.push(uri.clone()),
+            }
+        }
+        not_open
+    }
+
+    /// Switch the editor theme and re-style every open tab.
+    pub fn set_theme(&mut self, theme: &EditorTheme) {
+        self.highlighter.set_theme(theme);
+        for tab in &mut self.tabs {
+            for line in &mut tab.highlighted {
+                self.highlighter.restyle(line);
+            }
+        }
+    }
+
+    /// Change the highlighting language of the current tab and re-highlight it.
+    pub fn set_current_language(&mut self, language: &str) {
+        if let Some(tab) = self.tabs.get_mut(self.current_tab) {
+            tab.language = self.highlighter.lexers.get(language).name();
+            tab.highlight_all(&self.highlighter);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_relexed_lines_get_new_ids() {
+        let mut logic = CodeEditorLogic::new();
+        logic.open_text(Some(PathBuf::from("main.rs")), "let a = 1;\nlet b = 2;\nlet c = 3;\n".to_string());
+        let ids = logic.current().unwrap().line_ids.clone();
+        assert_eq!(ids.len(), 4);
+
+        logic.insert_at(15, "22");
+        let after_edit = logic.current().unwrap().line_ids.clone();
+        assert_eq!((after_edit[0], after_edit[2], after_edit[3]), (ids[0], ids[2], ids[3]));
+        assert_ne!(after_edit[1], ids[1]);
+
+        // Opening a block comment changes the state flowing into every later line.
+        logic.insert_at(0, "/* ");
+        let after_comment = logic.current().unwrap().line_ids.clone();
+        assert!(after_comment.iter().zip(&after_edit).all(|(new, old)| new != old));
+    }
 }
@@ -0,0 +1,78 @@
+use super::{is_ident_char, lex_block_comment, lex_number, lex_string_body, lex_symbol, lex_whitespace, LineState, Lexer, Scanner, Span, StringState};
+use crate::backend::code_editor::syntax_highlighting::TokenType;
+
+/// CSS: block comments, at-rules, selectors, properties, colors and units.
+pub struct CssLexer;
+
+impl Lexer for CssLexer {
+    fn name(&self) -> &'static str {
+        "css"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["css", "scss", "less"]
+    }
+
+    fn lex_line(&self, line: &str, state: &LineState) -> (Vec<Span>, LineState) {
+        let mut s = Scanner::new(line);
+        let mut state = match state {
+            LineState::BlockComment(depth) => lex_block_comment(&mut s, 0, *depth, None, "*/"),
+            _ => LineState::Normal,
+        };
+
+        while state == LineState::Normal && !s.is_done() {
+            if lex_whitespace(&mut s) {
+                continue;
+            }
+            let start = s.pos();
+            let c = s.peek().unwrap_or_default();
+            if s.eat("/*") {
+                state = lex_block_comment(&mut s, start, 1, None, "*/");
+            } else if c == '"' || c == '\'' {
+                s.bump();
+                lex_string_body(&mut s, start, StringState::new(c));
+            } else if c == '@' {
+                s.bump();
+                s.eat_while(is_css_ident_char);
+                s.emit(start, TokenType::Keyword);
+            } else if c == '#' && is_hex_color(&s.rest()[1..]) {
+                s.bump();
+                s.eat_while(|c| c.is_ascii_hexdigit());
+                s.emit(start, TokenType::Constant);
+            } else if c.is_ascii_digit() || (c == '.' && s.peek_nth(1).is_some_and(|c| c.is_ascii_digit())) {
+                lex_number(&mut s);
+                // Percentages are part of the number.
+                if s.eat("%") {
+                    s.emit(start, TokenType::Number);
+                }
+            } else if is_css_ident_char(c) || c == '.' || c == '#' {
+                s.bump();
+                s.eat_while(is_css_ident_char);
+                let after = s.rest().trim_start();
+                // `color: red` is a property; `a:hover` is a selector with a pseudo-class.
+                let is_property = after.strip_prefix(':').is_some_and(|v| v.is_empty() || v.starts_with(char::is_whitespace));
+                let token_type = if is_property {
+                    TokenType::Property
+                } else if s.before().trim_end().ends_with(':') || (!line.contains('{') && line.contains(';')) {
+                    TokenType::Constant
+                } else {
+                    TokenType::Tag
+                };
+                s.emit(start, token_type);
+            } else {
+                lex_symbol(&mut s);
+            }
+        }
+        (s.finish(), state)
+    }
+}
+
+fn is_css_ident_char(c: char) -> bool {
+    is_ident_char(c) || c == '-'
+}
+
+/// Check for 3, 4, 6 or 8 hex digits followed by the end of the word.
+fn is_hex_color(text: &str) -> bool {
+    let digits = text.chars().take_while(|c| c.is_ascii_hexdigit()).count();
+    matches!(digits, 3 | 4 | 6 | 8) && !text[digits..].starts_with(is_css_ident_char)
+}
@@ -0,0 +1,84 @@
+use super::{lex_block_comment, lex_whitespace, LineState, Lexer, Scanner, Span};
+use crate::backend::code_editor::syntax_highlighting::TokenType;
+
+/// HTML and XML: tags, attributes, entities and `<!-- -->` comments.
+pub struct HtmlLexer;
+
+impl Lexer for HtmlLexer {
+    fn name(&self) -> &'static str {
+        "html"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["html", "htm", "xhtml", "xml", "svg", "vue"]
+    }
+
+    fn lex_line(&self, line: &str, state: &LineState) -> (Vec<Span>, LineState) {
+        let mut s = Scanner::new(line);
+        let mut state = match state {
+            LineState::BlockComment(depth) => lex_block_comment(&mut s, 0, *depth, None, "-->"),
+            LineState::Tag => lex_tag_body(&mut s),
+            _ => LineState::Normal,
+        };
+
+        while !s.is_done() {
+            match state {
+                LineState::Tag => {
+                    state = lex_tag_body(&mut s);
+                    continue;
+                }
+                LineState::Normal => {}
+                _ => break,
+            }
+            let start = s.pos();
+            if s.eat("<!--") {
+                state = lex_block_comment(&mut s, start, 1, None, "-->");
+            } else if s.starts_with("<") && s.peek_nth(1).is_some_and(|c| c.is_alphabetic() || matches!(c, '/' | '!' | '?')) {
+                s.bump();
+                s.emit(start, TokenType::Symbol);
+                let name = s.pos();
+                s.eat_while(|c| matches!(c, '/' | '!' | '?'));
+                s.eat_while(|c| c.is_alphanumeric() || matches!(c, '-' | ':' | '_' | '.'));
+                s.emit(name, TokenType::Tag);
+                state = LineState::Tag;
+            } else if s.starts_with("&") {
+                s.bump();
+                s.eat_while(|c| c.is_alphanumeric() || c == '#');
+                s.eat(";");
+                s.emit(start, TokenType::Constant);
+            } else {
+                s.eat_while(|c| c != '<' && c != '&');
+                s.emit(start, TokenType::Other);
+            }
+        }
+        (s.finish(), state)
+    }
+}
+
+/// Lex attributes inside a tag until `>` closes it.
+fn lex_tag_body(s: &mut Scanner) -> LineState {
+    while !s.is_done() {
+        if lex_whitespace(s) {
+            continue;
+        }
+        let start = s.pos();
+        let c = s.peek().unwrap_or_default();
+        if s.eat("/>") || s.eat("?>") || s.eat(">") {
+            s.emit(start, TokenType::Symbol);
+            return LineState::Normal;
+        } else if c == '"' || c == '\'' {
+            s.bump();
+            s.eat_while(|next| next != c);
+            s.eat(&c.to_string());
+            s.emit(start, TokenType::String);
+        } else if c == '=' {
+            s.bump();
+            s.emit(start, TokenType::Symbol);
+        } else {
+            s.bump();
+            s.eat_while(|c| !c.is_whitespace() && !matches!(c, '=' | '>' | '/' | '"' | '\''));
+            s.emit(start, TokenType::Attribute);
+        }
+    }
+    LineState::Tag
+}
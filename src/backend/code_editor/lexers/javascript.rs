@@ -0,0 +1,85 @@
+use super::{classify_identifier, is_ident_start, lex_block_comment, lex_number, lex_string_body, lex_symbol, lex_whitespace, LineState, Lexer, Scanner, Span, StringState};
+use crate::backend::code_editor::syntax_highlighting::TokenType;
+
+const JS_KEYWORDS: &[&str] = &[
+    "async", "await", "break", "case", "catch", "class", "const", "continue", "debugger", "default", "delete", "do",
+    "else", "export", "extends", "false", "finally", "for", "from", "function", "if", "import", "in", "instanceof",
+    "let", "new", "null", "of", "return", "static", "super", "switch", "this", "throw", "true", "try", "typeof",
+    "undefined", "var", "void", "while", "with", "yield",
+];
+
+const TS_KEYWORDS: &[&str] = &[
+    "abstract", "any", "as", "boolean", "declare", "enum", "implements", "interface", "keyof", "namespace", "never",
+    "number", "private", "protected", "public", "readonly", "string", "type", "unknown",
+];
+
+/// JavaScript and TypeScript: C-style comments, quoted strings and template literals.
+pub struct JavaScriptLexer {
+    name: &'static str,
+    extensions: &'static [&'static str],
+    typescript: bool,
+}
+
+impl JavaScriptLexer {
+    pub fn javascript() -> Self {
+        Self { name: "javascript", extensions: &["js", "mjs", "cjs", "jsx"], typescript: false }
+    }
+
+    pub fn typescript() -> Self {
+        Self { name: "typescript", extensions: &["ts", "mts", "cts", "tsx"], typescript: true }
+    }
+
+    fn is_keyword(&self, word: &str) -> bool {
+        JS_KEYWORDS.contains(&word) || (self.typescript && TS_KEYWORDS.contains(&word))
+    }
+}
+
+impl Lexer for JavaScriptLexer {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        self.extensions
+    }
+
+    fn lex_line(&self, line: &str, state: &LineState) -> (Vec<Span>, LineState) {
+        let mut s = Scanner::new(line);
+        let mut state = match state {
+            LineState::BlockComment(depth) => lex_block_comment(&mut s, 0, *depth, None, "*/"),
+            LineState::String(string) => lex_string_body(&mut s, 0, *string),
+            _ => LineState::Normal,
+        };
+
+        while state == LineState::Normal && !s.is_done() {
+            if lex_whitespace(&mut s) {
+                continue;
+            }
+            let start = s.pos();
+            let c = s.peek().unwrap_or_default();
+            if s.eat("//") {
+                s.eat_rest();
+                s.emit(start, TokenType::Comment);
+            } else if s.eat("/*") {
+                state = lex_block_comment(&mut s, start, 1, None, "*/");
+            } else if c == '`' {
+                s.bump();
+                let template = StringState { format: true, multiline: true, ..StringState::new('`') };
+                state = lex_string_body(&mut s, start, template);
+            } else if c == '"' || c == '\'' {
+                s.bump();
+                state = lex_string_body(&mut s, start, StringState::new(c));
+            } else if c.is_ascii_digit() {
+                lex_number(&mut s);
+            } else if is_ident_start(c) || c == '$' {
+                s.eat_while(|c| c.is_alphanumeric() || c == '_' || c == '$');
+                let word = s.since(start);
+                let token_type = if self.is_keyword(word) { TokenType::Keyword } else { classify_identifier(word, s.rest()) };
+                s.emit(start, token_type);
+            } else {
+                lex_symbol(&mut s);
+            }
+        }
+        (s.finish(), state)
+    }
+}
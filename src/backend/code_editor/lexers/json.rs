@@ -0,0 +1,47 @@
+use super::{lex_number, lex_string_body, lex_symbol, lex_whitespace, LineState, Lexer, Scanner, Span, StringState};
+use crate::backend::code_editor::syntax_highlighting::TokenType;
+
+/// JSON: object keys, string values, numbers and literals. Also accepts `//` comments (JSONC).
+pub struct JsonLexer;
+
+impl Lexer for JsonLexer {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["json", "jsonc", "jsonl"]
+    }
+
+    fn lex_line(&self, line: &str, _state: &LineState) -> (Vec<Span>, LineState) {
+        let mut s = Scanner::new(line);
+        while !s.is_done() {
+            if lex_whitespace(&mut s) {
+                continue;
+            }
+            let start = s.pos();
+            let c = s.peek().unwrap_or_default();
+            if s.eat("//") {
+                s.eat_rest();
+                s.emit(start, TokenType::Comment);
+            } else if c == '"' {
+                s.bump();
+                lex_string_body(&mut s, start, StringState::new('"'));
+                if s.rest().trim_start().starts_with(':') {
+                    s.emit(start, TokenType::Property);
+                }
+            } else if c == '-' || c.is_ascii_digit() {
+                s.bump();
+                lex_number(&mut s);
+                s.emit(start, TokenType::Number);
+            } else if c.is_alphabetic() {
+                s.eat_while(char::is_alphanumeric);
+                let token_type = if matches!(s.since(start), "true" | "false" | "null") { TokenType::Keyword } else { TokenType::Other };
+                s.emit(start, token_type);
+            } else {
+                lex_symbol(&mut s);
+            }
+        }
+        (s.finish(), LineState::Normal)
+    }
+}
@@ -0,0 +1,99 @@
+use super::{LineState, Lexer, Scanner, Span};
+use crate::backend::code_editor::syntax_highlighting::TokenType;
+
+/// Markdown: headings, quotes, list markers, emphasis, inline code, links and fenced code blocks.
+pub struct MarkdownLexer;
+
+impl Lexer for MarkdownLexer {
+    fn name(&self) -> &'static str {
+        "markdown"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["md", "markdown"]
+    }
+
+    fn lex_line(&self, line: &str, state: &LineState) -> (Vec<Span>, LineState) {
+        let mut s = Scanner::new(line);
+        let trimmed = line.trim_start();
+        let is_fence = trimmed.starts_with("```") || trimmed.starts_with("~~~");
+
+        if *state == LineState::FencedCode {
+            s.eat_rest();
+            s.emit(0, if is_fence { TokenType::Symbol } else { TokenType::String });
+            let next = if is_fence { LineState::Normal } else { LineState::FencedCode };
+            return (s.finish(), next);
+        }
+
+        s.eat_while(char::is_whitespace);
+        let start = s.pos();
+        if is_fence {
+            s.eat_rest();
+            s.emit(start, TokenType::Symbol);
+            return (s.finish(), LineState::FencedCode);
+        }
+        if trimmed.starts_with('#') {
+            s.eat_rest();
+            s.emit(start, TokenType::Keyword);
+            return (s.finish(), LineState::Normal);
+        }
+        if trimmed.starts_with('>') {
+            s.eat_rest();
+            s.emit(start, TokenType::Comment);
+            return (s.finish(), LineState::Normal);
+        }
+        if s.eat("- ") || s.eat("* ") || s.eat("+ ") {
+            s.emit(start, TokenType::Symbol);
+        } else if s.eat_while(|c| c.is_ascii_digit()) > 0 {
+            if s.eat(". ") || s.eat(") ") {
+                s.emit(start, TokenType::Symbol);
+            } else {
+                s.emit(start, TokenType::Other);
+            }
+        }
+        lex_inline(&mut s);
+        (s.finish(), LineState::Normal)
+    }
+}
+
+/// Lex inline code spans, emphasis and links.
+fn lex_inline(s: &mut Scanner) {
+    while let Some(c) = s.peek() {
+        let start = s.pos();
+        match c {
+            '`' => {
+                let ticks = s.eat_while(|c| c == '`');
+                s.eat_until(&"`".repeat(ticks));
+                s.emit(start, TokenType::String);
+            }
+            // Underscores inside words (snake_case) are not emphasis.
+            '*' | '_' if s.peek_nth(1).is_some_and(|n| !n.is_whitespace())
+                && (c == '*' || !s.before().ends_with(char::is_alphanumeric)) =>
+            {
+                let marker = s.eat_while(|m| m == c);
+                let closer = c.to_string().repeat(marker);
+                if s.rest().contains(&closer) {
+                    s.eat_until(&closer);
+                    let token_type = if marker >= 2 { TokenType::Keyword } else { TokenType::Identifier };
+                    s.emit(start, token_type);
+                } else {
+                    s.emit(start, TokenType::Other);
+                }
+            }
+            '[' if s.rest().contains("](") => {
+                s.eat_until("]");
+                s.emit(start, TokenType::Tag);
+                if s.starts_with("(") {
+                    let url = s.pos();
+                    s.eat_until(")");
+                    s.emit(url, TokenType::String);
+                }
+            }
+            _ => {
+                s.bump();
+                s.eat_while(|c| !matches!(c, '`' | '*' | '_' | '['));
+                s.emit(start, TokenType::Other);
+            }
+        }
+    }
+}
@@ -0,0 +1,499 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+use crate::backend::code_editor::syntax_highlighting::TokenType;
+
+pub mod css;
+pub mod html;
+pub mod javascript;
+pub mod json;
+pub mod markdown;
+pub mod python;
+pub mod rust;
+pub mod toml;
+
+/// Lexer state carried from the end of one line into the next.
+///
+/// Each variant is interpreted by the lexer that produced it; for example
+/// `BlockComment` closes on `*/` in Rust but on `-->` in HTML.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum LineState {
+    #[default]
+    Normal,
+    /// Inside a block comment, with its nesting depth.
+    BlockComment(usize),
+    /// Inside a string literal that continues onto the next line.
+    String(StringState),
+    /// Inside a Rust raw string closed by `"` followed by this many `#`.
+    RawString(usize),
+    /// Inside an HTML/XML tag whose attributes continue onto the next line.
+    Tag,
+    /// Inside a fenced code block in Markdown.
+    FencedCode,
+}
+
+/// Describes an open string literal so it can be resumed on the next line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StringState {
+    /// The quote character that closes the string.
+    pub quote: char,
+    /// Closed by three quote characters (Python/TOML).
+    pub triple: bool,
+    /// Backslash escapes are not processed.
+    pub raw: bool,
+    /// Contains interpolations: `{...}` for Python f-strings, `${...}` for JS templates.
+    pub format: bool,
+    /// May span lines without a trailing backslash.
+    pub multiline: bool,
+}
+
+impl StringState {
+    /// A plain single-line string closed by `quote`.
+    pub fn new(quote: char) -> Self {
+        Self { quote, triple: false, raw: false, format: false, multiline: false }
+    }
+}
+
+/// A highlighted byte range of a line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub range: Range<usize>,
+    pub token_type: TokenType,
+}
+
+/// A per-language lexer that highlights one line at a time.
+pub trait Lexer: Send + Sync {
+    /// Language name, matching the names used by the editor UI (`"rust"`, `"python"`, ...).
+    fn name(&self) -> &'static str;
+
+    /// File extensions, without the dot, handled by this lexer.
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// Lex one line starting in `state`.
+    ///
+    /// Returns spans covering the whole line in order, and the state the next line starts in.
+    fn lex_line(&self, line: &str, state: &LineState) -> (Vec<Span>, LineState);
+}
+
+/// Lexer for files without a grammar; everything is plain text.
+pub struct PlainTextLexer;
+
+impl Lexer for PlainTextLexer {
+    fn name(&self) -> &'static str {
+        "text"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["txt"]
+    }
+
+    fn lex_line(&self, line: &str, _state: &LineState) -> (Vec<Span>, LineState) {
+        (Scanner::new(line).finish(), LineState::Normal)
+    }
+}
+
+/// Registry of lexers, looked up by language name or file extension.
+pub struct LexerRegistry {
+    lexers: HashMap<&'static str, Arc<dyn Lexer>>,
+    by_extension: HashMap<&'static str, &'static str>,
+}
+
+impl Default for LexerRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LexerRegistry {
+    /// Create a registry with all built-in grammars.
+    pub fn new() -> Self {
+        let mut registry = Self {
+            lexers: HashMap::new(),
+            by_extension: HashMap::new(),
+        };
+        registry.register(Arc::new(PlainTextLexer));
+        registry.register(Arc::new(rust::RustLexer));
+        registry.register(Arc::new(python::PythonLexer));
+        registry.register(Arc::new(javascript::JavaScriptLexer::javascript()));
+        registry.register(Arc::new(javascript::JavaScriptLexer::typescript()));
+        registry.register(Arc::new(html::HtmlLexer));
+        registry.register(Arc::new(css::CssLexer));
+        registry.register(Arc::new(toml::TomlLexer));
+        registry.register(Arc::new(json::JsonLexer));
+        registry.register(Arc::new(markdown::MarkdownLexer));
+        registry
+    }
+
+    /// Add or replace a lexer. Its extensions take precedence over earlier registrations.
+    pub fn register(&mut self, lexer: Arc<dyn Lexer>) {
+        for ext in lexer.extensions() {
+            self.by_extension.insert(ext, lexer.name());
+        }
+        self.lexers.insert(lexer.name(), lexer);
+    }
+
+    /// Get a lexer by language name, falling back to plain text.
+    pub fn get(&self, language: &str) -> Arc<dyn Lexer> {
+        self.lexers
+            .get(language)
+            .or_else(|| self.lexers.get("text"))
+            .cloned()
+            .unwrap_or_else(|| Arc::new(PlainTextLexer))
+    }
+
+    /// Get the language name for a file path from its extension, or `"text"`.
+    pub fn language_for_path(&self, path: &Path) -> &'static str {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| self.by_extension.get(ext.to_lowercase().as_str()).copied())
+            .unwrap_or("text")
+    }
+
+    /// List the registered language names.
+    pub fn languages(&self) -> Vec<&'static str> {
+        let mut names: Vec<_> = self.lexers.keys().copied().collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+/// Character cursor over a single line that records highlighted spans.
+///
+/// Spans are emitted as `start..current position`; any bytes skipped between
+/// spans are filled in as whitespace or plain text by `emit` and `finish`.
+pub struct Scanner<'a> {
+    text: &'a str,
+    pos: usize,
+    spans: Vec<Span>,
+    emitted: usize,
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(text: &'a str) -> Self {
+        Self { text, pos: 0, spans: Vec::new(), emitted: 0 }
+    }
+
+    /// Current byte position.
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// Check if the whole line has been consumed.
+    pub fn is_done(&self) -> bool {
+        self.pos >= self.text.len()
+    }
+
+    /// The unconsumed remainder of the line.
+    pub fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    /// Text consumed since `start`.
+    pub fn since(&self, start: usize) -> &'a str {
+        &self.text[start..self.pos]
+    }
+
+    /// Text already consumed on this line.
+    pub fn before(&self) -> &'a str {
+        &self.text[..self.pos]
+    }
+
+    pub fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    /// Look `n` characters ahead (`peek_nth(0)` is `peek`).
+    pub fn peek_nth(&self, n: usize) -> Option<char> {
+        self.rest().chars().nth(n)
+    }
+
+    pub fn starts_with(&self, pat: &str) -> bool {
+        self.rest().starts_with(pat)
+    }
+
+    pub fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    /// Consume `pat` if the input starts with it.
+    pub fn eat(&mut self, pat: &str) -> bool {
+        if self.starts_with(pat) {
+            self.pos += pat.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consume characters while `pred` holds. Returns the number of bytes consumed.
+    pub fn eat_while(&mut self, pred: impl Fn(char) -> bool) -> usize {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !pred(c) {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+        self.pos - start
+    }
+
+    /// Consume up to and including `pat`. Consumes the rest of the line and returns false if absent.
+    pub fn eat_until(&mut self, pat: &str) -> bool {
+        match self.rest().find(pat) {
+            Some(i) => {
+                self.pos += i + pat.len();
+                true
+            }
+            None => {
+                self.pos = self.text.len();
+                false
+            }
+        }
+    }
+
+    /// Consume the rest of the line.
+    pub fn eat_rest(&mut self) {
+        self.pos = self.text.len();
+    }
+
+    /// Record `start..pos` as a token of the given type.
+    ///
+    /// If `start` reaches back into text that was already emitted, those spans
+    /// are replaced, so a lexer can reclassify a token once it has seen what
+    /// follows it (e.g. a JSON string followed by `:` is a key).
+    pub fn emit(&mut self, start: usize, token_type: TokenType) {
+        if start < self.emitted {
+            self.spans.retain(|span| span.range.start < start);
+            if let Some(last) = self.spans.last_mut() {
+                last.range.end = last.range.end.min(start);
+            }
+            self.emitted = start;
+        }
+        self.fill_gap(start);
+        if self.pos > start {
+            self.spans.push(Span { range: start..self.pos, token_type });
+            self.emitted = self.pos;
+        }
+    }
+
+    /// Finish the line, filling any trailing gap.
+    pub fn finish(mut self) -> Vec<Span> {
+        let end = self.text.len();
+        self.fill_gap(end);
+        self.spans
+    }
+
+    fn fill_gap(&mut self, until: usize) {
+        let mut start = self.emitted;
+        while start < until {
+            let gap = &self.text[start..until];
+            let first_ws = gap.starts_with(char::is_whitespace);
+            let len = gap
+                .char_indices()
+                .find(|&(_, c)| c.is_whitespace() != first_ws)
+                .map_or(gap.len(), |(i, _)| i);
+            let token_type = if first_ws { TokenType::Whitespace } else { TokenType::Other };
+            self.spans.push(Span { range: start..start + len, token_type });
+            start += len;
+        }
+        self.emitted = self.emitted.max(until);
+    }
+}
+
+pub fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+pub fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Lex leading whitespace, if any.
+pub fn lex_whitespace(s: &mut Scanner) -> bool {
+    let start = s.pos();
+    if s.eat_while(char::is_whitespace) > 0 {
+        s.emit(start, TokenType::Whitespace);
+        true
+    } else {
+        false
+    }
+}
+
+/// Lex a numeric literal: decimal, hex/octal/binary, underscores, fraction, exponent and suffix.
+pub fn lex_number(s: &mut Scanner) {
+    let start = s.pos();
+    if s.eat("0x") || s.eat("0X") || s.eat("0o") || s.eat("0b") {
+        s.eat_while(|c| c.is_ascii_hexdigit() || c == '_');
+    } else {
+        s.eat_while(|c| c.is_ascii_digit() || c == '_');
+        if s.peek() == Some('.') && s.peek_nth(1).is_some_and(|c| c.is_ascii_digit()) {
+            s.bump();
+            s.eat_while(|c| c.is_ascii_digit() || c == '_');
+        }
+        if matches!(s.peek(), Some('e' | 'E')) && s.peek_nth(1).is_some_and(|c| c.is_ascii_digit() || c == '-' || c == '+') {
+            s.bump();
+            s.bump();
+            s.eat_while(|c| c.is_ascii_digit() || c == '_');
+        }
+    }
+    // Type suffixes and units: 10u8, 1.5f32, 12px, 10n
+    s.eat_while(is_ident_char);
+    s.emit(start, TokenType::Number);
+}
+
+/// Lex a block comment body until `close`, tracking nesting when `open` is given.
+///
+/// The opening delimiter must already have been consumed (or this is a
+/// continuation line). Returns the state for the next line.
+pub fn lex_block_comment(s: &mut Scanner, start: usize, mut depth: usize, open: Option<&str>, close: &str) -> LineState {
+    while !s.is_done() {
+        if s.eat(close) {
+            depth -= 1;
+            if depth == 0 {
+                s.emit(start, TokenType::Comment);
+                return LineState::Normal;
+            }
+        } else if open.is_some_and(|open| s.eat(open)) {
+            depth += 1;
+        } else {
+            s.bump();
+        }
+    }
+    s.emit(start, TokenType::Comment);
+    LineState::BlockComment(depth)
+}
+
+/// Lex a string body after its opening quote(s), up to and including the closing quote(s).
+///
+/// Interpolations in format strings are emitted as identifiers between symbol
+/// braces. Returns the state for the next line.
+pub fn lex_string_body(s: &mut Scanner, start: usize, string: StringState) -> LineState {
+    let close = string.quote.to_string().repeat(if string.triple { 3 } else { 1 });
+    let mut segment = start;
+    while let Some(c) = s.peek() {
+        if s.eat(&close) {
+            s.emit(segment, TokenType::String);
+            return LineState::Normal;
+        }
+        if c == '\\' && !string.raw {
+            s.bump();
+            if s.is_done() {
+                // Line continuation keeps even single-line strings open.
+                s.emit(segment, TokenType::String);
+                return LineState::String(string);
+            }
+            s.bump();
+            continue;
+        }
+        if string.format {
+            let interpolation = if string.quote == '`' {
+                s.starts_with("${")
+            } else {
+                c == '{' && s.peek_nth(1) != Some('{')
+            };
+            if interpolation {
+                s.emit(segment, TokenType::String);
+                let brace = s.pos();
+                s.eat(if string.quote == '`' { "${" } else { "{" });
+                s.emit(brace, TokenType::Symbol);
+                let expr = s.pos();
+                s.eat_while(|c| c != '}');
+                s.emit(expr, TokenType::Identifier);
+                let brace = s.pos();
+                s.eat("}");
+                s.emit(brace, TokenType::Symbol);
+                segment = s.pos();
+                continue;
+            }
+            if c == '{' {
+                // `{{` is a literal brace in f-strings.
+                s.bump();
+            }
+        }
+        s.bump();
+    }
+    s.emit(segment, TokenType::String);
+    if string.triple || string.multiline {
+        LineState::String(string)
+    } else {
+        LineState::Normal
+    }
+}
+
+/// Lex a word and classify it as a keyword or identifier.
+pub fn lex_word(s: &mut Scanner, keywords: &[&str]) {
+    let start = s.pos();
+    s.eat_while(is_ident_char);
+    let word = s.since(start);
+    let token_type = if keywords.contains(&word) { TokenType::Keyword } else { classify_identifier(word, s.rest()) };
+    s.emit(start, token_type);
+}
+
+/// Classify an identifier by its shape and what follows it.
+///
+/// Calls become functions, `ALL_CAPS` names constants and `CamelCase` names types.
+pub fn classify_identifier(word: &str, rest: &str) -> TokenType {
+    if rest.starts_with('(') {
+        TokenType::Function
+    } else if word.chars().count() > 1
+        && word.chars().any(char::is_uppercase)
+        && word.chars().all(|c| c.is_uppercase() || c.is_ascii_digit() || c == '_')
+    {
+        TokenType::Constant
+    } else if word.starts_with(char::is_uppercase) {
+        TokenType::Type
+    } else {
+        TokenType::Identifier
+    }
+}
+
+/// Lex a single punctuation character.
+pub fn lex_symbol(s: &mut Scanner) {
+    let start = s.pos();
+    s.bump();
+    s.emit(start, TokenType::Symbol);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex_lines(language: &str, lines: &[&str]) -> Vec<(Vec<(String, TokenType)>, LineState)> {
+        let lexer = LexerRegistry::new().get(language);
+        let mut state = LineState::Normal;
+        lines
+            .iter()
+            .map(|line| {
+                let (spans, next) = lexer.lex_line(line, &state);
+                state = next.clone();
+                let tokens = spans.into_iter().map(|span| (line[span.range].to_string(), span.token_type)).collect();
+                (tokens, next)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rust_lifetimes_and_chars() {
+        let lines = lex_lines("rust", &["fn f<'a>(c: char) -> &'a str { 'x' }"]);
+        let tokens = &lines[0].0;
+        assert!(tokens.contains(&("'a".to_string(), TokenType::Lifetime)));
+        assert!(tokens.contains(&("str".to_string(), TokenType::Type)));
+        assert!(tokens.contains(&("'x'".to_string(), TokenType::String)));
+        assert_eq!(lines[0].1, LineState::Normal);
+    }
+
+    #[test]
+    fn test_state_carries_across_lines() {
+        let lines = lex_lines("python", &["s = '''doc", "still doc''' + 1"]);
+        assert!(matches!(lines[0].1, LineState::String(StringState { triple: true, .. })));
+        assert_eq!(lines[1].0[0], ("still doc'''".to_string(), TokenType::String));
+        assert_eq!(lines[1].1, LineState::Normal);
+
+        let lines = lex_lines("rust", &["/* outer /* inner */", "still comment */ x"]);
+        assert_eq!(lines[0].1, LineState::BlockComment(1));
+        assert_eq!(lines[1].0.last(), Some(&("x".to_string(), TokenType::Identifier)));
+    }
+}
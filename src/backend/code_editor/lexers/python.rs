@@ -0,0 +1,77 @@
+use super::{is_ident_start, lex_number, lex_string_body, lex_symbol, lex_whitespace, lex_word, LineState, Lexer, Scanner, Span, StringState};
+use crate::backend::code_editor::syntax_highlighting::TokenType;
+
+const KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif",
+    "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is", "lambda", "match", "case",
+    "nonlocal", "not", "or", "pass", "raise", "return", "self", "try", "while", "with", "yield",
+];
+
+/// Python: `#` comments, string prefixes, triple-quoted strings and f-string interpolation.
+pub struct PythonLexer;
+
+impl Lexer for PythonLexer {
+    fn name(&self) -> &'static str {
+        "python"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["py", "pyi", "pyw"]
+    }
+
+    fn lex_line(&self, line: &str, state: &LineState) -> (Vec<Span>, LineState) {
+        let mut s = Scanner::new(line);
+        let mut state = match state {
+            LineState::String(string) => lex_string_body(&mut s, 0, *string),
+            _ => LineState::Normal,
+        };
+
+        while state == LineState::Normal && !s.is_done() {
+            if lex_whitespace(&mut s) {
+                continue;
+            }
+            let start = s.pos();
+            let c = s.peek().unwrap_or_default();
+            if c == '#' {
+                s.eat_rest();
+                s.emit(start, TokenType::Comment);
+            } else if let Some((prefix_len, string)) = string_start(s.rest()) {
+                let prefix = &s.rest()[..prefix_len];
+                s.eat(prefix);
+                state = lex_string_body(&mut s, start, string);
+            } else if c == '@' && s.peek_nth(1).is_some_and(is_ident_start) {
+                s.bump();
+                s.eat_while(|c| c.is_alphanumeric() || c == '_' || c == '.');
+                s.emit(start, TokenType::Attribute);
+            } else if c.is_ascii_digit() || (c == '.' && s.peek_nth(1).is_some_and(|c| c.is_ascii_digit())) {
+                lex_number(&mut s);
+            } else if is_ident_start(c) {
+                lex_word(&mut s, KEYWORDS);
+            } else {
+                lex_symbol(&mut s);
+            }
+        }
+        (s.finish(), state)
+    }
+}
+
+/// If the text starts a string literal (with optional `r`/`b`/`u`/`f` prefixes),
+/// return the length of the prefix and opening quotes, and the string kind.
+fn string_start(text: &str) -> Option<(usize, StringState)> {
+    let prefix_len = text.chars().take(2).take_while(|c| "rRbBuUfF".contains(*c)).count();
+    let prefix = text[..prefix_len].to_lowercase();
+    let body = &text[prefix_len..];
+    let quote = body.chars().next().filter(|&c| c == '"' || c == '\'')?;
+    if prefix_len == 2 && !matches!(prefix.as_str(), "rb" | "br" | "rf" | "fr") {
+        return None;
+    }
+    let triple = body.starts_with(&quote.to_string().repeat(3));
+    let string = StringState {
+        quote,
+        triple,
+        raw: prefix.contains('r'),
+        format: prefix.contains('f'),
+        multiline: false,
+    };
+    Some((prefix_len + if triple { 3 } else { 1 }, string))
+}
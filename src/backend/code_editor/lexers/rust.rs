@@ -0,0 +1,113 @@
+use super::{is_ident_char, is_ident_start, lex_block_comment, lex_number, lex_string_body, lex_symbol, lex_whitespace, lex_word, LineState, Lexer, Scanner, Span, StringState};
+use crate::backend::code_editor::syntax_highlighting::TokenType;
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false", "fn", "for",
+    "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self", "static",
+    "struct", "super", "trait", "true", "type", "union", "unsafe", "use", "where", "while", "yield",
+];
+
+const PRIMITIVES: &[&str] = &[
+    "bool", "char", "str", "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize", "f32",
+    "f64",
+];
+
+/// Rust: nested block comments, raw and byte strings, char literals vs lifetimes.
+pub struct RustLexer;
+
+impl Lexer for RustLexer {
+    fn name(&self) -> &'static str {
+        "rust"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["rs"]
+    }
+
+    fn lex_line(&self, line: &str, state: &LineState) -> (Vec<Span>, LineState) {
+        let mut s = Scanner::new(line);
+        let mut state = match state {
+            LineState::BlockComment(depth) => lex_block_comment(&mut s, 0, *depth, Some("/*"), "*/"),
+            LineState::String(string) => lex_string_body(&mut s, 0, *string),
+            LineState::RawString(hashes) => lex_raw_string(&mut s, 0, *hashes),
+            _ => LineState::Normal,
+        };
+
+        while state == LineState::Normal && !s.is_done() {
+            if lex_whitespace(&mut s) {
+                continue;
+            }
+            let start = s.pos();
+            let c = s.peek().unwrap_or_default();
+            if s.eat("//") {
+                s.eat_rest();
+                s.emit(start, TokenType::Comment);
+            } else if s.eat("/*") {
+                state = lex_block_comment(&mut s, start, 1, Some("/*"), "*/");
+            } else if let Some((prefix, hashes)) = raw_string_prefix(s.rest()) {
+                let prefix = &s.rest()[..prefix];
+                s.eat(prefix);
+                state = lex_raw_string(&mut s, start, hashes);
+            } else if s.eat("b\"") || s.eat("\"") {
+                state = lex_string_body(&mut s, start, StringState { multiline: true, ..StringState::new('"') });
+            } else if s.starts_with("b'") {
+                s.bump();
+                lex_char_or_lifetime(&mut s, start);
+            } else if c == '\'' {
+                lex_char_or_lifetime(&mut s, start);
+            } else if c.is_ascii_digit() {
+                lex_number(&mut s);
+            } else if s.starts_with("#[") || s.starts_with("#![") {
+                s.eat_until("]");
+                s.emit(start, TokenType::Attribute);
+            } else if is_ident_start(c) {
+                lex_word(&mut s, KEYWORDS);
+                let word = s.since(start);
+                if PRIMITIVES.contains(&word) {
+                    s.emit(start, TokenType::Type);
+                } else if s.starts_with("!") && !s.starts_with("!=") && !KEYWORDS.contains(&word) {
+                    s.bump();
+                    s.emit(start, TokenType::Macro);
+                }
+            } else {
+                lex_symbol(&mut s);
+            }
+        }
+        (s.finish(), state)
+    }
+}
+
+/// If the text starts a raw string (`r"`, `r#"`, `br#"`, ...), return the prefix length and number of `#`.
+fn raw_string_prefix(text: &str) -> Option<(usize, usize)> {
+    let after_r = text.strip_prefix("br").or_else(|| text.strip_prefix('r'))?;
+    let hashes = after_r.chars().take_while(|&c| c == '#').count();
+    after_r[hashes..].starts_with('"').then(|| (text.len() - after_r.len() + hashes + 1, hashes))
+}
+
+/// Lex a raw string body until `"` followed by `hashes` `#`.
+fn lex_raw_string(s: &mut Scanner, start: usize, hashes: usize) -> LineState {
+    let close = format!("\"{}", "#".repeat(hashes));
+    let closed = s.eat_until(&close);
+    s.emit(start, TokenType::String);
+    if closed { LineState::Normal } else { LineState::RawString(hashes) }
+}
+
+/// Lex `'x'`, `'\n'`, `'\u{1F600}'` as a char literal, or `'a`/`'static` as a lifetime.
+fn lex_char_or_lifetime(s: &mut Scanner, start: usize) {
+    s.bump(); // the quote
+    let is_char = s.peek() == Some('\\') || (s.peek().is_some() && s.peek_nth(1) == Some('\''));
+    if is_char {
+        if s.peek() == Some('\\') {
+            s.bump();
+        }
+        s.bump();
+        s.eat_while(|c| c != '\'');
+        s.eat("'");
+        s.emit(start, TokenType::String);
+    } else if s.peek().is_some_and(is_ident_start) {
+        s.eat_while(is_ident_char);
+        s.emit(start, TokenType::Lifetime);
+    } else {
+        s.emit(start, TokenType::Symbol);
+    }
+}
@@ -0,0 +1,79 @@
+use super::{lex_number, lex_string_body, lex_symbol, lex_whitespace, LineState, Lexer, Scanner, Span, StringState};
+use crate::backend::code_editor::syntax_highlighting::TokenType;
+
+/// TOML: tables, keys, basic/literal strings (including multi-line), numbers and dates.
+pub struct TomlLexer;
+
+impl Lexer for TomlLexer {
+    fn name(&self) -> &'static str {
+        "toml"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["toml", "lock"]
+    }
+
+    fn lex_line(&self, line: &str, state: &LineState) -> (Vec<Span>, LineState) {
+        let mut s = Scanner::new(line);
+        let mut state = match state {
+            LineState::String(string) => lex_string_body(&mut s, 0, *string),
+            _ => LineState::Normal,
+        };
+
+        while state == LineState::Normal && !s.is_done() {
+            if lex_whitespace(&mut s) {
+                continue;
+            }
+            let start = s.pos();
+            let c = s.peek().unwrap_or_default();
+            let at_line_start = s.before().trim().is_empty();
+            if c == '#' {
+                s.eat_rest();
+                s.emit(start, TokenType::Comment);
+            } else if c == '[' && at_line_start {
+                // Table header: [table] or [[array.of.tables]]
+                s.eat_while(|c| c == '[');
+                s.eat_while(|c| c != ']');
+                s.eat_while(|c| c == ']');
+                s.emit(start, TokenType::Type);
+            } else if c == '"' || c == '\'' {
+                let triple = s.starts_with(&c.to_string().repeat(3));
+                for _ in 0..if triple { 3 } else { 1 } {
+                    s.bump();
+                }
+                let string = StringState { triple, raw: c == '\'', ..StringState::new(c) };
+                state = lex_string_body(&mut s, start, string);
+                if is_key(s.rest()) {
+                    // Quoted key: "key" = value
+                    s.emit(start, TokenType::Property);
+                }
+            } else if c.is_ascii_digit() || ((c == '+' || c == '-') && s.peek_nth(1).is_some_and(|c| c.is_ascii_digit())) {
+                s.bump();
+                // Dates and times (1979-05-27T07:32:00Z) lex as one number.
+                s.eat_while(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-' | ':' | '+'));
+                s.emit(start, TokenType::Number);
+            } else if c.is_alphanumeric() || c == '_' || c == '-' {
+                s.eat_while(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'));
+                let token_type = if is_key(s.rest()) {
+                    TokenType::Property
+                } else if matches!(s.since(start), "true" | "false" | "inf" | "nan") {
+                    TokenType::Keyword
+                } else {
+                    TokenType::Other
+                };
+                s.emit(start, token_type);
+            } else if c == '.' && s.peek_nth(1).is_some_and(|c| c.is_ascii_digit()) {
+                lex_number(&mut s);
+            } else {
+                lex_symbol(&mut s);
+            }
+        }
+        (s.finish(), state)
+    }
+}
+
+/// Check if the remaining text continues a key (`= value` or `.subkey`).
+fn is_key(rest: &str) -> bool {
+    let rest = rest.trim_start();
+    rest.starts_with('=') || rest.starts_with('.')
+}
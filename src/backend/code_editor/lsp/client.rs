@@ -0,0 +1,620 @@
+use std::collections::HashMap;
+use std::io::BufReader;
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use serde_json::{json, Value};
+use super::protocol::{self, CompletionItem, Diagnostic, Hover, Location, Position, TextEdit, WorkspaceEdit};
+use super::transport;
+use super::LspServerConfig;
+
+/// Something the server sent without being asked.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LspEvent {
+    /// New diagnostics for a document, replacing any previous ones.
+    Diagnostics { uri: String, diagnostics: Vec<Diagnostic> },
+    /// `window/logMessage` or `window/showMessage`, with the LSP message type (1 = error ... 4 = log).
+    Message { level: u8, message: String },
+    /// The server asked the editor to apply an edit (`workspace/applyEdit`).
+    ApplyEdit(WorkspaceEdit),
+    /// The server process closed its output.
+    Exited,
+}
+
+type PendingMap = Arc<Mutex<HashMap<u64, Sender<Result<Value, String>>>>>;
+
+/// A request that has been sent and whose response may not have arrived yet.
+pub struct PendingRequest {
+    pub id: u64,
+    receiver: Receiver<Result<Value, String>>,
+}
+
+impl PendingRequest {
+    /// Take the response if it has arrived, without blocking.
+    pub fn try_take(&self) -> Option<Result<Value, String>> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Block until the response arrives or the timeout passes.
+    pub fn wait(self, timeout: Duration) -> Result<Value, String> {
+        self.receiver
+            .recv_timeout(timeout)
+            .map_err(|_| format!("Language server did not answer request {} in time", self.id))?
+    }
+}
+
+/// A language server process spoken to over stdio.
+pub struct LspClient {
+    pub name: String,
+    process: Child,
+    writer: Arc<Mutex<ChildStdin>>,
+    pending: PendingMap,
+    events: Receiver<LspEvent>,
+    next_id: u64,
+    timeout: Duration,
+    /// Capabilities the server reported from `initialize`.
+    pub capabilities: Value,
+    diagnostics: HashMap<String, Vec<Diagnostic>>,
+    /// The `initialize` request while it is unanswered, with when it was sent.
+    initializing: Option<(PendingRequest, Instant)>,
+    /// Messages sent before the server was initialized, written once it is.
+    queued: Vec<Value>,
+}
+
+impl LspClient {
+    /// Launch the configured server and send `initialize` without waiting for the
+    /// answer. Requests and notifications are queued until [`Self::poll_initialized`]
+    /// sees the handshake finish.
+    pub fn spawn(config: &LspServerConfig, root: &Path) -> Result<Self, String> {
+        let mut process = Command::new(&config.command)
+            .args(&config.args)
+            .envs(&config.env)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Failed to start {}: {}", config.command, e))?;
+        let stdin = process.stdin.take().ok_or("Failed to open server stdin")?;
+        let stdout = process.stdout.take().ok_or("Failed to open server stdout")?;
+
+        let writer = Arc::new(Mutex::new(stdin));
+        let pending: PendingMap = Arc::new(Mutex::new(HashMap::new()));
+        let (event_tx, event_rx) = mpsc::channel();
+        Self::spawn_reader(BufReader::new(stdout), Arc::clone(&writer), Arc::clone(&pending), event_tx);
+
+        let mut client = Self {
+            name: config.command.clone(),
+            process,
+            writer,
+            pending,
+            events: event_rx,
+            next_id: 1,
+            timeout: Duration::from_millis(config.request_timeout_ms),
+            capabilities: Value::Null,
+            diagnostics: HashMap::new(),
+            initializing: None,
+            queued: Vec::new(),
+        };
+        client.initialize(root)?;
+        Ok(client)
+    }
+
+    /// Read messages from the server until it exits, routing responses to
+    /// their pending requests and everything else to the event channel.
+    fn spawn_reader(
+        mut reader: BufReader<std::process::ChildStdout>,
+        writer: Arc<Mutex<ChildStdin>>,
+        pending: PendingMap,
+        events: Sender<LspEvent>,
+    ) {
+        thread::spawn(move || {
+            while let Ok(Some(message)) = transport::read_message(&mut reader) {
+                let method = message["method"].as_str();
+                match (method, message.get("id")) {
+                    // Response to one of our requests
+                    (None, Some(id)) => {
+                        let Some(id) = id.as_u64() else { continue };
+                        if let Some(sender) = pending.lock().ok().and_then(|mut p| p.remove(&id)) {
+                            let result = match message.get("error") {
+                                Some(error) => Err(format!(
+                                    "{} (code {})",
+                                    error["message"].as_str().unwrap_or("Language server error"),
+                                    error["code"]
+                                )),
+                                None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+                            };
+                            let _ = sender.send(result);
+                        }
+                    }
+                    // Request from the server
+                    (Some(method), Some(id)) => {
+                        let reply = Self::answer_server_request(method, &message["params"], &events);
+                        let mut response = json!({ "jsonrpc": "2.0", "id": id });
+                        match reply {
+                            Ok(result) => response["result"] = result,
+                            Err(error) => response["error"] = error,
+                        }
+                        if let Ok(mut writer) = writer.lock() {
+                            let _ = transport::write_message(&mut *writer, &response);
+                        }
+                    }
+                    // Notification
+                    (Some(method), None) => {
+                        if let Some(event) = Self::notification_event(method, &message["params"]) {
+                            if events.send(event).is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    (None, None) => {}
+                }
+            }
+            // Fail anything still waiting so callers don't hang.
+            if let Ok(mut pending) = pending.lock() {
+                for (_, sender) in pending.drain() {
+                    let _ = sender.send(Err("Language server exited".to_string()));
+                }
+            }
+            let _ = events.send(LspEvent::Exited);
+        });
+    }
+
+    fn answer_server_request(method: &str, params: &Value, events: &Sender<LspEvent>) -> Result<Value, Value> {
+        match method {
+            "workspace/configuration" => {
+                let count = params["items"].as_array().map_or(0, |items| items.len());
+                Ok(Value::Array(vec![Value::Null; count]))
+            }
+            "window/workDoneProgress/create" | "client/registerCapability" | "client/unregisterCapability" => Ok(Value::Null),
+            "workspace/applyEdit" => {
+                let applied = events.send(LspEvent::ApplyEdit(WorkspaceEdit::from_value(&params["edit"]))).is_ok();
+                Ok(json!({ "applied": applied }))
+            }
+            _ => Err(json!({ "code": -32601, "message": format!("Method not found: {}", method) })),
+        }
+    }
+
+    fn notification_event(method: &str, params: &Value) -> Option<LspEvent> {
+        match method {
+            "textDocument/publishDiagnostics" => Some(LspEvent::Diagnostics {
+                uri: params["uri"].as_str()?.to_string(),
+                diagnostics: params["diagnostics"]
+                    .as_array()
+                    .map(|items| items.iter().filter_map(Diagnostic::from_value).collect())
+                    .unwrap_or_default(),
+            }),
+            "window/logMessage" | "window/showMessage" => Some(LspEvent::Message {
+                level: params["type"].as_u64().unwrap_or(4) as u8,
+                message: params["message"].as_str().unwrap_or_default().to_string(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn initialize(&mut self, root: &Path) -> Result<(), String> {
+        let root_uri = protocol::path_to_uri(&root.canonicalize().unwrap_or_else(|_| root.to_path_buf()));
+        let request = self.send_request("initialize", json!({
+            "processId": std::process::id(),
+            "clientInfo": { "name": "Jadio IDE" },
+            "rootUri": root_uri,
+            "workspaceFolders": [{ "uri": root_uri, "name": root.file_name().and_then(|n| n.to_str()).unwrap_or("workspace") }],
+            "capabilities": {
+                "general": { "positionEncodings": ["utf-16"] },
+                "workspace": { "applyEdit": true, "configuration": true, "workspaceFolders": true },
+                "textDocument": {
+                    "synchronization": { "didSave": true, "dynamicRegistration": false },
+                    "publishDiagnostics": { "relatedInformation": false },
+                    "hover": { "contentFormat": ["markdown", "plaintext"] },
+                    "completion": { "completionItem": { "snippetSupport": false, "documentationFormat": ["markdown", "plaintext"] } },
+                    "definition": { "linkSupport": true },
+                    "references": {},
+                    "rename": { "prepareSupport": false },
+                    "formatting": {}
+                }
+            }
+        }))?;
+        self.initializing = Some((request, Instant::now()));
+        Ok(())
+    }
+
+    /// Check on the `initialize` handshake; true once the server is ready. When it
+    /// has just finished, sends `initialized` and then the queued messages.
+    pub fn poll_initialized(&mut self) -> Result<bool, String> {
+        let Some((request, sent)) = &self.initializing else {
+            return Ok(true);
+        };
+        let result = match request.try_take() {
+            Some(result) => result,
+            None if sent.elapsed() < self.timeout => return Ok(false),
+            None => Err(format!("{} did not answer initialize in time", self.name)),
+        };
+        self.initializing = None;
+        self.capabilities = result?["capabilities"].clone();
+        self.write(&json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }))?;
+        for message in std::mem::take(&mut self.queued) {
+            self.write(&message)?;
+        }
+        Ok(true)
+    }
+
+    /// Block until the `initialize` handshake finishes.
+    fn wait_initialized(&mut self) -> Result<(), String> {
+        while !self.poll_initialized()? {
+            thread::sleep(Duration::from_millis(10));
+        }
+        Ok(())
+    }
+
+    /// Send a request without waiting for its response.
+    pub fn send_request(&mut self, method: &str, params: Value) -> Result<PendingRequest, String> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let (sender, receiver) = mpsc::channel();
+        self.pending.lock().map_err(|e| e.to_string())?.insert(id, sender);
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))?;
+        Ok(PendingRequest { id, receiver })
+    }
+
+    /// Send a request and block until the response arrives or the request times out.
+    pub fn request(&mut self, method: &str, params: Value) -> Result<Value, String> {
+        self.wait_initialized()?;
+        let pending = self.send_request(method, params)?;
+        let id = pending.id;
+        let result = pending.wait(self.timeout);
+        if result.is_err() {
+            if let Ok(mut pending) = self.pending.lock() {
+                pending.remove(&id);
+            }
+        }
+        result
+    }
+
+    /// Send a notification.
+    pub fn notify(&mut self, method: &str, params: Value) -> Result<(), String> {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    /// Write a message, or queue it while the server is still initializing.
+    /// The `initialize` request itself always goes straight out.
+    fn send(&mut self, message: Value) -> Result<(), String> {
+        if self.initializing.is_some() {
+            self.queued.push(message);
+            return Ok(());
+        }
+        self.write(&message)
+    }
+
+    fn write(&self, message: &Value) -> Result<(), String> {
+        let mut writer = self.writer.lock().map_err(|e| e.to_string())?;
+        transport::write_message(&mut *writer, message).map_err(|e| format!("Failed to write to {}: {}", self.name, e))
+    }
+
+    /// Drain events received since the last call, keeping the diagnostics cache up to date.
+    pub fn poll_events(&mut self) -> Vec<LspEvent> {
+        let events: Vec<LspEvent> = self.events.try_iter().collect();
+        for event in &events {
+            if let LspEvent::Diagnostics { uri, diagnostics } = event {
+                self.diagnostics.insert(uri.clone(), diagnostics.clone());
+            }
+        }
+        events
+    }
+
+    /// Get the latest diagnostics for a document.
+    pub fn diagnostics(&self, uri: &str) -> &[Diagnostic] {
+        self.diagnostics.get(uri).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Check if the server supports a provider, e.g. `"hoverProvider"`.
+    pub fn supports(&self, provider: &str) -> bool {
+        !matches!(&self.capabilities[provider], Value::Null | Value::Bool(false))
+    }
+
+    pub fn did_open(&mut self, uri: &str, language_id: &str, version: i32, text: &str) -> Result<(), String> {
+        self.notify("textDocument/didOpen", json!({
+            "textDocument": { "uri": uri, "languageId": language_id, "version": version, "text": text }
+        }))
+    }
+
+    /// Whether the server takes document changes as edits rather than the full text
+    /// (`TextDocumentSyncKind.Incremental`). Unknown until it is initialized.
+    pub fn syncs_incrementally(&self) -> bool {
+        let sync = &self.capabilities["textDocumentSync"];
+        sync.as_u64().or_else(|| sync["change"].as_u64()) == Some(2)
+    }
+
+    /// Send the edits made to a document, in the order they were made and each
+    /// ranged in the text before it. Servers that don't sync incrementally get
+    /// the full new text from `text` instead.
+    pub fn did_change(&mut self, uri: &str, version: i32, edits: &[TextEdit], text: impl FnOnce() -> String) -> Result<(), String> {
+        let changes: Vec<Value> = match self.syncs_incrementally() {
+            true => edits.iter().map(|edit| json!({ "range": edit.range, "text": edit.new_text })).collect(),
+            false => vec![json!({ "text": text() })],
+        };
+        self.notify("textDocument/didChange", json!({
+            "textDocument": { "uri": uri, "version": version },
+            "contentChanges": changes
+        }))
+    }
+
+    pub fn did_save(&mut self, uri: &str, text: &str) -> Result<(), String> {
+        let sync = &self.capabilities["textDocumentSync"];
+        let include_text = sync["save"]["includeText"].as_bool().unwrap_or(false);
+        let mut params = json!({ "textDocument": { "uri": uri } });
+        if include_text {
+            params["text"] = json!(text);
+        }
+        self.notify("textDocument/didSave", params)
+    }
+
+    pub fn did_close(&mut self, uri: &str) -> Result<(), String> {
+        self.diagnostics.remove(uri);
+        self.notify("textDocument/didClose", json!({ "textDocument": { "uri": uri } }))
+    }
+
+    pub fn hover(&mut self, uri: &str, position: Position) -> Result<Option<Hover>, String> {
+        let result = self.request("textDocument/hover", Self::position_params(uri, position))?;
+        Ok(Hover::from_value(&result))
+    }
+
+    pub fn completion(&mut self, uri: &str, position: Position) -> Result<Vec<CompletionItem>, String> {
+        let result = self.request("textDocument/completion", Self::position_params(uri, position))?;
+        Ok(CompletionItem::list_from_value(&result))
+    }
+
+    pub fn definition(&mut self, uri: &str, position: Position) -> Result<Vec<Location>, String> {
+        let result = self.request("textDocument/definition", Self::position_params(uri, position))?;
+        Ok(protocol::locations_from_value(&result))
+    }
+
+    pub fn references(&mut self, uri: &str, position: Position, include_declaration: bool) -> Result<Vec<Location>, String> {
+        let mut params = Self::position_params(uri, position);
+        params["context"] = json!({ "includeDeclaration": include_declaration });
+        let result = self.request("textDocument/references", params)?;
+        Ok(protocol::locations_from_value(&result))
+    }
+
+    pub fn rename(&mut self, uri: &str, position: Position, new_name: &str) -> Result<WorkspaceEdit, String> {
+        let mut params = Self::position_params(uri, position);
+        params["newName"] = json!(new_name);
+        let result = self.request("textDocument/rename", params)?;
+        Ok(WorkspaceEdit::from_value(&result))
+    }
+
+    pub fn formatting(&mut self, uri: &str, tab_size: usize, insert_spaces: bool) -> Result<Vec<TextEdit>, String> {
+        let result = self.request("textDocument/formatting", json!({
+            "textDocument": { "uri": uri },
+            "options": { "tabSize": tab_size, "insertSpaces": insert_spaces }
+        }))?;
+        Ok(protocol::text_edits_from_value(&result))
+    }
+
+    /// Parameters for a request about a position in a document.
+    pub fn position_params(uri: &str, position: Position) -> Value {
+        json!({ "textDocument": { "uri": uri }, "position": position })
+    }
+
+    /// Check if the server process is still running.
+    pub fn is_running(&mut self) -> bool {
+        matches!(self.process.try_wait(), Ok(None))
+    }
+
+    /// Ask the server to shut down and exit, killing it if it does not.
+    pub fn shutdown(&mut self) -> Result<(), String> {
+        let result = self.request("shutdown", Value::Null).map(|_| ());
+        let _ = self.notify("exit", Value::Null);
+        for _ in 0..20 {
+            if !self.is_running() {
+                return result;
+            }
+            thread::sleep(Duration::from_millis(25));
+        }
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        result
+    }
+}
+
+impl Drop for LspClient {
+    fn drop(&mut self) {
+        if self.is_running() {
+            let _ = self.process.kill();
+            let _ = self.process.wait();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{self, Write};
+    use std::time::Instant;
+
+    const FAKE_SERVER_ENV: &str = "JADIO_FAKE_LSP_SERVER";
+
+    /// Run this test binary as a scripted language server.
+    fn fake_server_config() -> LspServerConfig {
+        LspServerConfig {
+            language: "rust".to_string(),
+            command: std::env::current_exe().unwrap().to_string_lossy().to_string(),
+            args: vec!["fake_lsp_server_main".to_string(), "--nocapture".to_string()],
+            env: HashMap::from([(FAKE_SERVER_ENV.to_string(), "1".to_string())]),
+            request_timeout_ms: 5000,
+        }
+    }
+
+    /// Entry point of the fake server. Does nothing in a normal test run.
+    #[test]
+    fn fake_lsp_server_main() {
+        if std::env::var_os(FAKE_SERVER_ENV).is_none() {
+            return;
+        }
+        run_fake_server();
+        // Exit before the test harness prints its summary onto our stdout.
+        std::process::exit(0);
+    }
+
+    fn run_fake_server() {
+        let mut stdin = io::BufReader::new(io::stdin());
+        let mut stdout = io::stdout();
+        // The harness has already printed "test ... " without a newline; end that banner line.
+        writeln!(stdout).unwrap();
+        let mut documents: HashMap<String, (i64, String)> = HashMap::new();
+        let mut send = |message: Value| {
+            transport::write_message(&mut stdout, &message).unwrap();
+            stdout.flush().unwrap();
+        };
+
+        while let Ok(Some(message)) = transport::read_message(&mut stdin) {
+            let params = &message["params"];
+            let uri = params["textDocument"]["uri"].as_str().unwrap_or_default().to_string();
+            let reply = |result: Value| json!({ "jsonrpc": "2.0", "id": message["id"], "result": result });
+            match message["method"].as_str().unwrap_or_default() {
+                "initialize" => send(reply(json!({ "capabilities": {
+                    "textDocumentSync": { "openClose": true, "change": 2, "save": { "includeText": true } },
+                    "hoverProvider": true,
+                    "completionProvider": {},
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "renameProvider": true,
+                    "documentFormattingProvider": true
+                }}))),
+                "initialized" => {
+                    // Exercise a server-to-client request.
+                    send(json!({ "jsonrpc": "2.0", "id": "progress-1", "method": "window/workDoneProgress/create", "params": { "token": "t" } }));
+                }
+                "textDocument/didOpen" | "textDocument/didChange" => {
+                    let version = params["textDocument"]["version"].as_i64().unwrap_or(0);
+                    let text = match params["contentChanges"].as_array() {
+                        // Incremental changes, applied in order. Test documents are ASCII, so columns are bytes.
+                        Some(changes) => changes.iter().fold(documents.get(&uri).map(|(_, text)| text.clone()).unwrap_or_default(), |mut text, change| {
+                            let offset = |position: &Value| {
+                                let line = position["line"].as_u64().unwrap() as usize;
+                                text.split_inclusive('\n').take(line).map(str::len).sum::<usize>() + position["character"].as_u64().unwrap() as usize
+                            };
+                            let range = offset(&change["range"]["start"])..offset(&change["range"]["end"]);
+                            text.replace_range(range, change["text"].as_str().unwrap());
+                            text
+                        }),
+                        None => params["textDocument"]["text"].as_str().unwrap().to_string(),
+                    };
+                    let diagnostics: Vec<Value> = text
+                        .lines()
+                        .enumerate()
+                        .filter(|(_, line)| line.contains("TODO"))
+                        .map(|(i, line)| json!({
+                            "range": { "start": { "line": i, "character": 0 }, "end": { "line": i, "character": line.len() } },
+                            "severity": 2,
+                            "source": "fake",
+                            "message": "TODO found"
+                        }))
+                        .collect();
+                    documents.insert(uri.clone(), (version, text));
+                    send(json!({ "jsonrpc": "2.0", "method": "textDocument/publishDiagnostics", "params": { "uri": uri, "diagnostics": diagnostics } }));
+                }
+                "textDocument/hover" => {
+                    let (version, text) = documents.get(&uri).cloned().unwrap_or_default();
+                    let line = text.lines().nth(params["position"]["line"].as_u64().unwrap_or(0) as usize).unwrap_or_default();
+                    send(reply(json!({ "contents": { "kind": "markdown", "value": format!("version {}: {}", version, line) } })));
+                }
+                "textDocument/completion" => send(reply(json!({ "isIncomplete": false, "items": [
+                    { "label": "println!", "kind": 3, "insertText": "println!(\"\")" },
+                    { "label": "print!", "kind": 3 }
+                ]}))),
+                "textDocument/definition" => send(reply(json!({ "uri": uri, "range": { "start": { "line": 0, "character": 3 }, "end": { "line": 0, "character": 7 } } }))),
+                "textDocument/references" => send(reply(json!([
+                    { "uri": uri, "range": { "start": { "line": 0, "character": 3 }, "end": { "line": 0, "character": 7 } } },
+                    { "targetUri": uri, "targetRange": {}, "targetSelectionRange": { "start": { "line": 2, "character": 4 }, "end": { "line": 2, "character": 8 } } }
+                ]))),
+                "textDocument/rename" => send(reply(json!({ "changes": { uri.clone(): [
+                    { "range": { "start": { "line": 0, "character": 3 }, "end": { "line": 0, "character": 7 } }, "newText": params["newName"] }
+                ]}}))),
+                "textDocument/formatting" => {
+                    // Strip trailing whitespace from every line.
+                    let text = documents.get(&uri).map(|(_, text)| text.clone()).unwrap_or_default();
+                    let edits: Vec<Value> = text
+                        .lines()
+                        .enumerate()
+                        .filter(|(_, line)| line.len() != line.trim_end().len())
+                        .map(|(i, line)| json!({
+                            "range": { "start": { "line": i, "character": line.trim_end().len() }, "end": { "line": i, "character": line.len() } },
+                            "newText": ""
+                        }))
+                        .collect();
+                    send(reply(Value::Array(edits)));
+                }
+                "shutdown" => send(reply(Value::Null)),
+                "exit" => return,
+                _ => {}
+            }
+        }
+    }
+
+    fn wait_for_diagnostics(client: &mut LspClient, uri: &str, count: usize) -> Vec<Diagnostic> {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            client.poll_initialized().unwrap();
+            client.poll_events();
+            if client.diagnostics.contains_key(uri) && client.diagnostics(uri).len() == count {
+                return client.diagnostics(uri).to_vec();
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("no diagnostics for {}", uri);
+    }
+
+    #[test]
+    fn test_client_against_fake_server() {
+        let mut client = LspClient::spawn(&fake_server_config(), Path::new(".")).unwrap();
+        // Sent while the handshake is still going, so queued until it finishes.
+        let uri = "file:///project/src/main.rs";
+        client.did_open(uri, "rust", 1, "fn main() {}  \n// TODO: greet\n    main();\n").unwrap();
+        let diagnostics = wait_for_diagnostics(&mut client, uri, 1);
+        assert_eq!(diagnostics[0].range.start.line, 1);
+        assert_eq!(diagnostics[0].severity, protocol::DiagnosticSeverity::Warning);
+        assert!(client.supports("renameProvider"));
+        assert!(!client.supports("codeActionProvider"));
+        assert!(client.syncs_incrementally());
+
+        let edit = |line, start, end, text: &str| TextEdit {
+            range: protocol::Range { start: Position { line, character: start }, end: Position { line, character: end } },
+            new_text: text.to_string(),
+        };
+        let edits = [edit(1, 0, 14, ""), edit(2, 4, 8, "start")];
+        client.did_change(uri, 2, &edits, || unreachable!("the server syncs incrementally")).unwrap();
+        wait_for_diagnostics(&mut client, uri, 0);
+        client.did_save(uri, "fn main() {}  \n\n    start();\n").unwrap();
+
+        let hover = client.hover(uri, Position { line: 0, character: 3 }).unwrap().unwrap();
+        assert_eq!(hover.contents, "version 2: fn main() {}  ");
+        let hover = client.hover(uri, Position { line: 2, character: 4 }).unwrap().unwrap();
+        assert_eq!(hover.contents, "version 2:     start();");
+
+        let completions = client.completion(uri, Position { line: 2, character: 4 }).unwrap();
+        assert_eq!(completions[0].insert_text, "println!(\"\")");
+        assert_eq!(completions[1].insert_text, "print!");
+
+        let definition = client.definition(uri, Position { line: 2, character: 5 }).unwrap();
+        assert_eq!(definition, vec![Location { uri: uri.to_string(), range: protocol::Range {
+            start: Position { line: 0, character: 3 },
+            end: Position { line: 0, character: 7 },
+        }}]);
+        let references = client.references(uri, Position { line: 0, character: 3 }, true).unwrap();
+        assert_eq!(references.len(), 2);
+        assert_eq!(references[1].range.start.line, 2);
+
+        let rename = client.rename(uri, Position { line: 0, character: 3 }, "start").unwrap();
+        assert_eq!(rename.changes[uri][0].new_text, "start");
+
+        let formatting = client.formatting(uri, 4, true).unwrap();
+        assert_eq!(formatting.len(), 1);
+        assert_eq!(formatting[0].range.start, Position { line: 0, character: 12 });
+
+        client.shutdown().unwrap();
+        assert!(!client.is_running());
+    }
+}
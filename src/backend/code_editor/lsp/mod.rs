@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use crate::backend::code_editor::code_editor_logic::EditorTab;
+
+pub mod client;
+pub mod protocol;
+pub mod transport;
+
+use client::{LspClient, LspEvent, PendingRequest};
+use protocol::{CompletionItem, Diagnostic, Hover, Location, TextEdit, WorkspaceEdit};
+
+/// How to launch the language server for one language.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LspServerConfig {
+    /// Editor language name, e.g. `"rust"`.
+    pub language: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default = "default_request_timeout_ms")]
+    pub request_timeout_ms: u64,
+}
+
+fn default_request_timeout_ms() -> u64 {
+    5000
+}
+
+impl LspServerConfig {
+    pub fn new(language: &str, command: &str, args: &[&str]) -> Self {
+        Self {
+            language: language.to_string(),
+            command: command.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            env: HashMap::new(),
+            request_timeout_ms: default_request_timeout_ms(),
+        }
+    }
+
+    /// Default servers for the built-in languages. They must be installed and on `PATH`.
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self::new("rust", "rust-analyzer", &[]),
+            Self::new("python", "pyright-langserver", &["--stdio"]),
+            Self::new("javascript", "typescript-language-server", &["--stdio"]),
+            Self::new("typescript", "typescript-language-server", &["--stdio"]),
+            Self::new("html", "vscode-html-language-server", &["--stdio"]),
+            Self::new("css", "vscode-css-language-server", &["--stdio"]),
+            Self::new("json", "vscode-json-language-server", &["--stdio"]),
+            Self::new("toml", "taplo", &["lsp", "stdio"]),
+            Self::new("markdown", "marksman", &["server"]),
+        ]
+    }
+}
+
+/// Starts one language server per language on demand and keeps editor tabs in sync with them.
+pub struct LspManager {
+    root: PathBuf,
+    configs: HashMap<String, LspServerConfig>,
+    clients: HashMap<String, LspClient>,
+    /// Languages whose server failed to start, with the error, so it is not retried every frame.
+    failed: HashMap<String, String>,
+    /// The last error each language's server reported or ran into, for the status line.
+    errors: HashMap<String, String>,
+    /// Document versions by URI, bumped on every change.
+    versions: HashMap<String, i32>,
+}
+
+impl LspManager {
+    /// Create a manager for a workspace root with the default server configs.
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            configs: LspServerConfig::defaults().into_iter().map(|c| (c.language.clone(), c)).collect(),
+            clients: HashMap::new(),
+            failed: HashMap::new(),
+            errors: HashMap::new(),
+            versions: HashMap::new(),
+        }
+    }
+
+    /// Add or replace the server config for a language. A running server is restarted on next use.
+    pub fn set_config(&mut self, config: LspServerConfig) {
+        if let Some(mut client) = self.clients.remove(&config.language) {
+            let _ = client.shutdown();
+        }
+        self.failed.remove(&config.language);
+        self.errors.remove(&config.language);
+        self.configs.insert(config.language.clone(), config);
+    }
+
+    /// Get the error from the last failed start of a language's server.
+    pub fn start_error(&self, language: &str) -> Option<&str> {
+        self.failed.get(language).map(String::as_str)
+    }
+
+    /// Get the last error from a language's server: a failed start, an error it
+    /// reported, or its exit.
+    pub fn error(&self, language: &str) -> Option<&str> {
+        self.start_error(language).or_else(|| self.errors.get(language).map(String::as_str))
+    }
+
+    /// Get the client for a language, starting its server if needed. A server that
+    /// is starting takes messages straight away and sends them once it is initialized.
+    pub fn client(&mut self, language: &str) -> Result<&mut LspClient, String> {
+        if let Some(error) = self.failed.get(language) {
+            return Err(error.clone());
+        }
+        if !self.clients.contains_key(language) {
+            let config = self
+                .configs
+                .get(language)
+                .ok_or_else(|| format!("No language server configured for {}", language))?;
+            match LspClient::spawn(config, &self.root) {
+                Ok(client) => {
+                    self.clients.insert(language.to_string(), client);
+                }
+                Err(e) => {
+                    self.failed.insert(language.to_string(), e.clone());
+                    return Err(e);
+                }
+            }
+        }
+        self.clients.get_mut(language).ok_or_else(|| "Language server not running".to_string())
+    }
+
+    fn tab_uri(tab: &EditorTab) -> Result<String, String> {
+        tab.uri().ok_or_else(|| "Untitled tabs are not sent to language servers".to_string())
+    }
+
+    /// Tell the server a tab was opened.
+    pub fn did_open(&mut self, tab: &EditorTab) -> Result<(), String> {
+        let uri = Self::tab_uri(tab)?;
+        self.versions.insert(uri.clone(), 1);
+        self.client(tab.language)?.did_open(&uri, tab.language, 1, &tab.content())
+    }
+
+    /// Send the edits made to a tab since the last call, taking them from the tab.
+    pub fn did_change(&mut self, tab: &mut EditorTab) -> Result<(), String> {
+        let edits = tab.take_content_changes();
+        let uri = Self::tab_uri(tab)?;
+        if edits.is_empty() {
+            return Ok(());
+        }
+        let version = self.versions.entry(uri.clone()).or_insert(0);
+        *version += 1;
+        let version = *version;
+        self.client(tab.language)?.did_change(&uri, version, &edits, || tab.content())
+    }
+
+    pub fn did_save(&mut self, tab: &EditorTab) -> Result<(), String> {
+        let uri = Self::tab_uri(tab)?;
+        self.client(tab.language)?.did_save(&uri, &tab.content())
+    }
+
+    pub fn did_close(&mut self, tab: &EditorTab) -> Result<(), String> {
+        let uri = Self::tab_uri(tab)?;
+        self.versions.remove(&uri);
+        self.client(tab.language)?.did_close(&uri)
+    }
+
+    /// Send a request about a byte offset in a tab (e.g. `"textDocument/hover"`) without waiting for the reply.
+    pub fn send_position_request(&mut self, tab: &EditorTab, method: &str, offset: usize) -> Result<PendingRequest, String> {
+        let (uri, position) = (Self::tab_uri(tab)?, protocol::offset_to_position(&tab.buffer, offset));
+        self.client(tab.language)?.send_request(method, LspClient::position_params(&uri, position))
+    }
+
+    pub fn hover(&mut self, tab: &EditorTab, offset: usize) -> Result<Option<Hover>, String> {
+        let (uri, position) = (Self::tab_uri(tab)?, protocol::offset_to_position(&tab.buffer, offset));
+        self.client(tab.language)?.hover(&uri, position)
+    }
+
+    pub fn completion(&mut self, tab: &EditorTab, offset: usize) -> Result<Vec<CompletionItem>, String> {
+        let (uri, position) = (Self::tab_uri(tab)?, protocol::offset_to_position(&tab.buffer, offset));
+        self.client(tab.language)?.completion(&uri, position)
+    }
+
+    pub fn definition(&mut self, tab: &EditorTab, offset: usize) -> Result<Vec<Location>, String> {
+        let (uri, position) = (Self::tab_uri(tab)?, protocol::offset_to_position(&tab.buffer, offset));
+        self.client(tab.language)?.definition(&uri, position)
+    }
+
+    pub fn references(&mut self, tab: &EditorTab, offset: usize) -> Result<Vec<Location>, String> {
+        let (uri, position) = (Self::tab_uri(tab)?, protocol::offset_to_position(&tab.buffer, offset));
+        self.client(tab.language)?.references(&uri, position, true)
+    }
+
+    pub fn rename(&mut self, tab: &EditorTab, offset: usize, new_name: &str) -> Result<WorkspaceEdit, String> {
+        let (uri, position) = (Self::tab_uri(tab)?, protocol::offset_to_position(&tab.buffer, offset));
+        self.client(tab.language)?.rename(&uri, position, new_name)
+    }
+
+    pub fn formatting(&mut self, tab: &EditorTab, tab_size: usize, insert_spaces: bool) -> Result<Vec<TextEdit>, String> {
+        let uri = Self::tab_uri(tab)?;
+        self.client(tab.language)?.formatting(&uri, tab_size, insert_spaces)
+    }
+
+    /// Get the latest diagnostics for a tab.
+    pub fn diagnostics(&self, tab: &EditorTab) -> Vec<Diagnostic> {
+        let Ok(uri) = Self::tab_uri(tab) else {
+            return Vec::new();
+        };
+        self.clients
+            .get(tab.language)
+            .map(|client| client.diagnostics(&uri).to_vec())
+            .unwrap_or_default()
+    }
+
+    /// Finish the handshakes of starting servers and drain events from every running one.
+    /// Servers that exited are dropped so they restart on next use; servers that failed
+    /// to initialize are not retried.
+    pub fn poll_events(&mut self) -> Vec<LspEvent> {
+        let mut events = Vec::new();
+        let mut stopped = Vec::new();
+        for (language, client) in &mut self.clients {
+            if let Err(e) = client.poll_initialized() {
+                self.failed.insert(language.clone(), e);
+                stopped.push(language.clone());
+                continue;
+            }
+            let client_events = client.poll_events();
+            for event in &client_events {
+                match event {
+                    LspEvent::Message { level: 1, message } => {
+                        self.errors.insert(language.clone(), message.clone());
+                    }
+                    LspEvent::Exited => {
+                        self.errors.insert(language.clone(), format!("{} exited", client.name));
+                        stopped.push(language.clone());
+                    }
+                    _ => {}
+                }
+            }
+            events.extend(client_events);
+        }
+        for language in stopped {
+            self.clients.remove(&language);
+        }
+        events
+    }
+
+    /// Shut down every running server.
+    pub fn shutdown_all(&mut self) {
+        for (_, mut client) in self.clients.drain() {
+            let _ = client.shutdown();
+        }
+    }
+}
+
+impl Drop for LspManager {
+    fn drop(&mut self) {
+        self.shutdown_all();
+    }
+}
@@ -0,0 +1,287 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use crate::backend::code_editor::text_buffer::TextBuffer;
+
+/// A position in a document: zero-based line and UTF-16 code unit column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Position {
+    pub line: u32,
+    pub character: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Location {
+    pub uri: String,
+    pub range: Range,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+/// A diagnostic published by the server for a document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub range: Range,
+    pub severity: DiagnosticSeverity,
+    pub message: String,
+    pub source: Option<String>,
+    pub code: Option<String>,
+}
+
+/// A single text replacement, as returned by formatting and rename.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextEdit {
+    pub range: Range,
+    pub new_text: String,
+}
+
+/// Edits to apply across documents, keyed by URI.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WorkspaceEdit {
+    pub changes: HashMap<String, Vec<TextEdit>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hover {
+    /// Hover text, as Markdown or plain text.
+    pub contents: String,
+    pub range: Option<Range>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompletionItem {
+    pub label: String,
+    /// LSP `CompletionItemKind` number (3 = function, 6 = variable, ...).
+    pub kind: Option<u32>,
+    pub detail: Option<String>,
+    pub documentation: Option<String>,
+    /// Text to insert, falling back to the label.
+    pub insert_text: String,
+    /// Range to replace, if the server gave one.
+    pub replace_range: Option<Range>,
+}
+
+impl Diagnostic {
+    pub fn from_value(value: &Value) -> Option<Self> {
+        let severity = match value["severity"].as_u64() {
+            Some(2) => DiagnosticSeverity::Warning,
+            Some(3) => DiagnosticSeverity::Information,
+            Some(4) => DiagnosticSeverity::Hint,
+            _ => DiagnosticSeverity::Error,
+        };
+        let code = match &value["code"] {
+            Value::String(code) => Some(code.clone()),
+            Value::Number(code) => Some(code.to_string()),
+            _ => None,
+        };
+        Some(Self {
+            range: serde_json::from_value(value["range"].clone()).ok()?,
+            severity,
+            message: value["message"].as_str()?.to_string(),
+            source: value["source"].as_str().map(str::to_string),
+            code,
+        })
+    }
+}
+
+impl Hover {
+    /// Parse a hover result; `contents` may be a string, a `MarkedString`, an array of them, or `MarkupContent`.
+    pub fn from_value(value: &Value) -> Option<Self> {
+        if value.is_null() {
+            return None;
+        }
+        let contents = markup_to_string(&value["contents"]);
+        Some(Self {
+            contents,
+            range: serde_json::from_value(value["range"].clone()).ok(),
+        })
+    }
+}
+
+impl CompletionItem {
+    pub fn from_value(value: &Value) -> Option<Self> {
+        let label = value["label"].as_str()?.to_string();
+        // textEdit is either a TextEdit or an InsertReplaceEdit.
+        let edit = &value["textEdit"];
+        let replace_range = serde_json::from_value(edit["range"].clone())
+            .or_else(|_| serde_json::from_value(edit["replace"].clone()))
+            .ok();
+        let insert_text = edit["newText"]
+            .as_str()
+            .or_else(|| value["insertText"].as_str())
+            .unwrap_or(&label)
+            .to_string();
+        Some(Self {
+            kind: value["kind"].as_u64().map(|k| k as u32),
+            detail: value["detail"].as_str().map(str::to_string),
+            documentation: Some(markup_to_string(&value["documentation"])).filter(|d| !d.is_empty()),
+            insert_text,
+            replace_range,
+            label,
+        })
+    }
+
+    /// Parse a completion result: either an item array or a `CompletionList`.
+    pub fn list_from_value(value: &Value) -> Vec<Self> {
+        let items = value.as_array().or_else(|| value["items"].as_array());
+        items.map(|items| items.iter().filter_map(Self::from_value).collect()).unwrap_or_default()
+    }
+}
+
+impl WorkspaceEdit {
+    /// Parse a `WorkspaceEdit`, from either `changes` or `documentChanges`.
+    pub fn from_value(value: &Value) -> Self {
+        let mut changes: HashMap<String, Vec<TextEdit>> = HashMap::new();
+        if let Some(map) = value["changes"].as_object() {
+            for (uri, edits) in map {
+                changes.entry(uri.clone()).or_default().extend(text_edits_from_value(edits));
+            }
+        }
+        if let Some(document_changes) = value["documentChanges"].as_array() {
+            // Only text document edits are supported; create/rename/delete file operations are skipped.
+            for change in document_changes {
+                if let Some(uri) = change["textDocument"]["uri"].as_str() {
+                    changes.entry(uri.to_string()).or_default().extend(text_edits_from_value(&change["edits"]));
+                }
+            }
+        }
+        Self { changes }
+    }
+}
+
+/// Parse an array of `TextEdit`s, skipping malformed entries.
+pub fn text_edits_from_value(value: &Value) -> Vec<TextEdit> {
+    value
+        .as_array()
+        .map(|edits| edits.iter().filter_map(|e| serde_json::from_value(e.clone()).ok()).collect())
+        .unwrap_or_default()
+}
+
+/// Parse a definition/references result: a `Location`, an array of them, or `LocationLink`s.
+pub fn locations_from_value(value: &Value) -> Vec<Location> {
+    let items = match value {
+        Value::Array(items) => items.clone(),
+        Value::Object(_) => vec![value.clone()],
+        _ => Vec::new(),
+    };
+    items
+        .iter()
+        .filter_map(|item| {
+            serde_json::from_value(item.clone()).ok().or_else(|| {
+                Some(Location {
+                    uri: item["targetUri"].as_str()?.to_string(),
+                    range: serde_json::from_value(item["targetSelectionRange"].clone()).ok()?,
+                })
+            })
+        })
+        .collect()
+}
+
+fn markup_to_string(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts.iter().map(markup_to_string).collect::<Vec<_>>().join("\n\n"),
+        Value::Object(markup) => match (markup.get("language").and_then(|l| l.as_str()), markup.get("value")) {
+            (Some(language), Some(Value::String(code))) => format!("```{}\n{}\n```", language, code),
+            (_, Some(Value::String(text))) => text.clone(),
+            _ => String::new(),
+        },
+        _ => String::new(),
+    }
+}
+
+/// Convert a byte offset into an LSP position (UTF-16 columns).
+pub fn offset_to_position(buffer: &TextBuffer, offset: usize) -> Position {
+    let (line, col) = buffer.offset_to_line_col(offset);
+    let text = buffer.line(line);
+    let col = col.min(text.len());
+    let character = text[..floor_char_boundary(&text, col)].encode_utf16().count();
+    Position { line: line as u32, character: character as u32 }
+}
+
+/// Convert an LSP position into a byte offset, clamping to the document.
+pub fn position_to_offset(buffer: &TextBuffer, position: Position) -> usize {
+    let line = position.line as usize;
+    if line >= buffer.line_count() {
+        return buffer.len();
+    }
+    let text = buffer.line(line);
+    let mut units = 0;
+    let mut col = text.len();
+    for (i, c) in text.char_indices() {
+        if units >= position.character as usize {
+            col = i;
+            break;
+        }
+        units += c.len_utf16();
+    }
+    buffer.line_col_to_offset(line, col)
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// Convert a file path to a `file://` URI.
+pub fn path_to_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from("file://");
+    if !path.starts_with('/') {
+        // Windows drive paths: file:///C:/...
+        uri.push('/');
+    }
+    for c in path.chars() {
+        match c {
+            ' ' => uri.push_str("%20"),
+            '#' => uri.push_str("%23"),
+            '%' => uri.push_str("%25"),
+            '?' => uri.push_str("%3F"),
+            _ => uri.push(c),
+        }
+    }
+    uri
+}
+
+/// Convert a `file://` URI back to a path.
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let mut decoded = Vec::with_capacity(path.len());
+    let bytes = path.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| path.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()))
+            .flatten();
+        if let Some(byte) = escaped {
+            decoded.push(byte);
+            i += 3;
+            continue;
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    let path = String::from_utf8(decoded).ok()?;
+    // file:///C:/x -> C:/x on Windows
+    let path = match path.as_bytes() {
+        [b'/', _, b':', ..] => path[1..].to_string(),
+        _ => path,
+    };
+    Some(PathBuf::from(path))
+}
@@ -0,0 +1,62 @@
+use std::io::{self, BufRead, Write};
+
+/// Read one `Content-Length` framed JSON-RPC message.
+///
+/// Returns `Ok(None)` at end of stream. Lines before the blank line that are
+/// not `Name: value` headers are skipped, so a banner printed by a server
+/// before its first message does not desynchronise the stream.
+pub fn read_message<R: BufRead>(reader: &mut R) -> io::Result<Option<serde_json::Value>> {
+    let mut content_length: Option<usize> = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            if content_length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                let length = value.trim().parse().map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid Content-Length: {}", value.trim()))
+                })?;
+                content_length = Some(length);
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length.unwrap_or(0)];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Write one JSON-RPC message with a `Content-Length` header.
+pub fn write_message<W: Write>(writer: &mut W, message: &serde_json::Value) -> io::Result<()> {
+    let body = serde_json::to_string(message)?;
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_skips_banner_lines() {
+        let mut stream = b"\nserver v1.0 starting\n".to_vec();
+        write_message(&mut stream, &serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": "é"})).unwrap();
+        write_message(&mut stream, &serde_json::json!({"jsonrpc": "2.0", "method": "exit"})).unwrap();
+
+        let mut reader = io::BufReader::new(stream.as_slice());
+        assert_eq!(read_message(&mut reader).unwrap().unwrap()["result"], "é");
+        assert_eq!(read_message(&mut reader).unwrap().unwrap()["method"], "exit");
+        assert!(read_message(&mut reader).unwrap().is_none());
+    }
+}
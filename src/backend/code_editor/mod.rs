@@ -1,4 +1,10 @@
 pub mod backup;
 pub mod code_editor_logic;
+pub mod lexers;
+pub mod lsp;
 pub mod saving;
+pub mod selection;
 pub mod syntax_highlighting;
+pub mod text_buffer;
+pub mod theme;
+pub mod undo_history;
@@ -0,0 +1,185 @@
+use std::ops::Range;
+
+/// A caret with an optional selection, as byte offsets into the buffer.
+///
+/// `anchor` is where the selection started and `head` is where the caret is.
+/// When they are equal the selection is empty and only the caret is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Selection {
+    pub anchor: usize,
+    pub head: usize,
+}
+
+impl Selection {
+    /// Create an empty selection (a plain caret) at the given offset.
+    pub fn caret(offset: usize) -> Self {
+        Self { anchor: offset, head: offset }
+    }
+
+    /// Create a selection from anchor to head.
+    pub fn new(anchor: usize, head: usize) -> Self {
+        Self { anchor, head }
+    }
+
+    /// The selected byte range, ordered from start to end.
+    pub fn range(&self) -> Range<usize> {
+        self.anchor.min(self.head)..self.anchor.max(self.head)
+    }
+
+    /// Check if the selection is only a caret.
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.head
+    }
+}
+
+/// Sort selections by position and merge any that overlap or touch.
+pub fn normalize_selections(selections: &mut Vec<Selection>) {
+    selections.sort_by_key(|s| s.range().start);
+    let mut merged: Vec<Selection> = Vec::with_capacity(selections.len());
+    for selection in selections.drain(..) {
+        if let Some(last) = merged.last_mut() {
+            let (prev, next) = (last.range(), selection.range());
+            // Carets touching a selection (or each other) collapse into it.
+            let touches = next.start == prev.end && (last.is_empty() || selection.is_empty());
+            if next.start < prev.end || touches {
+                let start = prev.start.min(next.start);
+                let end = prev.end.max(next.end);
+                *last = if last.anchor <= last.head { Selection::new(start, end) } else { Selection::new(end, start) };
+                continue;
+            }
+        }
+        merged.push(selection);
+    }
+    *selections = merged;
+}
+
+/// Byte offset at which each line of `text` starts.
+fn line_starts(text: &str) -> Vec<usize> {
+    std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect()
+}
+
+/// Byte range of the identifier-like word around an offset, if any.
+pub fn word_at(text: &str, offset: usize) -> Option<Range<usize>> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let offset = offset.min(text.len());
+    let start = text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_word(c))
+        .last()
+        .map_or(offset, |(i, _)| i);
+    let end = text[offset..]
+        .char_indices()
+        .find(|&(_, c)| !is_word(c))
+        .map_or(text.len(), |(i, _)| offset + i);
+    (start < end).then_some(start..end)
+}
+
+/// Add a selection on the next occurrence of the primary selection's text.
+///
+/// If the last selection is an empty caret, it is first expanded to the word
+/// under it, matching the usual "add next occurrence" behaviour. The search
+/// wraps around the end of the text and skips occurrences that are already
+/// selected. Returns false if nothing was added.
+pub fn add_next_occurrence(text: &str, selections: &mut Vec<Selection>) -> bool {
+    let Some(last) = selections.last().copied() else {
+        return false;
+    };
+    if last.is_empty() {
+        return match word_at(text, last.head) {
+            Some(word) => {
+                let idx = selections.len() - 1;
+                selections[idx] = Selection::new(word.start, word.end);
+                true
+            }
+            None => false,
+        };
+    }
+
+    let needle = &text[last.range()];
+    let search_from = selections.iter().map(|s| s.range().end).max().unwrap_or(0);
+    let candidates = text[search_from..]
+        .match_indices(needle)
+        .map(|(i, _)| search_from + i)
+        .chain(text[..search_from].match_indices(needle).map(|(i, _)| i));
+    for start in candidates {
+        let range = start..start + needle.len();
+        let taken = selections.iter().any(|s| {
+            let r = s.range();
+            r.start < range.end && range.start < r.end
+        });
+        if !taken {
+            selections.push(Selection::new(range.start, range.end));
+            return true;
+        }
+    }
+    false
+}
+
+/// Build a column (box) selection between two offsets.
+///
+/// Each line between the anchor and head gets one selection spanning the same
+/// character columns, clamped to the length of that line.
+pub fn column_selections(text: &str, anchor: usize, head: usize) -> Vec<Selection> {
+    let starts = line_starts(text);
+    let line_of = |offset: usize| starts.partition_point(|&s| s <= offset) - 1;
+    let line_text = |line: usize| {
+        let start = starts[line];
+        let end = starts.get(line + 1).map_or(text.len(), |&next| next - 1);
+        &text[start..end]
+    };
+    let char_col = |offset: usize| {
+        let line = line_of(offset);
+        text[starts[line]..offset].chars().count()
+    };
+    let offset_at = |line: usize, col: usize| {
+        let line_str = line_text(line);
+        starts[line] + line_str.char_indices().nth(col).map_or(line_str.len(), |(i, _)| i)
+    };
+
+    let anchor = anchor.min(text.len());
+    let head = head.min(text.len());
+    let (anchor_line, head_line) = (line_of(anchor), line_of(head));
+    let (anchor_col, head_col) = (char_col(anchor), char_col(head));
+    let lines: Vec<usize> = if anchor_line <= head_line {
+        (anchor_line..=head_line).collect()
+    } else {
+        (head_line..=anchor_line).rev().collect()
+    };
+    lines
+        .into_iter()
+        .map(|line| Selection::new(offset_at(line, anchor_col), offset_at(line, head_col)))
+        .collect()
+}
+
+/// Plan an edit that replaces every selection at once.
+///
+/// `replacement` is called with the index of each selection (in position order)
+/// and its range, and returns the replacement range and text. The returned
+/// edits are ordered from the end of the text to the start, so applying them in
+/// order keeps earlier offsets valid. The returned selections are carets placed
+/// after each inserted text, with offsets shifted by the preceding edits.
+pub fn plan_edits<F>(selections: &[Selection], mut replacement: F) -> (Vec<(Range<usize>, String)>, Vec<Selection>)
+where
+    F: FnMut(usize, Range<usize>) -> (Range<usize>, String),
+{
+    let mut sorted = selections.to_vec();
+    normalize_selections(&mut sorted);
+
+    let mut edits = Vec::with_capacity(sorted.len());
+    let mut carets = Vec::with_capacity(sorted.len());
+    let mut shift: isize = 0;
+    let mut last_end = 0;
+    for (i, selection) in sorted.iter().enumerate() {
+        let (range, text) = replacement(i, selection.range());
+        // Never let one edit reach back into text another edit already replaced.
+        let range = range.start.max(last_end)..range.end.max(range.start.max(last_end));
+        last_end = range.end;
+        let caret = (range.start as isize + shift) as usize + text.len();
+        shift += text.len() as isize - range.len() as isize;
+        carets.push(Selection::caret(caret));
+        edits.push((range, text));
+    }
+    edits.reverse();
+    (edits, carets)
+}
@@ -1,4 +1,8 @@
 use std::collections::HashMap;
+use std::path::Path;
+use crate::backend::code_editor::lexers::LexerRegistry;
+use crate::backend::code_editor::theme::EditorTheme;
+pub use crate::backend::code_editor::lexers::LineState;
 
 /// Represents a style for a token type (e.g., color, bold, italic).
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -19,6 +23,14 @@ pub enum TokenType {
     Symbol,
     Whitespace,
     Other,
+    Type,
+    Function,
+    Macro,
+    Attribute,
+    Lifetime,
+    Constant,
+    Tag,
+    Property,
 }
 
 /// Represents a highlighted token.
@@ -32,129 +44,54 @@ pub struct HighlightedToken {
 /// Syntax highlighter for a simple code editor.
 pub struct SyntaxHighlighter {
     pub theme: HashMap<TokenType, Style>,
+    pub lexers: LexerRegistry,
+}
+
+impl Default for SyntaxHighlighter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SyntaxHighlighter {
-    /// Create a new syntax highlighter with a default theme.
+    /// Create a new syntax highlighter with the default (Dark+) theme.
     pub fn new() -> Self {
-        let mut theme = HashMap::new();
-        theme.insert(TokenType::Keyword, Style { color: "#569CD6".to_string(), bold: true, italic: false });
-        theme.insert(TokenType::Identifier, Style { color: "#d4d4d4".to_string(), bold: false, italic: false });
-        theme.insert(TokenType::String, Style { color: "#ce9178".to_string(), bold: false, italic: false });
-        theme.insert(TokenType::Number, Style { color: "#b5cea8".to_string(), bold: false, italic: false });
-        theme.insert(TokenType::Comment, Style { color: "#6a9955".to_string(), bold: false, italic: true });
-        theme.insert(TokenType::Symbol, Style { color: "#d4d4d4".to_string(), bold: false, italic: false });
-        theme.insert(TokenType::Whitespace, Style { color: "#d4d4d4".to_string(), bold: false, italic: false });
-        theme.insert(TokenType::Other, Style { color: "#d4d4d4".to_string(), bold: false, italic: false });
-        Self { theme }
+        Self { theme: EditorTheme::dark_plus().styles, lexers: LexerRegistry::new() }
     }
 
-    /// Highlight a line of code (very basic, language-agnostic).
-    pub fn highlight_line(&self, line: &str) -> Vec<HighlightedToken> {
-        let keywords = ["fn", "let", "pub", "struct", "enum", "impl", "use", "mod", "if", "else", "for", "while", "loop", "match", "return", "true", "false", "const", "static", "mut", "as", "in", "break", "continue", "crate", "super", "self", "Self", "type", "where", "ref", "move", "async", "await", "dyn", "trait", "extern"];
-        let mut tokens = Vec::new();
-        let mut chars = line.chars().peekable();
-        let mut buf = String::new();
-        let mut current_type = TokenType::Other;
-        while let Some(&c) = chars.peek() {
-            if c.is_whitespace() {
-                if !buf.is_empty() {
-                    tokens.push(self.make_token(&buf, &current_type));
-                    buf.clear();
-                }
-                buf.push(c);
-                chars.next();
-                while let Some(&c2) = chars.peek() {
-                    if c2.is_whitespace() {
-                        buf.push(c2);
-                        chars.next();
-                    } else {
-                        break;
-                    }
-                }
-                tokens.push(self.make_token(&buf, &TokenType::Whitespace));
-                buf.clear();
-                current_type = TokenType::Other;
-            } else if c == '/' && chars.clone().nth(1) == Some('/') {
-                if !buf.is_empty() {
-                    tokens.push(self.make_token(&buf, &current_type));
-                    buf.clear();
-                }
-                let comment: String = chars.by_ref().collect();
-                tokens.push(self.make_token(&comment, &TokenType::Comment));
-                break;
-            } else if c == '"' {
-                if !buf.is_empty() {
-                    tokens.push(self.make_token(&buf, &current_type));
-                    buf.clear();
-                }
-                buf.push(c);
-                chars.next();
-                while let Some(&c2) = chars.peek() {
-                    buf.push(c2);
-                    chars.next();
-                    if c2 == '"' {
-                        break;
-                    }
-                }
-                tokens.push(self.make_token(&buf, &TokenType::String));
-                buf.clear();
-                current_type = TokenType::Other;
-            } else if c.is_ascii_digit() {
-                if !buf.is_empty() && current_type != TokenType::Number {
-                    tokens.push(self.make_token(&buf, &current_type));
-                    buf.clear();
-                }
-                current_type = TokenType::Number;
-                buf.push(c);
-                chars.next();
-                while let Some(&c2) = chars.peek() {
-                    if c2.is_ascii_digit() || c2 == '.' {
-                        buf.push(c2);
-                        chars.next();
-                    } else {
-                        break;
-                    }
-                }
-            } else if c.is_alphabetic() || c == '_' {
-                if !buf.is_empty() && current_type != TokenType::Identifier {
-                    tokens.push(self.make_token(&buf, &current_type));
-                    buf.clear();
-                }
-                current_type = TokenType::Identifier;
-                buf.push(c);
-                chars.next();
-                while let Some(&c2) = chars.peek() {
-                    if c2.is_alphanumeric() || c2 == '_' {
-                        buf.push(c2);
-                        chars.next();
-                    } else {
-                        break;
-                    }
-                }
-                if keywords.contains(&buf.as_str()) {
-                    tokens.push(self.make_token(&buf, &TokenType::Keyword));
-                } else {
-                    tokens.push(self.make_token(&buf, &TokenType::Identifier));
-                }
-                buf.clear();
-                current_type = TokenType::Other;
-            } else {
-                if !buf.is_empty() {
-                    tokens.push(self.make_token(&buf, &current_type));
-                    buf.clear();
-                }
-                buf.push(c);
-                chars.next();
-                tokens.push(self.make_token(&buf, &TokenType::Symbol));
-                buf.clear();
-                current_type = TokenType::Other;
-            }
-        }
-        if !buf.is_empty() {
-            tokens.push(self.make_token(&buf, &current_type));
+    /// Switch to the token styles of an editor theme.
+    pub fn set_theme(&mut self, theme: &EditorTheme) {
+        self.theme = theme.styles.clone();
+    }
+
+    /// Re-style already highlighted tokens with the current theme, without re-lexing.
+    pub fn restyle(&self, tokens: &mut [HighlightedToken]) {
+        for token in tokens {
+            token.style = self.make_token("", &token.token_type).style;
         }
-        tokens
+    }
+
+    /// Highlight a single line of Rust code.
+    pub fn highlight_line(&self, line: &str) -> Vec<HighlightedToken> {
+        self.highlight_line_with_state("rust", line, &LineState::Normal).0
+    }
+
+    /// Get the language name used to highlight a file, from its extension.
+    pub fn language_for_path(&self, path: &Path) -> &'static str {
+        self.lexers.language_for_path(path)
+    }
+
+    /// Highlight a line in the given language, starting in the given lexer state.
+    ///
+    /// Returns the tokens and the state the next line starts in, so block comments
+    /// and multi-line strings can be carried across lines.
+    pub fn highlight_line_with_state(&self, language: &str, line: &str, state: &LineState) -> (Vec<HighlightedToken>, LineState) {
+        let (spans, state) = self.lexers.get(language).lex_line(line, state);
+        let tokens = spans
+            .into_iter()
+            .map(|span| self.make_token(&line[span.range], &span.token_type))
+            .collect();
+        (tokens, state)
     }
 
     fn make_token(&self, text: &str, token_type: &TokenType) -> HighlightedToken {
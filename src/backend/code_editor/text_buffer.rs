@@ -0,0 +1,344 @@
+use std::ops::Range;
+
+/// Which backing store a piece points into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PieceSource {
+    Original,
+    Added,
+}
+
+/// A contiguous run of text taken from one of the backing stores.
+#[derive(Debug, Clone, Copy)]
+struct Piece {
+    source: PieceSource,
+    start: usize,
+    len: usize,
+}
+
+/// Describes which lines an edit replaced, using inclusive line indices.
+///
+/// Lines `start_line..=old_end_line` of the old text were replaced by lines
+/// `start_line..=new_end_line` of the new text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineEdit {
+    pub start_line: usize,
+    pub old_end_line: usize,
+    pub new_end_line: usize,
+}
+
+/// Piece-table text buffer with a line index, used as the backing store of an editor tab.
+///
+/// All offsets are byte offsets and must fall on UTF-8 character boundaries.
+#[derive(Debug, Clone)]
+pub struct TextBuffer {
+    original: String,
+    added: String,
+    pieces: Vec<Piece>,
+    line_starts: Vec<usize>,
+    len: usize,
+}
+
+impl Default for TextBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TextBuffer {
+    /// Create an empty buffer.
+    pub fn new() -> Self {
+        Self::from_text(String::new())
+    }
+
+    /// Create a buffer holding the given text.
+    pub fn from_text(text: String) -> Self {
+        let len = text.len();
+        let pieces = if len > 0 {
+            vec![Piece { source: PieceSource::Original, start: 0, len }]
+        } else {
+            Vec::new()
+        };
+        let mut line_starts = vec![0];
+        line_starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+        Self {
+            original: text,
+            added: String::new(),
+            pieces,
+            line_starts,
+            len,
+        }
+    }
+
+    /// Total length of the text in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if the buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of lines. A trailing newline starts a final, empty line.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Get the line containing the given byte offset.
+    pub fn line_of_offset(&self, offset: usize) -> usize {
+        let offset = offset.min(self.len);
+        self.line_starts.partition_point(|&start| start <= offset) - 1
+    }
+
+    /// Byte range of a line, excluding its line terminator.
+    pub fn line_range(&self, line: usize) -> Option<Range<usize>> {
+        let start = *self.line_starts.get(line)?;
+        let end = match self.line_starts.get(line + 1) {
+            Some(&next) => next - 1,
+            None => self.len,
+        };
+        Some(start..end)
+    }
+
+    /// Get the text of a line without its line terminator (`\n` or `\r\n`).
+    pub fn line(&self, line: usize) -> String {
+        let mut text = self.line_range(line).map(|r| self.slice(r)).unwrap_or_default();
+        if text.ends_with('\r') {
+            text.pop();
+        }
+        text
+    }
+
+    /// Convert a byte offset to a (line, column) pair, with the column in bytes.
+    pub fn offset_to_line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.len);
+        let line = self.line_of_offset(offset);
+        (line, offset - self.line_starts[line])
+    }
+
+    /// Convert a (line, column) pair to a byte offset, clamping to the line end.
+    pub fn line_col_to_offset(&self, line: usize, col: usize) -> usize {
+        match self.line_range(line) {
+            Some(range) => (range.start + col).min(range.end),
+            None => self.len,
+        }
+    }
+
+    /// Copy the text in the given byte range.
+    pub fn slice(&self, range: Range<usize>) -> String {
+        let start = range.start.min(self.len);
+        let end = range.end.min(self.len);
+        let mut out = String::with_capacity(end.saturating_sub(start));
+        let mut piece_start = 0;
+        for piece in &self.pieces {
+            let piece_end = piece_start + piece.len;
+            if piece_end > start && piece_start < end {
+                let from = start.max(piece_start) - piece_start;
+                let to = end.min(piece_end) - piece_start;
+                let source = self.source(piece.source);
+                out.push_str(&source[piece.start + from..piece.start + to]);
+            }
+            if piece_end >= end {
+                break;
+            }
+            piece_start = piece_end;
+        }
+        out
+    }
+
+    /// Round a byte offset down to the nearest character boundary.
+    pub fn floor_char_boundary(&self, offset: usize) -> usize {
+        let mut offset = offset.min(self.len);
+        let mut piece_start = 0;
+        for piece in &self.pieces {
+            let piece_end = piece_start + piece.len;
+            if offset < piece_end {
+                let source = self.source(piece.source);
+                while !source.is_char_boundary(piece.start + offset - piece_start) {
+                    offset -= 1;
+                }
+                break;
+            }
+            piece_start = piece_end;
+        }
+        offset
+    }
+
+    /// Insert text at a byte offset.
+    pub fn insert(&mut self, offset: usize, text: &str) -> LineEdit {
+        let offset = offset.min(self.len);
+        let start_line = self.line_of_offset(offset);
+        if text.is_empty() {
+            return LineEdit { start_line, old_end_line: start_line, new_end_line: start_line };
+        }
+
+        self.insert_piece(offset, text);
+
+        // Shift the following line starts and add one for each inserted newline.
+        for start in &mut self.line_starts[start_line + 1..] {
+            *start += text.len();
+        }
+        let new_starts: Vec<usize> = text.match_indices('\n').map(|(i, _)| offset + i + 1).collect();
+        let inserted_lines = new_starts.len();
+        self.line_starts.splice(start_line + 1..start_line + 1, new_starts);
+
+        LineEdit {
+            start_line,
+            old_end_line: start_line,
+            new_end_line: start_line + inserted_lines,
+        }
+    }
+
+    /// Delete the text in a byte range.
+    pub fn delete(&mut self, range: Range<usize>) -> LineEdit {
+        let start = range.start.min(self.len);
+        let end = range.end.min(self.len).max(start);
+        let start_line = self.line_of_offset(start);
+        let end_line = self.line_of_offset(end);
+        if start == end {
+            return LineEdit { start_line, old_end_line: start_line, new_end_line: start_line };
+        }
+
+        self.delete_pieces(start, end);
+
+        let removed = end - start;
+        self.line_starts.drain(start_line + 1..=end_line);
+        for line_start in &mut self.line_starts[start_line + 1..] {
+            *line_start -= removed;
+        }
+
+        LineEdit {
+            start_line,
+            old_end_line: end_line,
+            new_end_line: start_line,
+        }
+    }
+
+    /// Replace the text in a byte range with new text.
+    pub fn replace(&mut self, range: Range<usize>, text: &str) -> LineEdit {
+        let start = range.start.min(self.len);
+        let deleted = self.delete(start..range.end);
+        let inserted = self.insert(start, text);
+        LineEdit {
+            start_line: deleted.start_line,
+            old_end_line: deleted.old_end_line,
+            new_end_line: inserted.new_end_line,
+        }
+    }
+
+    fn source(&self, source: PieceSource) -> &str {
+        match source {
+            PieceSource::Original => &self.original,
+            PieceSource::Added => &self.added,
+        }
+    }
+
+    fn insert_piece(&mut self, offset: usize, text: &str) {
+        let added_start = self.added.len();
+        self.added.push_str(text);
+        let new_piece = Piece { source: PieceSource::Added, start: added_start, len: text.len() };
+
+        let mut piece_start = 0;
+        for i in 0..self.pieces.len() {
+            let piece = self.pieces[i];
+            let piece_end = piece_start + piece.len;
+            if offset == piece_end {
+                // Typing extends the last added piece instead of creating a new one.
+                if piece.source == PieceSource::Added && piece.start + piece.len == added_start {
+                    self.pieces[i].len += text.len();
+                    self.len += text.len();
+                    return;
+                }
+                if i + 1 == self.pieces.len() {
+                    break;
+                }
+            } else if offset > piece_start && offset < piece_end {
+                let inner = offset - piece_start;
+                let left = Piece { len: inner, ..piece };
+                let right = Piece { start: piece.start + inner, len: piece.len - inner, ..piece };
+                self.pieces.splice(i..=i, [left, new_piece, right]);
+                self.len += text.len();
+                return;
+            } else if offset == piece_start {
+                self.pieces.insert(i, new_piece);
+                self.len += text.len();
+                return;
+            }
+            piece_start = piece_end;
+        }
+        self.pieces.push(new_piece);
+        self.len += text.len();
+    }
+
+    fn delete_pieces(&mut self, start: usize, end: usize) {
+        let mut kept = Vec::with_capacity(self.pieces.len() + 1);
+        let mut piece_start = 0;
+        for piece in &self.pieces {
+            let piece_end = piece_start + piece.len;
+            if piece_end <= start || piece_start >= end {
+                kept.push(*piece);
+            } else {
+                if piece_start < start {
+                    kept.push(Piece { len: start - piece_start, ..*piece });
+                }
+                if piece_end > end {
+                    let skip = end - piece_start;
+                    kept.push(Piece { start: piece.start + skip, len: piece.len - skip, ..*piece });
+                }
+            }
+            piece_start = piece_end;
+        }
+        self.pieces = kept;
+        self.len -= end - start;
+    }
+}
+
+impl std::fmt::Display for TextBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for piece in &self.pieces {
+            let source = self.source(piece.source);
+            f.write_str(&source[piece.start..piece.start + piece.len])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_and_delete_keep_line_index() {
+        let mut buffer = TextBuffer::from_text("fn main() {\n}\n".to_string());
+        assert_eq!(buffer.line_count(), 3);
+
+        let edit = buffer.insert(12, "    println!(\"hi\");\n");
+        assert_eq!(edit, LineEdit { start_line: 1, old_end_line: 1, new_end_line: 2 });
+        assert_eq!(buffer.to_string(), "fn main() {\n    println!(\"hi\");\n}\n");
+        assert_eq!(buffer.line(1), "    println!(\"hi\");");
+        assert_eq!(buffer.line(2), "}");
+
+        let edit = buffer.delete(11..32);
+        assert_eq!(edit, LineEdit { start_line: 0, old_end_line: 2, new_end_line: 0 });
+        assert_eq!(buffer.to_string(), "fn main() {}\n");
+        assert_eq!(buffer.line_count(), 2);
+        assert_eq!(buffer.line_of_offset(13), 1);
+    }
+
+    #[test]
+    fn test_typing_coalesces_pieces() {
+        let mut buffer = TextBuffer::from_text("ab".to_string());
+        for (i, c) in ["x", "y", "z"].iter().enumerate() {
+            buffer.insert(1 + i, c);
+        }
+        assert_eq!(buffer.to_string(), "axyzb");
+        assert_eq!(buffer.pieces.len(), 3);
+        assert_eq!(buffer.slice(1..4), "xyz");
+        assert_eq!(buffer.offset_to_line_col(4), (0, 4));
+
+        buffer.insert(2, "é");
+        assert_eq!(buffer.floor_char_boundary(3), 2);
+        assert_eq!(buffer.floor_char_boundary(4), 4);
+        assert_eq!(buffer.floor_char_boundary(100), buffer.len());
+    }
+}
@@ -0,0 +1,644 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use crate::backend::code_editor::syntax_highlighting::{Style, TokenType};
+use crate::backend::settings_manager::Theme;
+
+/// All token types that a theme assigns a style to.
+pub const TOKEN_TYPES: [TokenType; 16] = [
+    TokenType::Keyword,
+    TokenType::Identifier,
+    TokenType::String,
+    TokenType::Number,
+    TokenType::Comment,
+    TokenType::Symbol,
+    TokenType::Whitespace,
+    TokenType::Other,
+    TokenType::Type,
+    TokenType::Function,
+    TokenType::Macro,
+    TokenType::Attribute,
+    TokenType::Lifetime,
+    TokenType::Constant,
+    TokenType::Tag,
+    TokenType::Property,
+];
+
+/// TextMate scopes that a token type stands for, most specific first.
+///
+/// A theme rule applies to a token type when one of its selectors is a
+/// dot-separated prefix of one of these scopes.
+pub fn scopes_for(token_type: &TokenType) -> &'static [&'static str] {
+    match token_type {
+        TokenType::Keyword => &["keyword.control", "storage.modifier", "storage.type.function", "keyword", "storage"],
+        TokenType::Identifier => &["variable.other", "variable"],
+        TokenType::String => &["string.quoted", "string"],
+        TokenType::Number => &["constant.numeric"],
+        TokenType::Comment => &["comment.line", "comment.block", "comment"],
+        TokenType::Symbol => &["keyword.operator", "punctuation"],
+        TokenType::Whitespace | TokenType::Other => &["source"],
+        TokenType::Type => &["entity.name.type", "support.type", "entity.name.class", "storage.type"],
+        TokenType::Function => &["entity.name.function", "support.function", "meta.function-call"],
+        TokenType::Macro => &["entity.name.function.macro", "support.macro", "entity.name.macro", "entity.name.function"],
+        TokenType::Attribute => &["entity.other.attribute-name", "meta.attribute", "meta.decorator"],
+        TokenType::Lifetime => &["storage.modifier.lifetime", "entity.name.type.lifetime", "storage.modifier"],
+        TokenType::Constant => &["variable.other.constant", "constant.language", "constant.other", "constant.character", "constant"],
+        TokenType::Tag => &["entity.name.tag"],
+        TokenType::Property => &["support.type.property-name", "variable.other.property", "meta.object-literal.key", "variable.other.member"],
+    }
+}
+
+/// An editor colour theme: token styles plus editor background and foreground.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditorTheme {
+    pub name: String,
+    pub background: String,
+    pub foreground: String,
+    pub selection: Option<String>,
+    pub styles: HashMap<TokenType, Style>,
+}
+
+/// One theme rule: scope selectors and the style they set.
+#[derive(Debug, Clone, Default)]
+struct ScopeRule {
+    selectors: Vec<String>,
+    foreground: Option<String>,
+    font_style: Option<String>,
+}
+
+impl EditorTheme {
+    /// Dark+ palette, the default editor theme.
+    pub fn dark_plus() -> Self {
+        Self::from_palette("Dark+", "#1e1e1e", "#d4d4d4", Some("#264f78"), &[
+            (TokenType::Keyword, "#569cd6", "bold"),
+            (TokenType::String, "#ce9178", ""),
+            (TokenType::Number, "#b5cea8", ""),
+            (TokenType::Comment, "#6a9955", "italic"),
+            (TokenType::Identifier, "#9cdcfe", ""),
+            (TokenType::Type, "#4ec9b0", ""),
+            (TokenType::Function, "#dcdcaa", ""),
+            (TokenType::Macro, "#c586c0", ""),
+            (TokenType::Attribute, "#9cdcfe", ""),
+            (TokenType::Lifetime, "#569cd6", "italic"),
+            (TokenType::Constant, "#4fc1ff", ""),
+            (TokenType::Tag, "#569cd6", ""),
+            (TokenType::Property, "#9cdcfe", ""),
+        ])
+    }
+
+    /// Light+ palette.
+    pub fn light_plus() -> Self {
+        Self::from_palette("Light+", "#ffffff", "#000000", Some("#add6ff"), &[
+            (TokenType::Keyword, "#0000ff", "bold"),
+            (TokenType::String, "#a31515", ""),
+            (TokenType::Number, "#098658", ""),
+            (TokenType::Comment, "#008000", "italic"),
+            (TokenType::Identifier, "#001080", ""),
+            (TokenType::Type, "#267f99", ""),
+            (TokenType::Function, "#795e26", ""),
+            (TokenType::Macro, "#af00db", ""),
+            (TokenType::Attribute, "#e50000", ""),
+            (TokenType::Lifetime, "#0000ff", "italic"),
+            (TokenType::Constant, "#0070c1", ""),
+            (TokenType::Tag, "#800000", ""),
+            (TokenType::Property, "#e50000", ""),
+        ])
+    }
+
+    /// High-contrast palette.
+    pub fn high_contrast() -> Self {
+        Self::from_palette("High Contrast", "#000000", "#ffffff", Some("#f38518"), &[
+            (TokenType::Keyword, "#569cd6", "bold"),
+            (TokenType::String, "#ce9178", ""),
+            (TokenType::Number, "#b5cea8", ""),
+            (TokenType::Comment, "#7ca668", "italic"),
+            (TokenType::Type, "#4ec9b0", ""),
+            (TokenType::Function, "#dcdcaa", ""),
+            (TokenType::Macro, "#c586c0", "bold"),
+            (TokenType::Attribute, "#9cdcfe", ""),
+            (TokenType::Lifetime, "#569cd6", "italic"),
+            (TokenType::Constant, "#4fc1ff", ""),
+            (TokenType::Tag, "#569cd6", ""),
+            (TokenType::Property, "#9cdcfe", ""),
+        ])
+    }
+
+    fn from_palette(name: &str, background: &str, foreground: &str, selection: Option<&str>, palette: &[(TokenType, &str, &str)]) -> Self {
+        let mut styles: HashMap<TokenType, Style> = TOKEN_TYPES
+            .iter()
+            .map(|t| (t.clone(), Style { color: foreground.to_string(), bold: false, italic: false }))
+            .collect();
+        for (token_type, color, font_style) in palette {
+            styles.insert(token_type.clone(), Style {
+                color: color.to_string(),
+                bold: font_style.contains("bold"),
+                italic: font_style.contains("italic"),
+            });
+        }
+        Self {
+            name: name.to_string(),
+            background: background.to_string(),
+            foreground: foreground.to_string(),
+            selection: selection.map(str::to_string),
+            styles,
+        }
+    }
+
+    /// Load a theme file, choosing the format from its extension (`.tmTheme` or `.json`).
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("Failed to read theme {}: {}", path.display(), e))?;
+        let fallback_name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("Custom");
+        let is_tmtheme = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("tmTheme") || ext.eq_ignore_ascii_case("plist"));
+        let mut theme = if is_tmtheme { Self::from_tmtheme(&content)? } else { Self::from_json(&content)? };
+        if theme.name.is_empty() {
+            theme.name = fallback_name.to_string();
+        }
+        Ok(theme)
+    }
+
+    /// Parse a VS Code style JSON theme (`colors` plus `tokenColors`).
+    pub fn from_json(content: &str) -> Result<Self, String> {
+        let json: serde_json::Value = serde_json::from_str(content).map_err(|e| format!("Invalid theme JSON: {}", e))?;
+        let name = json["name"].as_str().unwrap_or_default().to_string();
+        let colors = &json["colors"];
+        let is_light = json["type"].as_str() == Some("light");
+        let base = if is_light { Self::light_plus() } else { Self::dark_plus() };
+        let background = colors["editor.background"].as_str().map_or(base.background, str::to_string);
+        let foreground = colors["editor.foreground"].as_str().map_or(base.foreground, str::to_string);
+        let selection = colors["editor.selectionBackground"].as_str().map(str::to_string).or(base.selection);
+
+        let token_colors = json["tokenColors"]
+            .as_array()
+            .ok_or_else(|| "Theme JSON has no tokenColors array".to_string())?;
+        let rules = token_colors
+            .iter()
+            .map(|rule| {
+                let selectors = match &rule["scope"] {
+                    serde_json::Value::String(scope) => split_selectors(scope),
+                    serde_json::Value::Array(scopes) => scopes.iter().filter_map(|s| s.as_str()).flat_map(split_selectors).collect(),
+                    _ => Vec::new(),
+                };
+                ScopeRule {
+                    selectors,
+                    foreground: rule["settings"]["foreground"].as_str().map(str::to_string),
+                    font_style: rule["settings"]["fontStyle"].as_str().map(str::to_string),
+                }
+            })
+            .collect::<Vec<_>>();
+        Ok(Self::from_rules(name, background, foreground, selection, &rules))
+    }
+
+    /// Parse a TextMate/Sublime `.tmTheme` property list.
+    pub fn from_tmtheme(content: &str) -> Result<Self, String> {
+        let root = plist::parse(content)?;
+        let name = root.get("name").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+        let entries = root
+            .get("settings")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| "tmTheme has no settings array".to_string())?;
+
+        let base = Self::dark_plus();
+        let (mut background, mut foreground, mut selection) = (base.background, base.foreground, base.selection);
+        let mut rules = Vec::new();
+        for entry in entries {
+            let Some(settings) = entry.get("settings") else {
+                continue;
+            };
+            let setting = |key: &str| settings.get(key).and_then(|v| v.as_str()).map(str::to_string);
+            match entry.get("scope").and_then(|v| v.as_str()) {
+                // The entry without a scope holds the global editor colours.
+                None => {
+                    background = setting("background").unwrap_or(background);
+                    foreground = setting("foreground").unwrap_or(foreground);
+                    selection = setting("selection").or(selection);
+                }
+                Some(scope) => rules.push(ScopeRule {
+                    selectors: split_selectors(scope),
+                    foreground: setting("foreground"),
+                    font_style: setting("fontStyle"),
+                }),
+            }
+        }
+        Ok(Self::from_rules(name, background, foreground, selection, &rules))
+    }
+
+    /// Resolve theme rules into a style for every token type.
+    ///
+    /// For each token type the rule whose selector matches the longest prefix of
+    /// one of its scopes wins; later rules win ties, as in TextMate.
+    fn from_rules(name: String, background: String, foreground: String, selection: Option<String>, rules: &[ScopeRule]) -> Self {
+        let mut styles = HashMap::new();
+        for token_type in TOKEN_TYPES.iter() {
+            let mut best: Option<(usize, &ScopeRule)> = None;
+            for rule in rules.iter().filter(|r| r.foreground.is_some() || r.font_style.is_some()) {
+                let score = rule
+                    .selectors
+                    .iter()
+                    .filter_map(|selector| scopes_for(token_type).iter().filter_map(|scope| match_score(selector, scope)).max())
+                    .max();
+                if let Some(score) = score {
+                    if best.is_none_or(|(best_score, _)| score >= best_score) {
+                        best = Some((score, rule));
+                    }
+                }
+            }
+            let rule = best.map(|(_, rule)| rule);
+            let font_style = rule.and_then(|r| r.font_style.as_deref()).unwrap_or_default();
+            styles.insert(token_type.clone(), Style {
+                color: rule.and_then(|r| r.foreground.clone()).unwrap_or_else(|| foreground.clone()),
+                bold: font_style.contains("bold"),
+                italic: font_style.contains("italic"),
+            });
+        }
+        Self { name, background, foreground, selection, styles }
+    }
+
+    /// Get the style for a token type, falling back to the plain foreground.
+    pub fn style(&self, token_type: &TokenType) -> Style {
+        self.styles.get(token_type).cloned().unwrap_or_else(|| Style {
+            color: self.foreground.clone(),
+            bold: false,
+            italic: false,
+        })
+    }
+}
+
+/// Split a scope selector list ("a, b c") into the selectors it contains.
+///
+/// Descendant selectors ("source.rust comment") are reduced to their last
+/// part, since tokens here carry a single scope.
+fn split_selectors(scope: &str) -> Vec<String> {
+    scope
+        .split(',')
+        .filter_map(|selector| selector.split_whitespace().last())
+        .filter(|selector| !selector.starts_with('-'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Score how well `selector` matches `scope`: the number of matching scope parts, if it matches at all.
+fn match_score(selector: &str, scope: &str) -> Option<usize> {
+    let matches = scope == selector || (scope.starts_with(selector) && scope[selector.len()..].starts_with('.'));
+    matches.then(|| selector.split('.').count())
+}
+
+/// Extensions of the theme files [`ThemeManager`] loads.
+pub const THEME_EXTENSIONS: &[&str] = &["json", "tmTheme", "plist"];
+
+/// Loads editor themes from disk, follows `UISettings.theme` and hot-reloads the active file.
+pub struct ThemeManager {
+    themes_dir: PathBuf,
+    active: EditorTheme,
+    ui_theme: Option<Theme>,
+    /// File the active theme was loaded from and its modification time when loaded.
+    source: Option<(PathBuf, Option<SystemTime>)>,
+    check_interval: Duration,
+    last_check: Instant,
+    last_error: Option<String>,
+}
+
+impl Default for ThemeManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ThemeManager {
+    /// Create a manager reading themes from the Jadio config directory.
+    pub fn new() -> Self {
+        Self::with_dir(Self::default_dir())
+    }
+
+    /// The Jadio config directory's `jadio-ide/themes`, which may not exist yet.
+    pub fn default_dir() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("jadio-ide")
+            .join("themes")
+    }
+
+    /// Create a manager reading themes from the given directory.
+    pub fn with_dir(themes_dir: PathBuf) -> Self {
+        Self {
+            themes_dir,
+            active: EditorTheme::dark_plus(),
+            ui_theme: None,
+            source: None,
+            check_interval: Duration::from_secs(1),
+            last_check: Instant::now(),
+            last_error: None,
+        }
+    }
+
+    /// Get the active theme.
+    pub fn active(&self) -> &EditorTheme {
+        &self.active
+    }
+
+    /// Get the error from the last failed load, if any.
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    /// Follow the UI theme from settings. Returns true if the active theme changed.
+    ///
+    /// `Dark`, `Light` and `HighContrast` use the built-in palettes. `Custom`
+    /// loads the theme file named after the custom theme, falling back to its
+    /// background and foreground colours when no such file exists.
+    pub fn apply_ui_theme(&mut self, theme: &Theme) -> bool {
+        if self.ui_theme.as_ref() == Some(theme) {
+            return false;
+        }
+        self.ui_theme = Some(theme.clone());
+        self.source = None;
+        self.last_error = None;
+        self.active = match theme {
+            Theme::Dark => EditorTheme::dark_plus(),
+            Theme::Light => EditorTheme::light_plus(),
+            Theme::HighContrast => EditorTheme::high_contrast(),
+            Theme::Custom(custom) => match self.find_theme_file(&custom.name) {
+                Some(path) => match self.load_file(&path) {
+                    Ok(theme) => theme,
+                    Err(e) => {
+                        self.last_error = Some(e);
+                        EditorTheme::dark_plus()
+                    }
+                },
+                None => {
+                    let mut theme = EditorTheme::dark_plus();
+                    theme.name = custom.name.clone();
+                    theme.background = rgb_to_hex(custom.background);
+                    theme.foreground = rgb_to_hex(custom.foreground);
+                    theme
+                }
+            },
+        };
+        true
+    }
+
+    /// Reload the active theme file if it changed on disk. Returns true if reloaded.
+    ///
+    /// Cheap to call every frame; the file is only checked once per interval.
+    pub fn poll(&mut self) -> bool {
+        if self.last_check.elapsed() < self.check_interval {
+            return false;
+        }
+        self.last_check = Instant::now();
+        let Some((path, loaded_at)) = self.source.clone() else {
+            return false;
+        };
+        if modified_time(&path) == loaded_at {
+            return false;
+        }
+        match self.load_file(&path) {
+            Ok(theme) => {
+                self.active = theme;
+                self.last_error = None;
+                true
+            }
+            Err(e) => {
+                // Keep the previous theme while the file is being edited.
+                self.source = Some((path.clone(), modified_time(&path)));
+                self.last_error = Some(e);
+                false
+            }
+        }
+    }
+
+    fn load_file(&mut self, path: &Path) -> Result<EditorTheme, String> {
+        let modified = modified_time(path);
+        let theme = EditorTheme::load(path)?;
+        self.source = Some((path.to_path_buf(), modified));
+        Ok(theme)
+    }
+
+    fn find_theme_file(&self, name: &str) -> Option<PathBuf> {
+        THEME_EXTENSIONS
+            .iter()
+            .map(|ext| self.themes_dir.join(format!("{}.{}", name, ext)))
+            .find(|path| path.exists())
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn rgb_to_hex(rgb: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2])
+}
+
+/// Minimal reader for the XML property lists used by `.tmTheme` files.
+mod plist {
+    /// A property list value. Numbers, dates and data are kept as strings.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Value {
+        String(String),
+        Bool(bool),
+        Array(Vec<Value>),
+        Dict(Vec<(String, Value)>),
+    }
+
+    impl Value {
+        pub fn get(&self, key: &str) -> Option<&Value> {
+            match self {
+                Value::Dict(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+                _ => None,
+            }
+        }
+
+        pub fn as_str(&self) -> Option<&str> {
+            match self {
+                Value::String(s) => Some(s),
+                _ => None,
+            }
+        }
+
+        pub fn as_array(&self) -> Option<&[Value]> {
+            match self {
+                Value::Array(items) => Some(items),
+                _ => None,
+            }
+        }
+    }
+
+    /// Parse the top-level value of a plist document.
+    pub fn parse(content: &str) -> Result<Value, String> {
+        let mut tags = Tags { rest: content };
+        loop {
+            match tags.next()? {
+                Some(Tag::Open(name)) if name == "plist" => continue,
+                Some(Tag::Open(name)) => return parse_value(&mut tags, &name),
+                Some(_) => continue,
+                None => return Err("Empty property list".to_string()),
+            }
+        }
+    }
+
+    fn parse_value(tags: &mut Tags, name: &str) -> Result<Value, String> {
+        match name {
+            "dict" => {
+                let mut entries = Vec::new();
+                loop {
+                    match tags.next()? {
+                        Some(Tag::Close(n)) if n == "dict" => return Ok(Value::Dict(entries)),
+                        Some(Tag::Open(n)) if n == "key" => {
+                            let key = tags.text_until("key")?;
+                            let value = match tags.next()? {
+                                Some(Tag::Open(n)) => parse_value(tags, &n)?,
+                                Some(Tag::Empty(n)) => empty_value(&n),
+                                _ => return Err(format!("Missing value for key '{}'", key)),
+                            };
+                            entries.push((key, value));
+                        }
+                        Some(_) => continue,
+                        None => return Err("Unterminated <dict>".to_string()),
+                    }
+                }
+            }
+            "array" => {
+                let mut items = Vec::new();
+                loop {
+                    match tags.next()? {
+                        Some(Tag::Close(n)) if n == "array" => return Ok(Value::Array(items)),
+                        Some(Tag::Open(n)) => items.push(parse_value(tags, &n)?),
+                        Some(Tag::Empty(n)) => items.push(empty_value(&n)),
+                        Some(_) => continue,
+                        None => return Err("Unterminated <array>".to_string()),
+                    }
+                }
+            }
+            other => Ok(Value::String(tags.text_until(other)?)),
+        }
+    }
+
+    fn empty_value(name: &str) -> Value {
+        match name {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            "dict" => Value::Dict(Vec::new()),
+            "array" => Value::Array(Vec::new()),
+            _ => Value::String(String::new()),
+        }
+    }
+
+    enum Tag {
+        Open(String),
+        Close(String),
+        Empty(String),
+    }
+
+    struct Tags<'a> {
+        rest: &'a str,
+    }
+
+    impl<'a> Tags<'a> {
+        /// Next element tag, skipping text, comments, `<?xml ?>` and `<!DOCTYPE>`.
+        fn next(&mut self) -> Result<Option<Tag>, String> {
+            loop {
+                let Some(start) = self.rest.find('<') else {
+                    return Ok(None);
+                };
+                self.rest = &self.rest[start..];
+                if let Some(after) = self.rest.strip_prefix("<!--") {
+                    let end = after.find("-->").ok_or("Unterminated comment")?;
+                    self.rest = &after[end + 3..];
+                    continue;
+                }
+                let end = self.rest.find('>').ok_or("Unterminated tag")?;
+                let tag = &self.rest[1..end];
+                self.rest = &self.rest[end + 1..];
+                if tag.starts_with('?') || tag.starts_with('!') {
+                    continue;
+                }
+                let name = |s: &str| s.split_whitespace().next().unwrap_or_default().to_string();
+                return Ok(Some(if let Some(closing) = tag.strip_prefix('/') {
+                    Tag::Close(name(closing))
+                } else if let Some(empty) = tag.strip_suffix('/') {
+                    Tag::Empty(name(empty))
+                } else {
+                    Tag::Open(name(tag))
+                }));
+            }
+        }
+
+        /// Text content up to the closing tag `name`, with entities decoded.
+        fn text_until(&mut self, name: &str) -> Result<String, String> {
+            let close = format!("</{}>", name);
+            let end = self.rest.find(&close).ok_or_else(|| format!("Unterminated <{}>", name))?;
+            let text = unescape(&self.rest[..end]);
+            self.rest = &self.rest[end + close.len()..];
+            Ok(text)
+        }
+    }
+
+    fn unescape(text: &str) -> String {
+        text.replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tmtheme_scopes_map_to_token_types() {
+        let content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>name</key><string>Test</string>
+    <key>settings</key>
+    <array>
+        <dict><key>settings</key><dict>
+            <key>background</key><string>#272822</string>
+            <key>foreground</key><string>#F8F8F2</string>
+        </dict></dict>
+        <dict><key>scope</key><string>comment</string><key>settings</key><dict>
+            <key>foreground</key><string>#75715E</string>
+            <key>fontStyle</key><string>italic</string>
+        </dict></dict>
+        <dict><key>scope</key><string>keyword, storage</string><key>settings</key><dict>
+            <key>foreground</key><string>#F92672</string>
+        </dict></dict>
+        <dict><key>scope</key><string>entity.name.function</string><key>settings</key><dict>
+            <key>foreground</key><string>#A6E22E</string>
+        </dict></dict>
+    </array>
+</dict>
+</plist>"#;
+        let theme = EditorTheme::from_tmtheme(content).unwrap();
+        assert_eq!(theme.name, "Test");
+        assert_eq!(theme.background, "#272822");
+        assert_eq!(theme.style(&TokenType::Comment), Style { color: "#75715E".to_string(), bold: false, italic: true });
+        assert_eq!(theme.style(&TokenType::Keyword).color, "#F92672");
+        assert_eq!(theme.style(&TokenType::Function).color, "#A6E22E");
+        // entity.name.function is also the closest match for macros.
+        assert_eq!(theme.style(&TokenType::Macro).color, "#A6E22E");
+        assert_eq!(theme.style(&TokenType::Tag).color, "#F8F8F2");
+    }
+
+    #[test]
+    fn test_json_theme_prefers_more_specific_selectors() {
+        let content = r##"{
+            "name": "Json Test",
+            "colors": { "editor.background": "#000000", "editor.foreground": "#ffffff" },
+            "tokenColors": [
+                { "scope": "constant", "settings": { "foreground": "#111111" } },
+                { "scope": ["constant.numeric"], "settings": { "foreground": "#222222", "fontStyle": "bold" } }
+            ]
+        }"##;
+        let theme = EditorTheme::from_json(content).unwrap();
+        assert_eq!(theme.style(&TokenType::Number), Style { color: "#222222".to_string(), bold: true, italic: false });
+        assert_eq!(theme.style(&TokenType::Constant).color, "#111111");
+        assert_eq!(theme.style(&TokenType::String).color, "#ffffff");
+    }
+}
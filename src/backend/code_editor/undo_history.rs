@@ -0,0 +1,252 @@
+use std::time::{Duration, Instant};
+use crate::backend::code_editor::selection::Selection;
+
+/// What produced an edit, used to decide how edits are grouped into undo steps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditKind {
+    /// Characters typed by the user; grouped into word-level steps.
+    Typing,
+    /// Backspace/delete presses; grouped like typing.
+    Deletion,
+    /// A paste; always its own step.
+    Paste,
+    /// An edit applied by the code agent; always its own step.
+    Agent,
+    /// Any other programmatic edit; always its own step.
+    Other,
+}
+
+/// A single replacement: `deleted` at `offset` was replaced by `inserted`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChange {
+    pub offset: usize,
+    pub deleted: String,
+    pub inserted: String,
+}
+
+/// One undoable step, made of one or more changes applied in order.
+#[derive(Debug, Clone)]
+pub struct UndoStep {
+    pub kind: EditKind,
+    pub changes: Vec<TextChange>,
+    pub selections_before: Vec<Selection>,
+    pub selections_after: Vec<Selection>,
+    last_edit: Instant,
+}
+
+/// Per-tab undo/redo history.
+#[derive(Debug, Clone)]
+pub struct UndoHistory {
+    undo_stack: Vec<UndoStep>,
+    redo_stack: Vec<UndoStep>,
+    max_steps: usize,
+    group_timeout: Duration,
+    /// Whether the last undo step may still absorb further typing.
+    group_open: bool,
+    /// Depth of the undo stack that matches the file on disk, if still reachable.
+    saved_depth: Option<usize>,
+}
+
+impl Default for UndoHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UndoHistory {
+    /// Create an empty history.
+    pub fn new() -> Self {
+        Self {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            max_steps: 1000,
+            group_timeout: Duration::from_millis(1000),
+            group_open: false,
+            saved_depth: Some(0),
+        }
+    }
+
+    /// Record an edit that has already been applied to the buffer.
+    pub fn record(
+        &mut self,
+        kind: EditKind,
+        changes: Vec<TextChange>,
+        selections_before: Vec<Selection>,
+        selections_after: Vec<Selection>,
+    ) {
+        if changes.is_empty() {
+            return;
+        }
+        self.redo_stack.clear();
+        if self.saved_depth.is_some_and(|depth| depth > self.undo_stack.len()) {
+            self.saved_depth = None;
+        }
+
+        if self.group_open {
+            if let Some(last) = self.undo_stack.last_mut() {
+                if Self::can_merge(last, kind, &changes, self.group_timeout) {
+                    last.changes.extend(changes);
+                    last.selections_after = selections_after;
+                    last.last_edit = Instant::now();
+                    return;
+                }
+            }
+        }
+
+        self.undo_stack.push(UndoStep {
+            kind,
+            changes,
+            selections_before,
+            selections_after,
+            last_edit: Instant::now(),
+        });
+        self.group_open = matches!(kind, EditKind::Typing | EditKind::Deletion);
+
+        if self.undo_stack.len() > self.max_steps {
+            self.undo_stack.remove(0);
+            self.saved_depth = self.saved_depth.and_then(|depth| depth.checked_sub(1));
+        }
+    }
+
+    /// Stop merging further typing into the current step (e.g. after a cursor jump).
+    pub fn seal(&mut self) {
+        self.group_open = false;
+    }
+
+    /// Pop the most recent step for undoing. The caller applies its inverse.
+    pub fn undo(&mut self) -> Option<UndoStep> {
+        self.group_open = false;
+        let step = self.undo_stack.pop()?;
+        self.redo_stack.push(step.clone());
+        Some(step)
+    }
+
+    /// Pop the most recently undone step for redoing. The caller re-applies it.
+    pub fn redo(&mut self) -> Option<UndoStep> {
+        self.group_open = false;
+        let step = self.redo_stack.pop()?;
+        self.undo_stack.push(step.clone());
+        Some(step)
+    }
+
+    /// Check if there is anything to undo.
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    /// Check if there is anything to redo.
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Mark the current state as saved. History is kept across saves.
+    pub fn mark_saved(&mut self) {
+        self.group_open = false;
+        self.saved_depth = Some(self.undo_stack.len());
+    }
+
+    /// Check if the buffer differs from the last saved state.
+    pub fn is_dirty(&self) -> bool {
+        self.saved_depth != Some(self.undo_stack.len())
+    }
+
+    /// Drop all history, e.g. when a file is reloaded from disk.
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.group_open = false;
+        self.saved_depth = Some(0);
+    }
+
+    fn can_merge(last: &UndoStep, kind: EditKind, changes: &[TextChange], timeout: Duration) -> bool {
+        if last.kind != kind || last.last_edit.elapsed() > timeout || changes.len() != 1 {
+            return false;
+        }
+        let (Some(prev), next) = (last.changes.last(), &changes[0]) else {
+            return false;
+        };
+        match kind {
+            EditKind::Typing => {
+                let contiguous = prev.deleted.is_empty()
+                    && next.deleted.is_empty()
+                    && next.offset == prev.offset + prev.inserted.len();
+                // A new word starts a new step: "foo bar" undoes as "bar" then "foo ".
+                let starts_word = match (prev.inserted.chars().last(), next.inserted.chars().next()) {
+                    (Some(p), Some(n)) => is_word_char(n) && !is_word_char(p),
+                    _ => false,
+                };
+                contiguous && !starts_word && !next.inserted.contains('\n')
+            }
+            EditKind::Deletion => {
+                prev.inserted.is_empty()
+                    && next.inserted.is_empty()
+                    && !next.deleted.contains('\n')
+                    && (next.offset + next.deleted.len() == prev.offset || next.offset == prev.offset)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Record typing `text` one character at a time from `offset`.
+    fn type_chars(history: &mut UndoHistory, offset: usize, text: &str) {
+        for (i, c) in text.char_indices() {
+            let change = TextChange { offset: offset + i, deleted: String::new(), inserted: c.to_string() };
+            let caret = offset + i + c.len_utf8();
+            history.record(EditKind::Typing, vec![change], vec![Selection::caret(caret - 1)], vec![Selection::caret(caret)]);
+        }
+    }
+
+    fn inserted(step: &UndoStep) -> String {
+        step.changes.iter().map(|change| change.inserted.as_str()).collect()
+    }
+
+    #[test]
+    fn test_typing_groups_by_word_boundary() {
+        let mut history = UndoHistory::new();
+        type_chars(&mut history, 0, "foo bar");
+
+        assert_eq!(inserted(&history.undo().unwrap()), "bar");
+        assert_eq!(inserted(&history.undo().unwrap()), "foo ");
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn test_typing_after_timeout_starts_new_step() {
+        let mut history = UndoHistory::new();
+        history.group_timeout = Duration::from_millis(20);
+        type_chars(&mut history, 0, "ab");
+        std::thread::sleep(Duration::from_millis(40));
+        type_chars(&mut history, 2, "cd");
+
+        let step = history.undo().unwrap();
+        assert_eq!(inserted(&step), "cd");
+        assert_eq!(step.selections_before, vec![Selection::caret(2)]);
+        assert_eq!(inserted(&history.undo().unwrap()), "ab");
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn test_new_edit_after_undo_discards_redo_branch() {
+        let mut history = UndoHistory::new();
+        type_chars(&mut history, 0, "one");
+        history.seal();
+        type_chars(&mut history, 3, "two");
+        history.undo().unwrap();
+        assert!(history.can_redo());
+
+        type_chars(&mut history, 3, "three");
+        assert!(!history.can_redo());
+        assert!(history.redo().is_none());
+        assert_eq!(inserted(&history.undo().unwrap()), "three");
+        assert_eq!(inserted(&history.undo().unwrap()), "one");
+    }
+}
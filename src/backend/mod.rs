@@ -17,6 +17,14 @@ pub mod terminal_handler {
     include!("shell_terminal/shell_terminal_logic.rs");
 }
 
+pub mod status_bar {
+    include!("status_bar/status_bar_logic.rs");
+}
+
+pub mod code_editor;
+pub mod code_agent;
+pub mod activity_bar_right;
+
 // Re-exports
 pub use file_system::*;
 pub use project_manager::*;
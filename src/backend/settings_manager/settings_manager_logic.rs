@@ -55,7 +55,7 @@ pub struct UISettings {
 }
 
 /// AI integration and model configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AISettings {
     pub provider: AIProvider,
     pub api_key: String,
@@ -65,6 +65,28 @@ pub struct AISettings {
     pub enable_auto_complete: bool,
     pub enable_code_suggestions: bool,
     pub enable_code_review: bool,
+    /// Tokens one chat session may use before a warning; 0 for no limit.
+    #[serde(default)]
+    pub session_token_budget: u64,
+    /// US dollars a project may spend on model requests before a warning; 0 for no limit.
+    #[serde(default)]
+    pub project_cost_budget: f64,
+    /// Let the agent run only commands that don't change the project.
+    #[serde(default)]
+    pub agent_commands_read_only: bool,
+    /// Extra command patterns the agent may run without asking, e.g. `npm test*`.
+    #[serde(default)]
+    pub agent_command_allow: Vec<String>,
+    /// Extra command patterns the agent may never run.
+    #[serde(default)]
+    pub agent_command_deny: Vec<String>,
+    /// Embedding model on the local model server, used for semantic code search.
+    #[serde(default = "default_embedding_model")]
+    pub embedding_model: String,
+}
+
+fn default_embedding_model() -> String {
+    "nomic-embed-text".to_string()
 }
 
 /// Terminal emulator configuration
@@ -187,6 +209,12 @@ impl Default for AISettings {
             enable_auto_complete: true,
             enable_code_suggestions: true,
             enable_code_review: false,
+            session_token_budget: 0,
+            project_cost_budget: 0.0,
+            agent_commands_read_only: false,
+            agent_command_allow: Vec::new(),
+            agent_command_deny: Vec::new(),
+            embedding_model: default_embedding_model(),
         }
     }
 }
@@ -16,6 +16,7 @@ pub enum StatusBarAction {
 }
 
 /// Logic for managing the status bar at the bottom of the IDE.
+#[derive(Debug, Clone, Default)]
 pub struct StatusBarLogic {
     pub items: Vec<StatusBarItem>,
 }
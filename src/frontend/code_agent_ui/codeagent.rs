@@ -1,15 +1,705 @@
 use eframe::egui;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use crate::backend::code_agent::agent::AgentConfig;
+use crate::backend::code_agent::autoprompt::PromptSuggestion;
+use crate::backend::code_agent::code_agent_logic::{AgentStreamEvent, CodeAgentSystem};
+use crate::backend::code_agent::command_policy::CommandPolicy;
+use crate::backend::code_agent::completion::{Completion, FimContext};
+use crate::backend::code_agent::context_builder::{ContextEntry, ContextReport};
+use crate::backend::code_agent::diff::HunkLineKind;
+use crate::backend::code_agent::edit_proposal::{EditProposal, HunkDecision};
+use crate::backend::code_agent::instructions::command_approval::ApprovalDecision;
+use crate::backend::code_agent::instructions::project_instructions::InstructionScope;
+use crate::backend::code_agent::memory::agent_memory_logic::{MemoryKind, MemorySource};
+use crate::backend::code_agent::semantic_index::SemanticMatch;
+use crate::backend::code_agent::usage::UsageBudget;
+use crate::backend::settings_manager::AISettings;
+
+/// Results shown by the semantic search panel.
+const SEARCH_RESULTS: usize = 20;
+/// Lines of each result shown under its title.
+const SEARCH_PREVIEW_LINES: usize = 6;
+/// Prompt suggestion chips shown under the chat input.
+const SUGGESTIONS: usize = 5;
+/// How often suggestions are refreshed while the editor and terminal are unchanged,
+/// to pick up new diagnostics and git changes.
+const SUGGESTIONS_INTERVAL: Duration = Duration::from_secs(2);
 
 #[derive(Default)]
 pub struct CodeAgent {
     chat_input: String,
     messages: Vec<String>,
+    system: CodeAgentSystem,
+    /// Reply currently streaming into the last message.
+    pending: Option<mpsc::Receiver<AgentStreamEvent>>,
+    /// Tokens in `chat_input`, counted when the input or model last changed.
+    input_tokens: Option<(String, usize)>,
+    applied_settings: Option<AISettings>,
+    /// Agent edits waiting for the user to accept or reject them.
+    proposal: Option<EditProposal>,
+    /// New title being typed for the active session.
+    renaming: Option<String>,
+    /// Command lines being edited before approval, by pending command id.
+    command_edits: HashMap<u64, String>,
+    search_query: String,
+    search_results: Vec<SemanticMatch>,
+    /// Search running in the background.
+    search_pending: Option<mpsc::Receiver<Result<Vec<SemanticMatch>, String>>>,
+    /// Why the last search failed.
+    search_error: Option<String>,
+    /// A search result the user clicked, for the editor to open.
+    open_request: Option<PathBuf>,
+    memory_filter: String,
+    /// Memory entry being written or edited.
+    memory_draft: Option<MemoryDraft>,
+    /// Purge was clicked once and awaits confirmation.
+    confirm_purge: bool,
+    memory_error: Option<String>,
+    /// Slash commands and template load errors, read when the input starts with `/`.
+    slash_commands: Option<SlashCommands>,
+    /// Why the last slash command couldn't be expanded.
+    command_error: Option<String>,
+    /// The editor's active file name and caret line.
+    editor_cursor: Option<(String, usize)>,
+    terminal_output: Vec<String>,
+    suggestions: Vec<PromptSuggestion>,
+    suggestions_checked: Option<Instant>,
+    /// Set when the caret or terminal output changed since suggestions were asked for.
+    suggestions_stale: bool,
+    suggestions_pending: Option<mpsc::Receiver<Vec<PromptSuggestion>>>,
+}
+
+/// A memory entry in the editing form; `id` is `None` for a new entry.
+struct MemoryDraft {
+    id: Option<u64>,
+    kind: MemoryKind,
+    content: String,
+    /// Space or comma separated.
+    tags: String,
+}
+
+/// The prompt templates offered as slash commands.
+struct SlashCommands {
+    /// Name and description of each command.
+    commands: Vec<(String, String)>,
+    /// Template files that failed to load.
+    errors: Vec<String>,
+}
+
+/// What the user chose to do with the reviewed proposal.
+enum ReviewAction {
+    Apply,
+    Discard,
 }
 
 impl CodeAgent {
+    /// Point the agent at the configured provider, model and key.
+    pub fn apply_ai_settings(&mut self, settings: &AISettings) {
+        if self.applied_settings.as_ref() != Some(settings) {
+            self.system.set_config(AgentConfig::from(settings));
+            self.system.set_usage_budget(UsageBudget::from(settings));
+            self.system.set_auto_complete(settings.enable_auto_complete);
+            self.system.set_command_policy(CommandPolicy::from(settings));
+            self.applied_settings = Some(settings.clone());
+            self.input_tokens = None;
+        }
+    }
+    
+    /// Ask for ghost text at the editor's caret; ignored unless auto-complete is available.
+    pub fn request_completion(&mut self, context: FimContext) {
+        self.system.request_completion(context);
+    }
+    
+    /// A finished completion; keeps the UI repainting while one is on its way.
+    pub fn poll_completion(&mut self, ctx: &egui::Context) -> Option<Completion> {
+        let completion = self.system.poll_completion();
+        if self.system.completion_busy() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(50));
+        }
+        completion
+    }
+    
+    /// Scope the agent to a project and show its most recent chat.
+    pub fn open_project(&mut self, path: &Path) {
+        self.system.update_context(None, Some(path.to_string_lossy().to_string()));
+        self.reload_messages();
+    }
+    
+    /// Tell the agent which file the editor is showing.
+    pub fn set_current_file(&mut self, path: &Path) {
+        self.system.update_context(Some(path.to_string_lossy().to_string()), None);
+    }
+    
+    /// Where the editor's caret is and what the terminal printed last, for prompt suggestions.
+    pub fn set_editor_state(&mut self, cursor: Option<(&str, usize)>, terminal_output: Vec<String>) {
+        let cursor = cursor.map(|(file, line)| (file.to_string(), line));
+        if cursor != self.editor_cursor || terminal_output != self.terminal_output {
+            self.editor_cursor = cursor;
+            self.terminal_output = terminal_output;
+            self.suggestions_stale = true;
+        }
+    }
+    
+    /// A file to open in the editor, picked from the semantic search results.
+    pub fn take_open_request(&mut self) -> Option<PathBuf> {
+        self.open_request.take()
+    }
+    
+    /// Running token and cost totals for the status bar, flagged while a budget warning is active.
+    pub fn usage_status(&self) -> String {
+        let (session, project) = self.system.usage_totals();
+        if project.requests == 0 {
+            return String::new();
+        }
+        let warning = if self.system.budget_warnings().is_empty() { "" } else { "⚠ " };
+        format!("{}🪙 {} session · {} project · ${:.2}", warning, compact(session.tokens()), compact(project.tokens()), project.cost)
+    }
+    
+    /// Show the active session's history.
+    fn reload_messages(&mut self) {
+        self.messages = self.system.get_chat_history();
+        self.renaming = None;
+    }
+    
+    fn show_session_bar(&mut self, ui: &mut egui::Ui) {
+        let sessions = self.system.with_chat(|chat| chat.list_sessions()).unwrap_or_default();
+        let active_id = self.system.with_chat(|chat| chat.active_session_id().map(str::to_string)).flatten();
+        let active_title = sessions.iter().find(|s| Some(&s.id) == active_id.as_ref()).map(|s| s.title.clone()).unwrap_or_default();
+        let idle = self.pending.is_none();
+        
+        if let Some(mut title) = self.renaming.take() {
+            let mut editing = true;
+            ui.horizontal(|ui| {
+                let response = ui.text_edit_singleline(&mut title);
+                let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                if ui.button("Save").clicked() || submitted {
+                    if let Some(id) = &active_id {
+                        if let Some(Err(e)) = self.system.with_chat(|chat| chat.rename_session(id, &title)) {
+                            self.messages.push(format!("Error: {}", e));
+                        }
+                    }
+                    editing = false;
+                } else if ui.button("Cancel").clicked() {
+                    editing = false;
+                }
+            });
+            if editing {
+                self.renaming = Some(title);
+            }
+            return;
+        }
+        
+        ui.horizontal(|ui| {
+            let mut selected = None;
+            ui.add_enabled_ui(idle, |ui| {
+                egui::ComboBox::from_id_source("chat_session")
+                    .selected_text(active_title.as_str())
+                    .width(ui.available_width() - 90.0)
+                    .show_ui(ui, |ui| {
+                        for session in &sessions {
+                            if ui.selectable_label(Some(&session.id) == active_id.as_ref(), &session.title).clicked() {
+                                selected = Some(session.id.clone());
+                            }
+                        }
+                    });
+            });
+            if let Some(id) = selected {
+                if let Some(Err(e)) = self.system.with_chat(|chat| chat.switch_session(&id)) {
+                    self.messages.push(format!("Error: {}", e));
+                } else {
+                    self.reload_messages();
+                }
+            }
+            
+            if ui.add_enabled(idle, egui::Button::new("➕")).on_hover_text("New chat").clicked() {
+                self.system.with_chat(|chat| {
+                    chat.create_session();
+                });
+                self.reload_messages();
+            }
+            if ui.add_enabled(idle, egui::Button::new("✏")).on_hover_text("Rename chat").clicked() {
+                self.renaming = Some(active_title.clone());
+            }
+            if ui.add_enabled(idle && active_id.is_some(), egui::Button::new("🗑")).on_hover_text("Delete chat").clicked() {
+                if let Some(id) = &active_id {
+                    if let Some(Err(e)) = self.system.with_chat(|chat| chat.delete_session(id)) {
+                        self.messages.push(format!("Error: {}", e));
+                    } else {
+                        self.reload_messages();
+                    }
+                }
+            }
+        });
+    }
+    
+    fn send(&mut self, message: String) {
+        self.messages.push(format!("You: {}", message));
+        match self.system.send_message(message) {
+            Ok(receiver) => {
+                self.messages.push("AI: ".to_string());
+                self.pending = Some(receiver);
+            }
+            Err(e) => self.messages.push(format!("Error: {}", e)),
+        }
+    }
+    
+    fn poll_reply(&mut self, ctx: &egui::Context) {
+        // Taken while draining so the handlers below can borrow `self`; put back while the stream is open.
+        let Some(receiver) = self.pending.take() else {
+            return;
+        };
+        
+        loop {
+            match receiver.try_recv() {
+                Ok(AgentStreamEvent::Text(text)) => {
+                    if let Some(last) = self.messages.last_mut() {
+                        last.push_str(&text);
+                    }
+                }
+                Ok(AgentStreamEvent::ToolCall(call)) => {
+                    self.drop_empty_reply();
+                    self.messages.push(format!("🔧 {} {}", call.name, call.input));
+                }
+                Ok(AgentStreamEvent::ToolResult(result)) => {
+                    let first_line = result.content.lines().next().unwrap_or_default();
+                    let status = if result.is_error { "failed" } else { "done" };
+                    if let Some(last) = self.messages.last_mut() {
+                        last.push_str(&format!("\n  {}: {}", status, first_line));
+                    }
+                    // The model's next turn streams into a fresh message
+                    self.messages.push("AI: ".to_string());
+                }
+                Ok(AgentStreamEvent::Proposal(proposal)) => {
+                    self.proposal = Some(proposal);
+                }
+                Ok(AgentStreamEvent::BudgetWarning(warning)) => {
+                    // Shown above the reply, which hasn't started yet.
+                    let at = self.messages.len().saturating_sub(1);
+                    self.messages.insert(at, format!("⚠ {}", warning));
+                }
+                Ok(AgentStreamEvent::Done(_)) => {
+                    self.drop_empty_reply();
+                    return;
+                }
+                Ok(AgentStreamEvent::Error(e)) => {
+                    self.drop_empty_reply();
+                    self.messages.push(format!("Error: {}", e));
+                    return;
+                }
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    return;
+                }
+            }
+        }
+        
+        self.pending = Some(receiver);
+        // Keep repainting so text shows up as it arrives
+        ctx.request_repaint();
+    }
+    
+    /// Remove the trailing reply placeholder if nothing streamed into it.
+    fn drop_empty_reply(&mut self) {
+        if self.messages.last().is_some_and(|last| last.trim_end() == "AI:") {
+            self.messages.pop();
+        }
+    }
+    
+    fn apply_proposal(&mut self, proposal: EditProposal) {
+        let accepted = proposal.files.iter().flat_map(|file| &file.decisions).filter(|d| **d == HunkDecision::Accepted).count();
+        match self.system.apply_proposal(&proposal) {
+            Ok(()) => self.messages.push(format!("✅ Applied {} of {} changes", accepted, proposal.hunk_count())),
+            Err(e) => {
+                self.messages.push(format!("Error: {}", e));
+                self.proposal = Some(proposal);
+            }
+        }
+    }
+    
+    /// Per-hunk review of a proposal. Returns the action if a button was clicked.
+    fn show_proposal(ui: &mut egui::Ui, proposal: &mut EditProposal) -> Option<ReviewAction> {
+        let mut action = None;
+        ui.label(egui::RichText::new(format!("✏ Proposed edits: {}", proposal.description)).strong());
+        
+        for file in &mut proposal.files {
+            let (added, removed) = file.hunks.iter().fold((0, 0), |(a, r), hunk| (a + hunk.added(), r + hunk.removed()));
+            let title = format!("{}{}  +{} -{}", file.path.display(), if file.is_new_file() { " (new)" } else { "" }, added, removed);
+            egui::CollapsingHeader::new(title).default_open(true).show(ui, |ui| {
+                for (hunk, decision) in file.hunks.iter().zip(file.decisions.iter_mut()) {
+                    ui.horizontal(|ui| {
+                        ui.monospace(hunk.header());
+                        ui.selectable_value(decision, HunkDecision::Accepted, "✔ Accept");
+                        ui.selectable_value(decision, HunkDecision::Rejected, "✖ Reject");
+                    });
+                    for line in &hunk.lines {
+                        let (prefix, color) = match line.kind {
+                            HunkLineKind::Context => (' ', ui.visuals().weak_text_color()),
+                            HunkLineKind::Removed => ('-', egui::Color32::from_rgb(220, 90, 90)),
+                            HunkLineKind::Added => ('+', egui::Color32::from_rgb(90, 190, 90)),
+                        };
+                        let text = format!("{}{}", prefix, line.text.trim_end_matches('\n'));
+                        ui.label(egui::RichText::new(text).monospace().color(color));
+                    }
+                }
+            });
+        }
+        
+        ui.horizontal(|ui| {
+            if ui.button("Accept all").clicked() {
+                proposal.accept_all();
+            }
+            if ui.button("Reject all").clicked() {
+                proposal.reject_all();
+            }
+            let any_accepted = proposal.files.iter().any(|file| file.decisions.contains(&HunkDecision::Accepted));
+            if ui.add_enabled(any_accepted, egui::Button::new("Apply accepted")).clicked() {
+                action = Some(ReviewAction::Apply);
+            }
+            if ui.button("Discard").clicked() {
+                action = Some(ReviewAction::Discard);
+            }
+        });
+        action
+    }
+    
+    /// Commands the agent is waiting to run, each with approve, edit and reject.
+    fn show_pending_commands(&mut self, ui: &mut egui::Ui) {
+        let pending = self.system.pending_commands();
+        self.command_edits.retain(|id, _| pending.iter().any(|command| command.id == *id));
+        for command in pending {
+            let mut decision = None;
+            ui.label(egui::RichText::new(format!("⚠ The agent wants to run a command: {}", command.reason)).strong());
+            if let Some(edited) = self.command_edits.get_mut(&command.id) {
+                ui.add(egui::TextEdit::singleline(edited).code_editor().desired_width(f32::INFINITY));
+            } else {
+                ui.monospace(&command.instruction.text);
+            }
+            ui.weak(format!("in {}", command.dir.display()));
+            ui.horizontal(|ui| {
+                if ui.button("▶ Run").clicked() {
+                    decision = Some(match self.command_edits.get(&command.id) {
+                        Some(edited) if *edited != command.instruction.text => ApprovalDecision::Edit(edited.clone()),
+                        _ => ApprovalDecision::Approve,
+                    });
+                }
+                if !self.command_edits.contains_key(&command.id) && ui.button("✏ Edit").clicked() {
+                    self.command_edits.insert(command.id, command.instruction.text.clone());
+                }
+                if ui.button("✖ Reject").clicked() {
+                    decision = Some(ApprovalDecision::Reject("Rejected in the IDE".to_string()));
+                }
+            });
+            if let Some(decision) = decision {
+                self.command_edits.remove(&command.id);
+                if let Err(e) = self.system.decide_command(command.id, decision) {
+                    self.messages.push(format!("Error: {}", e));
+                }
+            }
+            ui.separator();
+        }
+    }
+    
+    /// The instruction files added to every prompt; clicking one opens it.
+    fn show_instructions(&mut self, ui: &mut egui::Ui) {
+        let instructions = self.system.instructions();
+        let files: Vec<(String, InstructionScope, PathBuf, bool)> = instructions
+            .files()
+            .iter()
+            .map(|file| (instructions.display_path(&file.path), file.scope, file.path.clone(), file.truncated))
+            .collect();
+        let title = match files.len() {
+            0 => "Instructions: none (add a JADIO.md to the project)".to_string(),
+            1 => "Instructions: 1 file".to_string(),
+            count => format!("Instructions: {} files", count),
+        };
+        egui::CollapsingHeader::new(title).id_source("instructions").show(ui, |ui| {
+            for (name, scope, path, truncated) in files {
+                ui.horizontal(|ui| {
+                    let scope = match scope {
+                        InstructionScope::User => "user",
+                        InstructionScope::Project => "project",
+                        InstructionScope::Directory => "directory",
+                    };
+                    ui.weak(scope);
+                    if ui.link(name).clicked() {
+                        self.open_request = Some(path);
+                    }
+                    if truncated {
+                        ui.weak("(truncated)");
+                    }
+                });
+            }
+            ui.weak("Later files take precedence.");
+        });
+    }
+    
+    /// What went into the last prompt, collapsed by default.
+    /// Chips for the prompts that fit what the editor and terminal show; clicking one fills the input.
+    fn show_suggestions(&mut self, ui: &mut egui::Ui) {
+        match self.suggestions_pending.as_ref().map(mpsc::Receiver::try_recv) {
+            Some(Ok(suggestions)) => {
+                self.suggestions = suggestions;
+                self.suggestions_pending = None;
+            }
+            Some(Err(mpsc::TryRecvError::Empty)) => ui.ctx().request_repaint_after(Duration::from_millis(100)),
+            Some(Err(mpsc::TryRecvError::Disconnected)) => self.suggestions_pending = None,
+            None => {}
+        }
+        let due = self.suggestions_stale || self.suggestions_checked.is_none_or(|checked| checked.elapsed() >= SUGGESTIONS_INTERVAL);
+        if self.pending.is_none() && self.suggestions_pending.is_none() && due {
+            self.suggestions_pending = Some(self.system.prompt_suggestions(self.editor_cursor.clone(), self.terminal_output.clone(), SUGGESTIONS));
+            self.suggestions_checked = Some(Instant::now());
+            self.suggestions_stale = false;
+        }
+        let mut chosen = None;
+        ui.horizontal_wrapped(|ui| {
+            for suggestion in &self.suggestions {
+                if ui.small_button(&suggestion.label).on_hover_text(&suggestion.reason).clicked() {
+                    chosen = Some(suggestion.clone());
+                }
+            }
+        });
+        if let Some(suggestion) = chosen {
+            if let Some(selection) = suggestion.selection {
+                self.system.with_context(|context| context.set_selection(Some(selection)));
+            }
+            self.chat_input = suggestion.input;
+            self.command_error = None;
+        }
+    }
+    
+    /// Commands matching the `/` input, and why the last one failed.
+    fn show_slash_commands(&mut self, ui: &mut egui::Ui) {
+        let Some(typed) = self.chat_input.trim_start().strip_prefix('/') else {
+            self.slash_commands = None;
+            self.command_error = None;
+            return;
+        };
+        let typed = typed.split_whitespace().next().unwrap_or_default().to_string();
+        if let Some(error) = &self.command_error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+        let SlashCommands { commands, errors } = self.slash_commands.get_or_insert_with(|| SlashCommands {
+            commands: self.system.slash_commands(),
+            errors: self.system.prompt_template_errors(),
+        });
+        let mut chosen = None;
+        for (name, description) in commands.iter().filter(|(name, _)| name.starts_with(&typed)) {
+            if ui.selectable_label(false, format!("/{}", name)).on_hover_text(description.as_str()).clicked() {
+                chosen = Some(name.clone());
+            }
+        }
+        for error in errors.iter() {
+            ui.colored_label(egui::Color32::YELLOW, format!("⚠ {}", error));
+        }
+        if let Some(name) = chosen {
+            self.chat_input = format!("/{} ", name);
+            self.command_error = None;
+        }
+    }
+    
+    fn show_context_report(ui: &mut egui::Ui, report: &ContextReport) {
+        let title = format!("Context: {} / {} tokens, {} dropped", report.used, report.budget, report.dropped.len());
+        egui::CollapsingHeader::new(title).id_source("context_report").show(ui, |ui| {
+            let entry_line = |entry: &ContextEntry| {
+                format!("{:?}: {} ({} tokens{})", entry.kind, entry.label, entry.tokens, if entry.truncated { ", truncated" } else { "" })
+            };
+            for entry in &report.included {
+                ui.label(egui::RichText::new(entry_line(entry)).small());
+            }
+            for entry in &report.dropped {
+                ui.label(egui::RichText::new(format!("✖ {}", entry_line(entry))).small().weak());
+            }
+        });
+    }
+    
+    /// Search the project's code by meaning rather than by text.
+    pub fn show_semantic_search(&mut self, ui: &mut egui::Ui) {
+        ui.vertical(|ui| {
+            ui.heading("🔍 Semantic Search");
+            match self.system.semantic_index_status() {
+                None => {
+                    ui.weak("Open a project and set an embedding model for a local or custom provider in the AI settings.");
+                    return;
+                }
+                Some(status) => {
+                    let state = if status.ready { "Indexed" } else { "Indexing" };
+                    ui.weak(format!("{} {} files, {} chunks", state, status.files, status.chunks));
+                    if let Some(error) = status.error {
+                        ui.colored_label(egui::Color32::from_rgb(220, 120, 60), error);
+                    }
+                    if !status.ready {
+                        ui.ctx().request_repaint_after(std::time::Duration::from_millis(500));
+                    }
+                }
+            }
+            
+            ui.horizontal(|ui| {
+                let response = ui.add(egui::TextEdit::singleline(&mut self.search_query).hint_text("e.g. where are settings saved?"));
+                let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                if (ui.button("Search").clicked() || submitted) && !self.search_query.trim().is_empty() {
+                    match self.system.semantic_search(self.search_query.trim(), SEARCH_RESULTS) {
+                        Ok(receiver) => self.search_pending = Some(receiver),
+                        Err(e) => (self.search_results, self.search_error) = (Vec::new(), Some(e)),
+                    }
+                }
+                if self.search_pending.is_some() {
+                    ui.spinner();
+                }
+            });
+            ui.separator();
+            
+            match self.search_pending.as_ref().map(mpsc::Receiver::try_recv) {
+                Some(Ok(result)) => {
+                    match result {
+                        Ok(results) => (self.search_results, self.search_error) = (results, None),
+                        Err(e) => (self.search_results, self.search_error) = (Vec::new(), Some(e)),
+                    }
+                    self.search_pending = None;
+                }
+                Some(Err(mpsc::TryRecvError::Empty)) => ui.ctx().request_repaint_after(std::time::Duration::from_millis(100)),
+                Some(Err(mpsc::TryRecvError::Disconnected)) => self.search_pending = None,
+                None => {}
+            }
+            
+            if let Some(error) = &self.search_error {
+                ui.colored_label(egui::Color32::from_rgb(220, 80, 80), error);
+            }
+            egui::ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
+                for result in &self.search_results {
+                    let name = result.path.file_name().unwrap_or_default().to_string_lossy();
+                    let title = match &result.symbol {
+                        Some(symbol) => format!("{} · {}:{}-{}", symbol, name, result.start_line, result.end_line),
+                        None => format!("{}:{}-{}", name, result.start_line, result.end_line),
+                    };
+                    ui.horizontal(|ui| {
+                        if ui.link(title).on_hover_text(result.path.display().to_string()).clicked() {
+                            self.open_request = Some(result.path.clone());
+                        }
+                        ui.weak(format!("{:.2}", result.score));
+                    });
+                    let preview: Vec<&str> = result.text.lines().take(SEARCH_PREVIEW_LINES).collect();
+                    ui.label(egui::RichText::new(preview.join("\n")).monospace().small());
+                    ui.separator();
+                }
+            });
+        });
+    }
+    
+    /// Review, edit and purge the project's long-term memory.
+    pub fn show_memory(&mut self, ui: &mut egui::Ui) {
+        ui.vertical(|ui| {
+            ui.heading("📒 Project Memory");
+            let (entries, load_error) = self
+                .system
+                .with_memory(|memory| (memory.entries().to_vec(), memory.load_error().map(str::to_string)))
+                .unwrap_or_default();
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(&mut self.memory_filter).hint_text("Filter by text or tag"));
+                if ui.button("➕ Add").clicked() {
+                    self.memory_draft = Some(MemoryDraft { id: None, kind: MemoryKind::Fact, content: String::new(), tags: String::new() });
+                }
+                if self.confirm_purge {
+                    if ui.button("Forget all").clicked() {
+                        self.memory_error = self.system.with_memory(|memory| memory.purge()).and_then(Result::err);
+                        self.confirm_purge = false;
+                    }
+                    if ui.button("Keep").clicked() {
+                        self.confirm_purge = false;
+                    }
+                } else if ui.add_enabled(!entries.is_empty() || load_error.is_some(), egui::Button::new("🗑 Purge")).clicked() {
+                    self.confirm_purge = true;
+                }
+            });
+            if let Some(error) = &load_error {
+                ui.colored_label(egui::Color32::from_rgb(220, 80, 80), format!("{}. Nothing is saved until the file is fixed or purged.", error));
+            }
+            if let Some(error) = &self.memory_error {
+                ui.colored_label(egui::Color32::from_rgb(220, 80, 80), error);
+            }
+            
+            if let Some(draft) = &mut self.memory_draft {
+                let mut done = None;
+                ui.group(|ui| {
+                    egui::ComboBox::from_id_source("memory_kind").selected_text(draft.kind.name()).show_ui(ui, |ui| {
+                        for kind in MemoryKind::ALL {
+                            ui.selectable_value(&mut draft.kind, kind, kind.name());
+                        }
+                    });
+                    ui.add(egui::TextEdit::multiline(&mut draft.content).desired_rows(3).desired_width(f32::INFINITY));
+                    ui.horizontal(|ui| {
+                        ui.label("Tags:");
+                        ui.text_edit_singleline(&mut draft.tags);
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.button("Save").clicked() {
+                            done = Some(true);
+                        }
+                        if ui.button("Cancel").clicked() {
+                            done = Some(false);
+                        }
+                    });
+                });
+                match done {
+                    Some(true) => {
+                        let tags: Vec<String> = draft.tags.split([' ', ',']).map(str::to_string).collect();
+                        let saved = self.system.with_memory(|memory| match draft.id {
+                            Some(id) => memory.update(id, draft.kind, &draft.content, &tags),
+                            None => memory.add(draft.kind, &draft.content, &tags, MemorySource::User).map(|_| ()),
+                        });
+                        self.memory_error = saved.and_then(Result::err);
+                        if self.memory_error.is_none() {
+                            self.memory_draft = None;
+                        }
+                    }
+                    Some(false) => self.memory_draft = None,
+                    None => {}
+                }
+            }
+            ui.separator();
+            
+            if entries.is_empty() {
+                ui.weak("Nothing remembered yet. The agent saves notes here with its remember tool, and you can add your own.");
+            }
+            let filter = self.memory_filter.trim().trim_start_matches('#').to_lowercase();
+            egui::ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
+                for entry in entries.iter().rev() {
+                    if !filter.is_empty() && !entry.content.to_lowercase().contains(&filter) && !entry.tags.iter().any(|tag| tag.contains(&filter)) {
+                        continue;
+                    }
+                    ui.horizontal(|ui| {
+                        ui.strong(entry.kind.name());
+                        let author = if entry.source == MemorySource::Agent { "agent" } else { "you" };
+                        ui.weak(format!("by {} · {}", author, entry.updated.format("%Y-%m-%d %H:%M")));
+                    });
+                    ui.label(&entry.content);
+                    if !entry.tags.is_empty() {
+                        ui.weak(entry.tags.iter().map(|tag| format!("#{}", tag)).collect::<Vec<_>>().join(" "));
+                    }
+                    ui.horizontal(|ui| {
+                        if ui.small_button("✏ Edit").clicked() {
+                            self.memory_draft = Some(MemoryDraft { id: Some(entry.id), kind: entry.kind, content: entry.content.clone(), tags: entry.tags.join(" ") });
+                        }
+                        if ui.small_button("✖ Forget").clicked() {
+                            self.memory_error = self.system.with_memory(|memory| memory.remove(entry.id)).and_then(Result::err);
+                        }
+                    });
+                    ui.separator();
+                }
+            });
+        });
+    }
+    
     pub fn show(&mut self, ui: &mut egui::Ui) {
+        self.poll_reply(ui.ctx());
+        
         ui.vertical(|ui| {
             ui.heading("🤖 Code Agent");
+            self.show_session_bar(ui);
+            self.show_instructions(ui);
             
             // Chat history
             egui::ScrollArea::vertical()
@@ -29,39 +719,78 @@ impl CodeAgent {
 
             ui.separator();
 
+            // Review of the agent's proposed edits
+            if let Some(mut proposal) = self.proposal.take() {
+                egui::ScrollArea::vertical().id_source("edit_proposal").max_height(300.0).show(ui, |ui| {
+                    match Self::show_proposal(ui, &mut proposal) {
+                        Some(ReviewAction::Apply) => self.apply_proposal(proposal),
+                        Some(ReviewAction::Discard) => self.messages.push("Discarded proposed edits".to_string()),
+                        None => self.proposal = Some(proposal),
+                    }
+                });
+                ui.separator();
+            }
+            
+            self.show_pending_commands(ui);
+            
+            if let Some(turn) = self.system.last_agent_turn().map(str::to_string) {
+                if ui.button(format!("↩ Revert last agent turn: {}", turn)).clicked() {
+                    match self.system.revert_last_agent_turn() {
+                        Ok(description) => self.messages.push(format!("↩ Reverted: {}", description)),
+                        Err(e) => self.messages.push(format!("Error: {}", e)),
+                    }
+                }
+            }
+
+            if let Some(report) = self.system.last_context_report() {
+                Self::show_context_report(ui, &report);
+            }
+
             // Input area
             ui.horizontal(|ui| {
                 let response = ui.text_edit_singleline(&mut self.chat_input);
+                let busy = self.pending.is_some();
+                if !self.chat_input.trim().is_empty() {
+                    let tokens = match &self.input_tokens {
+                        Some((counted, tokens)) if *counted == self.chat_input => *tokens,
+                        _ => {
+                            let tokens = self.system.count_tokens(&self.chat_input);
+                            self.input_tokens = Some((self.chat_input.clone(), tokens));
+                            tokens
+                        }
+                    };
+                    ui.weak(format!("~{} tokens", tokens));
+                }
                 
-                if ui.button("Send").clicked() || (response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter))) {
-                    if !self.chat_input.trim().is_empty() {
-                        self.messages.push(format!("You: {}", self.chat_input));
-                        self.messages.push(format!("AI: I received your message: '{}'", self.chat_input));
-                        self.chat_input.clear();
+                let submitted = ui.add_enabled(!busy, egui::Button::new("Send")).clicked() || (response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)));
+                if submitted && !busy && !self.chat_input.trim().is_empty() {
+                    // Slash commands are sent as their template's prompt; on error the input is kept to fix.
+                    match self.system.expand_slash_command(&self.chat_input) {
+                        Some(Ok(prompt)) => {
+                            self.chat_input.clear();
+                            self.send(prompt);
+                        }
+                        Some(Err(e)) => self.command_error = Some(e),
+                        None => {
+                            let message = std::mem::take(&mut self.chat_input);
+                            self.send(message);
+                        }
                     }
                 }
             });
+            self.show_slash_commands(ui);
 
             ui.separator();
-
-            // Quick action buttons
-            ui.horizontal_wrapped(|ui| {
-                if ui.button("📝 Review Code").clicked() {
-                    self.messages.push("AI: I'll review your code for improvements.".to_string());
-                }
-                
-                if ui.button("🐛 Find Bugs").clicked() {
-                    self.messages.push("AI: Scanning for potential bugs...".to_string());
-                }
-                
-                if ui.button("📚 Explain Code").clicked() {
-                    self.messages.push("AI: I'll explain the current code for you.".to_string());
-                }
-                
-                if ui.button("⚡ Optimize").clicked() {
-                    self.messages.push("AI: Looking for optimization opportunities...".to_string());
-                }
-            });
+            self.show_suggestions(ui);
         });
     }
-}
\ No newline at end of file
+}
+
+/// A token count for display, e.g. `950` or `12.3k`.
+fn compact(tokens: u64) -> String {
+    match tokens {
+        0..=999 => tokens.to_string(),
+        1_000..=999_999 => format!("{:.1}k", tokens as f64 / 1_000.0),
+        _ => format!("{:.1}M", tokens as f64 / 1_000_000.0),
+    }
+}
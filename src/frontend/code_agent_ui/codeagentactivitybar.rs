@@ -1,34 +1,30 @@
 use eframe::egui;
+use crate::backend::activity_bar_right::activity_bar_right_logic::{ActivityBarRightLogic, CodeAgentActivityItem};
 
 #[derive(Default)]
 pub struct CodeAgentActivityBar {
-    // State for code agent activity bar
+    /// Which code agent panel is shown.
+    pub logic: ActivityBarRightLogic,
 }
 
 impl CodeAgentActivityBar {
     pub fn show(&mut self, ui: &mut egui::Ui) {
         ui.vertical(|ui| {
             ui.spacing_mut().item_spacing.y = 8.0;
-            
-            if ui.button("🤖").on_hover_text("AI Assistant").clicked() {
-                // TODO: Toggle AI assistant
-            }
-            
-            if ui.button("🧠").on_hover_text("Code Analysis").clicked() {
-                // TODO: Toggle code analysis
-            }
-            
-            if ui.button("💡").on_hover_text("Suggestions").clicked() {
-                // TODO: Toggle suggestions
-            }
-            
-            if ui.button("📊").on_hover_text("Code Metrics").clicked() {
-                // TODO: Toggle code metrics
-            }
-            
-            if ui.button("🔍").on_hover_text("Code Search").clicked() {
-                // TODO: Toggle code search
+
+            let items = [
+                ("🤖", "AI Assistant", CodeAgentActivityItem::Assistant),
+                ("🧠", "Code Analysis", CodeAgentActivityItem::CodeAnalysis),
+                ("💡", "Suggestions", CodeAgentActivityItem::Suggestions),
+                ("📊", "Code Metrics", CodeAgentActivityItem::CodeMetrics),
+                ("🔍", "Semantic Code Search", CodeAgentActivityItem::CodeSearch),
+                ("📒", "Project Memory", CodeAgentActivityItem::Memory),
+            ];
+            for (icon, hover, item) in items {
+                if ui.selectable_label(self.logic.is_active(&item), icon).on_hover_text(hover).clicked() {
+                    self.logic.set_active(item);
+                }
             }
         });
     }
-}
\ No newline at end of file
+}
@@ -1,20 +1,121 @@
 use eframe::egui;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use crate::backend::code_agent::completion::{Completion, FimContext, GhostText};
+use crate::backend::code_editor::code_editor_logic::{CodeEditorLogic, EditorTab};
+use crate::backend::code_editor::lsp::client::{LspEvent, PendingRequest};
+use crate::backend::code_editor::lsp::protocol::{self, Diagnostic, DiagnosticSeverity, Hover, Position};
+use crate::backend::code_editor::lsp::LspManager;
+use crate::backend::code_editor::selection::{self, Selection};
+use crate::backend::code_editor::theme::ThemeManager;
+use crate::backend::code_editor::undo_history::EditKind;
+use crate::backend::settings_manager::Theme;
 
 #[derive(Default)]
 pub struct Editor {
-    files: HashMap<String, FileContent>,
-    open_files: Vec<String>,
-    active_file: Option<String>,
-    unsaved_changes: std::collections::HashSet<String>,
+    /// Open tabs with their text buffers, highlighting and undo history.
+    logic: CodeEditorLogic,
+    /// Widget state for each tab, in the same order as `logic.tabs`.
+    views: Vec<TabView>,
+    themes: ThemeManager,
+    /// Bumped whenever the theme changes, to invalidate cached layouts.
+    theme_generation: u64,
+    /// Caret context to ask the code agent to complete, taken once per frame.
+    completion_request: Option<FimContext>,
+    /// Language servers for the open workspace.
+    lsp: Option<LspManager>,
+    /// File to open for a definition outside the open tabs, taken once per frame.
+    open_request: Option<PathBuf>,
+    /// Where to put the caret once that file is opened.
+    pending_jump: Option<(PathBuf, Position)>,
 }
 
-#[derive(Clone)]
-struct FileContent {
-    content: String,
-    language: String,
+struct TabView {
+    /// Name shown in the tab bar.
+    name: String,
+    /// The tab's text for the text widget, which reads its buffer as one string.
+    /// Typing is mirrored here edit by edit rather than read back from the piece table.
+    text: String,
+    /// Byte offset of the primary caret in `text`.
     cursor_position: usize,
-    scroll_offset: egui::Vec2,
+    /// Carets/selections beyond the primary one owned by the text widget.
+    extra_selections: Vec<Selection>,
+    /// Where an Alt+Shift drag started, for column selection.
+    column_anchor: Option<usize>,
+    /// Highlighted layout of the tab, keyed by theme generation and tab revision.
+    layout_cache: Option<((u64, u64), egui::text::LayoutJob)>,
+    /// Layout sections of each highlighted line by line id, for the theme generation
+    /// they were built with, so a new layout only styles the lines that were lexed again.
+    line_sections: (u64, HashMap<u64, Vec<egui::text::LayoutSection>>),
+    /// Completion suggested at the caret, shown until accepted or typed past.
+    ghost: Option<GhostText>,
+    /// Hover request in flight, with the offset it asks about.
+    hover_request: Option<(usize, PendingRequest)>,
+    /// Hover text from the language server and the offset it is for.
+    hover: Option<(usize, String)>,
+    definition_request: Option<PendingRequest>,
+    /// Caret to move to on the next frame, e.g. after going to a definition.
+    pending_caret: Option<usize>,
+}
+
+impl TabView {
+    fn new(name: String, text: String) -> Self {
+        Self {
+            name,
+            text,
+            cursor_position: 0,
+            extra_selections: Vec::new(),
+            column_anchor: None,
+            layout_cache: None,
+            line_sections: (0, HashMap::new()),
+            ghost: None,
+            hover_request: None,
+            hover: None,
+            definition_request: None,
+            pending_caret: None,
+        }
+    }
+}
+
+/// The text widget's buffer for the current tab. Edits go to the tab's piece table,
+/// undo history and highlighting, and are mirrored in the view's copy of the text.
+struct TabText<'a, 'b> {
+    logic: &'a RefCell<&'b mut CodeEditorLogic>,
+    text: &'a mut String,
+    /// How inserted text is grouped in the undo history.
+    insert_kind: EditKind,
+}
+
+impl egui::TextBuffer for TabText<'_, '_> {
+    fn is_mutable(&self) -> bool {
+        true
+    }
+
+    fn as_str(&self) -> &str {
+        self.text.as_str()
+    }
+
+    fn insert_text(&mut self, text: &str, char_index: usize) -> usize {
+        let offset = self.byte_index_from_char_index(char_index);
+        self.logic.borrow_mut().apply_edit_with_kind(offset..offset, text, self.insert_kind);
+        self.text.insert_str(offset, text);
+        text.chars().count()
+    }
+
+    fn delete_char_range(&mut self, char_range: Range<usize>) {
+        let range = self.byte_index_from_char_index(char_range.start)..self.byte_index_from_char_index(char_range.end);
+        self.logic.borrow_mut().apply_edit_with_kind(range.clone(), "", EditKind::Deletion);
+        self.text.replace_range(range, "");
+    }
+}
+
+/// Text input taken over from the text widget while several carets are active.
+enum MultiCursorInput {
+    Insert(String),
+    Paste(String),
+    Backspace,
 }
 
 impl Editor {
@@ -22,97 +123,596 @@ impl Editor {
         let mut editor = Self::default();
         
         // Create a default file
-        let default_content = FileContent {
-            content: "// Welcome to JadioAI IDE\n// Start coding here!\n\nfn main() {\n    println!(\"Hello, World!\");\n}".to_string(),
-            language: "rust".to_string(),
-            cursor_position: 0,
-            scroll_offset: egui::Vec2::ZERO,
-        };
-        
-        editor.files.insert("main.rs".to_string(), default_content);
-        editor.open_files.push("main.rs".to_string());
-        editor.active_file = Some("main.rs".to_string());
+        let default_content = "// Welcome to JadioAI IDE\n// Start coding here!\n\nfn main() {\n    println!(\"Hello, World!\");\n}".to_string();
+        editor.open_file("main.rs".to_string(), default_content);
         
         editor
     }
 
+    /// Open an untitled tab. Its language is taken from the name.
     pub fn open_file(&mut self, filename: String, content: String) {
-        let file_content = FileContent {
-            content,
-            language: Self::detect_language(&filename),
-            cursor_position: 0,
-            scroll_offset: egui::Vec2::ZERO,
+        let language = self.logic.highlighter.language_for_path(Path::new(&filename));
+        self.logic.open_text(None, content.clone());
+        self.logic.set_current_language(language);
+        self.views.push(TabView::new(filename, content));
+    }
+
+    /// Open a file read from disk, or switch to its tab if it is already open.
+    pub fn open_path(&mut self, path: PathBuf, content: String) {
+        match self.logic.tabs.iter().position(|tab| tab.file_path.as_ref() == Some(&path)) {
+            Some(index) => self.logic.current_tab = index,
+            None => {
+                let name = path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().to_string());
+                self.logic.open_text(Some(path.clone()), content.clone());
+                self.views.push(TabView::new(name, content));
+                if let (Some(lsp), Some(tab)) = (&mut self.lsp, self.logic.current()) {
+                    let _ = lsp.did_open(tab);
+                }
+            }
+        }
+        if let Some((_, position)) = self.pending_jump.take_if(|(jump, _)| *jump == path) {
+            self.jump_to_position(position);
+        }
+    }
+
+    /// Start language servers for a workspace on demand and tell them about the open files.
+    pub fn set_workspace(&mut self, root: PathBuf) {
+        let mut lsp = LspManager::new(root);
+        for tab in &mut self.logic.tabs {
+            // The server gets the whole text on open, so earlier changes are moot.
+            tab.take_content_changes();
+            let _ = lsp.did_open(tab);
+        }
+        self.lsp = Some(lsp);
+    }
+
+    /// A file to open because a definition is in it, if any.
+    pub fn take_open_request(&mut self) -> Option<PathBuf> {
+        self.open_request.take()
+    }
+
+    /// The active file's name and the 1-based line of its caret.
+    pub fn cursor_line(&self) -> Option<(&str, usize)> {
+        let view = self.views.get(self.logic.current_tab)?;
+        let before = view.text.get(..view.cursor_position).unwrap_or(&view.text);
+        let line = before.matches('\n').count() + 1;
+        Some((&view.name, line))
+    }
+
+    /// The completion request raised by the last edit, if any.
+    pub fn take_completion_request(&mut self) -> Option<FimContext> {
+        self.completion_request.take()
+    }
+
+    /// Show a completion as ghost text if the caret and text are still where it was asked for.
+    pub fn set_ghost_text(&mut self, completion: Completion) {
+        let (Some(tab), Some(view)) = (self.logic.current(), self.views.get_mut(self.logic.current_tab)) else {
+            return;
         };
-        
-        self.files.insert(filename.clone(), file_content);
-        
-        if !self.open_files.contains(&filename) {
-            self.open_files.push(filename.clone());
+        let context = FimContext::new(&view.text, view.cursor_position, Self::tab_path(tab, view));
+        if view.extra_selections.is_empty() && context.offset == completion.offset && context.key() == completion.key {
+            view.ghost = Some(GhostText { offset: completion.offset, text: completion.text });
         }
-        
-        self.active_file = Some(filename);
     }
 
-    fn detect_language(filename: &str) -> String {
-        match filename.split('.').last().unwrap_or("") {
-            "rs" => "rust".to_string(),
-            "py" => "python".to_string(),
-            "js" => "javascript".to_string(),
-            "ts" => "typescript".to_string(),
-            "html" => "html".to_string(),
-            "css" => "css".to_string(),
-            "json" => "json".to_string(),
-            "yaml" | "yml" => "yaml".to_string(),
-            "toml" => "toml".to_string(),
-            "md" => "markdown".to_string(),
-            _ => "text".to_string(),
+    /// The path sent with completion requests: the file's, or its name for untitled tabs.
+    fn tab_path(tab: &EditorTab, view: &TabView) -> Option<PathBuf> {
+        tab.file_path.clone().or_else(|| Some(PathBuf::from(&view.name)))
+    }
+
+    pub fn close_file(&mut self, index: usize) {
+        if let Some(tab) = self.logic.close_tab(index) {
+            self.views.remove(index);
+            if let Some(lsp) = &mut self.lsp {
+                let _ = lsp.did_close(&tab);
+            }
+        }
+    }
+
+    /// Save the tab with this name. Untitled tabs have nowhere to go and are left as they are.
+    pub fn save_file(&mut self, filename: &str) -> std::io::Result<()> {
+        let Some(index) = self.views.iter().position(|view| view.name == filename) else {
+            return Ok(());
+        };
+        let current = std::mem::replace(&mut self.logic.current_tab, index);
+        let result = self.logic.save_current();
+        if let (Ok(true), Some(lsp), Some(tab)) = (&result, &mut self.lsp, self.logic.current()) {
+            let _ = lsp.did_save(tab);
+        }
+        self.logic.current_tab = current;
+        result.map(|_| ())
+    }
+
+    /// Follow the UI theme from settings, switching the editor colour theme if it changed.
+    pub fn set_ui_theme(&mut self, theme: &Theme) {
+        if self.themes.apply_ui_theme(theme) {
+            self.logic.set_theme(self.themes.active());
+            self.theme_generation += 1;
+        }
+    }
+
+    /// Apply what the language servers sent since the last frame and collect hover and definition replies.
+    fn poll_lsp(&mut self) {
+        let Some(lsp) = &mut self.lsp else {
+            return;
+        };
+        // Errors and exits are kept by the manager and shown next to the language.
+        for event in lsp.poll_events() {
+            if let LspEvent::ApplyEdit(edit) = event {
+                self.logic.apply_workspace_edit(&edit);
+                for (tab, view) in self.logic.tabs.iter_mut().zip(&mut self.views) {
+                    view.text = tab.content();
+                    let _ = lsp.did_change(tab);
+                }
+            }
+        }
+
+        let Some(view) = self.views.get_mut(self.logic.current_tab) else {
+            return;
+        };
+        if let Some((offset, result)) = view.hover_request.as_ref().and_then(|(offset, request)| Some((*offset, request.try_take()?))) {
+            let contents = result.ok().and_then(|value| Hover::from_value(&value)).map(|hover| hover.contents);
+            view.hover = Some((offset, contents.unwrap_or_default()));
+            view.hover_request = None;
+        }
+        if let Some(result) = view.definition_request.as_ref().and_then(PendingRequest::try_take) {
+            view.definition_request = None;
+            if let Some(location) = result.ok().and_then(|value| protocol::locations_from_value(&value).into_iter().next()) {
+                match protocol::uri_to_path(&location.uri) {
+                    Some(path) if self.logic.current().and_then(|tab| tab.file_path.as_ref()) == Some(&path) => {
+                        self.jump_to_position(location.range.start);
+                    }
+                    Some(path) => {
+                        self.open_request = Some(path.clone());
+                        self.pending_jump = Some((path, location.range.start));
+                    }
+                    None => {}
+                }
+            }
+        }
+    }
+
+    /// Move the current tab's caret to an LSP position on the next frame.
+    fn jump_to_position(&mut self, position: Position) {
+        if let (Some(tab), Some(view)) = (self.logic.current(), self.views.get_mut(self.logic.current_tab)) {
+            view.pending_caret = Some(protocol::position_to_offset(&tab.buffer, position));
+        }
+    }
+
+    /// Parse `#rrggbb` or `#rrggbbaa` into a colour, falling back to light grey.
+    fn hex_to_color(hex: &str) -> egui::Color32 {
+        let hex = hex.trim_start_matches('#');
+        let channel = |i: usize| hex.get(i..i + 2).and_then(|c| u8::from_str_radix(c, 16).ok());
+        match (channel(0), channel(2), channel(4), channel(6)) {
+            (Some(r), Some(g), Some(b), Some(a)) => egui::Color32::from_rgba_unmultiplied(r, g, b, a),
+            (Some(r), Some(g), Some(b), None) => egui::Color32::from_rgb(r, g, b),
+            _ => egui::Color32::from_rgb(212, 212, 212),
+        }
+    }
+
+    /// Build a layout of the text from the tab's highlighted lines. Sections of lines
+    /// that weren't lexed again since the last layout are taken from `line_sections`,
+    /// which is left holding the sections of the current lines. A line whose tokens
+    /// no longer match the text is laid out plain.
+    fn highlight_layout(tab: &EditorTab, text: &str, font_id: egui::FontId, line_sections: &mut HashMap<u64, Vec<egui::text::LayoutSection>>) -> egui::text::LayoutJob {
+        let mut job = egui::text::LayoutJob { text: text.to_string(), ..Default::default() };
+        let plain = egui::TextFormat::simple(font_id.clone(), egui::Color32::GRAY);
+        let section = |byte_range: Range<usize>, format: egui::TextFormat| egui::text::LayoutSection { leading_space: 0.0, byte_range, format };
+        let mut previous = std::mem::take(line_sections);
+        let mut start = 0;
+        for (line, (tokens, id)) in text.split_inclusive('\n').zip(tab.highlighted.iter().zip(&tab.line_ids)) {
+            let content = line.strip_suffix('\n').unwrap_or(line);
+            let content = content.strip_suffix('\r').unwrap_or(content);
+            let sections = previous
+                .remove(id)
+                .filter(|sections| match sections.last() {
+                    Some(last) => last.byte_range.end == content.len() && last.format.font_id == font_id,
+                    None => content.is_empty(),
+                })
+                .unwrap_or_else(|| {
+                    if tokens.iter().map(|token| token.text.len()).sum::<usize>() != content.len() {
+                        return vec![section(0..content.len(), plain.clone())];
+                    }
+                    let mut offset = 0;
+                    tokens.iter().map(|token| {
+                        let format = egui::TextFormat {
+                            font_id: font_id.clone(),
+                            color: Self::hex_to_color(&token.style.color),
+                            italics: token.style.italic,
+                            ..Default::default()
+                        };
+                        offset += token.text.len();
+                        section(offset - token.text.len()..offset, format)
+                    }).collect()
+                });
+            job.sections.extend(sections.iter().map(|line_section| section(start + line_section.byte_range.start..start + line_section.byte_range.end, line_section.format.clone())));
+            if content.len() < line.len() {
+                job.sections.push(section(start + content.len()..start + line.len(), plain.clone()));
+            }
+            line_sections.insert(*id, sections);
+            start += line.len();
+        }
+        job
+    }
+
+    fn char_to_byte(text: &str, char_index: usize) -> usize {
+        text.char_indices().nth(char_index).map_or(text.len(), |(i, _)| i)
+    }
+
+    fn byte_to_char(text: &str, byte_index: usize) -> usize {
+        text[..byte_index.min(text.len())].chars().count()
+    }
+
+    fn selection_from_ccursors(text: &str, range: egui::text::CCursorRange) -> Selection {
+        Selection::new(
+            Self::char_to_byte(text, range.secondary.index),
+            Self::char_to_byte(text, range.primary.index),
+        )
+    }
+
+    /// Make `selection` the text widget's own (primary) selection.
+    fn store_primary(ctx: &egui::Context, edit_id: egui::Id, text: &str, selection: Selection) {
+        let mut state = egui::TextEdit::load_state(ctx, edit_id).unwrap_or_default();
+        state.set_ccursor_range(Some(egui::text::CCursorRange::two(
+            egui::text::CCursor::new(Self::byte_to_char(text, selection.anchor)),
+            egui::text::CCursor::new(Self::byte_to_char(text, selection.head)),
+        )));
+        egui::TextEdit::store_state(ctx, edit_id, state);
+    }
+
+    /// Handle keyboard input for multiple carets before the text widget sees it.
+    /// Returns true if the text was changed.
+    fn handle_multi_cursor_input(ui: &egui::Ui, edit_id: egui::Id, logic: &mut CodeEditorLogic, view: &mut TabView, primary: Selection) -> bool {
+        // Ctrl+D: select the next occurrence and make it the primary selection.
+        if ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::D)) {
+            let mut all = view.extra_selections.clone();
+            all.push(primary);
+            if selection::add_next_occurrence(&view.text, &mut all) {
+                if let Some(new_primary) = all.pop() {
+                    Self::store_primary(ui.ctx(), edit_id, &view.text, new_primary);
+                }
+                view.extra_selections = all;
+            }
+            return false;
+        }
+
+        if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+            view.extra_selections.clear();
+        }
+        if view.extra_selections.is_empty() {
+            return false;
+        }
+
+        // Take over text input so it is applied at every caret.
+        let mut inputs = Vec::new();
+        ui.input_mut(|i| {
+            i.events.retain(|event| match event {
+                egui::Event::Text(text) => {
+                    inputs.push(MultiCursorInput::Insert(text.clone()));
+                    false
+                }
+                egui::Event::Paste(text) => {
+                    inputs.push(MultiCursorInput::Paste(text.clone()));
+                    false
+                }
+                egui::Event::Key { key: egui::Key::Enter, pressed: true, .. } => {
+                    inputs.push(MultiCursorInput::Insert("\n".to_string()));
+                    false
+                }
+                egui::Event::Key { key: egui::Key::Backspace, pressed: true, .. } => {
+                    inputs.push(MultiCursorInput::Backspace);
+                    false
+                }
+                _ => true,
+            })
+        });
+        if inputs.is_empty() {
+            return false;
+        }
+
+        let mut primary = primary;
+        for input in inputs {
+            let mut all = view.extra_selections.clone();
+            all.push(primary);
+            selection::normalize_selections(&mut all);
+            let primary_index = all.iter().position(|s| s.range().contains(&primary.head) || s.range().end == primary.head).unwrap_or(all.len() - 1);
+
+            logic.set_selections(all);
+            match &input {
+                MultiCursorInput::Insert(text) => logic.type_text(text),
+                MultiCursorInput::Paste(text) => logic.paste(text),
+                MultiCursorInput::Backspace => logic.delete_backward(),
+            }
+            let mut carets = logic.selections().to_vec();
+            primary = carets.remove(primary_index.min(carets.len() - 1));
+            view.extra_selections = carets;
+        }
+        view.text = logic.current_content().unwrap_or_default();
+        Self::store_primary(ui.ctx(), edit_id, &view.text, primary);
+        true
+    }
+
+    /// Handle undo and redo with the tab's history before the text widget's own undoer sees them.
+    /// Returns true if the text was changed.
+    fn handle_undo_input(ui: &egui::Ui, edit_id: egui::Id, logic: &mut CodeEditorLogic, view: &mut TabView) -> bool {
+        // Redo first: shortcut matching ignores an extra Shift, so Ctrl+Z would also match Ctrl+Shift+Z.
+        let (redo, undo) = ui.input_mut(|i| {
+            let redo = i.consume_key(egui::Modifiers::COMMAND | egui::Modifiers::SHIFT, egui::Key::Z)
+                || i.consume_key(egui::Modifiers::COMMAND, egui::Key::Y);
+            (redo, !redo && i.consume_key(egui::Modifiers::COMMAND, egui::Key::Z))
+        });
+        if !((redo && logic.redo()) || (undo && logic.undo())) {
+            return false;
+        }
+
+        view.text = logic.current_content().unwrap_or_default();
+        let mut selections = logic.selections().to_vec();
+        if let Some(primary) = selections.pop() {
+            Self::store_primary(ui.ctx(), edit_id, &view.text, primary);
+        }
+        view.extra_selections = selections;
+        true
+    }
+
+    /// Handle Alt+click (add caret) and Alt+Shift+drag (column selection).
+    fn handle_multi_cursor_pointer(
+        ui: &egui::Ui,
+        edit_id: egui::Id,
+        output: &egui::text_edit::TextEditOutput,
+        view: &mut TabView,
+        primary_before: Option<Selection>,
+    ) {
+        let modifiers = ui.input(|i| i.modifiers);
+        let pointer_offset = |pos: egui::Pos2| Self::offset_at(output, &view.text, pos);
+
+        if output.response.drag_started() && modifiers.alt && modifiers.shift {
+            view.column_anchor = ui.input(|i| i.pointer.press_origin()).map(pointer_offset);
+        }
+        if let (Some(anchor), true) = (view.column_anchor, output.response.dragged()) {
+            if let Some(pos) = ui.input(|i| i.pointer.interact_pos()) {
+                let mut selections = selection::column_selections(&view.text, anchor, pointer_offset(pos));
+                if let Some(primary) = selections.pop() {
+                    Self::store_primary(ui.ctx(), edit_id, &view.text, primary);
+                }
+                view.extra_selections = selections;
+            }
+            return;
+        }
+        if output.response.drag_released() {
+            view.column_anchor = None;
+        }
+
+        if output.response.clicked() {
+            match (modifiers.alt, primary_before) {
+                (true, Some(previous)) => {
+                    view.extra_selections.push(previous);
+                    selection::normalize_selections(&mut view.extra_selections);
+                }
+                _ => view.extra_selections.clear(),
+            }
         }
     }
 
-    pub fn close_file(&mut self, filename: &str) {
-        if let Some(pos) = self.open_files.iter().position(|f| f == filename) {
-            self.open_files.remove(pos);
-            
-            // Switch to another file if this was the active one
-            if self.active_file.as_ref() == Some(&filename.to_string()) {
-                self.active_file = self.open_files.first().cloned();
+    /// Handle ghost text keys before the text widget sees them: Tab accepts the whole
+    /// suggestion, Ctrl+Right its next word and Escape dismisses it. Typed text that
+    /// matches the suggestion is let through and consumed from it.
+    /// Returns true if the text was changed.
+    fn handle_ghost_input(ui: &egui::Ui, edit_id: egui::Id, logic: &mut CodeEditorLogic, view: &mut TabView) -> bool {
+        let Some(mut ghost) = view.ghost.take() else {
+            return false;
+        };
+        if ui.input(|i| i.key_pressed(egui::Key::Escape) || i.key_pressed(egui::Key::Delete)) {
+            return false;
+        }
+        let accepted = if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Tab)) {
+            Some(ghost.text.clone())
+        } else if ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::ArrowRight)) {
+            Some(ghost.next_word().to_string())
+        } else {
+            None
+        };
+        if let Some(text) = accepted {
+            logic.apply_agent_edit(ghost.offset..ghost.offset, &text);
+            view.text.insert_str(ghost.offset, &text);
+            let caret = ghost.offset + text.len();
+            Self::store_primary(ui.ctx(), edit_id, &view.text, Selection::caret(caret));
+            if ghost.type_through(&text) {
+                view.ghost = Some(ghost);
             }
+            return true;
+        }
+
+        let typed: Vec<String> = ui.input(|i| {
+            i.events.iter().filter_map(|event| match event {
+                egui::Event::Text(text) | egui::Event::Paste(text) => Some(text.clone()),
+                _ => None,
+            }).collect()
+        });
+        if typed.iter().all(|text| ghost.type_through(text)) {
+            view.ghost = Some(ghost);
         }
+        false
     }
 
-    pub fn save_file(&mut self, filename: &str) {
-        // TODO: Implement actual file saving
-        self.unsaved_changes.remove(filename);
+    /// Draw the ghost text in a faint colour from the caret on.
+    fn paint_ghost_text(ui: &egui::Ui, output: &egui::text_edit::TextEditOutput, view: &TabView) {
+        let Some(ghost) = &view.ghost else {
+            return;
+        };
+        let painter = ui.painter_at(output.text_clip_rect);
+        let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+        let color = ui.visuals().weak_text_color();
+        let galley = &output.galley;
+        let cursor = galley.from_ccursor(egui::text::CCursor::new(Self::byte_to_char(&view.text, ghost.offset)));
+        let caret = galley.pos_from_cursor(&cursor).translate(output.text_draw_pos.to_vec2());
+        for (i, line) in ghost.text.split('\n').enumerate() {
+            let pos = if i == 0 {
+                caret.left_top()
+            } else {
+                egui::pos2(output.text_draw_pos.x, caret.top() + caret.height() * i as f32)
+            };
+            painter.text(pos, egui::Align2::LEFT_TOP, line, font_id.clone(), color);
+        }
+    }
+
+    /// Byte offset of the character under a screen position.
+    fn offset_at(output: &egui::text_edit::TextEditOutput, text: &str, pos: egui::Pos2) -> usize {
+        let cursor = output.galley.cursor_from_pos(pos - output.text_draw_pos);
+        Self::char_to_byte(text, cursor.ccursor.index)
+    }
+
+    /// Screen rectangles covering a byte range, one per row it spans.
+    fn range_rects(output: &egui::text_edit::TextEditOutput, text: &str, range: Range<usize>) -> Vec<egui::Rect> {
+        let galley = &output.galley;
+        let offset = output.text_draw_pos.to_vec2();
+        let rect_at = |byte: usize| {
+            let cursor = galley.from_ccursor(egui::text::CCursor::new(Self::byte_to_char(text, byte)));
+            (cursor.rcursor.row, galley.pos_from_cursor(&cursor).translate(offset))
+        };
+        let (start_row, start) = rect_at(range.start);
+        let (end_row, end) = rect_at(range.end);
+        (start_row..=end_row)
+            .filter_map(|row| {
+                let row_rect = galley.rows.get(row)?.rect.translate(offset);
+                let left = if row == start_row { start.left() } else { row_rect.left() };
+                let right = if row == end_row { end.left() } else { row_rect.right() };
+                Some(egui::Rect::from_x_y_ranges(left..=right, row_rect.top()..=row_rect.bottom()))
+            })
+            .collect()
+    }
+
+    /// Draw the carets and selections that the text widget does not know about.
+    fn paint_extra_selections(ui: &egui::Ui, output: &egui::text_edit::TextEditOutput, view: &TabView) {
+        let painter = ui.painter_at(output.text_clip_rect);
+        let visuals = &ui.visuals().selection;
+        for extra in &view.extra_selections {
+            let range = extra.range();
+            if !range.is_empty() {
+                for rect in Self::range_rects(output, &view.text, range) {
+                    painter.rect_filled(rect, 0.0, visuals.bg_fill);
+                }
+            }
+            let caret = Self::range_rects(output, &view.text, extra.head..extra.head);
+            if let Some(caret) = caret.first() {
+                painter.line_segment([caret.left_top(), caret.left_bottom()], visuals.stroke);
+            }
+        }
+    }
+
+    fn severity_color(visuals: &egui::Visuals, severity: DiagnosticSeverity) -> egui::Color32 {
+        match severity {
+            DiagnosticSeverity::Error => visuals.error_fg_color,
+            DiagnosticSeverity::Warning => visuals.warn_fg_color,
+            DiagnosticSeverity::Information => visuals.hyperlink_color,
+            DiagnosticSeverity::Hint => visuals.weak_text_color(),
+        }
+    }
+
+    /// Underline each diagnostic's range in its severity colour.
+    fn paint_diagnostics(ui: &egui::Ui, output: &egui::text_edit::TextEditOutput, text: &str, diagnostics: &[(Range<usize>, Diagnostic)]) {
+        let painter = ui.painter_at(output.text_clip_rect);
+        for (range, diagnostic) in diagnostics {
+            let stroke = egui::Stroke::new(1.5, Self::severity_color(ui.visuals(), diagnostic.severity));
+            for rect in Self::range_rects(output, text, range.clone()) {
+                painter.line_segment([rect.left_bottom(), rect.right_bottom()], stroke);
+            }
+        }
+    }
+
+    /// Ask the language server about the symbol under a resting pointer, and show the
+    /// answer together with any diagnostics there.
+    fn show_hover(
+        ui: &egui::Ui,
+        edit_id: egui::Id,
+        output: &egui::text_edit::TextEditOutput,
+        lsp: Option<&mut LspManager>,
+        tab: &EditorTab,
+        view: &mut TabView,
+        diagnostics: &[(Range<usize>, Diagnostic)],
+    ) {
+        let resting = ui.input(|i| i.pointer.time_since_last_movement() > 0.5);
+        let Some(pos) = ui.input(|i| i.pointer.hover_pos()).filter(|_| resting && output.response.hovered()) else {
+            return;
+        };
+        let offset = Self::offset_at(output, &view.text, pos);
+        let asked = view.hover.as_ref().map(|(at, _)| *at) == Some(offset)
+            || view.hover_request.as_ref().map(|(at, _)| *at) == Some(offset);
+        if let (Some(lsp), false) = (lsp, asked) {
+            match lsp.send_position_request(tab, "textDocument/hover", offset) {
+                Ok(request) => view.hover_request = Some((offset, request)),
+                Err(_) => view.hover = Some((offset, String::new())),
+            }
+        }
+
+        let messages: Vec<&Diagnostic> = diagnostics
+            .iter()
+            .filter(|(range, _)| range.start <= offset && offset < range.end)
+            .map(|(_, diagnostic)| diagnostic)
+            .collect();
+        let hover = view.hover.as_ref().filter(|(at, text)| *at == offset && !text.is_empty()).map(|(_, text)| text);
+        if messages.is_empty() && hover.is_none() {
+            return;
+        }
+        egui::show_tooltip_at_pointer(ui.ctx(), edit_id.with("hover"), |ui| {
+            ui.set_max_width(500.0);
+            for diagnostic in &messages {
+                ui.colored_label(Self::severity_color(ui.visuals(), diagnostic.severity), &diagnostic.message);
+            }
+            if let Some(text) = hover {
+                if !messages.is_empty() {
+                    ui.separator();
+                }
+                ui.label(egui::RichText::new(text).monospace());
+            }
+        });
     }
 
     pub fn show(&mut self, ui: &mut egui::Ui) {
+        self.poll_lsp();
+        if self.themes.poll() {
+            self.logic.set_theme(self.themes.active());
+            self.theme_generation += 1;
+        }
+        let theme = self.themes.active();
+        ui.visuals_mut().extreme_bg_color = Self::hex_to_color(&theme.background);
+        if let Some(selection) = &theme.selection {
+            ui.visuals_mut().selection.bg_fill = Self::hex_to_color(selection);
+        }
+
         ui.vertical(|ui| {
+            if let Some(error) = self.themes.last_error() {
+                ui.colored_label(egui::Color32::from_rgb(220, 80, 80), format!("Theme: {}", error));
+            }
             // Tab bar for open files
-            if !self.open_files.is_empty() {
+            if !self.views.is_empty() {
                 ui.horizontal(|ui| {
-                    for filename in self.open_files.clone() {
-                        let is_active = self.active_file.as_ref() == Some(&filename);
-                        let has_changes = self.unsaved_changes.contains(&filename);
+                    let mut close = None;
+                    for (index, view) in self.views.iter().enumerate() {
+                        let is_active = self.logic.current_tab == index;
+                        let has_changes = self.logic.tabs.get(index).is_some_and(|tab| tab.is_dirty);
                         
                         let tab_text = if has_changes {
-                            format!("● {}", filename)
+                            format!("● {}", view.name)
                         } else {
-                            filename.clone()
+                            view.name.clone()
                         };
                         
                         if ui.selectable_label(is_active, tab_text).clicked() {
-                            self.active_file = Some(filename.clone());
+                            self.logic.current_tab = index;
                         }
                         
                         // Close button for tab
-                        ui.small_button("×").clicked().then(|| {
-                            self.close_file(&filename);
-                        });
+                        if ui.small_button("×").clicked() {
+                            close = Some(index);
+                        }
+                    }
+                    if let Some(index) = close {
+                        self.close_file(index);
                     }
                     
                     // Add new tab button
                     if ui.button("+").clicked() {
-                        let new_filename = format!("untitled_{}.txt", self.open_files.len() + 1);
+                        let new_filename = format!("untitled_{}.txt", self.views.len() + 1);
                         self.open_file(new_filename, String::new());
                     }
                 });
@@ -121,54 +721,8 @@ impl Editor {
             }
 
             // Main editor area
-            if let Some(active_filename) = &self.active_file.clone() {
-                if let Some(file_content) = self.files.get_mut(active_filename) {
-                    // Language indicator
-                    ui.horizontal(|ui| {
-                        ui.label(format!("Language: {}", file_content.language));
-                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                            ui.label(format!("Lines: {}", file_content.content.lines().count()));
-                            ui.label(format!("Chars: {}", file_content.content.len()));
-                        });
-                    });
-                    
-                    ui.separator();
-
-                    // Editor with line numbers
-                    egui::ScrollArea::both()
-                        .auto_shrink([false, false])
-                        .show(ui, |ui| {
-                            ui.horizontal_top(|ui| {
-                                // Line numbers
-                                let line_count = file_content.content.lines().count();
-                                ui.vertical(|ui| {
-                                    ui.set_width(30.0);
-                                    for i in 1..=line_count.max(1) {
-                                        ui.label(format!("{:3}", i));
-                                    }
-                                });
-
-                                ui.separator();
-
-                                // Main text editor
-                                let response = ui.add(
-                                    egui::TextEdit::multiline(&mut file_content.content)
-                                        .font(egui::TextStyle::Monospace)
-                                        .code_editor()
-                                        .desired_width(f32::INFINITY)
-                                        .desired_rows(30)
-                                );
-
-                                if response.changed() {
-                                    self.unsaved_changes.insert(active_filename.clone());
-                                }
-                            });
-                        });
-                } else {
-                    ui.centered_and_justified(|ui| {
-                        ui.label("No file selected");
-                    });
-                }
+            if self.logic.current().is_some() && self.logic.current_tab < self.views.len() {
+                self.show_current_tab(ui);
             } else {
                 // Welcome screen when no files are open
                 ui.centered_and_justified(|ui| {
@@ -192,4 +746,188 @@ impl Editor {
             }
         });
     }
-}
\ No newline at end of file
+
+    fn show_current_tab(&mut self, ui: &mut egui::Ui) {
+        let Self { logic, views, theme_generation, completion_request, lsp, .. } = self;
+        let index = logic.current_tab;
+        let view = &mut views[index];
+        let Some(tab) = logic.current() else {
+            return;
+        };
+
+        // Language indicator
+        ui.horizontal(|ui| {
+            ui.label(format!("Language: {}", tab.language));
+            if let Some(error) = lsp.as_ref().and_then(|lsp| lsp.start_error(tab.language)) {
+                ui.weak("Language server unavailable").on_hover_text(error);
+            } else if let Some(error) = lsp.as_ref().and_then(|lsp| lsp.error(tab.language)) {
+                let first_line = error.lines().next().unwrap_or_default();
+                ui.colored_label(ui.visuals().error_fg_color, format!("Language server: {}", first_line)).on_hover_text(error);
+            }
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                ui.label(format!("Lines: {}", tab.buffer.line_count()));
+                ui.label(format!("Chars: {}", tab.buffer.len()));
+            });
+        });
+        let line_count = tab.buffer.line_count();
+        let revision_before = tab.revision;
+        let diagnostics: Vec<(Range<usize>, Diagnostic)> = lsp
+            .as_ref()
+            .map(|lsp| lsp.diagnostics(tab))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|diagnostic| {
+                let start = protocol::position_to_offset(&tab.buffer, diagnostic.range.start);
+                let end = protocol::position_to_offset(&tab.buffer, diagnostic.range.end);
+                // Give empty ranges one character so they can be seen and hovered.
+                let end = if end > start { end } else { view.text[start..].chars().next().map_or(start, |c| start + c.len_utf8()) };
+                (start..end, diagnostic)
+            })
+            .collect();
+        
+        ui.separator();
+
+        // Editor with line numbers
+        egui::ScrollArea::both()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                ui.horizontal_top(|ui| {
+                    // Line numbers
+                    ui.vertical(|ui| {
+                        ui.set_width(30.0);
+                        for i in 1..=line_count.max(1) {
+                            let worst = diagnostics
+                                .iter()
+                                .filter(|(_, diagnostic)| diagnostic.range.start.line as usize + 1 == i)
+                                .map(|(_, diagnostic)| diagnostic.severity)
+                                .min();
+                            match worst {
+                                Some(severity) => ui.colored_label(Self::severity_color(ui.visuals(), severity), format!("{:3}", i)),
+                                None => ui.label(format!("{:3}", i)),
+                            };
+                        }
+                    });
+
+                    ui.separator();
+
+                    // Main text editor
+                    let edit_id = ui.make_persistent_id(("code_editor", index, &view.name));
+                    if let Some(caret) = view.pending_caret.take() {
+                        Self::store_primary(ui.ctx(), edit_id, &view.text, Selection::caret(caret));
+                        view.extra_selections.clear();
+                        ui.memory_mut(|m| m.request_focus(edit_id));
+                    }
+                    let primary_before = egui::TextEdit::load_state(ui.ctx(), edit_id)
+                        .and_then(|state| state.ccursor_range())
+                        .map(|range| Self::selection_from_ccursors(&view.text, range));
+                    if let Some(primary) = primary_before {
+                        let mut all = view.extra_selections.clone();
+                        all.push(primary);
+                        logic.set_selections(all);
+                    }
+                    let has_focus = ui.memory(|m| m.has_focus(edit_id));
+                    // F12: go to the definition of the symbol at the caret.
+                    if let (true, Some(primary), Some(lsp)) = (has_focus, primary_before, lsp.as_mut()) {
+                        if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::F12)) {
+                            view.definition_request = logic.current().and_then(|tab| lsp.send_position_request(tab, "textDocument/definition", primary.head).ok());
+                        }
+                    }
+                    let undone = has_focus && Self::handle_undo_input(ui, edit_id, logic, view);
+                    if let (true, false, Some(primary)) = (has_focus, undone, primary_before) {
+                        Self::handle_multi_cursor_input(ui, edit_id, logic, view, primary);
+                    }
+                    if has_focus && !undone && view.extra_selections.is_empty() {
+                        Self::handle_ghost_input(ui, edit_id, logic, view);
+                    } else {
+                        view.ghost = None;
+                    }
+
+                    let insert_kind = if ui.input(|i| i.events.iter().any(|event| matches!(event, egui::Event::Paste(_)))) {
+                        EditKind::Paste
+                    } else {
+                        EditKind::Typing
+                    };
+                    let logic = RefCell::new(&mut *logic);
+                    let theme_generation = *theme_generation;
+                    let layout_cache = &mut view.layout_cache;
+                    let line_sections = &mut view.line_sections;
+                    let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+                        let logic = logic.borrow();
+                        let mut job = match logic.current() {
+                            Some(tab) => {
+                                let key = (theme_generation, tab.revision);
+                                if layout_cache.as_ref().map(|(cached, _)| *cached) != Some(key) {
+                                    if line_sections.0 != theme_generation {
+                                        *line_sections = (theme_generation, HashMap::new());
+                                    }
+                                    let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+                                    *layout_cache = Some((key, Self::highlight_layout(tab, text, font_id, &mut line_sections.1)));
+                                }
+                                layout_cache.as_ref().map(|(_, job)| job.clone()).unwrap_or_default()
+                            }
+                            None => egui::text::LayoutJob::default(),
+                        };
+                        job.wrap.max_width = wrap_width;
+                        ui.fonts(|f| f.layout_job(job))
+                    };
+                    let mut buffer = TabText { logic: &logic, text: &mut view.text, insert_kind };
+                    let output = egui::TextEdit::multiline(&mut buffer)
+                        .id(edit_id)
+                        .font(egui::TextStyle::Monospace)
+                        .code_editor()
+                        .desired_width(f32::INFINITY)
+                        .desired_rows(30)
+                        .layouter(&mut layouter)
+                        .show(ui);
+                    let logic = logic.into_inner();
+
+                    Self::handle_multi_cursor_pointer(ui, edit_id, &output, view, primary_before);
+                    Self::paint_extra_selections(ui, &output, view);
+
+                    // Ghost text only survives while the caret stays at its start.
+                    let caret = output.state.ccursor_range()
+                        .map(|range| Self::selection_from_ccursors(&view.text, range))
+                        .filter(|selection| selection.range().is_empty())
+                        .map(|selection| selection.head);
+                    if view.ghost.as_ref().is_some_and(|ghost| Some(ghost.offset) != caret) {
+                        view.ghost = None;
+                    }
+                    Self::paint_ghost_text(ui, &output, view);
+
+                    let Some(tab) = logic.current() else {
+                        return;
+                    };
+                    if tab.revision == revision_before {
+                        Self::paint_diagnostics(ui, &output, &view.text, &diagnostics);
+                        Self::show_hover(ui, edit_id, &output, lsp.as_mut(), tab, view, &diagnostics);
+                    }
+                    // Ctrl+click: go to the definition of the symbol under the pointer.
+                    if let (true, Some(pos), Some(lsp)) = (ui.input(|i| i.modifiers.command), output.response.interact_pointer_pos(), lsp.as_mut()) {
+                        if output.response.clicked() {
+                            let offset = Self::offset_at(&output, &view.text, pos);
+                            view.definition_request = lsp.send_position_request(tab, "textDocument/definition", offset).ok();
+                        }
+                    }
+                    if tab.revision != revision_before {
+                        view.hover = None;
+                        if let (Some(caret), None, true) = (caret, &view.ghost, view.extra_selections.is_empty()) {
+                            *completion_request = Some(FimContext::new(&view.text, caret, Self::tab_path(tab, view)));
+                        }
+                        if let Some(tab) = logic.tabs.get_mut(index) {
+                            match lsp.as_mut() {
+                                Some(lsp) => {
+                                    let _ = lsp.did_change(tab);
+                                }
+                                None => {
+                                    tab.take_content_changes();
+                                }
+                            }
+                        }
+                    }
+                    if let Some(caret) = caret {
+                        view.cursor_position = caret;
+                    }
+                });
+            });
+    }
+}
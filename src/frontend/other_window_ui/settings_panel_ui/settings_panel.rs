@@ -1,5 +1,7 @@
 use eframe::egui;
-use crate::backend::settings_manager::{SettingsManager, Theme, AIProvider, CursorStyle};
+use crate::backend::settings_manager::{SettingsManager, Theme, CustomTheme, AIProvider, CursorStyle};
+use crate::backend::code_editor::theme::{ThemeManager, THEME_EXTENSIONS};
+use crate::backend::code_agent::usage::UsageTotals;
 
 // Removed #[derive(Default)] to resolve trait conflict
 pub struct SettingsPanel {
@@ -7,6 +9,10 @@ pub struct SettingsPanel {
     settings_manager: Option<SettingsManager>,
     temp_api_key: String,
     show_api_key: bool,
+    /// Editor theme files found in the themes directory.
+    available_themes: Vec<String>,
+    /// AI usage of the active chat session and the project, shown next to the budgets.
+    usage_totals: Option<(UsageTotals, UsageTotals)>,
 }
 
 #[derive(Default, PartialEq)]
@@ -28,14 +34,31 @@ impl SettingsPanel {
             .as_ref()
             .map(|sm| sm.get_settings().ai.api_key.clone())
             .unwrap_or_default();
+        // Theme names are the file stems; a missing themes directory just lists none.
+        let mut available_themes: Vec<String> = std::fs::read_dir(ThemeManager::default_dir())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| THEME_EXTENSIONS.iter().any(|known| known.eq_ignore_ascii_case(ext))))
+            .filter_map(|path| path.file_stem().and_then(|stem| stem.to_str()).map(str::to_string))
+            .collect();
+        available_themes.sort();
+        available_themes.dedup();
         Self {
             selected_category: SettingsCategory::default(),
             settings_manager,
             temp_api_key,
             show_api_key: false,
+            available_themes,
+            usage_totals: None,
         }
     }
 
+    /// Running AI usage to show in the AI section: the active session's, then the project's.
+    pub fn set_usage_totals(&mut self, session: UsageTotals, project: UsageTotals) {
+        self.usage_totals = Some((session, project));
+    }
+
     pub fn show(&mut self, ui: &mut egui::Ui) {
         ui.heading("⚙️ Settings");
         ui.separator();
@@ -61,7 +84,7 @@ impl SettingsPanel {
                     match self.selected_category {
                         SettingsCategory::General => Self::show_general_settings(ui, settings_manager),
                         SettingsCategory::Editor => Self::show_editor_settings(ui, settings_manager),
-                        SettingsCategory::UI => Self::show_ui_settings(ui, settings_manager),
+                        SettingsCategory::UI => Self::show_ui_settings(ui, settings_manager, &self.available_themes),
                         SettingsCategory::AI => {
                             // Only borrow settings_manager for settings, set flag for save
                             let settings = settings_manager.get_settings_mut();
@@ -82,6 +105,11 @@ impl SettingsPanel {
                                     ui.label("Model:");
                                     ui.text_edit_singleline(&mut settings.ai.model);
                                 });
+                                ui.horizontal(|ui| {
+                                    ui.label("Embedding Model:");
+                                    ui.text_edit_singleline(&mut settings.ai.embedding_model)
+                                        .on_hover_text("Used for semantic code search with local models; leave empty to turn it off");
+                                });
                                 ui.horizontal(|ui| {
                                     ui.label("API Key:");
                                     if self.show_api_key {
@@ -114,6 +142,39 @@ impl SettingsPanel {
                                 ui.checkbox(&mut settings.ai.enable_code_suggestions, "Enable code suggestions");
                                 ui.checkbox(&mut settings.ai.enable_code_review, "Enable automatic code review");
                             });
+                            ui.group(|ui| {
+                                ui.label("Usage & Budgets");
+                                if let Some((session, project)) = &self.usage_totals {
+                                    for (name, totals) in [("Session", session), ("Project", project)] {
+                                        let unpriced = if totals.unpriced > 0 { format!(" ({} unpriced)", totals.unpriced) } else { String::new() };
+                                        ui.label(format!(
+                                            "{}: {} requests, {} in / {} out tokens, ${:.2}{}",
+                                            name, totals.requests, totals.input_tokens, totals.output_tokens, totals.cost, unpriced
+                                        ));
+                                    }
+                                }
+                                ui.horizontal(|ui| {
+                                    ui.label("Session token budget:");
+                                    ui.add(egui::DragValue::new(&mut settings.ai.session_token_budget).speed(1000.0));
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Project cost budget ($):");
+                                    ui.add(egui::DragValue::new(&mut settings.ai.project_cost_budget).speed(0.5).clamp_range(0.0..=f64::MAX));
+                                });
+                                ui.weak("0 means no limit. You are warned when a request nears or passes a budget.");
+                            });
+                            ui.group(|ui| {
+                                ui.label("Agent Commands");
+                                ui.checkbox(&mut settings.ai.agent_commands_read_only, "Read-only: only run commands that don't change the project");
+                                for (label, patterns) in [("Also run without asking:", &mut settings.ai.agent_command_allow), ("Never run:", &mut settings.ai.agent_command_deny)] {
+                                    ui.label(label);
+                                    let mut text = patterns.join("\n");
+                                    if ui.add(egui::TextEdit::multiline(&mut text).code_editor().desired_rows(2)).changed() {
+                                        *patterns = text.split('\n').map(str::to_string).collect();
+                                    }
+                                }
+                                ui.weak("One pattern per line; * matches anything, e.g. npm test*. Other commands wait for your approval.");
+                            });
                             ui.separator();
                             ui.horizontal(|ui| {
                                 if ui.button("Test Connection").clicked() {
@@ -256,7 +317,7 @@ impl SettingsPanel {
             ui.checkbox(&mut settings.editor.word_wrap, "Word wrap");
         });
     }
-    fn show_ui_settings(ui: &mut egui::Ui, settings_manager: &mut SettingsManager) {
+    fn show_ui_settings(ui: &mut egui::Ui, settings_manager: &mut SettingsManager, available_themes: &[String]) {
         ui.heading("UI & Theme Settings");
         let settings = settings_manager.get_settings_mut();
         ui.group(|ui| {
@@ -266,6 +327,31 @@ impl SettingsPanel {
                 ui.radio_value(&mut settings.ui.theme, Theme::Light, "Light");
                 ui.radio_value(&mut settings.ui.theme, Theme::HighContrast, "High Contrast");
             });
+            let selected = match &settings.ui.theme {
+                Theme::Custom(custom) => custom.name.clone(),
+                _ => "None".to_string(),
+            };
+            ui.horizontal(|ui| {
+                ui.label("Editor theme file:");
+                egui::ComboBox::from_id_source("editor_theme_file")
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        for name in available_themes {
+                            let custom = CustomTheme {
+                                name: name.clone(),
+                                background: [30, 30, 30],
+                                foreground: [212, 212, 212],
+                                accent: [0, 122, 204],
+                                panel: [37, 37, 38],
+                                border: [60, 60, 60],
+                            };
+                            ui.radio_value(&mut settings.ui.theme, Theme::Custom(custom), name);
+                        }
+                    });
+            });
+            if available_themes.is_empty() {
+                ui.label("Add .tmTheme or .json theme files to the jadio-ide/themes config folder.");
+            }
         });
         ui.group(|ui| {
             ui.label("Panel Visibility");
@@ -309,6 +395,11 @@ impl SettingsPanel {
                 ui.label("Model:");
                 ui.text_edit_singleline(&mut settings_manager.get_settings_mut().ai.model);
             });
+            ui.horizontal(|ui| {
+                ui.label("Embedding Model:");
+                ui.text_edit_singleline(&mut settings_manager.get_settings_mut().ai.embedding_model)
+                    .on_hover_text("Used for semantic code search with local models; leave empty to turn it off");
+            });
             ui.horizontal(|ui| {
                 ui.label("API Key:");
                 if *show_api_key {
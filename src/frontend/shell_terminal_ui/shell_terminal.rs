@@ -37,6 +37,13 @@ impl Terminal {
         terminal
     }
     
+    /// The last `count` terminal messages, oldest first.
+    pub fn recent_output(&self, count: usize) -> Vec<String> {
+        let mut output: Vec<String> = self.terminal_handler.get_recent_output(count).into_iter().map(|message| message.content.clone()).collect();
+        output.reverse();
+        output
+    }
+    
     pub fn show(&mut self, ui: &mut egui::Ui) {
         ui.vertical(|ui| {
             // Tab bar with controls
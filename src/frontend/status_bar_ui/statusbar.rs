@@ -1,8 +1,26 @@
 use eframe::egui;
+use crate::backend::status_bar::{StatusBarItem, StatusBarLogic};
+
+/// Label of the item showing AI token usage and cost.
+pub const AI_USAGE: &str = "AI usage";
 
-#[derive(Default)]
 pub struct StatusBar {
-    // Status bar state
+    /// Items shown on the right, updated by their owners each frame.
+    pub logic: StatusBarLogic,
+}
+
+impl Default for StatusBar {
+    fn default() -> Self {
+        let mut logic = StatusBarLogic::new();
+        logic.set_items(vec![StatusBarItem {
+            label: AI_USAGE.to_string(),
+            value: String::new(),
+            tooltip: Some("Tokens and cost of AI requests in this project".to_string()),
+            clickable: false,
+            on_click: None,
+        }]);
+        Self { logic }
+    }
 }
 
 impl StatusBar {
@@ -25,6 +43,19 @@ impl StatusBar {
                 ui.label("Ln 1, Col 1");
                 ui.separator();
                 ui.label("🔔");  // Notifications
+                
+                for item in self.logic.items.iter().filter(|item| !item.value.is_empty()) {
+                    ui.separator();
+                    let text = if item.value.starts_with('⚠') {
+                        egui::RichText::new(&item.value).color(ui.visuals().warn_fg_color)
+                    } else {
+                        egui::RichText::new(&item.value)
+                    };
+                    let response = ui.label(text);
+                    if let Some(tooltip) = &item.tooltip {
+                        response.on_hover_text(tooltip);
+                    }
+                }
             });
         });
     }
@@ -14,9 +14,10 @@ use frontend::code_agent_ui::codeagentactivitybar::CodeAgentActivityBar;
 use frontend::code_agent_ui::codeagent::CodeAgent;
 use frontend::code_editor_ui::code_editor::Editor;
 use frontend::shell_terminal_ui::shell_terminal::Terminal;
-use frontend::status_bar_ui::statusbar::StatusBar;
+use frontend::status_bar_ui::statusbar::{StatusBar, AI_USAGE};
 
 use backend::{SettingsManager, ProjectManager, FileSystem};
+use backend::activity_bar_right::activity_bar_right_logic::CodeAgentActivityItem;
 
 // Main application structure
 #[derive(Default)]
@@ -101,11 +102,8 @@ impl IDEApp {
             FileOperation::OpenFile(path) => {
                 match self.file_system.read_file(&path) {
                     Ok(content) => {
-                        let filename = path.file_name()
-                            .unwrap_or_default()
-                            .to_string_lossy()
-                            .to_string();
-                        self.editor.open_file(filename, content);
+                        self.editor.open_path(path.clone(), content);
+                        self.code_agent.set_current_file(&path);
                     }
                     Err(e) => {
                         self.last_error = Some(format!("Failed to open file: {}", e));
@@ -113,16 +111,16 @@ impl IDEApp {
                 }
             }
             FileOperation::SaveFile(filename) => {
-                if let Some(current_project) = self.project_manager.get_current_project() {
-                    let _file_path = current_project.path.join(&filename); // prefix with _
-                    // TODO: Get content from editor and save
-                    // self.file_system.write_file(_file_path, content);
+                if let Err(e) = self.editor.save_file(&filename) {
+                    self.last_error = Some(format!("Failed to save file: {}", e));
                 }
             }
             FileOperation::OpenProject(path) => {
                 match self.project_manager.open_project(&path) {
                     Ok(()) => {
                         let _ = self.file_system.set_workspace(&path);
+                        self.code_agent.open_project(&path);
+                        self.editor.set_workspace(path.clone());
                         self.explorer.open_workspace(path).ok();
                     }
                     Err(e) => {
@@ -244,6 +242,7 @@ impl eframe::App for IDEApp {
 
         // Bottom status bar
         if self.status_bar_open {
+            self.status_bar.logic.update_item(AI_USAGE, self.code_agent.usage_status());
             egui::TopBottomPanel::bottom("status_bar")
                 .exact_height(22.0)
                 .show(ctx, |ui| {
@@ -325,8 +324,14 @@ impl eframe::App for IDEApp {
                     });
             });
 
+        // Settings apply even with the panel closed, since editor completions use them too.
+        if let Some(ref settings_manager) = self.settings_manager {
+            self.code_agent.apply_ai_settings(&settings_manager.get_settings().ai);
+        }
+
         // Right code agent panel
         if self.code_agent_open {
+            self.code_agent.set_editor_state(self.editor.cursor_line(), self.terminal.recent_output(50));
             let code_agent_width = self.settings_manager
                 .as_ref()
                 .map(|sm| sm.get_settings().ui.code_agent_width)
@@ -339,12 +344,22 @@ impl eframe::App for IDEApp {
                     egui::Frame::none()
                         .stroke(egui::Stroke::new(1.0, egui::Color32::GRAY))
                         .show(ui, |ui| {
-                            self.code_agent.show(ui);
+                            match self.code_agent_activity_bar.logic.get_active() {
+                                Some(CodeAgentActivityItem::CodeSearch) => self.code_agent.show_semantic_search(ui),
+                                Some(CodeAgentActivityItem::Memory) => self.code_agent.show_memory(ui),
+                                _ => self.code_agent.show(ui),
+                            }
                         });
                 });
         }
+        if let Some(path) = self.code_agent.take_open_request() {
+            self.handle_file_operation(FileOperation::OpenFile(path));
+        }
 
         // Central editor area
+        if let Some(ref settings_manager) = self.settings_manager {
+            self.editor.set_ui_theme(&settings_manager.get_settings().ui.theme);
+        }
         egui::CentralPanel::default().show(ctx, |ui| {
             egui::Frame::none()
                 .stroke(egui::Stroke::new(1.0, egui::Color32::GRAY))
@@ -352,6 +367,15 @@ impl eframe::App for IDEApp {
                     self.editor.show(ui);
                 });
         });
+        if let Some(path) = self.editor.take_open_request() {
+            self.handle_file_operation(FileOperation::OpenFile(path));
+        }
+        if let Some(context) = self.editor.take_completion_request() {
+            self.code_agent.request_completion(context);
+        }
+        if let Some(completion) = self.code_agent.poll_completion(ctx) {
+            self.editor.set_ghost_text(completion);
+        }
         
         // Show error popup if there's an error
         self.show_error_popup(ctx);